@@ -0,0 +1,109 @@
+//! Random Dutch auctions bought into and then settled, checking the same invariants as the
+//! `invest_and_settle_conserve_usdc` property test over far more inputs:
+//! `cargo +nightly fuzz run invest_and_settle`
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use sangini_invoice::{AuctionTerms, AuctionType, InvoiceStatus, SanginiInvoiceContract, SanginiInvoiceContractClient};
+use soroban_sdk::{
+    testutils::{Address as _, Ledger},
+    token::{StellarAssetClient, TokenClient},
+    Address, Env, String,
+};
+
+const INVESTORS: usize = 3;
+const MAX_INVESTMENTS: usize = 4;
+
+#[derive(Arbitrary, Debug)]
+struct Investment {
+    investor: u8,
+    share_bps: u16,
+    minute: u16,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    amount: u64,
+    duration_hours: u8,
+    max_discount_bps: u16,
+    investments: Vec<Investment>,
+    settle_after_days: u8,
+}
+
+fn total_usdc(usdc: &TokenClient, holders: &[Address]) -> i128 {
+    holders.iter().map(|h| usdc.balance(h)).sum()
+}
+
+fuzz_target!(|input: Input| {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|l| l.timestamp = 1_700_000_000);
+
+    let admin = Address::generate(&env);
+    let supplier = Address::generate(&env);
+    let buyer = Address::generate(&env);
+    let usdc_id = env.register_stellar_asset_contract_v2(admin.clone()).address();
+    let usdc = TokenClient::new(&env, &usdc_id);
+    let usdc_admin = StellarAssetClient::new(&env, &usdc_id);
+    let contract = SanginiInvoiceContractClient::new(&env, &env.register(SanginiInvoiceContract, ()));
+    contract.initialize(&admin, &usdc_id, &1000, &2400, &30, &500);
+
+    // Same ranges as the property test
+    let amount = (input.amount as i128 % 1_000_000_000_0000000).max(1_0000000);
+    let duration_hours = 1 + input.duration_hours as u64 % 72;
+    let max_discount_bps = input.max_discount_bps as u32 % 5001;
+    let investors: Vec<Address> = (0..INVESTORS).map(|_| Address::generate(&env)).collect();
+    for investor in &investors {
+        contract.set_investor_kyc(&admin, investor, &true);
+        usdc_admin.mint(investor, &amount);
+    }
+    usdc_admin.mint(&buyer, &(amount * 2));
+
+    let due_date = env.ledger().timestamp() + 90 * 86400;
+    let invoice_id = contract.mint_draft(
+        &supplier, &buyer, &amount, &String::from_str(&env, "INR"), &due_date,
+        &String::from_str(&env, "Fuzz"), &String::from_str(&env, "PO-FUZZ"), &String::from_str(&env, "QmFuzz"),
+    );
+    contract.approve_invoice(&invoice_id, &buyer, &0);
+    contract.start_auction(&invoice_id, &supplier, &duration_hours, &max_discount_bps, &AuctionType::Dutch, &AuctionTerms::default());
+    let start = env.ledger().timestamp();
+
+    let mut holders = investors.clone();
+    holders.extend([contract.address.clone(), supplier.clone(), buyer.clone()]);
+    let total = total_usdc(&usdc, &holders);
+    let before: Vec<i128> = investors.iter().map(|i| usdc.balance(i)).collect();
+
+    let mut investments: Vec<&Investment> = input.investments.iter().take(MAX_INVESTMENTS).collect();
+    investments.sort_by_key(|i| i.minute % (72 * 60 + 1));
+    for investment in investments {
+        let invoice = contract.get_invoice(&invoice_id);
+        let at = start + (investment.minute % (72 * 60 + 1)) as u64 * 60;
+        if invoice.status != InvoiceStatus::Funding || at >= invoice.auction_end { break; }
+        let share_bps = 1 + investment.share_bps as i128 % 10000;
+        let tokens = (invoice.tokens_remaining * share_bps / 10000).max(1).min(invoice.tokens_remaining);
+        env.ledger().with_mut(|l| l.timestamp = at);
+        contract.invest(&invoice_id, &investors[investment.investor as usize % INVESTORS], &tokens);
+
+        let invoice = contract.get_invoice(&invoice_id);
+        assert_eq!(invoice.tokens_sold + invoice.tokens_remaining, invoice.total_tokens);
+        let held: i128 = core::iter::once(&supplier).chain(investors.iter())
+            .filter_map(|h| contract.try_get_holding(&invoice_id, h).ok().and_then(|r| r.ok()))
+            .map(|h| h.amount)
+            .sum();
+        assert_eq!(held, invoice.total_tokens);
+        assert_eq!(total_usdc(&usdc, &holders), total);
+    }
+
+    env.ledger().with_mut(|l| l.timestamp = start + (input.settle_after_days as u64 % 121) * 86400);
+    let settlement = contract.get_settlement_amount(&invoice_id);
+    assert!(settlement >= amount);
+    contract.settle(&invoice_id, &buyer, &settlement);
+    assert!(contract.get_invoice(&invoice_id).status.is_settled());
+    assert_eq!(total_usdc(&usdc, &holders), total);
+    // Bought at or below face and repaid in full: no investor ends up worse off
+    for (investor, start_balance) in investors.iter().zip(before.iter()) {
+        assert!(usdc.balance(investor) >= *start_balance);
+    }
+});
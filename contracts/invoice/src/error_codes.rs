@@ -0,0 +1,99 @@
+//! Stable error code table for off-chain consumers (relayers, indexers, backend services)
+//! Decodes `Error(Contract, #N)` failures into the `ContractError` name and a readable message.
+
+use crate::errors::ContractError;
+
+/// One `ContractError` case as seen off-chain
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ErrorInfo {
+    pub code: u32,
+    pub name: &'static str,
+    pub message: &'static str,
+}
+
+/// Every error the contract can return, in code order. Codes are never reused, so
+/// retired codes (14, 20, 28, 47) are simply absent.
+pub const ERROR_CODES: &[ErrorInfo] = &[
+    ErrorInfo { code: 1, name: "AlreadyInitialized", message: "Contract has already been initialized" },
+    ErrorInfo { code: 2, name: "Unauthorized", message: "Caller is not authorized for this action" },
+    ErrorInfo { code: 3, name: "InvoiceNotFound", message: "Invoice not found" },
+    ErrorInfo { code: 4, name: "InvalidStatus", message: "Invalid invoice status for this operation" },
+    ErrorInfo { code: 5, name: "InvalidAmount", message: "Invalid amount (must be positive) or query range" },
+    ErrorInfo { code: 6, name: "InsufficientTokens", message: "Insufficient tokens for transfer" },
+    ErrorInfo { code: 7, name: "KYCRequired", message: "Investor KYC not approved" },
+    ErrorInfo { code: 8, name: "InvoiceDisputed", message: "Invoice is currently disputed" },
+    ErrorInfo { code: 9, name: "InsufficientPayment", message: "Payment amount is insufficient" },
+    ErrorInfo { code: 10, name: "CannotRevoke", message: "Cannot revoke invoice in current state" },
+    ErrorInfo { code: 11, name: "DisputeNotFound", message: "Dispute not found" },
+    ErrorInfo { code: 12, name: "HoldingNotFound", message: "Token holding not found" },
+    ErrorInfo { code: 13, name: "AuctionNotStarted", message: "Auction has not started yet" },
+    ErrorInfo { code: 15, name: "InsufficientInsurancePool", message: "Insurance pool has insufficient funds" },
+    ErrorInfo { code: 16, name: "NotDefaulted", message: "Invoice is not in defaulted state" },
+    ErrorInfo { code: 17, name: "AlreadyClaimed", message: "Insurance already claimed for this holding" },
+    ErrorInfo { code: 18, name: "OrderNotFound", message: "Sell order not found" },
+    ErrorInfo { code: 19, name: "OrderNotActive", message: "Order is not active (already filled or cancelled), or an RFQ quote is past its expiry" },
+    ErrorInfo { code: 21, name: "InvalidAuctionParams", message: "Invalid auction parameters" },
+    ErrorInfo { code: 22, name: "TenorExceedsLimit", message: "Due date is beyond the allowed tenor" },
+    ErrorInfo { code: 23, name: "AmountExceedsLimit", message: "Invoice amount is above the allowed maximum" },
+    ErrorInfo { code: 24, name: "InvalidFeeSchedule", message: "Fee schedule or rate model not found or has out-of-range values" },
+    ErrorInfo { code: 25, name: "TokenNotAllowed", message: "Token is not on the settlement allow-list" },
+    ErrorInfo { code: 26, name: "OracleNotConfigured", message: "No usable conversion oracle rate" },
+    ErrorInfo { code: 27, name: "CrossCurrencyUnsupported", message: "Operation not available for invoices settling in another currency" },
+    ErrorInfo { code: 29, name: "ObligationLimitExceeded", message: "Approving the draft would take the buyer above their outstanding-obligation limit" },
+    ErrorInfo { code: 30, name: "ExposureLimitExceeded", message: "Investment or bid would take the investor above their at-risk exposure limit" },
+    ErrorInfo { code: 31, name: "HasExternalHolders", message: "Invoice still has holders other than the supplier" },
+    ErrorInfo { code: 32, name: "Reentrant", message: "Call re-entered a flow that is already in progress for this invoice or order" },
+    ErrorInfo { code: 33, name: "ExceedsUnaccountedBalance", message: "Sweep amount exceeds the balance not backed by internal ledgers" },
+    ErrorInfo { code: 34, name: "NotCollectable", message: "Invoice is not terminal or still inside its retention period" },
+    ErrorInfo { code: 35, name: "AppealWindowClosed", message: "Appeal, appeal review or arbitration voting window has already closed" },
+    ErrorInfo { code: 36, name: "AppealPending", message: "Ruling cannot be finalized while its appeal, review or voting window is open" },
+    ErrorInfo { code: 37, name: "ProgramNotFound", message: "Financing program does not exist" },
+    ErrorInfo { code: 38, name: "ExceedsTreasuryBalance", message: "Withdrawal exceeds the program's treasury balance" },
+    ErrorInfo { code: 39, name: "NotReversible", message: "Action is not the latest change for its address or is past the undo window" },
+    ErrorInfo { code: 40, name: "InvalidCommitment", message: "Revealed amount and salt do not match the draft's commitment, or a confidential draft was approved without revealing its amount" },
+    ErrorInfo { code: 41, name: "NotRefundable", message: "Nothing escrowed to refund, or the auction is still running or met its threshold" },
+    ErrorInfo { code: 42, name: "TermsMismatch", message: "Invoice has no private terms hash, the given terms do not match it, or a draft was amended since the buyer reviewed it" },
+    ErrorInfo { code: 43, name: "AuctionStillRunning", message: "Auction has not reached its end time yet" },
+    ErrorInfo { code: 44, name: "NotAllowlisted", message: "Investor is not in the invoice's private-placement allow-list (or no proof was given)" },
+    ErrorInfo { code: 45, name: "InvalidBasket", message: "Basket order is empty, too large or lists an invoice twice" },
+    ErrorInfo { code: 46, name: "InstallmentConflict", message: "Installment settlement can't be combined with prepayments or a lump-sum settle" },
+    ErrorInfo { code: 48, name: "OutsidePriceBand", message: "Fill price is outside the price band around fair value and the sale isn't flagged distressed" },
+    ErrorInfo { code: 49, name: "SupplierInDebt", message: "Supplier owes dispute refunds and can't mint new drafts until they are repaid" },
+    ErrorInfo { code: 50, name: "QuorumNotReached", message: "Too few arbitrators voted for the panel's ruling to stand" },
+    ErrorInfo { code: 51, name: "ContractPaused", message: "Contract is paused; investing, trading, settlement and claims are blocked" },
+    ErrorInfo { code: 52, name: "ConcentrationLimitExceeded", message: "Investment or bid would take the investor above the invoice's per-investor concentration cap" },
+    ErrorInfo { code: 53, name: "SymbolTaken", message: "Token symbol derived from the invoice ID already belongs to another invoice" },
+    ErrorInfo { code: 54, name: "LimitReached", message: "A capped list (event subscribers, pending transfer offers) is already full" },
+];
+
+/// Entry for a contract error code
+pub fn describe(code: u32) -> Option<&'static ErrorInfo> {
+    ERROR_CODES.iter().find(|info| info.code == code)
+}
+
+/// Finds the contract error code in a host error rendering such as
+/// `HostError: Error(Contract, #9)` and returns its entry
+pub fn decode(text: &str) -> Option<&'static ErrorInfo> {
+    let rest = &text[text.find("Error(Contract, #")? + "Error(Contract, #".len()..];
+    let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    describe(rest[..digits].parse().ok()?)
+}
+
+impl ContractError {
+    pub fn info(&self) -> &'static ErrorInfo {
+        describe(self.clone() as u32).expect("every ContractError has an ERROR_CODES entry")
+    }
+}
+
+impl core::fmt::Display for ContractError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let info = self.info();
+        write!(f, "{} (#{}): {}", info.name, info.code, info.message)
+    }
+}
+
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "std")]
+impl std::error::Error for ContractError {}
@@ -18,7 +18,7 @@ pub enum ContractError {
     /// Invalid invoice status for this operation
     InvalidStatus = 4,
     
-    /// Invalid amount (must be positive)
+    /// Invalid amount (must be positive) or query range
     InvalidAmount = 5,
     
     /// Insufficient tokens for transfer
@@ -45,8 +45,7 @@ pub enum ContractError {
     /// Auction has not started yet
     AuctionNotStarted = 13,
     
-    /// Auction is not currently active
-    AuctionNotActive = 14,
+    // 14 (AuctionNotActive) was never returned; retired to stay within the 50-case limit
     
     /// Insurance pool has insufficient funds
     InsufficientInsurancePool = 15,
@@ -60,12 +59,109 @@ pub enum ContractError {
     /// Sell order not found
     OrderNotFound = 18,
     
-    /// Order is not active (already filled or cancelled)
+    /// Order is not active (already filled or cancelled), or an RFQ quote is past its expiry
     OrderNotActive = 19,
     
-    /// Order has already been filled
-    OrderAlreadyFilled = 20,
+    // 20 (OrderAlreadyFilled) folded into OrderNotActive; retired to stay within the 50-case limit
     
     /// Invalid auction parameters
     InvalidAuctionParams = 21,
+    
+    /// Due date is beyond the allowed tenor
+    TenorExceedsLimit = 22,
+    
+    /// Invoice amount is above the allowed maximum
+    AmountExceedsLimit = 23,
+    
+    /// Fee schedule or rate model not found or has out-of-range values
+    InvalidFeeSchedule = 24,
+    
+    /// Token is not on the settlement allow-list
+    TokenNotAllowed = 25,
+    
+    /// No usable conversion oracle rate
+    OracleNotConfigured = 26,
+    
+    /// Operation not available for invoices settling in another currency
+    CrossCurrencyUnsupported = 27,
+    
+    // 28 (InvalidMaturityRange) folded into InvalidAmount; retired to stay within the 50-case limit
+    
+    /// Approving the draft would take the buyer above their outstanding-obligation limit
+    ObligationLimitExceeded = 29,
+    
+    /// Investment or bid would take the investor above their at-risk exposure limit
+    ExposureLimitExceeded = 30,
+    
+    /// Invoice still has holders other than the supplier
+    HasExternalHolders = 31,
+    
+    /// Call re-entered a flow that is already in progress for this invoice or order
+    Reentrant = 32,
+    
+    /// Sweep amount exceeds the balance not backed by internal ledgers
+    ExceedsUnaccountedBalance = 33,
+    
+    /// Invoice is not terminal or still inside its retention period
+    NotCollectable = 34,
+    
+    /// Appeal, appeal review or arbitration voting window has already closed
+    AppealWindowClosed = 35,
+    
+    /// Ruling cannot be finalized while its appeal, review or voting window is open
+    AppealPending = 36,
+    
+    /// Financing program does not exist
+    ProgramNotFound = 37,
+    
+    /// Withdrawal exceeds the program's treasury balance
+    ExceedsTreasuryBalance = 38,
+    
+    /// Action is not the latest change for its address or is past the undo window
+    NotReversible = 39,
+    
+    /// Revealed amount and salt do not match the draft's commitment, or a confidential
+    /// draft was approved without revealing its amount
+    InvalidCommitment = 40,
+    
+    /// Nothing escrowed to refund, or the auction is still running or met its threshold
+    NotRefundable = 41,
+    
+    /// Invoice has no private terms hash, the given terms do not match it, or a draft was amended since the buyer reviewed it
+    TermsMismatch = 42,
+    
+    /// Auction has not reached its end time yet
+    AuctionStillRunning = 43,
+    
+    /// Investor is not in the invoice's private-placement allow-list (or no proof was given)
+    NotAllowlisted = 44,
+    
+    /// Basket order is empty, too large or lists an invoice twice
+    InvalidBasket = 45,
+    
+    /// Installment settlement can't be combined with prepayments or a lump-sum settle
+    InstallmentConflict = 46,
+    
+    // 47 (QuoteExpired) folded into OrderNotActive; retired to stay within the 50-case limit
+    
+    /// Fill price is outside the price band around fair value and the sale isn't flagged distressed
+    OutsidePriceBand = 48,
+    
+    /// Supplier owes dispute refunds and can't mint new drafts until they are repaid
+    SupplierInDebt = 49,
+    
+    /// Too few arbitrators voted for the panel's ruling to stand
+    QuorumNotReached = 50,
+    
+    /// Contract is paused; investing, trading, settlement and claims are blocked
+    ContractPaused = 51,
+    
+    /// Investment or bid would take the investor above the invoice's per-investor concentration cap
+    ConcentrationLimitExceeded = 52,
+    
+    /// Token symbol derived from the invoice ID already belongs to another invoice
+    SymbolTaken = 53,
+    
+    /// A capped list (event subscribers, pending transfer offers) is already full
+    LimitReached = 54,
 }
@@ -1,7 +1,20 @@
 //! Events module for the Sangini Invoice Contract
 //! Emits events for frontend real-time updates
+//!
+//! Events keyed by an invoice carry `(seq, data)`, where `seq` counts that invoice's events
+//! from 1. Indexers can use it to spot missed events and to order events within a ledger.
 
-use soroban_sdk::{symbol_short, Address, Env, String};
+use soroban_sdk::{symbol_short, Address, BytesN, Env, IntoVal, String, Symbol, Val, Vec};
+
+use crate::storage;
+use crate::types::{AuthorizationRecord, BasketLeg, FeeSchedule, ProgramFees, Role};
+
+/// Publishes an invoice-keyed event, prefixing its data with the invoice's next sequence number
+fn publish_sequenced<D: IntoVal<Env, Val>>(env: &Env, topics: (Symbol, &String), data: D) {
+    let seq = storage::next_event_seq(env, topics.1);
+    let data: Val = data.into_val(env);
+    env.events().publish((topics.0, topics.1.clone()), (seq, data));
+}
 
 pub struct InvoiceEvents;
 
@@ -14,8 +27,9 @@ impl InvoiceEvents {
         buyer: &Address,
         amount: i128,
     ) {
-        env.events().publish(
-            (symbol_short!("CREATED"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("CREATED"), invoice_id),
             (supplier.clone(), buyer.clone(), amount),
         );
     }
@@ -27,8 +41,9 @@ impl InvoiceEvents {
         buyer: &Address,
         tokens_minted: i128,
     ) {
-        env.events().publish(
-            (symbol_short!("VERIFIED"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("VERIFIED"), invoice_id),
             (buyer.clone(), tokens_minted),
         );
     }
@@ -41,8 +56,9 @@ impl InvoiceEvents {
         to: &Address,
         amount: i128,
     ) {
-        env.events().publish(
-            (symbol_short!("TRANSFER"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("TRANSFER"), invoice_id),
             (from.clone(), to.clone(), amount),
         );
     }
@@ -55,52 +71,377 @@ impl InvoiceEvents {
         token_amount: i128,
         payment_amount: i128,
     ) {
-        env.events().publish(
-            (symbol_short!("INVESTED"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("INVESTED"), invoice_id),
             (investor.clone(), token_amount, payment_amount),
         );
     }
 
+    /// Emitted alongside INVESTED when a relayer contract paid for the beneficial owner
+    pub fn investment_delegated(
+        env: &Env,
+        invoice_id: &String,
+        investing_contract: &Address,
+        beneficial_owner: &Address,
+    ) {
+        publish_sequenced(
+            env,
+            (symbol_short!("INVVIA"), invoice_id),
+            (investing_contract.clone(), beneficial_owner.clone()),
+        );
+    }
+
     /// Emitted when an invoice is settled
     pub fn invoice_settled(env: &Env, invoice_id: &String, amount: i128) {
-        env.events().publish(
-            (symbol_short!("SETTLED"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("SETTLED"), invoice_id),
             amount,
         );
     }
 
+    /// Emitted when the buyer escrows a prepayment ahead of settlement
+    pub fn prepayment_made(env: &Env, invoice_id: &String, amount: i128, total_prepaid: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("PREPAID"), invoice_id),
+            (amount, total_prepaid),
+        );
+    }
+
+    /// Emitted when the buyer pays an installment toward settlement
+    pub fn installment_paid(env: &Env, invoice_id: &String, amount: i128, total_received: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("INSTALL"), invoice_id),
+            (amount, total_received),
+        );
+    }
+
+    /// Emitted when the buyer re-attests the outstanding balance
+    pub fn balance_confirmed(env: &Env, invoice_id: &String, outstanding_amount: i128, confirmation_hash: &String) {
+        publish_sequenced(
+            env,
+            (symbol_short!("CONFIRMD"), invoice_id),
+            (outstanding_amount, confirmation_hash.clone()),
+        );
+    }
+
     /// Emitted when an invoice becomes defaulted
     pub fn invoice_defaulted(env: &Env, invoice_id: &String) {
-        env.events().publish(
-            (symbol_short!("DEFAULT"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("DEFAULT"), invoice_id),
             true,
         );
     }
 
+    /// Emitted when collections recover money on a defaulted invoice
+    pub fn recovery_recorded(env: &Env, invoice_id: &String, collector: &Address, amount: i128, insurance_repaid: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("RECOVERY"), invoice_id),
+            (collector.clone(), amount, insurance_repaid),
+        );
+    }
+
+    /// Emitted when a defaulted invoice is closed short of face value
+    pub fn invoice_written_off(env: &Env, invoice_id: &String, recovered_amount: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("WRITEOFF"), invoice_id),
+            recovered_amount,
+        );
+    }
+
+    /// Emitted when the supplier changes a draft's terms before verification
+    pub fn invoice_amended(env: &Env, invoice_id: &String, amount: i128, due_date: u64, amendment: u32) {
+        publish_sequenced(
+            env,
+            (symbol_short!("AMENDED"), invoice_id),
+            (amount, due_date, amendment),
+        );
+    }
+
+    /// Emitted when tokens are offered to a recipient that must accept them
+    pub fn transfer_offered(env: &Env, invoice_id: &String, transfer_id: u64, from: &Address, to: &Address, amount: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("OFFERED"), invoice_id),
+            (transfer_id, from.clone(), to.clone(), amount),
+        );
+    }
+
+    /// Emitted when the sender takes back an offer that was not accepted in time
+    pub fn transfer_reclaimed(env: &Env, invoice_id: &String, transfer_id: u64, from: &Address, amount: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("RECLAIMED"), invoice_id),
+            (transfer_id, from.clone(), amount),
+        );
+    }
+
+    /// Emitted when the supplier passes the declared shares of an invoice to its sub-vendors
+    pub fn subvendors_paid(env: &Env, invoice_id: &String, supplier: &Address, subvendors: u32, total: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("SUBVEND"), invoice_id),
+            (supplier.clone(), subvendors, total),
+        );
+    }
+
+    /// Emitted when the buyer rejects a draft
+    pub fn invoice_rejected(env: &Env, invoice_id: &String, buyer: &Address, reason: &String) {
+        publish_sequenced(
+            env,
+            (symbol_short!("REJECTED"), invoice_id),
+            (buyer.clone(), reason.clone()),
+        );
+    }
+
+    /// Emitted when the supplier sends an amended draft back to the buyer
+    pub fn invoice_resubmitted(env: &Env, invoice_id: &String, amount: i128, due_date: u64) {
+        publish_sequenced(
+            env,
+            (symbol_short!("RESUBMIT"), invoice_id),
+            (amount, due_date),
+        );
+    }
+
     /// Emitted when an invoice is revoked
     pub fn invoice_revoked(env: &Env, invoice_id: &String) {
-        env.events().publish(
-            (symbol_short!("REVOKED"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("REVOKED"), invoice_id),
             true,
         );
     }
 
+    /// Emitted when the supplier closes out an external holder
+    pub fn holder_refunded(env: &Env, invoice_id: &String, holder: &Address, amount: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("REFUNDED"), invoice_id),
+            (holder.clone(), amount),
+        );
+    }
+
+    /// Emitted when a clawed-back investor's refund can't be covered and becomes supplier debt
+    pub fn refund_deferred(env: &Env, invoice_id: &String, investor: &Address, supplier: &Address, amount: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("DEBT"), invoice_id),
+            (investor.clone(), supplier.clone(), amount),
+        );
+    }
+
+    /// Emitted when a funding threshold is met and escrowed payments go to the supplier
+    pub fn funding_released(env: &Env, invoice_id: &String, amount: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("RELEASED"), invoice_id),
+            amount,
+        );
+    }
+
+    /// Emitted when a party publishes an invoice's confidential terms
+    pub fn terms_revealed(env: &Env, invoice_id: &String, revealed_by: &Address) {
+        publish_sequenced(
+            env,
+            (symbol_short!("REVEALED"), invoice_id),
+            revealed_by.clone(),
+        );
+    }
+
     /// Emitted when a dispute is raised
     pub fn dispute_raised(env: &Env, invoice_id: &String, buyer: &Address) {
-        env.events().publish(
-            (symbol_short!("DISPUTE"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("DISPUTE"), invoice_id),
             buyer.clone(),
         );
     }
 
+    /// Emitted when a throttled buyer's dispute bond is returned (upheld) or paid to the supplier
+    pub fn dispute_bond_settled(env: &Env, invoice_id: &String, bond: i128, refunded: bool) {
+        publish_sequenced(
+            env,
+            (symbol_short!("DISPBOND"), invoice_id),
+            (bond, refunded),
+        );
+    }
+
     /// Emitted when a dispute is resolved
     pub fn dispute_resolved(env: &Env, invoice_id: &String, is_valid: bool) {
-        env.events().publish(
-            (symbol_short!("RESOLVED"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("RESOLVED"), invoice_id),
             is_valid,
         );
     }
 
+    /// Emitted when the supplier appeals an upheld dispute
+    pub fn dispute_appealed(env: &Env, invoice_id: &String, supplier: &Address, review_deadline: u64) {
+        publish_sequenced(
+            env,
+            (symbol_short!("APPEAL"), invoice_id),
+            (supplier.clone(), review_deadline),
+        );
+    }
+
+    /// Emitted when a uniform-clearing auction rebates its investors down to the clearing price
+    pub fn auction_cleared(env: &Env, invoice_id: &String, clearing_price: i128, total_rebate: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("CLEARED"), invoice_id),
+            (clearing_price, total_rebate),
+        );
+    }
+
+    /// Emitted when a floating-rate invoice's benchmark is sampled at verification or settlement
+    pub fn benchmark_sampled(env: &Env, invoice_id: &String, rate_bps: u32, at_settlement: bool) {
+        publish_sequenced(
+            env,
+            (symbol_short!("BENCHMARK"), invoice_id),
+            (rate_bps, at_settlement),
+        );
+    }
+
+    /// Emitted when the admin appoints or removes an arbitrator
+    pub fn arbitrator_set(env: &Env, arbitrator: &Address, active: bool) {
+        env.events().publish(
+            (symbol_short!("ARBITER"), arbitrator.clone()),
+            active,
+        );
+    }
+
+    /// Emitted when a party submits evidence on a pending dispute
+    pub fn evidence_submitted(env: &Env, invoice_id: &String, party: &Address, document_hash: &String) {
+        publish_sequenced(
+            env,
+            (symbol_short!("EVIDENCE"), invoice_id),
+            (party.clone(), document_hash.clone()),
+        );
+    }
+
+    /// Emitted when an arbitrator votes on a dispute
+    pub fn arbitration_vote(env: &Env, invoice_id: &String, arbitrator: &Address, uphold: bool) {
+        publish_sequenced(
+            env,
+            (symbol_short!("ARBVOTE"), invoice_id),
+            (arbitrator.clone(), uphold),
+        );
+    }
+
+    /// Emitted when a buyer asks holders to extend the due date
+    pub fn extension_proposed(env: &Env, invoice_id: &String, new_due_date: u64, bonus_rate_bps: u32, voting_ends: u64) {
+        publish_sequenced(
+            env,
+            (symbol_short!("EXTPROP"), invoice_id),
+            (new_due_date, bonus_rate_bps, voting_ends),
+        );
+    }
+
+    /// Emitted when a holder votes on a due-date extension
+    pub fn extension_vote(env: &Env, invoice_id: &String, holder: &Address, approve: bool) {
+        publish_sequenced(
+            env,
+            (symbol_short!("EXTVOTE"), invoice_id),
+            (holder.clone(), approve),
+        );
+    }
+
+    /// Emitted when an extension vote is tallied (`applied` false when it lapsed unapproved)
+    pub fn extension_tallied(env: &Env, invoice_id: &String, approving_tokens: i128, applied: bool) {
+        publish_sequenced(
+            env,
+            (symbol_short!("EXTTALLY"), invoice_id),
+            (approving_tokens, applied),
+        );
+    }
+
+    /// Emitted when the panel's votes are tallied
+    pub fn arbitration_tallied(env: &Env, invoice_id: &String, upheld_votes: u32, rejected_votes: u32) {
+        publish_sequenced(
+            env,
+            (symbol_short!("ARBTALLY"), invoice_id),
+            (upheld_votes, rejected_votes),
+        );
+    }
+
+    /// Emitted when a program admin withdraws accrued fees
+    pub fn treasury_withdrawn(env: &Env, program_id: u32, to: &Address, amount: i128) {
+        env.events().publish(
+            (symbol_short!("TREASURY"), program_id),
+            (to.clone(), amount),
+        );
+    }
+
+    /// Emitted when a new fee schedule version is published
+    pub fn fee_schedule_updated(env: &Env, schedule: &FeeSchedule) {
+        env.events().publish(
+            (symbol_short!("FEES"),),
+            (schedule.version, schedule.effective_from, schedule.protocol_fee_bps, schedule.trading_fee_bps),
+        );
+    }
+
+    /// Emitted when a program's fees are changed
+    pub fn program_fees_updated(env: &Env, program_id: u32, fees: &ProgramFees) {
+        env.events().publish(
+            (symbol_short!("PRGFEES"), program_id),
+            (fees.investment_fee_bps, fees.trading_fee_bps, fees.anchor_share_bps, fees.maker_fee_bps),
+        );
+    }
+
+    /// Emitted when a wasm upgrade is queued behind the timelock
+    pub fn upgrade_scheduled(env: &Env, wasm_hash: &BytesN<32>, eta: u64) {
+        env.events().publish(
+            (symbol_short!("UPGRSCHED"),),
+            (wasm_hash.clone(), eta),
+        );
+    }
+
+    /// Emitted just before the contract's wasm is replaced
+    pub fn upgraded(env: &Env, wasm_hash: &BytesN<32>) {
+        env.events().publish(
+            (symbol_short!("UPGRADED"),),
+            wasm_hash.clone(),
+        );
+    }
+
+    /// Emitted when the admin nominates a successor
+    pub fn admin_proposed(env: &Env, admin: &Address, proposed: &Address) {
+        env.events().publish(
+            (symbol_short!("ADMINPROP"),),
+            (admin.clone(), proposed.clone()),
+        );
+    }
+
+    /// Emitted when the nominated admin accepts and takes over
+    pub fn admin_transferred(env: &Env, previous: &Address, admin: &Address) {
+        env.events().publish(
+            (symbol_short!("ADMIN"),),
+            (previous.clone(), admin.clone()),
+        );
+    }
+
+    /// Emitted when the contract is paused or unpaused
+    pub fn pause_changed(env: &Env, pauser: &Address, paused: bool) {
+        env.events().publish(
+            (symbol_short!("PAUSED"),),
+            (pauser.clone(), paused),
+        );
+    }
+
+    /// Emitted when a role is granted or revoked
+    pub fn role_updated(env: &Env, role: &Role, addr: &Address, granted: bool) {
+        env.events().publish(
+            (symbol_short!("ROLE"), addr.clone()),
+            (role.clone(), granted),
+        );
+    }
+
     /// Emitted when KYC status is updated
     pub fn kyc_updated(env: &Env, investor: &Address, approved: bool) {
         env.events().publish(
@@ -109,6 +450,14 @@ impl InvoiceEvents {
         );
     }
 
+    /// Emitted when an applicant joins the KYC request queue
+    pub fn kyc_requested(env: &Env, applicant: &Address, metadata_hash: &String) {
+        env.events().publish(
+            (symbol_short!("KYCREQ"),),
+            (applicant.clone(), metadata_hash.clone()),
+        );
+    }
+
     /// Emitted when settlement is distributed to a holder
     pub fn settlement_distributed(
         env: &Env,
@@ -116,12 +465,21 @@ impl InvoiceEvents {
         holder: &Address,
         amount: i128,
     ) {
-        env.events().publish(
-            (symbol_short!("PAYOUT"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("PAYOUT"), invoice_id),
             (holder.clone(), amount),
         );
     }
 
+    /// Emitted when a holder withdraws settlement shares consolidated as dust
+    pub fn dust_claimed(env: &Env, holder: &Address, token: &Address, amount: i128) {
+        env.events().publish(
+            (symbol_short!("DUSTCLM"), holder.clone()),
+            (token.clone(), amount),
+        );
+    }
+
     /// Emitted when clawback is executed on tokens
     pub fn clawback_executed(
         env: &Env,
@@ -129,8 +487,9 @@ impl InvoiceEvents {
         holder: &Address,
         amount: i128,
     ) {
-        env.events().publish(
-            (symbol_short!("CLAWBACK"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("CLAWBACK"), invoice_id),
             (holder.clone(), amount),
         );
     }
@@ -146,18 +505,39 @@ impl InvoiceEvents {
         end_time: u64,
         start_price: i128,
         min_price: i128,
+        risk_score: u32,
     ) {
-        env.events().publish(
-            (symbol_short!("AUCTION"), invoice_id.clone()),
-            (end_time, start_price, min_price),
+        publish_sequenced(
+            env,
+            (symbol_short!("AUCTION"), invoice_id),
+            (end_time, start_price, min_price, risk_score),
+        );
+    }
+
+    /// Emitted when an investor bids in an English auction
+    pub fn auction_bid(env: &Env, invoice_id: &String, bidder: &Address, token_amount: i128, price: i128, rank: u32) {
+        publish_sequenced(
+            env,
+            (symbol_short!("BID"), invoice_id),
+            (bidder.clone(), token_amount, price, rank),
         );
     }
 
     /// Emitted when an auction ends
-    pub fn auction_ended(env: &Env, invoice_id: &String, final_price: i128) {
-        env.events().publish(
-            (symbol_short!("AUCTEND"), invoice_id.clone()),
-            final_price,
+    pub fn auction_ended(env: &Env, invoice_id: &String, final_price: i128, risk_score: u32) {
+        publish_sequenced(
+            env,
+            (symbol_short!("AUCTEND"), invoice_id),
+            (final_price, risk_score),
+        );
+    }
+
+    /// Emitted when an expired auction is closed with unsold tokens
+    pub fn auction_finalized(env: &Env, invoice_id: &String, tokens_sold: i128, tokens_unsold: i128, fill_bps: u32) {
+        publish_sequenced(
+            env,
+            (symbol_short!("AUCTEND"), invoice_id),
+            (tokens_sold, tokens_unsold, fill_bps),
         );
     }
 
@@ -172,8 +552,9 @@ impl InvoiceEvents {
         investor: &Address,
         amount: i128,
     ) {
-        env.events().publish(
-            (symbol_short!("INSCLAIM"), invoice_id.clone()),
+        publish_sequenced(
+            env,
+            (symbol_short!("INSCLAIM"), invoice_id),
             (investor.clone(), amount),
         );
     }
@@ -186,6 +567,22 @@ impl InvoiceEvents {
         );
     }
 
+    /// Emitted when an underwriter stakes into a program's insurance pool
+    pub fn insurance_staked(env: &Env, program_id: u32, staker: &Address, amount: i128, shares: i128) {
+        env.events().publish(
+            (symbol_short!("STAKE"), program_id),
+            (staker.clone(), amount, shares),
+        );
+    }
+
+    /// Emitted when an underwriter withdraws from a program's insurance pool
+    pub fn insurance_unstaked(env: &Env, program_id: u32, staker: &Address, shares: i128, amount: i128) {
+        env.events().publish(
+            (symbol_short!("UNSTAKE"), program_id),
+            (staker.clone(), shares, amount),
+        );
+    }
+
     // ========================================================================
     // ORDER BOOK EVENTS
     // ========================================================================
@@ -199,9 +596,10 @@ impl InvoiceEvents {
         token_amount: i128,
         price_per_token: i128,
     ) {
-        env.events().publish(
-            (symbol_short!("ORDERCR"), order_id.clone()),
-            (invoice_id.clone(), seller.clone(), token_amount, price_per_token),
+        publish_sequenced(
+            env,
+            (symbol_short!("ORDERCR"), invoice_id),
+            (order_id.clone(), seller.clone(), token_amount, price_per_token),
         );
     }
 
@@ -209,21 +607,148 @@ impl InvoiceEvents {
     pub fn order_filled(
         env: &Env,
         order_id: &String,
+        invoice_id: &String,
         buyer: &Address,
         token_amount: i128,
         payment: i128,
     ) {
+        publish_sequenced(
+            env,
+            (symbol_short!("ORDERFIL"), invoice_id),
+            (order_id.clone(), buyer.clone(), token_amount, payment),
+        );
+    }
+
+    /// Emitted once per leg, on that leg's invoice, when a basket of holdings is listed
+    pub fn basket_created(env: &Env, order_id: &String, seller: &Address, legs: &Vec<BasketLeg>, total_price: i128) {
+        for leg in legs.iter() {
+            publish_sequenced(
+                env,
+                (symbol_short!("BASKETCR"), &leg.invoice_id),
+                (order_id.clone(), seller.clone(), leg.token_amount, total_price),
+            );
+        }
+    }
+
+    /// Emitted once per leg, on that leg's invoice, when a basket order is bought in full
+    pub fn basket_filled(env: &Env, order_id: &String, buyer: &Address, legs: &Vec<BasketLeg>, total_price: i128) {
+        for leg in legs.iter() {
+            publish_sequenced(
+                env,
+                (symbol_short!("BASKETFL"), &leg.invoice_id),
+                (order_id.clone(), buyer.clone(), leg.token_amount, total_price),
+            );
+        }
+    }
+
+    /// Emitted when a holder requests quotes for a block of tokens
+    pub fn rfq_created(env: &Env, rfq_id: &String, invoice_id: &String, holder: &Address, token_amount: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("RFQ"), invoice_id),
+            (rfq_id.clone(), holder.clone(), token_amount),
+        );
+    }
+
+    /// Emitted when a holder executes a maker's quote
+    pub fn rfq_executed(env: &Env, rfq_id: &String, invoice_id: &String, maker: &Address, price_per_token: i128, payment: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("RFQFIL"), invoice_id),
+            (rfq_id.clone(), maker.clone(), price_per_token, payment),
+        );
+    }
+
+    /// Emitted when a buy order (bid) is posted and its USDC escrowed
+    pub fn bid_created(
+        env: &Env,
+        order_id: &String,
+        invoice_id: &String,
+        buyer: &Address,
+        token_amount: i128,
+        price_per_token: i128,
+    ) {
+        publish_sequenced(
+            env,
+            (symbol_short!("BIDCR"), invoice_id),
+            (order_id.clone(), buyer.clone(), token_amount, price_per_token),
+        );
+    }
+
+    /// Emitted when a holder sells into a bid (fully or partially)
+    pub fn bid_filled(
+        env: &Env,
+        order_id: &String,
+        invoice_id: &String,
+        seller: &Address,
+        token_amount: i128,
+        payment: i128,
+    ) {
+        publish_sequenced(
+            env,
+            (symbol_short!("BIDFIL"), invoice_id),
+            (order_id.clone(), seller.clone(), token_amount, payment),
+        );
+    }
+
+    /// Emitted when a bid is cancelled and its remaining escrow refunded
+    pub fn bid_cancelled(env: &Env, order_id: &String, invoice_id: &String, refunded: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("BIDCAN"), invoice_id),
+            (order_id.clone(), refunded),
+        );
+    }
+
+    /// Emitted when a secondary sale's royalty is credited to the insurance pool
+    pub fn royalty_paid(env: &Env, invoice_id: &String, seller: &Address, royalty: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("ROYALTY"), invoice_id),
+            (seller.clone(), royalty),
+        );
+    }
+
+    /// Emitted when a dead invoice's storage is reclaimed
+    pub fn invoice_collected(env: &Env, invoice_id: &String, caller: &Address, bounty: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("GC"), invoice_id),
+            (caller.clone(), bounty),
+        );
+    }
+
+    /// Emitted when a keeper is paid for moving an invoice to Overdue or Defaulted
+    pub fn keeper_rewarded(env: &Env, invoice_id: &String, keeper: &Address, bounty: i128) {
+        publish_sequenced(
+            env,
+            (symbol_short!("KEEPER"), invoice_id),
+            (keeper.clone(), bounty),
+        );
+    }
+
+    /// Emitted when the admin recovers tokens sent to the contract by mistake
+    pub fn tokens_swept(env: &Env, token: &Address, to: &Address, amount: i128) {
         env.events().publish(
-            (symbol_short!("ORDERFIL"), order_id.clone()),
-            (buyer.clone(), token_amount, payment),
+            (symbol_short!("SWEPT"), token.clone()),
+            (to.clone(), amount),
         );
     }
 
-    /// Emitted when a sell order is cancelled
-    pub fn order_cancelled(env: &Env, order_id: &String) {
+    /// Emitted for every KYC or relayer change, with its compliance reason
+    pub fn authorization_logged(env: &Env, addr: &Address, record: &AuthorizationRecord) {
         env.events().publish(
-            (symbol_short!("ORDERCAN"), order_id.clone()),
-            true,
+            (symbol_short!("AUTHLOG"), addr.clone()),
+            (record.kind.clone(), record.granted, record.reason_code, record.reference_hash.clone()),
+        );
+    }
+
+    /// Emitted when a sell order, RFQ or basket leg is cancelled
+    pub fn order_cancelled(env: &Env, order_id: &String, invoice_id: &String) {
+        publish_sequenced(
+            env,
+            (symbol_short!("ORDERCAN"), invoice_id),
+            order_id.clone(),
         );
     }
 }
@@ -0,0 +1,31 @@
+//! One-shot reentrancy guard for flows that call out to token contracts
+//! Holds a temporary-storage lock per invoice or order for the duration of the call
+
+use soroban_sdk::{Env, String};
+
+use crate::errors::ContractError;
+use crate::storage::DataKey;
+
+/// Lock released when dropped at the end of the guarded scope
+pub struct ReentrancyGuard {
+    env: Env,
+    key: DataKey,
+}
+
+impl ReentrancyGuard {
+    /// Takes the lock for `scope` (an invoice or order ID), failing if it is already held
+    pub fn acquire(env: &Env, scope: &String) -> Result<Self, ContractError> {
+        let key = DataKey::Lock(scope.clone());
+        if env.storage().temporary().has(&key) {
+            return Err(ContractError::Reentrant);
+        }
+        env.storage().temporary().set(&key, &true);
+        Ok(ReentrancyGuard { env: env.clone(), key })
+    }
+}
+
+impl Drop for ReentrancyGuard {
+    fn drop(&mut self) {
+        self.env.storage().temporary().remove(&self.key);
+    }
+}
@@ -0,0 +1,28 @@
+//! Best-effort notifications to subscriber contracts on key invoice transitions
+//! Lets on-chain automation (e.g. a credit-scoring contract) react without polling events
+
+use soroban_sdk::{contractclient, Env, String};
+
+use crate::storage;
+use crate::types::{Invoice, InvoiceStatus};
+
+/// Most subscribers the admin may register; bounds the cost every transition pays
+pub const MAX_SUBSCRIBERS: u32 = 5;
+
+#[allow(dead_code)]
+#[contractclient(name = "InvoiceListenerClient")]
+pub trait InvoiceListener {
+    /// Called after an invoice becomes Funded, settles or defaults
+    fn on_invoice_transition(env: Env, invoice_id: String, status: InvoiceStatus);
+}
+
+/// Calls every subscriber on funded, settled and defaulted transitions. A subscriber that
+/// errors is skipped and its changes roll back, but one that exhausts the budget still aborts
+/// the whole transaction, which is why only the admin registers them, up to `MAX_SUBSCRIBERS`.
+pub fn notify(env: &Env, invoice: &Invoice) {
+    let notable = invoice.status == InvoiceStatus::Funded || invoice.status == InvoiceStatus::Defaulted || invoice.status.is_settled();
+    if !notable { return; }
+    for subscriber in storage::get_subscribers(env).iter() {
+        let _ = InvoiceListenerClient::new(env, &subscriber).try_on_invoice_transition(&invoice.id, &invoice.status);
+    }
+}
@@ -0,0 +1,155 @@
+//! Settlement interest models for the Sangini Invoice Contract
+//! The settlement flow asks for simple interest on a principal over a time window; the
+//! invoice's RateModel decides which rate applies before maturity.
+
+use soroban_sdk::{Address, Env};
+
+use crate::events::InvoiceEvents;
+use crate::oracle::BenchmarkOracleClient;
+use crate::storage;
+use crate::types::{DayCount, GraceInterest, Invoice, RateConfig, RateModel};
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// Most tiers a tiered model may have
+pub const MAX_RATE_TIERS: u32 = 10;
+
+/// Model in force for an invoice: its own override, else its program's, else fixed
+pub fn rate_model(env: &Env, invoice: &Invoice) -> RateModel {
+    storage::get_invoice_rate_model(env, &invoice.id)
+        .or_else(|| storage::get_program_rate_model(env, invoice.program_id))
+        .unwrap_or(RateModel::Fixed)
+}
+
+/// Rate terms for an invoice: those pinned when funding started, else its program's current config
+pub fn rate_config(env: &Env, invoice: &Invoice) -> RateConfig {
+    if invoice.fee_version > 0 { invoice.rate_config.clone() } else { storage::get_program_rate_config(env, invoice.program_id) }
+}
+
+/// Interest on `principal` from `from` to `to`. Once `to` is past the due date the program's
+/// penalty rate applies to the whole window, whatever the model, unless `to` falls within the
+/// grace period and the config's `grace_interest` says otherwise.
+pub fn interest(env: &Env, invoice: &Invoice, principal: i128, from: u64, to: u64) -> i128 {
+    let rate_config = rate_config(env, invoice);
+    let convention = rate_config.day_count.clone();
+    let days = day_count(&convention, from, to);
+    if to > invoice.due_date {
+        let in_grace = to <= invoice.due_date + rate_config.grace_period_days as u64 * SECONDS_PER_DAY;
+        match rate_config.grace_interest {
+            GraceInterest::Base if in_grace => {}
+            GraceInterest::Zero if in_grace => return interest(env, invoice, principal, from, invoice.due_date),
+            _ => return simple(principal, rate_config.penalty_rate, days, &convention),
+        }
+    }
+    let accrued = match rate_model(env, invoice) {
+        RateModel::Fixed => simple(principal, rate_config.base_interest_rate, days, &convention),
+        RateModel::Benchmark(oracle, spread_bps) => {
+            let benchmark = floating_benchmark(env, invoice, &oracle) as i64;
+            simple(principal, (benchmark + spread_bps as i64).max(0) as u32, days, &convention)
+        }
+        RateModel::Tiered(tiers) => {
+            // Tiers are keyed by invoice age, so a window opened later starts in a later tier
+            let start = from.saturating_sub(invoice.created_at) / SECONDS_PER_DAY;
+            let end = start + days;
+            let mut total = 0;
+            for i in 0..tiers.len() {
+                let tier = tiers.get(i).unwrap();
+                let tier_end = tiers.get(i + 1).map(|next| next.from_day as u64).unwrap_or(u64::MAX);
+                let overlap = end.min(tier_end).saturating_sub(start.max(tier.from_day as u64));
+                total += simple(principal, tier.rate_bps, overlap, &convention);
+            }
+            total
+        }
+    };
+    accrued + extension_bonus(env, invoice, principal, from, to, &convention)
+}
+
+/// Bonus holders approved with a due-date extension, on the days past the original due date
+fn extension_bonus(env: &Env, invoice: &Invoice, principal: i128, from: u64, to: u64, convention: &DayCount) -> i128 {
+    let Some(extension) = storage::get_due_date_extension(env, &invoice.id) else { return 0 };
+    let days = day_count(convention, from.max(extension.original_due_date), to);
+    simple(principal, extension.bonus_rate_bps, days, convention)
+}
+
+/// Discount off `amount` for settling at `at`: the invoice's per-day rate for each whole day
+/// before the due date, up to its cap
+pub fn early_discount(env: &Env, invoice: &Invoice, amount: i128, at: u64) -> i128 {
+    let rate_config = rate_config(env, invoice);
+    let days_early = invoice.due_date.saturating_sub(at) / SECONDS_PER_DAY;
+    let bps = (days_early * rate_config.early_discount_bps_per_day as u64).min(rate_config.max_early_discount_bps as u64);
+    (amount * bps as i128) / 10000
+}
+
+/// Stores the benchmark reading for a floating-rate invoice at verification or settlement;
+/// no-op for other models
+pub fn record_benchmark(env: &Env, invoice: &Invoice, at_settlement: bool) {
+    let RateModel::Benchmark(oracle, _) = rate_model(env, invoice) else { return };
+    let rate = BenchmarkOracleClient::new(env, &oracle).benchmark_rate();
+    let now = env.ledger().timestamp();
+    let mut readings = storage::get_benchmark_readings(env, &invoice.id).unwrap_or_default();
+    if at_settlement {
+        readings.settlement_rate = rate;
+        readings.settled_at = now;
+    } else {
+        readings.verification_rate = rate;
+        readings.verified_at = now;
+    }
+    storage::set_benchmark_readings(env, &invoice.id, &readings);
+    InvoiceEvents::benchmark_sampled(env, &invoice.id, rate, at_settlement);
+}
+
+/// Benchmark a floating-rate invoice accrues at: the average of its verification reading and
+/// its settlement reading (or the live rate until it settles). Invoices switched to the model
+/// after verification have no first reading and use the later one alone.
+fn floating_benchmark(env: &Env, invoice: &Invoice, oracle: &Address) -> u32 {
+    let readings = storage::get_benchmark_readings(env, &invoice.id).unwrap_or_default();
+    let latest = if readings.settled_at > 0 { readings.settlement_rate } else { BenchmarkOracleClient::new(env, oracle).benchmark_rate() };
+    if readings.verified_at == 0 { return latest; }
+    ((readings.verification_rate as u64 + latest as u64) / 2) as u32
+}
+
+/// Tiers must start at day 0, be strictly ascending and fit MAX_RATE_TIERS;
+/// benchmark spreads are capped at +/-100%
+pub fn is_valid(model: &RateModel) -> bool {
+    match model {
+        RateModel::Fixed => true,
+        RateModel::Benchmark(_, spread_bps) => spread_bps.abs() <= 10000,
+        RateModel::Tiered(tiers) => {
+            if tiers.is_empty() || tiers.len() > MAX_RATE_TIERS || tiers.get(0).unwrap().from_day != 0 { return false; }
+            (1..tiers.len()).all(|i| tiers.get(i).unwrap().from_day > tiers.get(i - 1).unwrap().from_day)
+        }
+    }
+}
+
+fn simple(principal: i128, rate_bps: u32, days: u64, convention: &DayCount) -> i128 {
+    let year_days = if *convention == DayCount::Act365 { 365 } else { 360 };
+    (principal * (rate_bps as i128) * (days as i128)) / (10000 * year_days)
+}
+
+/// Whole days accrued from `from` to `to` under `convention`
+pub fn day_count(convention: &DayCount, from: u64, to: u64) -> u64 {
+    if to <= from { return 0; }
+    match convention {
+        DayCount::Act365 | DayCount::Act360 => (to - from) / SECONDS_PER_DAY,
+        DayCount::Thirty360 => {
+            let (y1, m1, d1) = civil_date(from / SECONDS_PER_DAY);
+            let (y2, m2, d2) = civil_date(to / SECONDS_PER_DAY);
+            let d1 = d1.min(30);
+            let d2 = if d1 == 30 { d2.min(30) } else { d2 };
+            (360 * (y2 - y1) + 30 * (m2 - m1) + d2 - d1).max(0) as u64
+        }
+    }
+}
+
+/// (year, month, day) of a count of days since 1970-01-01, proleptic Gregorian
+fn civil_date(days: u64) -> (i64, i64, i64) {
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + (month <= 2) as i64, month, day)
+}
@@ -8,15 +8,89 @@ mod types;
 mod storage;
 mod events;
 mod errors;
+mod risk;
+mod interest;
+mod oracle;
+mod guard;
+mod hooks;
+mod state_machine;
+mod migration;
+#[cfg(any(test, feature = "std"))]
+pub mod error_codes;
 
-use soroban_sdk::{contract, contractimpl, Address, Env, String, Vec, token::TokenClient};
+use soroban_sdk::{contract, contractimpl, xdr::ToXdr, Address, IntoVal, TryFromVal, Bytes, BytesN, Env, String, Vec, token::TokenClient};
 
-use types::{Invoice, InvoiceStatus, Dispute, DisputeResolution, TokenHolding, SellOrder, OrderStatus};
-use storage::{get_invoice, set_invoice, get_admin, set_admin, set_token_holding, remove_token_holding, get_kyc_status, set_kyc_status, get_rate_config, set_rate_config};
-use errors::ContractError;
+use types::{AuctionBid, AuctionParams, ExportSummary, InsuranceSnapshot, InvoiceSnapshot, KycSnapshot, OpenOrdersSnapshot, Investment, Invoice, KycRequest, KycRequestStatus, Dispute, DisputeLimits, DisputeResolution, Evidence, ArbitrationVote, DraftAmendment, DraftRejection, PendingTransfer, RelationshipStats, SubvendorDistribution, SubvendorSplit, ExtensionProposal, ExtensionVote, DueDateExtension, TokenHolding, TokenLot, SellOrder, BuyOrder, BasketLeg, BasketOrder, Rfq, OrderStatus, Prepayment, BalanceConfirmation, RedemptionReceipt, InstallmentAccount, StateRecord, SettlementSimulation, SettlementQuote, InvestQuote, KeeperAction, ActionableInvoice, ActionableCursor, ActionablePage, MaturityEntry, AuthorizationKind, AuthorizationRecord, AuctionResult, SupplierSummary, InvestorSummary, Program, ProgramFees, FeeConfig, PremiumQuote, RateModel, BenchmarkReadings, BuyerStats, SupplierStats, Role, PendingUpgrade};
+use storage::{get_invoice, set_invoice, get_admin, set_admin, set_token_holding, remove_token_holding, get_kyc_status, set_kyc_status, set_rate_config};
 use events::InvoiceEvents;
+use guard::ReentrancyGuard;
+use state_machine::Event;
+use oracle::{OracleClient, SwapRouterClient, MAX_SWAP_SLIPPAGE_BPS, RATE_SCALE};
 
-pub use types::RateConfig;
+pub use types::{AuctionTerms, AuctionType, DraftTerms, RevisedTerms, InvoiceStatus, RateConfig, DayCount, GraceInterest, IssuanceLimits, FeeSchedule, UnderwriterStake, UnderwriterTerms, PremiumTier};
+pub use errors::ContractError;
+
+/// How long a terminal invoice is kept before anyone may garbage-collect it (1 year)
+const GC_RETENTION_SECONDS: u64 = 365 * 86400;
+
+/// Time the supplier has to appeal an upheld dispute (3 days)
+const APPEAL_WINDOW_SECONDS: u64 = 3 * 86400;
+
+/// Time the arbiter has to rule on an appeal before the original ruling stands (7 days)
+const REVIEW_WINDOW_SECONDS: u64 = 7 * 86400;
+
+/// Time the arbitration panel has to vote on a dispute (5 days)
+const VOTING_WINDOW_SECONDS: u64 = 5 * 86400;
+
+/// Time holders have to vote on a buyer's due-date extension (3 days)
+const EXTENSION_VOTING_SECONDS: u64 = 3 * 86400;
+
+/// Share of an invoice's tokens (bps) that must approve a due-date extension
+const EXTENSION_APPROVAL_BPS: i128 = 6667;
+
+/// Minimum wait between scheduling a wasm upgrade and executing it (2 days)
+const UPGRADE_DELAY_SECONDS: u64 = 2 * 86400;
+
+/// Storage schema version this build expects; `migrate` brings older data up to it
+const STORAGE_VERSION: u32 = 12;
+
+/// How long an admin may undo a KYC or relayer change (1 day)
+const UNDO_WINDOW_SECONDS: u64 = 86400;
+
+/// Reason code recorded on authorization changes made by undo_last_action
+const UNDO_REASON_CODE: u32 = u32::MAX;
+
+/// Maximum number of invoices in one basket order
+const MAX_BASKET_LEGS: u32 = 10;
+
+/// Largest page any list query returns; larger `limit`s are clamped so a call's read and
+/// return footprint stays bounded however busy an invoice gets
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Most risk-based premium tiers; each investment scans the table
+const MAX_PREMIUM_TIERS: u32 = 10;
+
+/// Most sub-vendors a supplier may declare on one invoice; distribution transfers to each
+const MAX_SUBVENDORS: u32 = 10;
+
+/// Highest secondary-sale royalty. It comes out of seller proceeds along with the seller's
+/// trading fee, so trading fees are capped to leave room for it.
+const MAX_ROYALTY_BPS: u32 = 500;
+
+/// Most open bids in one English auction; closing it walks them all
+const MAX_AUCTION_BIDS: u32 = 50;
+
+/// How long a recipient has to accept offered tokens before the sender may reclaim them
+const TRANSFER_ACCEPTANCE_SECONDS: u64 = 7 * 86400;
+
+/// Most offers pending for one recipient, or out of one holding
+const MAX_PENDING_OFFERS: u32 = 20;
+
+/// Maximum number of weekly buckets a single get_maturities call may scan (~2 years)
+const MAX_MATURITY_BUCKETS: u64 = 104;
+
+/// Most queue entries and index slots a single get_actionable call reads, listed or not
+const MAX_ACTIONABLE_SCAN: u32 = 200;
 
 #[contract]
 pub struct SanginiInvoiceContract;
@@ -44,12 +118,152 @@ impl SanginiInvoiceContract {
             default_auction_duration: 604800,
             default_price_drop_rate: 50,
             default_max_discount: 1500,
-            insurance_cut_bps,
+            grace_interest: GraceInterest::Penalty,
+            day_count: DayCount::Act365,
+            early_discount_bps_per_day: 0,
+            max_early_discount_bps: 0,
+            max_investor_bps: 0,
         };
         set_rate_config(&env, &rate_config);
+        storage::add_fee_schedule(&env, &FeeSchedule {
+            version: 1, effective_from: 0, insurance_cut_bps,
+            protocol_fee_bps: 0, trading_fee_bps: 0, royalty_bps: 0, withholding_bps: 0,
+        });
+        storage::set_storage_version(&env, STORAGE_VERSION);
+        Ok(())
+    }
+
+    /// Registers a contract to be called (best-effort) when invoices are funded, settle or
+    /// default; see `hooks::InvoiceListener` for the callback
+    pub fn add_subscriber(env: Env, admin: Address, subscriber: Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        let mut subscribers = storage::get_subscribers(&env);
+        if subscribers.contains(&subscriber) { return Ok(()); }
+        if subscribers.len() >= hooks::MAX_SUBSCRIBERS { return Err(ContractError::LimitReached); }
+        subscribers.push_back(subscriber);
+        storage::set_subscribers(&env, &subscribers);
+        Ok(())
+    }
+
+    pub fn remove_subscriber(env: Env, admin: Address, subscriber: Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        let mut subscribers = storage::get_subscribers(&env);
+        if let Some(i) = subscribers.first_index_of(&subscriber) {
+            subscribers.remove(i);
+            storage::set_subscribers(&env, &subscribers);
+        }
+        Ok(())
+    }
+
+    pub fn get_subscribers(env: Env) -> Vec<Address> { storage::get_subscribers(&env) }
+
+    /// Queues a wasm upgrade; it can run once the upgrade delay has passed. Scheduling again
+    /// replaces the pending upgrade and restarts the clock.
+    pub fn schedule_upgrade(env: Env, admin: Address, new_wasm_hash: BytesN<32>) -> Result<u64, ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        let eta = env.ledger().timestamp() + storage::get_upgrade_delay(&env).max(UPGRADE_DELAY_SECONDS);
+        storage::set_pending_upgrade(&env, &Some(PendingUpgrade { wasm_hash: new_wasm_hash.clone(), eta }));
+        InvoiceEvents::upgrade_scheduled(&env, &new_wasm_hash, eta);
+        Ok(eta)
+    }
+
+    pub fn cancel_upgrade(env: Env, admin: Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_pending_upgrade(&env, &None);
+        Ok(())
+    }
+
+    /// Swaps in the scheduled wasm once its timelock has expired. Call `migrate` afterwards if
+    /// the new build bumps the storage version.
+    pub fn upgrade(env: Env, admin: Address, new_wasm_hash: BytesN<32>) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        let pending = storage::get_pending_upgrade(&env).ok_or(ContractError::InvalidStatus)?;
+        if pending.wasm_hash != new_wasm_hash || env.ledger().timestamp() < pending.eta { return Err(ContractError::InvalidStatus); }
+        storage::set_pending_upgrade(&env, &None);
+        InvoiceEvents::upgraded(&env, &new_wasm_hash);
+        env.deployer().update_current_contract_wasm(new_wasm_hash);
+        Ok(())
+    }
+
+    /// Lengthens the upgrade timelock. It can't be shortened, so a compromised admin key
+    /// can't skip the wait.
+    pub fn set_upgrade_delay(env: Env, admin: Address, delay_seconds: u64) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if delay_seconds < storage::get_upgrade_delay(&env).max(UPGRADE_DELAY_SECONDS) { return Err(ContractError::InvalidAmount); }
+        storage::set_upgrade_delay(&env, delay_seconds);
         Ok(())
     }
 
+    /// Runs schema migrations from the stored version up to this build's STORAGE_VERSION.
+    /// Deployments from before versioning read as version 0. Invoices are rewritten `limit` per
+    /// call (capped at MAX_PAGE_SIZE); the stored version only moves once all of them are, so
+    /// call again until it returns STORAGE_VERSION, with the contract paused in between.
+    /// Returns the stored version.
+    pub fn migrate(env: Env, admin: Address, limit: u32) -> Result<u32, ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        let from = storage::get_storage_version(&env);
+        if from > STORAGE_VERSION { return Err(ContractError::InvalidStatus); }
+        if from == STORAGE_VERSION { return Ok(from); }
+        // Layout changes (see migration.rs):
+        // 0 -> 1: fee versions, settlement currency, programs and the funding options on Invoice;
+        //         the insurance cut moves from RateConfig to the fee schedule
+        // 3 -> 4: Invoice.rate_config
+        // 4 -> 5: RateConfig.grace_interest
+        // 5 -> 6: RateConfig.day_count
+        // 6 -> 7: Invoice.recovered_amount
+        // 7 -> 8: RateConfig early-settlement discount
+        // 8 -> 9: Invoice.auction_type
+        // 9 -> 10: RateConfig.max_investor_bps
+        let mut cursor = match storage::get_migration_cursor(&env) {
+            Some(cursor) => cursor,
+            None => { migration::upgrade_rate_configs(&env, from); 0 }
+        };
+        let mut count = storage::get_invoice_counter(&env);
+        if from < 12 { count = count.max(storage::get_kyc_index_count(&env, 0)); }
+        let end = cursor.saturating_add(limit.min(MAX_PAGE_SIZE)).min(count);
+        while cursor < end {
+            if let Some(invoice) = migration::upgrade_invoice(&env, &Self::format_invoice_id(&env, cursor), from) {
+                // 1 -> 2: buyer and status listing indexes
+                if from < 2 { storage::index_invoice(&env, &invoice); }
+                // 10 -> 11: running per-buyer outstanding and per-bidder escrow totals
+                if from < 11 {
+                    storage::add_buyer_outstanding(&env, &invoice.buyer, storage::outstanding_of(&invoice));
+                    storage::index_auction_bids(&env, &invoice.id);
+                }
+            }
+            // 11 -> 12: KYC export index entries marked as indexed, paged alongside the invoices
+            if from < 12 { storage::mark_kyc_index_entry(&env, cursor); }
+            cursor += 1;
+        }
+        if cursor < count {
+            storage::set_migration_cursor(&env, Some(cursor));
+            return Ok(from);
+        }
+        // 2 -> 3: the single-list default queue split into per-day buckets
+        if from < 3 {
+            for (due_date, invoice_id) in storage::take_legacy_default_queue(&env).iter() {
+                storage::enqueue_for_default(&env, due_date, &invoice_id);
+            }
+        }
+        // 10 -> 11: the single-list KYC queue moved to sequence-keyed slots
+        if from < 11 {
+            for applicant in storage::take_legacy_kyc_queue(&env).iter() { storage::enqueue_kyc(&env, &applicant); }
+        }
+        storage::set_migration_cursor(&env, None);
+        storage::set_storage_version(&env, STORAGE_VERSION);
+        Ok(STORAGE_VERSION)
+    }
+
+    pub fn get_storage_version(env: Env) -> u32 { storage::get_storage_version(&env) }
+    pub fn get_pending_upgrade(env: Env) -> Option<PendingUpgrade> { storage::get_pending_upgrade(&env) }
+
     pub fn mint_draft(
         env: Env,
         supplier: Address,
@@ -65,211 +279,695 @@ impl SanginiInvoiceContract {
         if amount <= 0 {
             return Err(ContractError::InvalidAmount);
         }
-        let invoice_id = Self::generate_invoice_id(&env);
-        let invoice = Invoice {
-            id: invoice_id.clone(),
-            supplier: supplier.clone(),
-            buyer: buyer.clone(),
-            amount,
-            currency,
-            created_at: env.ledger().timestamp(),
-            due_date,
-            verified_at: 0,
-            settled_at: 0,
-            status: InvoiceStatus::Draft,
-            token_symbol: String::from_str(&env, ""),
-            total_tokens: 0,
-            tokens_sold: 0,
-            tokens_remaining: 0,
-            description,
-            purchase_order,
-            document_hash,
-            repayment_received: 0,
-            buyer_signed_at: 0,
-            auction_start: 0,
-            auction_end: 0,
-            start_price: 0,
-            min_price: 0,
-            price_drop_rate: 0,
-        };
-        set_invoice(&env, &invoice_id, &invoice);
-        InvoiceEvents::invoice_created(&env, &invoice_id, &supplier, &buyer, amount);
-        Ok(invoice_id)
+        Self::check_issuance_limits(&env, &supplier, amount, due_date)?;
+        Self::create_draft(&env, supplier, buyer, amount, None, due_date, (currency, description, purchase_order, document_hash))
+    }
+
+    /// Draft whose face value stays hidden until buyer approval: only `commitment`
+    /// (sha256 of the amount as 16 big-endian bytes followed by a 32-byte salt) is stored.
+    /// The buyer reveals the amount through `approve_confidential_invoice`.
+    pub fn mint_confidential_draft(env: Env, supplier: Address, buyer: Address, commitment: BytesN<32>, terms: DraftTerms) -> Result<String, ContractError> {
+        supplier.require_auth();
+        // Amount limits are checked once the amount is revealed
+        Self::check_issuance_limits(&env, &supplier, 0, terms.due_date)?;
+        Self::create_draft(&env, supplier, buyer, 0, Some(commitment), terms.due_date, (terms.currency, terms.description, terms.purchase_order, terms.document_hash))
+    }
+
+    /// Buyer approves a draft, naming how many amendments it had when they reviewed it
+    pub fn approve_invoice(env: Env, invoice_id: String, buyer: Address, expected_amendments: u32) -> Result<(), ContractError> {
+        Self::verify_and_tokenize(&env, &invoice_id, &buyer, None, expected_amendments)
     }
 
+    /// Approves a confidential draft, revealing its amount; fails unless `amount` and `salt`
+    /// match the draft's commitment
+    pub fn approve_confidential_invoice(
+        env: Env,
+        invoice_id: String,
+        buyer: Address,
+        amount: i128,
+        salt: BytesN<32>,
+        expected_amendments: u32,
+    ) -> Result<(), ContractError> {
+        Self::verify_and_tokenize(&env, &invoice_id, &buyer, Some((amount, salt)), expected_amendments)
+    }
+
+    pub fn compute_amount_commitment(env: Env, amount: i128, salt: BytesN<32>) -> BytesN<32> { Self::amount_commitment(&env, amount, &salt) }
 
-    pub fn approve_invoice(env: Env, invoice_id: String, buyer: Address) -> Result<(), ContractError> {
+    /// Buyer turns down a draft, recording why. The supplier can amend and resubmit it, or revoke it.
+    pub fn reject_invoice(env: Env, invoice_id: String, buyer: Address, reason: String) -> Result<(), ContractError> {
         buyer.require_auth();
         let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
         if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
+        let now = env.ledger().timestamp();
+        state_machine::transition(&mut invoice, Event::Reject, now)?;
+        set_invoice(&env, &invoice_id, &invoice);
+        storage::set_draft_rejection(&env, &invoice_id, &DraftRejection { reason: reason.clone(), rejected_at: now });
+        let mut stats = storage::get_relationship_stats(&env, &invoice.supplier, &buyer);
+        stats.drafts_rejected += 1;
+        storage::set_relationship_stats(&env, &invoice.supplier, &buyer, &stats);
+        Self::record_transition(&env, &invoice, &buyer, 0);
+        InvoiceEvents::invoice_rejected(&env, &invoice_id, &buyer, &reason);
+        Ok(())
+    }
+
+    /// Supplier amends a rejected draft and sends it back to the buyer as a draft. Confidential
+    /// drafts keep their committed amount, so `amount` must be 0 for them.
+    pub fn resubmit_invoice(env: Env, invoice_id: String, supplier: Address, terms: RevisedTerms) -> Result<(), ContractError> {
+        supplier.require_auth();
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
+        let now = env.ledger().timestamp();
+        state_machine::check(&invoice, &Event::Resubmit, now)?;
+        let (amount, due_date) = (terms.amount, terms.due_date);
+        Self::amend_terms(&env, &mut invoice, terms)?;
+        state_machine::transition(&mut invoice, Event::Resubmit, now)?;
+        set_invoice(&env, &invoice_id, &invoice);
+        let mut stats = storage::get_relationship_stats(&env, &supplier, &invoice.buyer);
+        stats.resubmitted += 1;
+        storage::set_relationship_stats(&env, &supplier, &invoice.buyer, &stats);
+        Self::record_transition(&env, &invoice, &supplier, amount);
+        InvoiceEvents::invoice_resubmitted(&env, &invoice_id, amount, due_date);
+        Ok(())
+    }
+
+    /// Supplier corrects a draft's amount, due date, terms or document before the buyer approves
+    /// it, keeping its id. Any buyer signature is cleared and the change is kept in the draft's
+    /// amendment history. Confidential drafts keep their committed amount (`amount` must be 0).
+    pub fn amend_draft(env: Env, invoice_id: String, supplier: Address, terms: RevisedTerms) -> Result<u32, ContractError> {
+        supplier.require_auth();
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
         if invoice.status != InvoiceStatus::Draft { return Err(ContractError::InvalidStatus); }
-        
-        invoice.status = InvoiceStatus::Verified;
-        invoice.verified_at = env.ledger().timestamp();
-        invoice.buyer_signed_at = env.ledger().timestamp();
-        invoice.token_symbol = Self::generate_token_symbol(&env, &invoice_id);
-        invoice.total_tokens = invoice.amount;
-        invoice.tokens_sold = 0;
-        invoice.tokens_remaining = invoice.amount;
+        let (amount, due_date) = (terms.amount, terms.due_date);
+        let amendment = Self::amend_terms(&env, &mut invoice, terms)?;
         set_invoice(&env, &invoice_id, &invoice);
+        InvoiceEvents::invoice_amended(&env, &invoice_id, amount, due_date, amendment);
+        Ok(amendment)
+    }
 
-        let holding = TokenHolding {
-            invoice_id: invoice_id.clone(),
-            holder: invoice.supplier.clone(),
-            amount: invoice.total_tokens,
-            acquired_at: env.ledger().timestamp(),
-            acquired_price: invoice.amount,
-        };
-        set_token_holding(&env, &invoice_id, &invoice.supplier, &holding);
-        InvoiceEvents::invoice_verified(&env, &invoice_id, &buyer, invoice.total_tokens);
+    /// Changes a draft's terms made so far, oldest first
+    pub fn get_amendments(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<DraftAmendment> { storage::get_amendments(&env, &invoice_id, start, limit.min(MAX_PAGE_SIZE)) }
+
+    /// Why the buyer last rejected the invoice's draft, if it ever did
+    pub fn get_draft_rejection(env: Env, invoice_id: String) -> Option<DraftRejection> { storage::get_draft_rejection(&env, &invoice_id) }
+
+    /// Drafts the buyer approved, rejected, and the supplier resubmitted, between the two parties
+    pub fn get_relationship_stats(env: Env, supplier: Address, buyer: Address) -> RelationshipStats {
+        storage::get_relationship_stats(&env, &supplier, &buyer)
+    }
+
+    /// Supplier declares how much of a draft's tokens it owes each sub-vendor (bps of the total
+    /// tokens, at most 100% overall). The buyer approves them with the draft; replacing the
+    /// list is only possible while the invoice is still a draft.
+    pub fn set_subvendor_splits(env: Env, invoice_id: String, supplier: Address, splits: Vec<SubvendorSplit>) -> Result<(), ContractError> {
+        supplier.require_auth();
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
+        if invoice.status != InvoiceStatus::Draft { return Err(ContractError::InvalidStatus); }
+        if splits.len() > MAX_SUBVENDORS { return Err(ContractError::InvalidAmount); }
+        let mut total_bps = 0u32;
+        for (index, split) in splits.iter().enumerate() {
+            let repeated = splits.iter().take(index).any(|earlier| earlier.subvendor == split.subvendor);
+            if split.bps == 0 || split.bps > 10000 || split.subvendor == supplier || repeated { return Err(ContractError::InvalidAmount); }
+            total_bps += split.bps;
+        }
+        if total_bps > 10000 { return Err(ContractError::InvalidAmount); }
+        storage::set_subvendor_splits(&env, &invoice_id, &splits);
         Ok(())
     }
 
-    pub fn start_auction(env: Env, invoice_id: String, supplier: Address, duration_hours: u64, max_discount_bps: u32) -> Result<(), ContractError> {
+    /// Transfers every declared sub-vendor share from the supplier's holding in one call, once,
+    /// recording what each sub-vendor received. With `require_acceptance` each share is offered
+    /// instead (see `offer_tokens`) and only moves when the sub-vendor accepts it. Fails if the
+    /// supplier no longer holds enough tokens. Returns the total transferred or offered.
+    pub fn distribute_to_subvendors(env: Env, invoice_id: String, require_acceptance: bool) -> Result<i128, ContractError> {
+        Self::require_not_paused(&env)?;
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        invoice.supplier.require_auth();
+        if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
+        let splits = storage::get_subvendor_splits(&env, &invoice_id);
+        if splits.is_empty() || storage::distribution_count(&env, &invoice_id) > 0 { return Err(ContractError::InvalidStatus); }
+        let now = env.ledger().timestamp();
+        let (mut distributed, mut total) = (0, 0);
+        for split in splits.iter() {
+            let amount = invoice.total_tokens * split.bps as i128 / 10000;
+            if amount == 0 { continue; }
+            let transfer_id = if require_acceptance {
+                Self::offer_transfer(&env, &invoice_id, &invoice.supplier, &split.subvendor, amount)?
+            } else {
+                Self::internal_transfer_tokens(&env, &invoice_id, &invoice.supplier, &split.subvendor, amount)?;
+                InvoiceEvents::token_transfer(&env, &invoice_id, &invoice.supplier, &split.subvendor, amount);
+                0
+            };
+            storage::add_subvendor_distribution(&env, &invoice_id, &SubvendorDistribution { subvendor: split.subvendor.clone(), amount, distributed_at: now, transfer_id });
+            distributed += 1;
+            total += amount;
+        }
+        InvoiceEvents::subvendors_paid(&env, &invoice_id, &invoice.supplier, distributed, total);
+        Ok(total)
+    }
+
+    /// Sub-vendor shares the supplier declared on the invoice
+    pub fn get_subvendor_splits(env: Env, invoice_id: String) -> Vec<SubvendorSplit> { storage::get_subvendor_splits(&env, &invoice_id) }
+
+    /// What each sub-vendor received from `distribute_to_subvendors`, empty until it runs
+    pub fn get_subvendor_distributions(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<SubvendorDistribution> {
+        storage::get_subvendor_distributions(&env, &invoice_id, start, limit.min(MAX_PAGE_SIZE))
+    }
+
+    /// Opens the primary auction. `max_discount_bps` sets the floor price: where a Dutch auction's
+    /// price decays to, or the lowest bid an English auction accepts. Through `terms` the supplier
+    /// keeps `reserved_tokens` out of the sale, and each purchase or bid must be at least
+    /// `min_ticket` tokens unless it takes the last of them. A funding threshold must still be
+    /// reachable from the tokens on sale. `royalty_bps` overrides the fee schedule's royalty on the
+    /// invoice's secondary sales; either way the rate is frozen here.
+    pub fn start_auction(
+        env: Env,
+        invoice_id: String,
+        supplier: Address,
+        duration_hours: u64,
+        max_discount_bps: u32,
+        auction_type: AuctionType,
+        terms: AuctionTerms,
+    ) -> Result<(), ContractError> {
         supplier.require_auth();
+        let AuctionTerms { reserved_tokens, min_ticket, royalty_bps } = terms;
         let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        if invoice.status != InvoiceStatus::Verified { return Err(ContractError::InvalidStatus); }
+        let now = env.ledger().timestamp();
+        state_machine::check(&invoice, &Event::StartAuction, now)?;
         if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
         if duration_hours == 0 || max_discount_bps > 5000 { return Err(ContractError::InvalidAuctionParams); }
+        if reserved_tokens < 0 || reserved_tokens >= invoice.tokens_remaining || min_ticket < 0 || min_ticket > invoice.tokens_remaining - reserved_tokens {
+            return Err(ContractError::InvalidAuctionParams);
+        }
+        if invoice.min_funding_tokens > invoice.tokens_remaining - reserved_tokens { return Err(ContractError::InvalidAuctionParams); }
+        if royalty_bps.is_some_and(|bps| bps > MAX_ROYALTY_BPS) { return Err(ContractError::InvalidFeeSchedule); }
+        let limits = Self::limits_for_supplier(&env, &supplier);
+        if limits.max_discount_bps > 0 && max_discount_bps > limits.max_discount_bps { return Err(ContractError::InvalidAuctionParams); }
 
-        let now = env.ledger().timestamp();
-        let rate_config = get_rate_config(&env);
+        let rate_config = storage::get_program_rate_config(&env, invoice.program_id);
         invoice.auction_start = now;
         invoice.auction_end = now + (duration_hours * 3600);
         invoice.start_price = invoice.amount;
         invoice.min_price = invoice.amount - (invoice.amount * max_discount_bps as i128 / 10000);
         invoice.price_drop_rate = rate_config.default_price_drop_rate;
-        invoice.status = InvoiceStatus::Funding;
+        let schedule = storage::get_active_fee_schedule(&env, now);
+        invoice.fee_version = schedule.version;
+        invoice.rate_config = rate_config;
+        invoice.auction_type = auction_type;
+        storage::set_royalty_override(&env, &invoice_id, Some(royalty_bps.unwrap_or(schedule.royalty_bps)));
+        // Reserved tokens stay in the supplier's holding, just not for sale
+        invoice.tokens_remaining -= reserved_tokens;
+        storage::set_auction_reserve(&env, &invoice_id, reserved_tokens, min_ticket);
+        state_machine::transition(&mut invoice, Event::StartAuction, now)?;
         set_invoice(&env, &invoice_id, &invoice);
-        InvoiceEvents::auction_started(&env, &invoice_id, invoice.auction_end, invoice.start_price, invoice.min_price);
+        Self::record_transition(&env, &invoice, &supplier, invoice.min_price);
+        InvoiceEvents::auction_started(&env, &invoice_id, invoice.auction_end, invoice.start_price, invoice.min_price, risk::risk_score(&env, &invoice));
         Ok(())
     }
 
-    pub fn get_current_price(env: Env, invoice_id: String) -> Result<i128, ContractError> {
-        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        if invoice.auction_start == 0 { return Err(ContractError::AuctionNotStarted); }
-        let now = env.ledger().timestamp();
-        if now >= invoice.auction_end { return Ok(invoice.min_price); }
-        let hours_elapsed = (now - invoice.auction_start) / 3600;
-        let total_drop = (invoice.start_price * invoice.price_drop_rate as i128 * hours_elapsed as i128) / 10000;
-        Ok((invoice.start_price - total_drop).max(invoice.min_price))
+    /// Funding-threshold mode: investor payments are escrowed until `min_tokens` are sold and
+    /// refundable through `claim_refund` if the auction expires short of it. Set before the auction.
+    pub fn set_funding_threshold(env: Env, invoice_id: String, supplier: Address, min_tokens: i128) -> Result<(), ContractError> {
+        supplier.require_auth();
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
+        if invoice.status != InvoiceStatus::Verified || invoice.tokens_sold > 0 { return Err(ContractError::InvalidStatus); }
+        if min_tokens < 0 || min_tokens > invoice.total_tokens { return Err(ContractError::InvalidAmount); }
+        invoice.min_funding_tokens = min_tokens;
+        set_invoice(&env, &invoice_id, &invoice);
+        Ok(())
     }
 
-    pub fn get_available_tokens(env: Env, invoice_id: String) -> Result<i128, ContractError> {
-        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        Ok(invoice.tokens_remaining)
+    /// Uniform-clearing mode: auction payments are escrowed, and when the auction sells out or is
+    /// finalized every investor is rebated down to the last fill's price, so bidding early costs
+    /// nothing. Set before the auction.
+    pub fn set_uniform_clearing(env: Env, invoice_id: String, supplier: Address, enabled: bool) -> Result<(), ContractError> {
+        supplier.require_auth();
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
+        if invoice.status != InvoiceStatus::Verified || invoice.tokens_sold > 0 { return Err(ContractError::InvalidStatus); }
+        invoice.uniform_clearing = enabled;
+        set_invoice(&env, &invoice_id, &invoice);
+        Ok(())
     }
 
-
-    pub fn invest(env: Env, invoice_id: String, investor: Address, token_amount: i128) -> Result<(), ContractError> {
+    /// Refunds an investor's escrowed payment after the auction expired below its funding
+    /// threshold; the tokens return to the supplier. Refunds stay claimable after
+    /// `finalize_auction` closed the round. Returns the refund.
+    pub fn claim_refund(env: Env, invoice_id: String, investor: Address) -> Result<i128, ContractError> {
         investor.require_auth();
-        if !get_kyc_status(&env, &investor) { return Err(ContractError::KYCRequired); }
-        
+        Self::require_not_paused(&env)?;
+        let _guard = ReentrancyGuard::acquire(&env, &invoice_id)?;
         let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        if invoice.status != InvoiceStatus::Funding && invoice.status != InvoiceStatus::Verified {
-            return Err(ContractError::InvalidStatus);
+        let deferred = storage::take_funding_refund(&env, &invoice_id, &investor);
+        if deferred > 0 {
+            TokenClient::new(&env, &storage::get_usdc_token(&env)).transfer(&env.current_contract_address(), &investor, &deferred);
+            InvoiceEvents::holder_refunded(&env, &invoice_id, &investor, deferred);
+            return Ok(deferred);
         }
-        if token_amount > invoice.tokens_remaining { return Err(ContractError::InsufficientTokens); }
+        let auction_expired = invoice.auction_end > 0 && env.ledger().timestamp() >= invoice.auction_end;
+        // Uniform-clearing escrow past the threshold is settled by finalize_auction, not refunded
+        let threshold_met = invoice.tokens_sold >= invoice.min_funding_tokens;
+        if !auction_expired || invoice.escrowed_payments == 0 || threshold_met { return Err(ContractError::NotRefundable); }
+        let (tokens, payment) = storage::get_funding_escrow(&env, &invoice_id, &investor);
+        if payment == 0 { return Err(ContractError::NotRefundable); }
+        let holding = storage::get_token_holding(&env, &invoice_id, &investor).ok_or(ContractError::HoldingNotFound)?;
+        if holding.amount < tokens { return Err(ContractError::InsufficientTokens); }
 
-        let current_price = if invoice.auction_start > 0 {
-            Self::get_current_price(env.clone(), invoice_id.clone())?
-        } else { invoice.amount };
-        let payment_amount = (token_amount * current_price) / invoice.total_tokens;
+        Self::return_escrowed_tokens(&env, &mut invoice, holding, tokens, payment);
+        storage::remove_funding_escrow(&env, &invoice_id, &investor);
+        set_invoice(&env, &invoice_id, &invoice);
+        TokenClient::new(&env, &storage::get_usdc_token(&env)).transfer(&env.current_contract_address(), &investor, &payment);
+        InvoiceEvents::holder_refunded(&env, &invoice_id, &investor, payment);
+        Ok(payment)
+    }
 
-        let rate_config = get_rate_config(&env);
-        let insurance_amount = (payment_amount * rate_config.insurance_cut_bps as i128) / 10000;
-        let supplier_payment = payment_amount - insurance_amount;
+    /// Closes an auction that expired with unsold tokens. The unsold tokens stay with the
+    /// supplier; the invoice becomes Funded, or returns to Verified (auction cleared) if
+    /// nothing sold. Below the funding threshold, escrowed tokens go back to the supplier and
+    /// the payments stay claimable through `claim_refund`; uniform-clearing escrow is rebated
+    /// to the clearing price and released here.
+    pub fn finalize_auction(env: Env, invoice_id: String) -> Result<InvoiceStatus, ContractError> {
+        let _guard = ReentrancyGuard::acquire(&env, &invoice_id)?;
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let now = env.ledger().timestamp();
+        // Either outcome needs a running auction
+        state_machine::check(&invoice, &Event::ClearAuction, now)?;
+        if now < invoice.auction_end { return Err(ContractError::AuctionStillRunning); }
+        // Bids count towards the funding threshold
+        if invoice.auction_type == AuctionType::English { Self::close_english_auction(&env, &mut invoice)?; }
+        if invoice.escrowed_payments > 0 && invoice.tokens_sold < invoice.min_funding_tokens { Self::unwind_funding_escrow(&env, &mut invoice); }
+        if invoice.escrowed_payments > 0 { Self::clear_uniform_auction(&env, &mut invoice); }
 
-        let payment_token = storage::get_usdc_token(&env);
-        let token_client = TokenClient::new(&env, &payment_token);
-        token_client.transfer(&investor, &env.current_contract_address(), &payment_amount);
-        token_client.transfer(&env.current_contract_address(), &invoice.supplier, &supplier_payment);
-        storage::add_to_insurance_pool(&env, insurance_amount);
-
-        let supplier = invoice.supplier.clone();
-        let mut supplier_holding = storage::get_token_holding(&env, &invoice_id, &supplier).ok_or(ContractError::InsufficientTokens)?;
-        if supplier_holding.amount < token_amount { return Err(ContractError::InsufficientTokens); }
-        supplier_holding.amount -= token_amount;
-        if supplier_holding.amount == 0 {
-            remove_token_holding(&env, &invoice_id, &supplier);
+        let fill_bps = ((invoice.tokens_sold * 10000) / invoice.total_tokens) as u32;
+        let unsold = invoice.tokens_remaining;
+        if let Some(mut result) = storage::get_auction_result(&env, &invoice_id) {
+            Self::close_auction_result(&env, &invoice, &mut result, invoice.auction_end);
+            storage::set_auction_result(&env, &result);
+        }
+        if invoice.tokens_sold > 0 {
+            state_machine::transition(&mut invoice, Event::Fund, now)?;
+            invoice.tokens_remaining = 0;
+            storage::set_auction_reserve(&env, &invoice_id, 0, 0);
         } else {
-            set_token_holding(&env, &invoice_id, &supplier, &supplier_holding);
+            state_machine::transition(&mut invoice, Event::ClearAuction, now)?;
+            invoice.auction_start = 0;
+            invoice.auction_end = 0;
+            // The reserve goes back on offer for the next auction
+            invoice.tokens_remaining += storage::get_auction_reserve(&env, &invoice_id).0;
+            storage::set_auction_reserve(&env, &invoice_id, 0, 0);
         }
+        set_invoice(&env, &invoice_id, &invoice);
+        Self::record_transition(&env, &invoice, &env.current_contract_address(), invoice.tokens_sold);
+        InvoiceEvents::auction_finalized(&env, &invoice_id, invoice.tokens_sold, unsold, fill_bps);
+        Ok(invoice.status)
+    }
 
-        let investor_holding = match storage::get_token_holding(&env, &invoice_id, &investor) {
-            Some(mut existing) => { existing.amount += token_amount; existing.acquired_price += payment_amount; existing }
-            None => TokenHolding { invoice_id: invoice_id.clone(), holder: investor.clone(), amount: token_amount, acquired_at: env.ledger().timestamp(), acquired_price: payment_amount }
-        };
-        set_token_holding(&env, &invoice_id, &investor, &investor_holding);
+    /// Bids for `token_amount` tokens at `price` (for the whole invoice, from the floor up to face
+    /// value) in a running English auction. The payment is escrowed until `finalize_auction`, which
+    /// fills the best bids and refunds the rest. `proof` is the allow-list proof for private
+    /// placements, as in `invest_with_proof`. Once the book holds MAX_AUCTION_BIDS bids, a new bid
+    /// must outrank the worst one, which is dropped and refunded. Returns the bid's rank (0 = best).
+    pub fn place_auction_bid(env: Env, invoice_id: String, bidder: Address, token_amount: i128, price: i128, proof: Option<Vec<BytesN<32>>>) -> Result<u32, ContractError> {
+        bidder.require_auth();
+        Self::require_not_paused(&env)?;
+        Self::require_program_kyc(&env, &invoice_id, &bidder)?;
+        Self::require_allowlisted(&env, &invoice_id, &bidder, proof)?;
+        let _guard = ReentrancyGuard::acquire(&env, &invoice_id)?;
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let now = env.ledger().timestamp();
+        if invoice.status != InvoiceStatus::Funding || invoice.auction_type != AuctionType::English || now >= invoice.auction_end {
+            return Err(ContractError::InvalidStatus);
+        }
+        if token_amount <= 0 || token_amount > invoice.tokens_remaining || price < invoice.min_price || price > invoice.start_price {
+            return Err(ContractError::InvalidAmount);
+        }
+        if Self::below_min_ticket(&env, &invoice, token_amount) { return Err(ContractError::InvalidAmount); }
+        // A full book only takes bids that outrank its worst one, which is refunded to make room
+        let rank = storage::auction_bid_rank(&env, &invoice_id, price);
+        let full = storage::auction_bid_count(&env, &invoice_id) >= MAX_AUCTION_BIDS;
+        if full && rank >= MAX_AUCTION_BIDS { return Err(ContractError::InvalidAmount); }
+        let escrow = (token_amount * price) / invoice.total_tokens;
+        if escrow <= 0 { return Err(ContractError::InvalidAmount); }
+        let exposure_limit = storage::get_exposure_limit(&env, &bidder);
+        if exposure_limit > 0 && Self::exposure_of(&env, &bidder) + escrow > exposure_limit {
+            return Err(ContractError::ExposureLimitExceeded);
+        }
+        Self::check_concentration(&env, &invoice, &bidder, token_amount)?;
 
-        invoice.tokens_sold += token_amount;
-        invoice.tokens_remaining -= token_amount;
-        if invoice.tokens_remaining == 0 {
-            invoice.status = InvoiceStatus::Funded;
-            InvoiceEvents::auction_ended(&env, &invoice_id, current_price);
+        let token = TokenClient::new(&env, &storage::get_usdc_token(&env));
+        token.transfer(&bidder, &env.current_contract_address(), &escrow);
+        storage::add_to_escrow(&env, escrow);
+        if full {
+            if let Some(evicted) = storage::pop_last_auction_bid(&env, &invoice_id) {
+                storage::release_from_escrow(&env, evicted.escrow);
+                token.transfer(&env.current_contract_address(), &evicted.bidder, &evicted.escrow);
+                InvoiceEvents::holder_refunded(&env, &invoice_id, &evicted.bidder, evicted.escrow);
+            }
         }
+        // Equal prices keep their arrival order
+        storage::insert_auction_bid(&env, &invoice_id, rank, &AuctionBid { bidder: bidder.clone(), token_amount, price, escrow, placed_at: now });
+        InvoiceEvents::auction_bid(&env, &invoice_id, &bidder, token_amount, price, rank);
+        Ok(rank)
+    }
+
+    /// Refunds the bidder's escrowed bids once the invoice has left its English auction without
+    /// the bids being closed (e.g. frozen by a dispute). Bids are binding while it runs.
+    /// Returns the amount refunded.
+    pub fn withdraw_auction_bids(env: Env, invoice_id: String, bidder: Address) -> Result<i128, ContractError> {
+        bidder.require_auth();
+        let _guard = ReentrancyGuard::acquire(&env, &invoice_id)?;
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.status == InvoiceStatus::Funding { return Err(ContractError::InvalidStatus); }
+        let refund = storage::remove_bidder_bids(&env, &invoice_id, &bidder);
+        if refund == 0 { return Err(ContractError::NotRefundable); }
+        storage::release_from_escrow(&env, refund);
+        TokenClient::new(&env, &storage::get_usdc_token(&env)).transfer(&env.current_contract_address(), &bidder, &refund);
+        InvoiceEvents::holder_refunded(&env, &invoice_id, &bidder, refund);
+        Ok(refund)
+    }
+
+    /// The invoice's auction terms, including the supplier's reserve and minimum ticket while it runs
+    pub fn get_auction_params(env: Env, invoice_id: String) -> Result<AuctionParams, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.auction_start == 0 { return Err(ContractError::AuctionNotStarted); }
+        let (reserved_tokens, min_ticket) = storage::get_auction_reserve(&env, &invoice_id);
+        Ok(AuctionParams {
+            auction_type: invoice.auction_type, auction_start: invoice.auction_start, auction_end: invoice.auction_end,
+            start_price: invoice.start_price, min_price: invoice.min_price, reserved_tokens, min_ticket,
+        })
+    }
+
+    /// Open English auction bids ranked `[start, start + limit)`, best first
+    pub fn get_auction_bids(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<AuctionBid> {
+        storage::get_auction_bids(&env, &invoice_id, start, limit.min(MAX_PAGE_SIZE))
+    }
+
+    pub fn get_current_price(env: Env, invoice_id: String) -> Result<i128, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.auction_start == 0 { return Err(ContractError::AuctionNotStarted); }
+        Ok(Self::price_at(&invoice, env.ledger().timestamp()))
+    }
+
+    pub fn get_available_tokens(env: Env, invoice_id: String) -> Result<i128, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        Ok(invoice.tokens_remaining)
+    }
+
+    /// Buys tokens at the current auction price. Private placements go through `invest_with_proof`.
+    pub fn invest(env: Env, invoice_id: String, investor: Address, token_amount: i128) -> Result<(), ContractError> {
+        investor.require_auth();
+        Self::require_program_kyc(&env, &invoice_id, &investor)?;
+        Self::require_allowlisted(&env, &invoice_id, &investor, None)?;
+        Self::execute_investment(&env, &invoice_id, &investor, &investor, token_amount)
+    }
+
+    /// `invest` for private placements: `proof` shows the investor is a leaf of the invoice's
+    /// allow-list root. Leaves are sha256 of the investor address XDR; pairs are hashed sorted.
+    pub fn invest_with_proof(env: Env, invoice_id: String, investor: Address, token_amount: i128, proof: Vec<BytesN<32>>) -> Result<(), ContractError> {
+        investor.require_auth();
+        Self::require_program_kyc(&env, &invoice_id, &investor)?;
+        Self::require_allowlisted(&env, &invoice_id, &investor, Some(proof))?;
+        Self::execute_investment(&env, &invoice_id, &investor, &investor, token_amount)
+    }
+
+    /// Restricts investing to a private invite list, committed as a Merkle root so the list
+    /// itself is never published. `None` reopens the invoice to any KYC'd investor.
+    pub fn set_investor_allowlist(env: Env, invoice_id: String, supplier: Address, root: Option<BytesN<32>>) -> Result<(), ContractError> {
+        supplier.require_auth();
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
+        if invoice.status != InvoiceStatus::Draft && invoice.status != InvoiceStatus::Verified { return Err(ContractError::InvalidStatus); }
+        invoice.allowlist_root = root;
         set_invoice(&env, &invoice_id, &invoice);
-        InvoiceEvents::investment_made(&env, &invoice_id, &investor, token_amount, payment_amount);
         Ok(())
     }
 
+    /// Invest through an authorized relayer contract (vault, smart wallet) that pays on behalf of a KYC'd beneficial owner.
+    /// Tokens are credited to the beneficial owner, whose allow-list `proof` a private placement needs.
+    pub fn invest_from_contract(env: Env, invoice_id: String, investing_contract: Address, beneficial_owner: Address, token_amount: i128, proof: Option<Vec<BytesN<32>>>) -> Result<(), ContractError> {
+        investing_contract.require_auth();
+        if !storage::is_authorized_relayer(&env, &investing_contract) { return Err(ContractError::Unauthorized); }
+        Self::require_program_kyc(&env, &invoice_id, &beneficial_owner)?;
+        Self::require_allowlisted(&env, &invoice_id, &beneficial_owner, proof)?;
+        Self::execute_investment(&env, &invoice_id, &investing_contract, &beneficial_owner, token_amount)?;
+        InvoiceEvents::investment_delegated(&env, &invoice_id, &investing_contract, &beneficial_owner);
+        Ok(())
+    }
+
+    /// Dry run of `invest` at an arbitrary timestamp, using the same rounding as the contract
+    pub fn simulate_invest(env: Env, invoice_id: String, token_amount: i128, at_timestamp: u64) -> Result<InvestQuote, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        Self::quote_invest(&env, &invoice, token_amount, at_timestamp)
+    }
+
     pub fn claim_insurance(env: Env, invoice_id: String, investor: Address) -> Result<i128, ContractError> {
         investor.require_auth();
+        Self::require_not_paused(&env)?;
         let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        if invoice.status != InvoiceStatus::Defaulted { return Err(ContractError::NotDefaulted); }
+        if invoice.status != InvoiceStatus::Defaulted && invoice.status != InvoiceStatus::WrittenOff { return Err(ContractError::NotDefaulted); }
         if storage::is_insurance_claimed(&env, &invoice_id, &investor) { return Err(ContractError::AlreadyClaimed); }
         
         let holding = storage::get_token_holding(&env, &invoice_id, &investor).ok_or(ContractError::HoldingNotFound)?;
-        let claim_amount = holding.acquired_price / 2;
-        let pool_balance = storage::get_insurance_pool(&env);
-        let actual_payout = claim_amount.min(pool_balance);
+        // Pro-rata share of the sub-pool as it stood at the first claim, never more than was paid
+        let at_default = storage::get_insurance_at_default(&env, &invoice_id).unwrap_or_else(|| {
+            let balance = storage::get_invoice_insurance(&env, &invoice_id);
+            storage::set_insurance_at_default(&env, &invoice_id, balance);
+            balance
+        });
+        let share = if invoice.tokens_sold > 0 { (at_default * holding.amount) / invoice.tokens_sold } else { 0 };
+        // Recoveries already paid out count against the loss being covered
+        let received = storage::get_recovery_received(&env, &invoice_id, &investor);
+        let uncovered = (holding.acquired_price - received).max(0);
+        let own = share.min(uncovered).min(storage::get_invoice_insurance(&env, &invoice_id));
+        if own > 0 { storage::withdraw_invoice_insurance(&env, &invoice, own); }
+        // The shared pool tops the payout up to the backstop's coverage of cost basis, if enabled,
+        // counting no more basis than the face value of the holding
+        let face_value = (holding.amount * invoice.amount) / invoice.total_tokens;
+        let target = (holding.acquired_price.min(face_value) * storage::get_insurance_backstop(&env) as i128) / 10000 - received;
+        let shared = (target - own).min(storage::get_free_insurance(&env, invoice.program_id));
+        if shared > 0 { storage::withdraw_from_insurance_pool(&env, invoice.program_id, shared); }
+        let actual_payout = own + shared.max(0);
         if actual_payout == 0 { return Err(ContractError::InsufficientInsurancePool); }
-        if !storage::withdraw_from_insurance_pool(&env, actual_payout) { return Err(ContractError::InsufficientInsurancePool); }
 
         let payment_token = storage::get_usdc_token(&env);
         TokenClient::new(&env, &payment_token).transfer(&env.current_contract_address(), &investor, &actual_payout);
         storage::set_insurance_claimed(&env, &invoice_id, &investor);
+        storage::set_insurance_owed(&env, &invoice_id, &investor, actual_payout);
+        storage::add_realized_loss(&env, &investor, uncovered - actual_payout);
+        storage::record_insurance_recovery(&env, &investor, actual_payout);
         InvoiceEvents::insurance_claimed(&env, &invoice_id, &investor, actual_payout);
         Ok(actual_payout)
     }
 
-    pub fn get_insurance_pool_balance(env: Env) -> i128 { storage::get_insurance_pool(&env) }
+    pub fn get_insurance_pool_balance(env: Env) -> i128 { storage::get_insurance_pool(&env, 0) }
 
+    /// Records money collected from the buyer of a defaulted invoice and distributes it pro-rata
+    /// to holders. A holder's share first repays any insurance they were paid, back into the
+    /// program's pool; what reaches a holder who hasn't claimed yet reduces their later claim.
+    /// Rounding dust from the pro-rata split goes to the pool too. Once face value is recovered
+    /// the invoice moves to Recovered.
+    pub fn record_recovery(env: Env, invoice_id: String, collector: Address, amount: i128) -> Result<InvoiceStatus, ContractError> {
+        Self::require_role(&env, &collector, Role::Collector)?;
+        Self::require_not_paused(&env)?;
+        let _guard = ReentrancyGuard::acquire(&env, &invoice_id)?;
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.status != InvoiceStatus::Defaulted { return Err(ContractError::NotDefaulted); }
+        if amount <= 0 { return Err(ContractError::InvalidAmount); }
+
+        let payment_token = storage::get_usdc_token(&env);
+        TokenClient::new(&env, &payment_token).transfer(&collector, &env.current_contract_address(), &amount);
+        let mut insurance_repaid = 0;
+        let mut distributed = 0;
+        for holder in storage::get_all_holders(&env, &invoice_id).iter() {
+            let Some(holding) = storage::get_token_holding(&env, &invoice_id, &holder) else { continue };
+            let share = (holding.amount * amount) / invoice.total_tokens;
+            distributed += share;
+            let owed = storage::get_insurance_owed(&env, &invoice_id, &holder);
+            let repaid = share.min(owed);
+            if repaid > 0 {
+                storage::set_insurance_owed(&env, &invoice_id, &holder, owed - repaid);
+                insurance_repaid += repaid;
+            }
+            if share > repaid {
+                Self::route_payout(&env, &payment_token, &holder, share - repaid);
+                if !storage::is_insurance_claimed(&env, &invoice_id, &holder) {
+                    storage::add_recovery_received(&env, &invoice_id, &holder, share - repaid);
+                }
+            }
+        }
+        let dust = amount - distributed;
+        if insurance_repaid + dust > 0 { storage::repay_insurance(&env, invoice.program_id, insurance_repaid + dust); }
+
+        invoice.recovered_amount += amount;
+        if invoice.recovered_amount >= invoice.amount {
+            state_machine::transition(&mut invoice, Event::Recover, env.ledger().timestamp())?;
+            set_invoice(&env, &invoice_id, &invoice);
+            Self::record_transition(&env, &invoice, &collector, invoice.recovered_amount);
+        } else {
+            set_invoice(&env, &invoice_id, &invoice);
+        }
+        InvoiceEvents::recovery_recorded(&env, &invoice_id, &collector, amount, insurance_repaid);
+        Ok(invoice.status)
+    }
+
+    /// Closes collections on a defaulted invoice short of face value. Holders can still claim insurance.
+    pub fn write_off(env: Env, invoice_id: String, collector: Address) -> Result<(), ContractError> {
+        Self::require_role(&env, &collector, Role::Collector)?;
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        state_machine::transition(&mut invoice, Event::WriteOff, env.ledger().timestamp())?;
+        set_invoice(&env, &invoice_id, &invoice);
+        Self::record_transition(&env, &invoice, &collector, invoice.recovered_amount);
+        InvoiceEvents::invoice_written_off(&env, &invoice_id, invoice.recovered_amount);
+        Ok(())
+    }
+
+    /// Insurance earmarked for the invoice's holders (0 once it settles or its claims are paid)
+    pub fn get_invoice_insurance_balance(env: Env, invoice_id: String) -> i128 { storage::get_invoice_insurance(&env, &invoice_id) }
+
+    /// Share of cost basis (bps) the program's shared pool tops defaulted holders up to when
+    /// their invoice's own sub-pool falls short. 0 disables the backstop.
+    pub fn set_insurance_backstop(env: Env, admin: Address, coverage_bps: u32) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if coverage_bps > 10000 { return Err(ContractError::InvalidAmount); }
+        storage::set_insurance_backstop(&env, coverage_bps);
+        Ok(())
+    }
+
+    /// Risk-based insurance premiums replacing the fee schedule's flat insurance cut. An investment
+    /// pays the first tier its invoice's buyer score and days to maturity qualify for, or the flat
+    /// cut when none does; an empty table restores the flat cut.
+    pub fn set_premium_tiers(env: Env, admin: Address, tiers: Vec<PremiumTier>) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if tiers.len() > MAX_PREMIUM_TIERS || tiers.iter().any(|tier| tier.premium_bps > 10000) {
+            return Err(ContractError::InvalidFeeSchedule);
+        }
+        storage::set_premium_tiers(&env, &tiers);
+        Ok(())
+    }
+
+    pub fn get_premium_tiers(env: Env, start: u32, limit: u32) -> Vec<PremiumTier> { storage::get_premium_tiers(&env, start, limit.min(MAX_PAGE_SIZE)) }
+
+    /// Premium the invoice would pay and what the supplier would net if its remaining tokens sold
+    /// now, at face value before the auction starts
+    pub fn quote_insurance_premium(env: Env, invoice_id: String) -> Result<PremiumQuote, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let now = env.ledger().timestamp();
+        let flat_bps = Self::fee_schedule_for(&env, &invoice, now).insurance_cut_bps;
+        Ok(PremiumQuote {
+            buyer_score: risk::buyer_score(&env, &invoice),
+            tenor_days: risk::tenor_days(&env, &invoice) as u32,
+            premium_bps: risk::insurance_premium_bps(&env, &invoice, flat_bps),
+            sale: Self::split_payment(&env, &invoice, invoice.tokens_remaining, now),
+        })
+    }
+
+    /// Stakes `amount` of the funding token into a program's insurance pool as first-loss capital.
+    /// Shares are priced at the pool's free (unearmarked) balance: they grow with the premiums settled
+    /// invoices release and the underwriters' cut of settlement interest, and shrink when the pool
+    /// backstops defaults or pays keeper bounties. Each stake restarts the staker's lockup.
+    /// Returns the shares minted.
+    pub fn stake_insurance(env: Env, staker: Address, program_id: u32, amount: i128) -> Result<i128, ContractError> {
+        staker.require_auth();
+        Self::require_not_paused(&env)?;
+        if amount <= 0 { return Err(ContractError::InvalidAmount); }
+        if program_id != 0 && storage::get_program(&env, program_id).is_none() { return Err(ContractError::ProgramNotFound); }
+        let unlock_at = env.ledger().timestamp() + storage::get_underwriter_terms(&env).lockup_seconds;
+        let shares = storage::stake_insurance(&env, program_id, &staker, amount, unlock_at);
+        if shares == 0 { return Err(ContractError::InvalidAmount); }
+        TokenClient::new(&env, &storage::get_usdc_token(&env)).transfer(&staker, &env.current_contract_address(), &amount);
+        InvoiceEvents::insurance_staked(&env, program_id, &staker, amount, shares);
+        Ok(shares)
+    }
+
+    /// Burns `shares` after the lockup and pays out their value. Only the pool's free balance can
+    /// be withdrawn, so insurance earmarked for open invoices stays in place. Returns the amount paid.
+    pub fn unstake_insurance(env: Env, staker: Address, program_id: u32, shares: i128) -> Result<i128, ContractError> {
+        staker.require_auth();
+        Self::require_not_paused(&env)?;
+        let stake = storage::get_underwriter_stake(&env, program_id, &staker);
+        if shares <= 0 || shares > stake.shares { return Err(ContractError::InvalidAmount); }
+        if env.ledger().timestamp() < stake.unlock_at { return Err(ContractError::InvalidStatus); }
+        let amount = storage::unstake_insurance(&env, program_id, &staker, shares).ok_or(ContractError::InsufficientInsurancePool)?;
+        if amount > 0 {
+            TokenClient::new(&env, &storage::get_usdc_token(&env)).transfer(&env.current_contract_address(), &staker, &amount);
+        }
+        InvoiceEvents::insurance_unstaked(&env, program_id, &staker, shares, amount);
+        Ok(amount)
+    }
+
+    pub fn get_underwriter_stake(env: Env, program_id: u32, staker: Address) -> UnderwriterStake {
+        storage::get_underwriter_stake(&env, program_id, &staker)
+    }
+
+    /// What the staker's shares would pay out if unstaked now
+    pub fn get_underwriter_value(env: Env, program_id: u32, staker: Address) -> i128 {
+        let shares = storage::get_underwriter_stake(&env, program_id, &staker).shares;
+        storage::underwriter_share_value(&env, program_id, shares)
+    }
+
+    pub fn set_underwriter_terms(env: Env, admin: Address, terms: UnderwriterTerms) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if terms.yield_bps > 10000 { return Err(ContractError::InvalidAmount); }
+        storage::set_underwriter_terms(&env, &terms);
+        Ok(())
+    }
+
+    pub fn get_underwriter_terms(env: Env) -> UnderwriterTerms { storage::get_underwriter_terms(&env) }
+
+
+    pub fn create_sell_order(env: Env, invoice_id: String, seller: Address, token_amount: i128, price_per_token: i128) -> Result<String, ContractError> {
+        seller.require_auth();
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let holding = storage::get_token_holding(&env, &invoice_id, &seller).ok_or(ContractError::HoldingNotFound)?;
+        if Self::free_tokens(&env, &holding) < token_amount { return Err(ContractError::InsufficientTokens); }
+        
+        let order_id = Self::generate_order_id(&env);
+        let order = SellOrder {
+            id: order_id.clone(), invoice_id: invoice_id.clone(), seller: seller.clone(),
+            token_amount, price_per_token, tokens_remaining: token_amount,
+            created_at: env.ledger().timestamp(), status: OrderStatus::Open, program_id: invoice.program_id,
+        };
+        storage::set_sell_order(&env, &order_id, &order);
+        storage::add_order_to_invoice(&env, &invoice_id, &order_id);
+        InvoiceEvents::order_created(&env, &order_id, &invoice_id, &seller, token_amount, price_per_token);
+        Ok(order_id)
+    }
 
-    pub fn create_sell_order(env: Env, invoice_id: String, seller: Address, token_amount: i128, price_per_token: i128) -> Result<String, ContractError> {
-        seller.require_auth();
-        let holding = storage::get_token_holding(&env, &invoice_id, &seller).ok_or(ContractError::HoldingNotFound)?;
-        if holding.amount < token_amount { return Err(ContractError::InsufficientTokens); }
-        
-        let order_id = Self::generate_order_id(&env);
-        let order = SellOrder {
-            id: order_id.clone(), invoice_id: invoice_id.clone(), seller: seller.clone(),
-            token_amount, price_per_token, tokens_remaining: token_amount,
-            created_at: env.ledger().timestamp(), status: OrderStatus::Open,
-        };
-        storage::set_sell_order(&env, &order_id, &order);
-        storage::add_order_to_invoice(&env, &invoice_id, &order_id);
-        InvoiceEvents::order_created(&env, &order_id, &invoice_id, &seller, token_amount, price_per_token);
-        Ok(order_id)
-    }
-
     pub fn fill_order(env: Env, order_id: String, buyer: Address, token_amount: i128) -> Result<(), ContractError> {
         buyer.require_auth();
-        if !get_kyc_status(&env, &buyer) { return Err(ContractError::KYCRequired); }
+        Self::require_not_paused(&env)?;
+        let _guard = ReentrancyGuard::acquire(&env, &order_id)?;
         
         let mut order = storage::get_sell_order(&env, &order_id).ok_or(ContractError::OrderNotFound)?;
+        if !storage::get_program_kyc_status(&env, order.program_id, &buyer) { return Err(ContractError::KYCRequired); }
         if order.status != OrderStatus::Open && order.status != OrderStatus::PartiallyFilled { return Err(ContractError::OrderNotActive); }
         if token_amount > order.tokens_remaining { return Err(ContractError::InsufficientTokens); }
 
+        // The seller's order was resting: the buyer pays the taker fee, the seller the maker fee
         let payment = token_amount * order.price_per_token;
-        let payment_token = storage::get_usdc_token(&env);
-        TokenClient::new(&env, &payment_token).transfer(&buyer, &order.seller, &payment);
+        let invoice = get_invoice(&env, &order.invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        Self::check_concentration(&env, &invoice, &buyer, token_amount)?;
+        Self::check_price_band(&env, &invoice, &order.seller, token_amount, payment)?;
+        let (taker_fee, maker_fee, protocol_fee) = Self::trading_fees(&env, &invoice, payment);
+        let royalty = Self::take_royalty(&env, &invoice, &order.seller, payment, maker_fee)?;
+        let token_client = TokenClient::new(&env, &storage::get_usdc_token(&env));
+        token_client.transfer(&buyer, &order.seller, &(payment - maker_fee - royalty));
+        if taker_fee + maker_fee + royalty > 0 {
+            token_client.transfer(&buyer, &env.current_contract_address(), &(taker_fee + maker_fee + royalty));
+            Self::credit_trading_fees(&env, order.program_id, taker_fee + maker_fee, protocol_fee);
+        }
         Self::internal_transfer_tokens(&env, &order.invoice_id, &order.seller, &buyer, token_amount)?;
+        Self::record_investment(&env, &invoice, &buyer, token_amount, payment + taker_fee);
 
         order.tokens_remaining -= token_amount;
         order.status = if order.tokens_remaining == 0 { OrderStatus::Filled } else { OrderStatus::PartiallyFilled };
         storage::set_sell_order(&env, &order_id, &order);
-        InvoiceEvents::order_filled(&env, &order_id, &buyer, token_amount, payment);
+        InvoiceEvents::order_filled(&env, &order_id, &order.invoice_id, &buyer, token_amount, payment);
         Ok(())
     }
 
@@ -277,10 +975,10 @@ impl SanginiInvoiceContract {
         seller.require_auth();
         let mut order = storage::get_sell_order(&env, &order_id).ok_or(ContractError::OrderNotFound)?;
         if order.seller != seller { return Err(ContractError::Unauthorized); }
-        if order.status == OrderStatus::Filled { return Err(ContractError::OrderAlreadyFilled); }
+        if order.status == OrderStatus::Filled { return Err(ContractError::OrderNotActive); }
         order.status = OrderStatus::Cancelled;
         storage::set_sell_order(&env, &order_id, &order);
-        InvoiceEvents::order_cancelled(&env, &order_id);
+        InvoiceEvents::order_cancelled(&env, &order_id, &order.invoice_id);
         Ok(())
     }
 
@@ -288,8 +986,255 @@ impl SanginiInvoiceContract {
         storage::get_sell_order(&env, &order_id).ok_or(ContractError::OrderNotFound)
     }
 
-    pub fn get_open_orders(env: Env, invoice_id: String) -> Vec<SellOrder> {
-        let order_ids = storage::get_orders_for_invoice(&env, &invoice_id);
+    /// Lists holdings across several invoices (e.g. all of one buyer's paper) as a single
+    /// order for `total_price`. Tokens stay with the seller until the basket is filled.
+    pub fn create_basket_order(env: Env, seller: Address, legs: Vec<BasketLeg>, total_price: i128) -> Result<String, ContractError> {
+        seller.require_auth();
+        if total_price <= 0 { return Err(ContractError::InvalidAmount); }
+        if legs.is_empty() || legs.len() > MAX_BASKET_LEGS { return Err(ContractError::InvalidBasket); }
+        for (i, leg) in legs.iter().enumerate() {
+            if leg.token_amount <= 0 { return Err(ContractError::InvalidAmount); }
+            if legs.iter().skip(i + 1).any(|other| other.invoice_id == leg.invoice_id) { return Err(ContractError::InvalidBasket); }
+            let invoice = get_invoice(&env, &leg.invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+            if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
+            let holding = storage::get_token_holding(&env, &leg.invoice_id, &seller).ok_or(ContractError::HoldingNotFound)?;
+            if Self::free_tokens(&env, &holding) < leg.token_amount { return Err(ContractError::InsufficientTokens); }
+        }
+
+        let order_id = Self::generate_order_id(&env);
+        let order = BasketOrder {
+            id: order_id.clone(), seller: seller.clone(), legs: legs.clone(), total_price,
+            created_at: env.ledger().timestamp(), status: OrderStatus::Open,
+        };
+        storage::set_basket_order(&env, &order_id, &order);
+        InvoiceEvents::basket_created(&env, &order_id, &seller, &legs, total_price);
+        Ok(order_id)
+    }
+
+    /// Buys a whole basket atomically. Each leg's program charges its taker and maker fees on a
+    /// token-weighted share of the price; any leg that can no longer be delivered fails the fill.
+    pub fn fill_basket_order(env: Env, order_id: String, buyer: Address) -> Result<(), ContractError> {
+        buyer.require_auth();
+        Self::require_not_paused(&env)?;
+        let _guard = ReentrancyGuard::acquire(&env, &order_id)?;
+
+        let mut order = storage::get_basket_order(&env, &order_id).ok_or(ContractError::OrderNotFound)?;
+        if order.status != OrderStatus::Open { return Err(ContractError::OrderNotActive); }
+
+        let total_tokens: i128 = order.legs.iter().map(|leg| leg.token_amount).sum();
+        let token_client = TokenClient::new(&env, &storage::get_usdc_token(&env));
+        let mut allocated = 0;
+        let (mut taker_total, mut maker_total, mut royalty_total) = (0, 0, 0);
+        let (mut fair_total, mut all_distressed) = (0, true);
+        for (i, leg) in order.legs.iter().enumerate() {
+            Self::require_program_kyc(&env, &leg.invoice_id, &buyer)?;
+            let invoice = get_invoice(&env, &leg.invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+            if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
+            Self::check_concentration(&env, &invoice, &buyer, leg.token_amount)?;
+            fair_total += Self::fair_value(&env, &invoice, leg.token_amount);
+            all_distressed &= storage::is_distressed_sale(&env, &leg.invoice_id, &order.seller);
+            Self::internal_transfer_tokens(&env, &leg.invoice_id, &order.seller, &buyer, leg.token_amount)?;
+
+            let share = if i as u32 + 1 == order.legs.len() { order.total_price - allocated } else { order.total_price * leg.token_amount / total_tokens };
+            allocated += share;
+            let (taker_fee, maker_fee, protocol_fee) = Self::trading_fees(&env, &invoice, share);
+            Self::record_investment(&env, &invoice, &buyer, leg.token_amount, share + taker_fee);
+            Self::credit_trading_fees(&env, invoice.program_id, taker_fee + maker_fee, protocol_fee);
+            royalty_total += Self::take_royalty(&env, &invoice, &order.seller, share, maker_fee)?;
+            taker_total += taker_fee;
+            maker_total += maker_fee;
+        }
+        // The band applies to the basket as a whole unless every leg is a flagged distressed sale
+        if !all_distressed && !Self::within_price_band(&env, order.total_price, fair_total) { return Err(ContractError::OutsidePriceBand); }
+        token_client.transfer(&buyer, &order.seller, &(order.total_price - maker_total - royalty_total));
+        let to_contract = taker_total + maker_total + royalty_total;
+        if to_contract > 0 { token_client.transfer(&buyer, &env.current_contract_address(), &to_contract); }
+
+        order.status = OrderStatus::Filled;
+        storage::set_basket_order(&env, &order_id, &order);
+        InvoiceEvents::basket_filled(&env, &order_id, &buyer, &order.legs, order.total_price);
+        Ok(())
+    }
+
+    pub fn cancel_basket_order(env: Env, order_id: String, seller: Address) -> Result<(), ContractError> {
+        seller.require_auth();
+        let mut order = storage::get_basket_order(&env, &order_id).ok_or(ContractError::OrderNotFound)?;
+        if order.seller != seller { return Err(ContractError::Unauthorized); }
+        if order.status != OrderStatus::Open { return Err(ContractError::OrderNotActive); }
+        order.status = OrderStatus::Cancelled;
+        storage::set_basket_order(&env, &order_id, &order);
+        for leg in order.legs.iter() { InvoiceEvents::order_cancelled(&env, &order_id, &leg.invoice_id); }
+        Ok(())
+    }
+
+    pub fn get_basket_order(env: Env, order_id: String) -> Result<BasketOrder, ContractError> {
+        storage::get_basket_order(&env, &order_id).ok_or(ContractError::OrderNotFound)
+    }
+
+    /// Requests quotes for a block of tokens. Makers reply off-chain with quotes signed as
+    /// authorizations of `(rfq_id, price_per_token, expires_at)`, executed via `execute_rfq`.
+    pub fn create_rfq(env: Env, invoice_id: String, holder: Address, token_amount: i128) -> Result<String, ContractError> {
+        holder.require_auth();
+        if token_amount <= 0 { return Err(ContractError::InvalidAmount); }
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
+        let holding = storage::get_token_holding(&env, &invoice_id, &holder).ok_or(ContractError::HoldingNotFound)?;
+        if Self::free_tokens(&env, &holding) < token_amount { return Err(ContractError::InsufficientTokens); }
+
+        let rfq_id = Self::generate_order_id(&env);
+        let rfq = Rfq {
+            id: rfq_id.clone(), invoice_id: invoice_id.clone(), holder: holder.clone(), token_amount,
+            created_at: env.ledger().timestamp(), status: OrderStatus::Open, program_id: invoice.program_id,
+        };
+        storage::set_rfq(&env, &rfq_id, &rfq);
+        InvoiceEvents::rfq_created(&env, &rfq_id, &invoice_id, &holder, token_amount);
+        Ok(rfq_id)
+    }
+
+    /// Sells the whole block to `maker` at their quoted price. The maker's signed authorization
+    /// of the quote is checked by the host, so a quote can't be altered or reused on another RFQ.
+    pub fn execute_rfq(env: Env, rfq_id: String, holder: Address, maker: Address, price_per_token: i128, expires_at: u64) -> Result<(), ContractError> {
+        holder.require_auth();
+        Self::require_not_paused(&env)?;
+        let _guard = ReentrancyGuard::acquire(&env, &rfq_id)?;
+        let mut rfq = storage::get_rfq(&env, &rfq_id).ok_or(ContractError::OrderNotFound)?;
+        if rfq.holder != holder { return Err(ContractError::Unauthorized); }
+        if rfq.status != OrderStatus::Open { return Err(ContractError::OrderNotActive); }
+        if env.ledger().timestamp() > expires_at { return Err(ContractError::OrderNotActive); }
+        if price_per_token <= 0 { return Err(ContractError::InvalidAmount); }
+        maker.require_auth_for_args((rfq_id.clone(), price_per_token, expires_at).into_val(&env));
+        if !storage::get_program_kyc_status(&env, rfq.program_id, &maker) { return Err(ContractError::KYCRequired); }
+        let invoice = get_invoice(&env, &rfq.invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
+        Self::check_concentration(&env, &invoice, &maker, rfq.token_amount)?;
+
+        // The holder takes the maker's quote: the holder pays the taker fee, the maker the maker fee
+        let payment = rfq.token_amount * price_per_token;
+        Self::check_price_band(&env, &invoice, &holder, rfq.token_amount, payment)?;
+        let (taker_fee, maker_fee, protocol_fee) = Self::trading_fees(&env, &invoice, payment);
+        let royalty = Self::take_royalty(&env, &invoice, &holder, payment, taker_fee)?;
+        let token_client = TokenClient::new(&env, &storage::get_usdc_token(&env));
+        token_client.transfer(&maker, &holder, &(payment - taker_fee - royalty));
+        if taker_fee + maker_fee + royalty > 0 {
+            token_client.transfer(&maker, &env.current_contract_address(), &(taker_fee + maker_fee + royalty));
+            Self::credit_trading_fees(&env, rfq.program_id, taker_fee + maker_fee, protocol_fee);
+        }
+        Self::internal_transfer_tokens(&env, &rfq.invoice_id, &holder, &maker, rfq.token_amount)?;
+        Self::record_investment(&env, &invoice, &maker, rfq.token_amount, payment + maker_fee);
+
+        rfq.status = OrderStatus::Filled;
+        storage::set_rfq(&env, &rfq_id, &rfq);
+        InvoiceEvents::rfq_executed(&env, &rfq_id, &rfq.invoice_id, &maker, price_per_token, payment);
+        Ok(())
+    }
+
+    pub fn cancel_rfq(env: Env, rfq_id: String, holder: Address) -> Result<(), ContractError> {
+        holder.require_auth();
+        let mut rfq = storage::get_rfq(&env, &rfq_id).ok_or(ContractError::OrderNotFound)?;
+        if rfq.holder != holder { return Err(ContractError::Unauthorized); }
+        if rfq.status != OrderStatus::Open { return Err(ContractError::OrderNotActive); }
+        rfq.status = OrderStatus::Cancelled;
+        storage::set_rfq(&env, &rfq_id, &rfq);
+        InvoiceEvents::order_cancelled(&env, &rfq_id, &rfq.invoice_id);
+        Ok(())
+    }
+
+    pub fn get_rfq(env: Env, rfq_id: String) -> Result<Rfq, ContractError> {
+        storage::get_rfq(&env, &rfq_id).ok_or(ContractError::OrderNotFound)
+    }
+
+    /// Posts a standing bid; the full `token_amount * price_per_token` (plus any maker fee) is
+    /// escrowed until the bid is filled or cancelled
+    pub fn create_buy_order(env: Env, invoice_id: String, buyer: Address, token_amount: i128, price_per_token: i128) -> Result<String, ContractError> {
+        buyer.require_auth();
+        if token_amount <= 0 || price_per_token <= 0 { return Err(ContractError::InvalidAmount); }
+        Self::require_program_kyc(&env, &invoice_id, &buyer)?;
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
+
+        let order_id = Self::generate_order_id(&env);
+        let order = BuyOrder {
+            id: order_id.clone(), invoice_id: invoice_id.clone(), buyer: buyer.clone(),
+            token_amount, price_per_token, tokens_remaining: token_amount,
+            created_at: env.ledger().timestamp(), status: OrderStatus::Open, program_id: invoice.program_id,
+            maker_fee_bps: storage::get_program_fees(&env, invoice.program_id).maker_fee_bps,
+        };
+        let escrow = token_amount * price_per_token + Self::bid_fee_escrow(&order);
+        TokenClient::new(&env, &storage::get_usdc_token(&env)).transfer(&buyer, &env.current_contract_address(), &escrow);
+        storage::add_to_escrow(&env, escrow);
+        storage::set_buy_order(&env, &order_id, &order);
+        storage::add_buy_order_to_invoice(&env, &invoice_id, &order_id);
+        InvoiceEvents::bid_created(&env, &order_id, &invoice_id, &buyer, token_amount, price_per_token);
+        Ok(order_id)
+    }
+
+    /// Sells tokens into a bid, paid from its escrow (less the program's taker fee)
+    pub fn fill_buy_order(env: Env, order_id: String, seller: Address, token_amount: i128) -> Result<(), ContractError> {
+        seller.require_auth();
+        Self::require_not_paused(&env)?;
+        let _guard = ReentrancyGuard::acquire(&env, &order_id)?;
+
+        let mut order = storage::get_buy_order(&env, &order_id).ok_or(ContractError::OrderNotFound)?;
+        if order.status != OrderStatus::Open && order.status != OrderStatus::PartiallyFilled { return Err(ContractError::OrderNotActive); }
+        if token_amount <= 0 { return Err(ContractError::InvalidAmount); }
+        if token_amount > order.tokens_remaining { return Err(ContractError::InsufficientTokens); }
+        let invoice = get_invoice(&env, &order.invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
+        Self::check_concentration(&env, &invoice, &order.buyer, token_amount)?;
+
+        Self::internal_transfer_tokens(&env, &order.invoice_id, &seller, &order.buyer, token_amount)?;
+        // The bid was resting: the seller pays the taker fee, the bidder the maker fee locked at posting
+        let payment = token_amount * order.price_per_token;
+        Self::check_price_band(&env, &invoice, &seller, token_amount, payment)?;
+        let (taker_fee, _, protocol_fee) = Self::trading_fees(&env, &invoice, payment);
+        let fee_held = Self::bid_fee_escrow(&order);
+        order.tokens_remaining -= token_amount;
+        let maker_fee = if order.maker_fee_bps > 0 { fee_held - Self::bid_fee_escrow(&order) }
+            else { ((payment * order.maker_fee_bps as i128) / 10000).max(protocol_fee - taker_fee) };
+        storage::release_from_escrow(&env, payment + maker_fee.max(0));
+        let royalty = Self::take_royalty(&env, &invoice, &seller, payment, taker_fee)?;
+        let token_client = TokenClient::new(&env, &storage::get_usdc_token(&env));
+        token_client.transfer(&env.current_contract_address(), &seller, &(payment - taker_fee - royalty));
+        if maker_fee < 0 { token_client.transfer(&env.current_contract_address(), &order.buyer, &-maker_fee); }
+        Self::credit_trading_fees(&env, order.program_id, taker_fee + maker_fee, protocol_fee);
+        Self::record_investment(&env, &invoice, &order.buyer, token_amount, payment + maker_fee);
+
+        order.status = if order.tokens_remaining == 0 { OrderStatus::Filled } else { OrderStatus::PartiallyFilled };
+        storage::set_buy_order(&env, &order_id, &order);
+        InvoiceEvents::bid_filled(&env, &order_id, &order.invoice_id, &seller, token_amount, payment);
+        Ok(())
+    }
+
+    /// Cancels a bid and refunds the escrow for its unfilled remainder. Returns the refund.
+    pub fn cancel_buy_order(env: Env, order_id: String, buyer: Address) -> Result<i128, ContractError> {
+        buyer.require_auth();
+        let mut order = storage::get_buy_order(&env, &order_id).ok_or(ContractError::OrderNotFound)?;
+        if order.buyer != buyer { return Err(ContractError::Unauthorized); }
+        if order.status != OrderStatus::Open && order.status != OrderStatus::PartiallyFilled { return Err(ContractError::OrderNotActive); }
+        Ok(Self::close_bid(&env, &mut order))
+    }
+
+    pub fn get_buy_order(env: Env, order_id: String) -> Result<BuyOrder, ContractError> {
+        storage::get_buy_order(&env, &order_id).ok_or(ContractError::OrderNotFound)
+    }
+
+    /// Open bids among the invoice's bids `[start, start + limit)` in posting order
+    pub fn get_open_buy_orders(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<BuyOrder> {
+        let mut open_orders = Vec::new(&env);
+        for id in Self::page(storage::get_buy_orders_for_invoice(&env, &invoice_id), start, limit).iter() {
+            if let Some(order) = storage::get_buy_order(&env, &id) {
+                if order.status == OrderStatus::Open || order.status == OrderStatus::PartiallyFilled {
+                    open_orders.push_back(order);
+                }
+            }
+        }
+        open_orders
+    }
+
+    /// Open sell orders among the invoice's orders `[start, start + limit)` in listing order.
+    /// Pages may hold fewer than `limit` entries; advance `start` by `limit` to continue.
+    pub fn get_open_orders(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<SellOrder> {
+        let order_ids = Self::page(storage::get_orders_for_invoice(&env, &invoice_id), start, limit);
         let mut open_orders = Vec::new(&env);
         for id in order_ids.iter() {
             if let Some(order) = storage::get_sell_order(&env, &id) {
@@ -304,226 +1249,2764 @@ impl SanginiInvoiceContract {
 
     pub fn transfer_tokens(env: Env, invoice_id: String, from: Address, to: Address, amount: i128) -> Result<(), ContractError> {
         from.require_auth();
+        Self::require_not_paused(&env)?;
         let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        if invoice.status != InvoiceStatus::Verified && invoice.status != InvoiceStatus::Funded && invoice.status != InvoiceStatus::Funding {
-            return Err(ContractError::InvalidStatus);
-        }
+        if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
         Self::internal_transfer_tokens(&env, &invoice_id, &from, &to, amount)?;
         InvoiceEvents::token_transfer(&env, &invoice_id, &from, &to, amount);
         Ok(())
     }
 
+    /// `transfer_tokens` that waits for the recipient to accept, so tokens never land at an
+    /// address that can't or won't use them. The tokens stay in the sender's holding, locked,
+    /// until `to` accepts; the sender may reclaim them once TRANSFER_ACCEPTANCE_SECONDS pass
+    /// without that. Returns the transfer id.
+    pub fn offer_tokens(env: Env, invoice_id: String, from: Address, to: Address, amount: i128) -> Result<u64, ContractError> {
+        from.require_auth();
+        Self::require_not_paused(&env)?;
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
+        Self::offer_transfer(&env, &invoice_id, &from, &to, amount)
+    }
+
+    /// Recipient takes an offered transfer, completing it. Possible until the sender reclaims it.
+    pub fn accept_tokens(env: Env, transfer_id: u64, to: Address) -> Result<(), ContractError> {
+        to.require_auth();
+        Self::require_not_paused(&env)?;
+        let transfer = storage::get_pending_transfer(&env, transfer_id).ok_or(ContractError::HoldingNotFound)?;
+        if transfer.to != to { return Err(ContractError::Unauthorized); }
+        let invoice = get_invoice(&env, &transfer.invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if !Self::is_tradeable(&invoice) { return Err(ContractError::InvalidStatus); }
+        storage::remove_pending_transfer(&env, &transfer);
+        Self::internal_transfer_tokens(&env, &transfer.invoice_id, &transfer.from, &to, transfer.amount)?;
+        InvoiceEvents::token_transfer(&env, &transfer.invoice_id, &transfer.from, &to, transfer.amount);
+        Ok(())
+    }
+
+    /// Sender takes back an offer its recipient has not accepted in time, unlocking the tokens.
+    /// Returns the amount unlocked.
+    pub fn reclaim_tokens(env: Env, transfer_id: u64, from: Address) -> Result<i128, ContractError> {
+        from.require_auth();
+        let transfer = storage::get_pending_transfer(&env, transfer_id).ok_or(ContractError::HoldingNotFound)?;
+        if transfer.from != from { return Err(ContractError::Unauthorized); }
+        if env.ledger().timestamp() < transfer.reclaimable_at { return Err(ContractError::InvalidStatus); }
+        storage::remove_pending_transfer(&env, &transfer);
+        InvoiceEvents::transfer_reclaimed(&env, &transfer.invoice_id, transfer_id, &from, transfer.amount);
+        Ok(transfer.amount)
+    }
+
+    pub fn get_pending_transfer(env: Env, transfer_id: u64) -> Option<PendingTransfer> { storage::get_pending_transfer(&env, transfer_id) }
+
+    /// Transfers offered to `to` awaiting its acceptance, `[start, start + limit)` of them in no
+    /// particular order (accepting or reclaiming one moves the last into its place)
+    pub fn get_pending_transfers(env: Env, to: Address, start: u32, limit: u32) -> Vec<PendingTransfer> {
+        let mut transfers = Vec::new(&env);
+        for transfer_id in storage::get_pending_transfer_ids(&env, &to, start, limit.min(MAX_PAGE_SIZE)).iter() {
+            if let Some(transfer) = storage::get_pending_transfer(&env, transfer_id) { transfers.push_back(transfer); }
+        }
+        transfers
+    }
+
+    /// Tokens in the holder's holding locked by its pending transfers
+    pub fn get_locked_tokens(env: Env, invoice_id: String, holder: Address) -> i128 { storage::get_locked_tokens(&env, &invoice_id, &holder) }
+
     pub fn check_status(env: Env, invoice_id: String) -> Result<InvoiceStatus, ContractError> {
         let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        let current_time = env.ledger().timestamp();
-        let rate_config = get_rate_config(&env);
-
-        if invoice.status == InvoiceStatus::Verified || invoice.status == InvoiceStatus::Funded || 
-           invoice.status == InvoiceStatus::Funding || invoice.status == InvoiceStatus::Overdue {
-            if invoice.repayment_received == 0 {
-                let grace_period_seconds = (rate_config.grace_period_days as u64) * 86400;
-                if current_time > invoice.due_date + grace_period_seconds {
-                    invoice.status = InvoiceStatus::Defaulted;
-                    set_invoice(&env, &invoice_id, &invoice);
-                    InvoiceEvents::invoice_defaulted(&env, &invoice_id);
-                } else if current_time > invoice.due_date && invoice.status != InvoiceStatus::Overdue {
-                    invoice.status = InvoiceStatus::Overdue;
-                    set_invoice(&env, &invoice_id, &invoice);
-                }
-            }
-        }
+        Self::apply_due_date_status(&env, &mut invoice)?;
         Ok(invoice.status)
     }
 
-    pub fn settle(env: Env, invoice_id: String, buyer: Address, payment_amount: i128) -> Result<(), ContractError> {
-        buyer.require_auth();
+    /// `check_status` that pays the keeper the configured bounty (capped at the invoice's
+    /// insurance pool) when it moves the invoice to Overdue or Defaulted. Only the call that
+    /// makes the transition is paid. Returns the bounty paid.
+    pub fn poke(env: Env, invoice_id: String, keeper: Address) -> Result<i128, ContractError> {
+        keeper.require_auth();
         let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
-        if invoice.status != InvoiceStatus::Funded && invoice.status != InvoiceStatus::Overdue && invoice.status != InvoiceStatus::Verified && invoice.status != InvoiceStatus::Funding {
-            return Err(ContractError::InvalidStatus);
+        Self::poke_invoice(&env, &mut invoice, &keeper)
+    }
+
+    /// `poke` over several invoices. Unknown invoices and ones with nothing to do are skipped.
+    /// Returns the total bounty paid.
+    pub fn poke_many(env: Env, invoice_ids: Vec<String>, keeper: Address) -> Result<i128, ContractError> {
+        keeper.require_auth();
+        if invoice_ids.len() > MAX_PAGE_SIZE { return Err(ContractError::InvalidAmount); }
+        let mut total = 0;
+        for invoice_id in invoice_ids.iter() {
+            if let Some(mut invoice) = get_invoice(&env, &invoice_id) {
+                total += Self::poke_invoice(&env, &mut invoice, &keeper)?;
+            }
         }
-        if invoice.status == InvoiceStatus::Disputed { return Err(ContractError::InvoiceDisputed); }
+        Ok(total)
+    }
 
-        let required_payment = Self::calculate_settlement_amount(&env, &invoice);
-        if payment_amount < required_payment { return Err(ContractError::InsufficientPayment); }
+    /// Walks the default queue's day buckets from the earliest due day, defaulting invoices past
+    /// their program's grace period. Stops after today's bucket or after `max_n` entries.
+    /// Returns the number of invoices defaulted.
+    pub fn process_defaults(env: Env, max_n: u32) -> u32 {
+        let now = env.ledger().timestamp();
+        let (mut visited, mut defaulted) = (0u32, 0u32);
+        for day in storage::get_default_days(&env).iter() {
+            if visited >= max_n || day > now / storage::DEFAULT_BUCKET_SECONDS { break; }
+            let mut bucket = storage::get_default_bucket(&env, day);
+            let mut index = 0;
+            while index < bucket.len() && visited < max_n {
+                let invoice_id = bucket.get(index).unwrap();
+                visited += 1;
+                match get_invoice(&env, &invoice_id) {
+                    Some(mut invoice) if Self::pending_action(&env, &invoice, now) == Some(KeeperAction::MarkDefaulted) => {
+                        if Self::mark_defaulted(&env, &mut invoice).is_ok() { defaulted += 1; }
+                        bucket.remove(index);
+                    }
+                    // Not yet due, or within its grace period (which differs per program, so
+                    // later entries may already be defaultable)
+                    Some(invoice) if now <= invoice.due_date + Self::grace_period_seconds(&env, &invoice) => index += 1,
+                    // Paid, settled, revoked, already defaulted or collected; or disputed, which
+                    // is queued again when the dispute resolves
+                    _ => { bucket.remove(index); }
+                }
+            }
+            storage::set_default_bucket(&env, day, &bucket);
+        }
+        defaulted
+    }
 
-        let payment_token = storage::get_usdc_token(&env);
-        let token_client = TokenClient::new(&env, &payment_token);
-        token_client.transfer(&buyer, &env.current_contract_address(), &payment_amount);
-        Self::distribute_settlement(&env, &invoice_id, payment_amount)?;
+    /// Up to `limit` invoices due in `[from_ts, to_ts]`, read from the weekly maturity index
+    pub fn get_maturities(env: Env, from_ts: u64, to_ts: u64, limit: u32) -> Result<Vec<MaturityEntry>, ContractError> {
+        if to_ts < from_ts { return Err(ContractError::InvalidAmount); }
+        let first = from_ts / storage::MATURITY_BUCKET_SECONDS;
+        let last = to_ts / storage::MATURITY_BUCKET_SECONDS;
+        if last - first >= MAX_MATURITY_BUCKETS { return Err(ContractError::InvalidAmount); }
+        let limit = limit.min(MAX_PAGE_SIZE);
+        let mut result = Vec::new(&env);
+        for bucket in first..=last {
+            for invoice_id in storage::get_maturity_bucket(&env, bucket).iter() {
+                if result.len() >= limit { return Ok(result); }
+                if let Some(invoice) = get_invoice(&env, &invoice_id) {
+                    if invoice.due_date >= from_ts && invoice.due_date <= to_ts {
+                        result.push_back(MaturityEntry { invoice_id, due_date: invoice.due_date, amount: invoice.amount, status: invoice.status });
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
 
-        invoice.status = InvoiceStatus::Settled;
-        invoice.settled_at = env.ledger().timestamp();
-        invoice.repayment_received = payment_amount;
-        set_invoice(&env, &invoice_id, &invoice);
-        InvoiceEvents::invoice_settled(&env, &invoice_id, payment_amount);
-        Ok(())
+    /// Deletes a terminal invoice's storage once the retention period has passed and pays
+    /// the caller the configured bounty (capped at the protocol treasury). Defaulted and
+    /// written-off invoices stay while any holder can still claim insurance or is owed a recovery.
+    pub fn gc_invoice(env: Env, invoice_id: String, caller: Address) -> Result<i128, ContractError> {
+        caller.require_auth();
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let terminal_at = if invoice.status.is_settled() {
+            invoice.settled_at
+        } else if matches!(invoice.status, InvoiceStatus::Revoked | InvoiceStatus::Defaulted | InvoiceStatus::Recovered | InvoiceStatus::WrittenOff) {
+            storage::get_history(&env, &invoice_id).last().map(|r| r.timestamp).unwrap_or(0)
+        } else {
+            return Err(ContractError::NotCollectable);
+        };
+        if env.ledger().timestamp() < terminal_at + GC_RETENTION_SECONDS { return Err(ContractError::NotCollectable); }
+        if Self::has_open_claims(&env, &invoice) { return Err(ContractError::NotCollectable); }
+
+        storage::purge_invoice(&env, &invoice);
+        let bounty = Self::pay_treasury_bounty(&env, &caller, storage::get_gc_bounty(&env));
+        InvoiceEvents::invoice_collected(&env, &invoice_id, &caller, bounty);
+        Ok(bounty)
     }
 
+    /// Drafts, live auctions, invoices awaiting settlement and lifetime proceeds for a supplier,
+    /// read from the supplier's own invoice index
+    pub fn get_supplier_summary(env: Env, supplier: Address) -> SupplierSummary {
+        let mut summary = SupplierSummary { lifetime_proceeds: storage::get_supplier_proceeds(&env, &supplier), ..Default::default() };
+        for invoice_id in storage::get_supplier_invoices(&env, &supplier).iter() {
+            let Some(invoice) = get_invoice(&env, &invoice_id) else { continue };
+            match invoice.status {
+                InvoiceStatus::Draft => { summary.draft_count += 1; summary.draft_amount += invoice.amount; }
+                InvoiceStatus::Funding => {
+                    summary.auction_count += 1;
+                    summary.auction_tokens_total += invoice.total_tokens;
+                    summary.auction_tokens_sold += invoice.tokens_sold;
+                }
+                InvoiceStatus::Funded | InvoiceStatus::Overdue => { summary.awaiting_count += 1; summary.awaiting_amount += invoice.amount; }
+                _ => {}
+            }
+        }
+        summary
+    }
 
-    pub fn raise_dispute(env: Env, invoice_id: String, buyer: Address, reason: String) -> Result<(), ContractError> {
-        buyer.require_auth();
-        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
-        if invoice.status != InvoiceStatus::Verified && invoice.status != InvoiceStatus::Funded && 
-           invoice.status != InvoiceStatus::Funding && invoice.status != InvoiceStatus::Overdue {
-            return Err(ContractError::InvalidStatus);
+    /// Principal at risk, realized returns and losses, insurance recoveries and cost-weighted
+    /// yield across settled positions, from running per-investor totals
+    pub fn get_investor_summary(env: Env, investor: Address) -> InvestorSummary {
+        let totals = storage::get_investor_totals(&env, &investor);
+        let realized_returns = totals.settled_received - totals.settled_cost;
+        InvestorSummary {
+            principal_outstanding: Self::exposure_of(&env, &investor),
+            realized_returns,
+            realized_losses: storage::get_realized_loss(&env, &investor),
+            insurance_recoveries: totals.insurance_recovered,
+            avg_yield_bps: if totals.settled_cost > 0 { (realized_returns * 10000 / totals.settled_cost) as i32 } else { 0 },
         }
-        let dispute = Dispute {
-            invoice_id: invoice_id.clone(), raised_by: buyer.clone(), reason,
-            raised_at: env.ledger().timestamp(), resolution: DisputeResolution::Pending, resolved_at: 0,
+    }
+
+    /// Invoices that currently need a keeper-triggered transition: past-due invoices from the
+    /// default queue's day buckets in due-day order, then expired auctions from the Funding
+    /// status index. Scans from `start` (`Start` to begin) and stops after `limit` matches or
+    /// MAX_ACTIONABLE_SCAN entries read; pass the returned `next` back to continue until it is
+    /// `Done`. Entries move as invoices change status, so a scan spanning transitions may skip
+    /// or repeat some.
+    pub fn get_actionable(env: Env, start: ActionableCursor, limit: u32) -> ActionablePage {
+        let limit = limit.min(MAX_PAGE_SIZE);
+        let mut invoices = Vec::new(&env);
+        let now = env.ledger().timestamp();
+        let mut scanned = 0;
+        let (queue_from, funding_from) = match start {
+            ActionableCursor::Start => (Some((0, 0)), 0),
+            ActionableCursor::Queue(day, index) => (Some((day, index)), 0),
+            ActionableCursor::Funding(slot) => (None, slot),
+            ActionableCursor::Done => return ActionablePage { invoices, next: ActionableCursor::Done },
         };
-        storage::set_dispute(&env, &invoice_id, &dispute);
-        invoice.status = InvoiceStatus::Disputed;
-        set_invoice(&env, &invoice_id, &invoice);
-        InvoiceEvents::dispute_raised(&env, &invoice_id, &buyer);
-        Ok(())
+        if let Some((from_day, from_index)) = queue_from {
+            for day in storage::get_default_days(&env).iter() {
+                if day < from_day { continue; }
+                if day > now / storage::DEFAULT_BUCKET_SECONDS { break; }
+                let bucket = storage::get_default_bucket(&env, day);
+                for index in if day == from_day { from_index } else { 0 }..bucket.len() {
+                    if invoices.len() >= limit || scanned >= MAX_ACTIONABLE_SCAN {
+                        return ActionablePage { invoices, next: ActionableCursor::Queue(day, index) };
+                    }
+                    scanned += 1;
+                    let invoice_id = bucket.get(index).unwrap();
+                    let Some(invoice) = get_invoice(&env, &invoice_id) else { continue };
+                    if let Some(action) = Self::pending_action(&env, &invoice, now) {
+                        invoices.push_back(ActionableInvoice { invoice_id, status: invoice.status, action });
+                    }
+                }
+            }
+        }
+        for slot in funding_from..storage::status_invoice_count(&env, &InvoiceStatus::Funding) {
+            if invoices.len() >= limit || scanned >= MAX_ACTIONABLE_SCAN {
+                return ActionablePage { invoices, next: ActionableCursor::Funding(slot) };
+            }
+            scanned += 1;
+            let Some(invoice_id) = storage::get_status_invoices(&env, &InvoiceStatus::Funding, slot, 1).first() else { continue };
+            let Some(invoice) = get_invoice(&env, &invoice_id) else { continue };
+            // Past-due auctions were already listed from the queue
+            if Self::pending_action(&env, &invoice, now) == Some(KeeperAction::AuctionExpired) {
+                invoices.push_back(ActionableInvoice { invoice_id, status: invoice.status, action: KeeperAction::AuctionExpired });
+            }
+        }
+        ActionablePage { invoices, next: ActionableCursor::Done }
     }
 
-    pub fn resolve_dispute(env: Env, invoice_id: String, admin: Address, is_valid: bool) -> Result<(), ContractError> {
-        admin.require_auth();
-        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
-        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        if invoice.status != InvoiceStatus::Disputed { return Err(ContractError::InvalidStatus); }
-        let mut dispute = storage::get_dispute(&env, &invoice_id).ok_or(ContractError::DisputeNotFound)?;
+    // ========================================================================
+    // MIGRATION EXPORTS
+    // ========================================================================
+    // Deterministic snapshots of key state for seeding a redeployment. Each view pages over
+    // positions `[start, start + limit)` (limit capped at MAX_PAGE_SIZE) of the ranges in
+    // `get_export_summary`; gaps (e.g. collected invoices, closed orders) are skipped.
 
-        if is_valid {
-            Self::execute_clawback(&env, &invoice_id)?;
-            dispute.resolution = DisputeResolution::Valid;
-        } else {
-            dispute.resolution = DisputeResolution::Invalid;
-            invoice.status = InvoiceStatus::Funded;
+    pub fn get_export_summary(env: Env) -> ExportSummary {
+        ExportSummary {
+            invoice_count: storage::get_invoice_counter(&env),
+            order_count: storage::get_order_counter(&env),
+            program_count: storage::get_program_counter(&env),
+            kyc_count: storage::get_kyc_index_count(&env, 0),
+            total_insurance_pools: storage::get_total_insurance_pools(&env),
+            total_treasuries: storage::get_total_treasuries(&env),
+            escrow_total: storage::get_escrow_total(&env),
+            insurance_backstop_bps: storage::get_insurance_backstop(&env),
         }
-        dispute.resolved_at = env.ledger().timestamp();
-        storage::set_dispute(&env, &invoice_id, &dispute);
-        set_invoice(&env, &invoice_id, &invoice);
-        InvoiceEvents::dispute_resolved(&env, &invoice_id, is_valid);
-        Ok(())
     }
 
-    pub fn revoke(env: Env, invoice_id: String, supplier: Address) -> Result<(), ContractError> {
-        supplier.require_auth();
-        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
-        let current_time = env.ledger().timestamp();
-        let can_revoke = match invoice.status {
-            InvoiceStatus::Draft => true,
-            InvoiceStatus::Verified => current_time > invoice.due_date,
-            _ => false,
-        };
-        if !can_revoke { return Err(ContractError::CannotRevoke); }
-        storage::clear_token_holdings(&env, &invoice_id);
-        invoice.status = InvoiceStatus::Revoked;
-        set_invoice(&env, &invoice_id, &invoice);
-        InvoiceEvents::invoice_revoked(&env, &invoice_id);
-        Ok(())
+    /// Invoices in issuance order
+    pub fn export_invoices(env: Env, start: u32, limit: u32) -> Vec<InvoiceSnapshot> {
+        let mut snapshots = Vec::new(&env);
+        for counter in Self::export_range(start, limit, storage::get_invoice_counter(&env)) {
+            let Some(invoice) = get_invoice(&env, &Self::format_invoice_id(&env, counter)) else { continue };
+            snapshots.push_back(InvoiceSnapshot {
+                insurance_balance: storage::get_invoice_insurance(&env, &invoice.id),
+                invoice_id: invoice.id, status: invoice.status, program_id: invoice.program_id,
+            });
+        }
+        snapshots
     }
 
-    pub fn set_investor_kyc(env: Env, admin: Address, investor: Address, approved: bool) -> Result<(), ContractError> {
-        admin.require_auth();
-        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
-        set_kyc_status(&env, &investor, approved);
-        InvoiceEvents::kyc_updated(&env, &investor, approved);
-        Ok(())
+    /// Global KYC list, including revoked entries. Addresses whose status predates the index
+    /// are listed once `backfill_kyc_index` has been run over them.
+    pub fn export_kyc(env: Env, start: u32, limit: u32) -> Vec<KycSnapshot> {
+        Self::export_program_kyc(env, 0, start, limit)
     }
 
-    pub fn set_relayer(env: Env, admin: Address, relayer: Address, authorized: bool) -> Result<(), ContractError> {
-        admin.require_auth();
-        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
-        storage::set_authorized_relayer(&env, &relayer, authorized);
-        Ok(())
+    /// A program's own KYC list, as `export_kyc`; positions run 0..get_program_kyc_count
+    pub fn export_program_kyc(env: Env, program_id: u32, start: u32, limit: u32) -> Vec<KycSnapshot> {
+        let mut snapshots = Vec::new(&env);
+        for position in Self::export_range(start, limit, storage::get_kyc_index_count(&env, program_id)) {
+            let Some(investor) = storage::get_kyc_index(&env, program_id, position) else { continue };
+            snapshots.push_back(KycSnapshot { approved: storage::get_program_kyc_status(&env, program_id, &investor), investor });
+        }
+        snapshots
     }
 
-    pub fn is_kyc_approved(env: Env, investor: Address) -> bool { get_kyc_status(&env, &investor) }
-    pub fn get_invoice(env: Env, invoice_id: String) -> Result<Invoice, ContractError> { get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound) }
-    pub fn get_holding(env: Env, invoice_id: String, holder: Address) -> Result<TokenHolding, ContractError> { storage::get_token_holding(&env, &invoice_id, &holder).ok_or(ContractError::HoldingNotFound) }
-    pub fn get_dispute(env: Env, invoice_id: String) -> Result<Dispute, ContractError> { storage::get_dispute(&env, &invoice_id).ok_or(ContractError::DisputeNotFound) }
-    pub fn get_settlement_amount(env: Env, invoice_id: String) -> Result<i128, ContractError> { let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?; Ok(Self::calculate_settlement_amount(&env, &invoice)) }
-    pub fn verify_document(env: Env, invoice_id: String, document_hash: String) -> bool { get_invoice(&env, &invoice_id).map(|inv| inv.document_hash == document_hash).unwrap_or(false) }
-
+    pub fn get_program_kyc_count(env: Env, program_id: u32) -> u32 { storage::get_kyc_index_count(&env, program_id) }
 
-    // ========================================================================
+    /// Adds addresses whose KYC status on the program's list (global for program 0) was set
+    /// before the export index existed, e.g. as collected from past `kyc_updated` events.
+    /// Addresses already indexed or never KYC'd are skipped. Returns how many were added.
+    pub fn backfill_kyc_index(env: Env, admin: Address, program_id: u32, investors: Vec<Address>) -> Result<u32, ContractError> {
+        Self::require_program_admin(&env, program_id, &admin)?;
+        if investors.len() > MAX_PAGE_SIZE { return Err(ContractError::InvalidAmount); }
+        let mut added = 0;
+        for investor in investors.iter() {
+            if storage::has_program_kyc_status(&env, program_id, &investor) && storage::index_kyc_address(&env, program_id, &investor) {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Open (or partly filled) sell and buy orders, in order-id order
+    pub fn export_open_orders(env: Env, start: u32, limit: u32) -> OpenOrdersSnapshot {
+        let (mut sell_orders, mut buy_orders) = (Vec::new(&env), Vec::new(&env));
+        let open = |status: &OrderStatus| *status == OrderStatus::Open || *status == OrderStatus::PartiallyFilled;
+        for counter in Self::export_range(start, limit, storage::get_order_counter(&env)) {
+            let order_id = Self::format_order_id(&env, counter);
+            if let Some(order) = storage::get_sell_order(&env, &order_id).filter(|order| open(&order.status)) { sell_orders.push_back(order); }
+            if let Some(order) = storage::get_buy_order(&env, &order_id).filter(|order| open(&order.status)) { buy_orders.push_back(order); }
+        }
+        OpenOrdersSnapshot { sell_orders, buy_orders }
+    }
+
+    /// Insurance pool accounting for program ids `[start, start + limit)`, program 0 included
+    pub fn export_insurance(env: Env, start: u32, limit: u32) -> Vec<InsuranceSnapshot> {
+        let mut snapshots = Vec::new(&env);
+        for program_id in Self::export_range(start, limit, storage::get_program_counter(&env) + 1) {
+            snapshots.push_back(InsuranceSnapshot {
+                program_id,
+                pool_balance: storage::get_insurance_pool(&env, program_id),
+                earmarked: storage::get_earmarked_insurance(&env, program_id),
+                underwriter_shares: storage::get_underwriter_shares(&env, program_id),
+            });
+        }
+        snapshots
+    }
+
+    pub fn settle(env: Env, invoice_id: String, buyer: Address, payment_amount: i128) -> Result<(), ContractError> {
+        buyer.require_auth();
+        Self::require_not_paused(&env)?;
+        let _guard = ReentrancyGuard::acquire(&env, &invoice_id)?;
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
+        let now = env.ledger().timestamp();
+        state_machine::check(&invoice, &Event::Settle { short: false }, now)?;
+        if invoice.repayment_received > 0 { return Err(ContractError::InstallmentConflict); }
+
+        // payment_amount is in the settlement token; obligations are measured in the funding token
+        let (settlement_token, fx_rate) = Self::settlement_leg(&env, &invoice)?;
+        let payment_in_funding = (payment_amount * RATE_SCALE) / fx_rate;
+        let (required_payment, total_paid) = Self::settlement_totals(&env, &invoice, payment_in_funding, now)?;
+        let prepaid = total_paid - payment_in_funding;
+
+        let token_client = TokenClient::new(&env, &settlement_token);
+        if payment_amount > 0 {
+            token_client.transfer(&buyer, &env.current_contract_address(), &payment_amount);
+        }
+        storage::clear_prepayments(&env, &invoice_id);
+        let underwriter_cut = Self::underwriter_cut(&env, &invoice, total_paid);
+        if underwriter_cut > 0 {
+            storage::add_underwriter_yield(&env, invoice.program_id, underwriter_cut);
+            InvoiceEvents::insurance_funded(&env, underwriter_cut, storage::get_insurance_pool(&env, invoice.program_id));
+        }
+        Self::distribute_settlement(&env, &invoice_id, &settlement_token, payment_amount + prepaid - underwriter_cut, (invoice.amount * fx_rate) / RATE_SCALE)?;
+
+        interest::record_benchmark(&env, &invoice, true);
+        state_machine::transition(&mut invoice, Event::Settle { short: total_paid < required_payment }, now)?;
+        invoice.settled_at = now;
+        invoice.repayment_received = total_paid;
+        invoice.settlement_fx_rate = fx_rate;
+        invoice.settlement_paid = payment_amount + prepaid;
+        set_invoice(&env, &invoice_id, &invoice);
+        risk::record_buyer_outcome(&env, &invoice);
+        Self::record_transition(&env, &invoice, &buyer, total_paid);
+        InvoiceEvents::invoice_settled(&env, &invoice_id, total_paid);
+        Ok(())
+    }
+
+    /// Pays part of the settlement. Installments are credited to current holders pro-rata and can
+    /// be withdrawn at any time with `claim_installments`; once the full settlement amount is
+    /// reached the invoice settles and unclaimed shares are paid out. Funding-token invoices only.
+    pub fn settle_partial(env: Env, invoice_id: String, buyer: Address, amount: i128) -> Result<InvoiceStatus, ContractError> {
+        buyer.require_auth();
+        Self::require_not_paused(&env)?;
+        let _guard = ReentrancyGuard::acquire(&env, &invoice_id)?;
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
+        if invoice.status != InvoiceStatus::Funded && invoice.status != InvoiceStatus::Overdue { return Err(ContractError::InvalidStatus); }
+        if invoice.settlement_token.is_some() { return Err(ContractError::CrossCurrencyUnsupported); }
+        if storage::get_prepaid_total(&env, &invoice_id) > 0 { return Err(ContractError::InstallmentConflict); }
+        if amount <= 0 { return Err(ContractError::InvalidAmount); }
+
+        // Overpayment is capped at what is still owed
+        let now = env.ledger().timestamp();
+        let required_payment = Self::calculate_settlement_amount(&env, &invoice);
+        let accepted = amount.min(required_payment - invoice.repayment_received);
+        TokenClient::new(&env, &storage::get_usdc_token(&env)).transfer(&buyer, &env.current_contract_address(), &accepted);
+        storage::add_to_escrow(&env, accepted);
+        invoice.repayment_received += accepted;
+        InvoiceEvents::installment_paid(&env, &invoice_id, accepted, invoice.repayment_received);
+
+        if invoice.repayment_received >= required_payment {
+            for holder in storage::get_all_holders(&env, &invoice_id).iter() {
+                Self::close_installment_position(&env, &invoice, &holder, now);
+            }
+            interest::record_benchmark(&env, &invoice, true);
+            let short = invoice.repayment_received < required_payment;
+            state_machine::transition(&mut invoice, Event::Settle { short }, now)?;
+            invoice.settled_at = now;
+            invoice.settlement_fx_rate = RATE_SCALE;
+            invoice.settlement_paid = invoice.repayment_received;
+            set_invoice(&env, &invoice_id, &invoice);
+            risk::record_buyer_outcome(&env, &invoice);
+            Self::record_transition(&env, &invoice, &buyer, invoice.repayment_received);
+            InvoiceEvents::invoice_settled(&env, &invoice_id, invoice.repayment_received);
+        } else {
+            set_invoice(&env, &invoice_id, &invoice);
+        }
+        Ok(invoice.status)
+    }
+
+    /// Withdraws a holder's share of the installments paid so far. Returns the amount paid out.
+    pub fn claim_installments(env: Env, invoice_id: String, holder: Address) -> Result<i128, ContractError> {
+        holder.require_auth();
+        Self::require_not_paused(&env)?;
+        let _guard = ReentrancyGuard::acquire(&env, &invoice_id)?;
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let mut account = Self::sync_installments(&env, &invoice, &holder);
+        let amount = account.accrued;
+        if amount == 0 { return Ok(0); }
+        account.accrued = 0;
+        account.withdrawn += amount;
+        storage::set_installment_account(&env, &invoice_id, &holder, &account);
+        storage::release_from_escrow(&env, amount);
+        Self::route_payout(&env, &storage::get_usdc_token(&env), &holder, amount);
+        InvoiceEvents::settlement_distributed(&env, &invoice_id, &holder, amount);
+        Ok(amount)
+    }
+
+    /// Buyer asks the holders of a funded invoice to move its due date back, paying
+    /// `bonus_rate_bps` on top of the pre-maturity rate for the days past the current due date.
+    /// Holders vote for EXTENSION_VOTING_SECONDS. Returns when voting ends.
+    pub fn propose_extension(env: Env, invoice_id: String, buyer: Address, new_due_date: u64, bonus_rate_bps: u32) -> Result<u64, ContractError> {
+        buyer.require_auth();
+        Self::require_not_paused(&env)?;
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
+        let now = env.ledger().timestamp();
+        state_machine::check(&invoice, &Event::Extend, now)?;
+        if storage::get_extension_proposal(&env, &invoice_id).is_some() { return Err(ContractError::InvalidStatus); }
+        if new_due_date <= invoice.due_date || new_due_date <= now || bonus_rate_bps > 10000 { return Err(ContractError::InvalidAmount); }
+
+        let voting_ends = now + EXTENSION_VOTING_SECONDS;
+        storage::set_extension_proposal(&env, &invoice_id, &ExtensionProposal { new_due_date, bonus_rate_bps, proposed_at: now, voting_ends });
+        InvoiceEvents::extension_proposed(&env, &invoice_id, new_due_date, bonus_rate_bps, voting_ends);
+        Ok(voting_ends)
+    }
+
+    /// Holder votes on the pending extension; the vote can be changed until voting ends
+    pub fn vote_extension(env: Env, invoice_id: String, holder: Address, approve: bool) -> Result<(), ContractError> {
+        holder.require_auth();
+        let proposal = storage::get_extension_proposal(&env, &invoice_id).ok_or(ContractError::InvalidStatus)?;
+        let now = env.ledger().timestamp();
+        if now > proposal.voting_ends { return Err(ContractError::AppealWindowClosed); }
+        if storage::get_token_holding(&env, &invoice_id, &holder).is_none() { return Err(ContractError::HoldingNotFound); }
+        storage::set_extension_vote(&env, &invoice_id, &ExtensionVote { holder: holder.clone(), approve, voted_at: now });
+        InvoiceEvents::extension_vote(&env, &invoice_id, &holder, approve);
+        Ok(())
+    }
+
+    /// Tallies the pending extension at current holdings, so tokens moved after a vote only count
+    /// for their new holder. Applies it as soon as EXTENSION_APPROVAL_BPS of the tokens approve;
+    /// drops it if voting ended short. Returns whether the extension was applied.
+    pub fn finalize_extension(env: Env, invoice_id: String) -> Result<bool, ContractError> {
+        let proposal = storage::get_extension_proposal(&env, &invoice_id).ok_or(ContractError::InvalidStatus)?;
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let now = env.ledger().timestamp();
+        let approving: i128 = storage::get_extension_votes(&env, &invoice_id, 0, storage::extension_vote_count(&env, &invoice_id)).iter()
+            .filter(|v| v.approve)
+            .filter_map(|v| storage::get_token_holding(&env, &invoice_id, &v.holder))
+            .map(|h| h.amount)
+            .sum();
+        let approved = approving * 10000 >= invoice.total_tokens * EXTENSION_APPROVAL_BPS;
+        if !approved && now <= proposal.voting_ends { return Err(ContractError::QuorumNotReached); }
+
+        storage::remove_extension_proposal(&env, &invoice_id);
+        InvoiceEvents::extension_tallied(&env, &invoice_id, approving, approved);
+        if !approved { return Ok(false); }
+
+        state_machine::transition(&mut invoice, Event::Extend, now)?;
+        let original_due_date = storage::get_due_date_extension(&env, &invoice_id).map_or(invoice.due_date, |e| e.original_due_date);
+        storage::set_due_date_extension(&env, &invoice_id, &DueDateExtension { original_due_date, bonus_rate_bps: proposal.bonus_rate_bps });
+        storage::remove_from_maturity_bucket(&env, invoice.due_date, &invoice_id);
+        storage::add_to_maturity_bucket(&env, proposal.new_due_date, &invoice_id);
+        storage::dequeue_for_default(&env, invoice.due_date, &invoice_id);
+        storage::enqueue_for_default(&env, proposal.new_due_date, &invoice_id);
+        invoice.due_date = proposal.new_due_date;
+        set_invoice(&env, &invoice_id, &invoice);
+        Self::record_transition(&env, &invoice, &invoice.buyer, 0);
+        Ok(true)
+    }
+
+    pub fn get_extension_proposal(env: Env, invoice_id: String) -> Option<ExtensionProposal> { storage::get_extension_proposal(&env, &invoice_id) }
+
+    pub fn get_extension_votes(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<ExtensionVote> {
+        storage::get_extension_votes(&env, &invoice_id, start, limit.min(MAX_PAGE_SIZE))
+    }
+
+    /// Original due date and bonus rate of an invoice whose due date holders extended
+    pub fn get_due_date_extension(env: Env, invoice_id: String) -> Option<DueDateExtension> { storage::get_due_date_extension(&env, &invoice_id) }
+
+    /// Dry run of `settle` at an arbitrary timestamp. Returns the per-holder payouts without moving funds.
+    pub fn simulate_settlement(env: Env, invoice_id: String, payment_amount: i128, at_timestamp: u64) -> Result<SettlementSimulation, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        state_machine::check(&invoice, &Event::Settle { short: false }, at_timestamp)?;
+        let (settlement_token, fx_rate) = Self::settlement_leg(&env, &invoice)?;
+        let payment_in_funding = (payment_amount * RATE_SCALE) / fx_rate;
+        let (required_payment, total_paid) = Self::settlement_totals(&env, &invoice, payment_in_funding, at_timestamp)?;
+        let distributed = payment_amount + total_paid - payment_in_funding - Self::underwriter_cut(&env, &invoice, total_paid);
+        Ok(SettlementSimulation {
+            required_payment,
+            total_paid,
+            status: state_machine::next(&invoice, &Event::Settle { short: total_paid < required_payment }, at_timestamp)?,
+            payouts: Self::compute_payouts(&env, &invoice, &settlement_token, distributed, (invoice.amount * fx_rate) / RATE_SCALE, at_timestamp),
+        })
+    }
+
+    /// Escrow part of the settlement ahead of the due date. Prepaid amounts stop accruing interest from deposit.
+    pub fn prepay(env: Env, invoice_id: String, buyer: Address, amount: i128) -> Result<i128, ContractError> {
+        buyer.require_auth();
+        Self::require_not_paused(&env)?;
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
+        // A prepayment is part of the eventual settlement, so it is allowed wherever settling is
+        state_machine::check(&invoice, &Event::Settle { short: false }, env.ledger().timestamp())?;
+        if invoice.settlement_token.is_some() { return Err(ContractError::CrossCurrencyUnsupported); }
+        if invoice.repayment_received > 0 { return Err(ContractError::InstallmentConflict); }
+        let prepaid = storage::get_prepaid_total(&env, &invoice_id);
+        if amount <= 0 || prepaid + amount > invoice.amount { return Err(ContractError::InvalidAmount); }
+
+        let payment_token = storage::get_usdc_token(&env);
+        TokenClient::new(&env, &payment_token).transfer(&buyer, &env.current_contract_address(), &amount);
+        storage::add_prepayment(&env, &invoice_id, &Prepayment { amount, deposited_at: env.ledger().timestamp() });
+        InvoiceEvents::prepayment_made(&env, &invoice_id, amount, prepaid + amount);
+        Ok(prepaid + amount)
+    }
+
+    /// Buyer re-attests the amount still owed once investors are in. Optional; repeatable to refresh the timestamp.
+    pub fn confirm_balance(env: Env, invoice_id: String, buyer: Address, outstanding_amount: i128, confirmation_hash: String) -> Result<(), ContractError> {
+        buyer.require_auth();
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
+        if invoice.status != InvoiceStatus::Funding && invoice.status != InvoiceStatus::Funded && invoice.status != InvoiceStatus::Overdue {
+            return Err(ContractError::InvalidStatus);
+        }
+        if invoice.tokens_sold == 0 { return Err(ContractError::InvalidStatus); }
+        if outstanding_amount != invoice.amount - storage::get_prepaid_total(&env, &invoice_id) { return Err(ContractError::InvalidAmount); }
+
+        let confirmation = BalanceConfirmation {
+            invoice_id: invoice_id.clone(), outstanding_amount, confirmation_hash: confirmation_hash.clone(),
+            confirmed_at: env.ledger().timestamp(),
+        };
+        storage::set_balance_confirmation(&env, &invoice_id, &confirmation);
+        InvoiceEvents::balance_confirmed(&env, &invoice_id, outstanding_amount, &confirmation_hash);
+        Ok(())
+    }
+
+    /// Commits a draft to confidential commercial terms by `sha256(terms || salt)`. The salt is
+    /// the supplier's own random 32 bytes, so short or guessable terms can't be recovered from the
+    /// hash by trying candidates. Terms and salt are shared off-chain with investors, who check
+    /// them with `verify_private_terms`.
+    pub fn set_private_terms(env: Env, invoice_id: String, supplier: Address, terms_hash: BytesN<32>) -> Result<(), ContractError> {
+        supplier.require_auth();
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
+        if invoice.status != InvoiceStatus::Draft { return Err(ContractError::InvalidStatus); }
+        invoice.private_terms_hash = Some(terms_hash);
+        set_invoice(&env, &invoice_id, &invoice);
+        Ok(())
+    }
+
+    /// Publishes the confidential terms on-chain (e.g. for a dispute). Only the supplier, the
+    /// buyer or the invoice's program admin may reveal, and only terms and salt matching the hash.
+    pub fn reveal_private_terms(env: Env, invoice_id: String, caller: Address, terms: Bytes, salt: BytesN<32>) -> Result<(), ContractError> {
+        caller.require_auth();
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let is_party = caller == invoice.supplier || caller == invoice.buyer
+            || storage::get_program_admin(&env, invoice.program_id).as_ref() == Some(&caller);
+        if !is_party { return Err(ContractError::Unauthorized); }
+        if !Self::terms_match(&env, &invoice, &terms, &salt) { return Err(ContractError::TermsMismatch); }
+        storage::set_private_terms(&env, &invoice_id, &terms);
+        InvoiceEvents::terms_revealed(&env, &invoice_id, &caller);
+        Ok(())
+    }
+
+    /// Buyer picks an allow-listed stablecoin to settle in, converted via the oracle at settlement.
+    /// Only before funding starts so investors know which currency they will be repaid in.
+    pub fn set_settlement_token(env: Env, invoice_id: String, buyer: Address, token: Address) -> Result<(), ContractError> {
+        buyer.require_auth();
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
+        if invoice.status != InvoiceStatus::Draft && invoice.status != InvoiceStatus::Verified { return Err(ContractError::InvalidStatus); }
+        if token == storage::get_usdc_token(&env) {
+            invoice.settlement_token = None;
+        } else {
+            if !storage::is_settlement_token_allowed(&env, &token) { return Err(ContractError::TokenNotAllowed); }
+            if !storage::get_prepayments(&env, &invoice_id).is_empty() { return Err(ContractError::CrossCurrencyUnsupported); }
+            invoice.settlement_token = Some(token);
+        }
+        set_invoice(&env, &invoice_id, &invoice);
+        Ok(())
+    }
+
+    /// Reclassify an invoice settled before late/short statuses existed. Anyone may call; the result is derived from stored data.
+    pub fn migrate_settled_status(env: Env, invoice_id: String) -> Result<InvoiceStatus, ContractError> {
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        // Legacy settlements always covered the full required amount, so only lateness can differ
+        let (legacy_status, settled_at) = (invoice.status.clone(), invoice.settled_at);
+        state_machine::transition(&mut invoice, Event::ReclassifyLegacy, settled_at)?;
+        if invoice.status != legacy_status {
+            set_invoice(&env, &invoice_id, &invoice);
+            Self::record_transition(&env, &invoice, &env.current_contract_address(), invoice.repayment_received);
+        }
+        Ok(invoice.status)
+    }
+
+
+    pub fn raise_dispute(env: Env, invoice_id: String, buyer: Address, reason: String) -> Result<(), ContractError> {
+        buyer.require_auth();
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.buyer != buyer { return Err(ContractError::Unauthorized); }
+        let now = env.ledger().timestamp();
+        state_machine::check(&invoice, &Event::RaiseDispute, now)?;
+        Self::check_dispute_throttle(&env, &invoice_id, &buyer)?;
+        // Freeze the auction clock so the price doesn't decay while the invoice is frozen
+        let auction_elapsed = if invoice.status == InvoiceStatus::Funding { now.min(invoice.auction_end) - invoice.auction_start } else { 0 };
+        // With an arbitration panel configured, the panel rules instead of the program admin
+        let quorum = storage::get_arbitration_quorum(&env);
+        let vote_deadline = if quorum > 0 { now + VOTING_WINDOW_SECONDS } else { 0 };
+        let dispute = Dispute {
+            invoice_id: invoice_id.clone(), raised_by: buyer.clone(), reason,
+            raised_at: now, resolution: DisputeResolution::Pending, resolved_at: 0,
+            prior_status: invoice.status.clone(), auction_elapsed, appeal_deadline: 0, review_deadline: 0, clawback_after: 0,
+            vote_deadline, quorum, valid_bps: 10000,
+        };
+        storage::set_dispute(&env, &invoice_id, &dispute);
+        state_machine::transition(&mut invoice, Event::RaiseDispute, now)?;
+        set_invoice(&env, &invoice_id, &invoice);
+        Self::record_transition(&env, &invoice, &buyer, 0);
+        InvoiceEvents::dispute_raised(&env, &invoice_id, &buyer);
+        Ok(())
+    }
+
+    pub fn resolve_dispute(env: Env, invoice_id: String, admin: Address, is_valid: bool) -> Result<(), ContractError> {
+        Self::rule_on_dispute(&env, &invoice_id, &admin, is_valid, 0, 10000)
+    }
+
+    /// Upholds a dispute where the buyer is partly at fault: only holdings acquired after
+    /// `acquired_after` (e.g. when the buyer flagged the problem) are clawed back. The supplier's
+    /// own holding is always clawed back.
+    pub fn resolve_dispute_after(env: Env, invoice_id: String, admin: Address, acquired_after: u64) -> Result<(), ContractError> {
+        Self::rule_on_dispute(&env, &invoice_id, &admin, true, acquired_after, 10000)
+    }
+
+    /// Upholds a dispute over only part of the goods (e.g. 40% defective): once the appeal window
+    /// lapses, `valid_bps` of every holding is clawed back and refunded, the invoice's amount and
+    /// token supply shrink by the same share, and the rest of the invoice proceeds to settlement.
+    pub fn resolve_dispute_partial(env: Env, invoice_id: String, admin: Address, valid_bps: u32) -> Result<(), ContractError> {
+        if valid_bps == 0 || valid_bps > 10000 { return Err(ContractError::InvalidAmount); }
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.repayment_received > 0 { return Err(ContractError::InstallmentConflict); }
+        Self::rule_on_dispute(&env, &invoice_id, &admin, true, 0, valid_bps)
+    }
+
+    /// Either party attaches a document to a pending dispute for the arbitration panel
+    pub fn submit_evidence(env: Env, invoice_id: String, party: Address, document_hash: String) -> Result<(), ContractError> {
+        party.require_auth();
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != party && invoice.buyer != party { return Err(ContractError::Unauthorized); }
+        let dispute = storage::get_dispute(&env, &invoice_id).ok_or(ContractError::DisputeNotFound)?;
+        if invoice.status != InvoiceStatus::Disputed || dispute.resolution != DisputeResolution::Pending { return Err(ContractError::InvalidStatus); }
+        let now = env.ledger().timestamp();
+        if dispute.vote_deadline > 0 && now > dispute.vote_deadline { return Err(ContractError::AppealWindowClosed); }
+
+        storage::add_evidence(&env, &invoice_id, &Evidence { submitted_by: party.clone(), document_hash: document_hash.clone(), submitted_at: now });
+        InvoiceEvents::evidence_submitted(&env, &invoice_id, &party, &document_hash);
+        Ok(())
+    }
+
+    /// Appointed arbitrator votes on a pending dispute; the vote can be changed until the deadline
+    pub fn vote_on_dispute(env: Env, invoice_id: String, arbitrator: Address, uphold: bool) -> Result<(), ContractError> {
+        arbitrator.require_auth();
+        if !storage::get_arbitrators(&env).contains(&arbitrator) { return Err(ContractError::Unauthorized); }
+        let dispute = storage::get_dispute(&env, &invoice_id).ok_or(ContractError::DisputeNotFound)?;
+        if dispute.resolution != DisputeResolution::Pending || dispute.vote_deadline == 0 { return Err(ContractError::InvalidStatus); }
+        let now = env.ledger().timestamp();
+        if now > dispute.vote_deadline { return Err(ContractError::AppealWindowClosed); }
+        storage::set_vote(&env, &invoice_id, &ArbitrationVote { arbitrator: arbitrator.clone(), uphold, voted_at: now });
+        InvoiceEvents::arbitration_vote(&env, &invoice_id, &arbitrator, uphold);
+        Ok(())
+    }
+
+    /// Applies the panel's majority ruling once the voting window closes (or every appointed
+    /// arbitrator has voted). A tie rejects the dispute. An upheld ruling still goes through
+    /// the supplier's appeal window. Callable by anyone.
+    pub fn tally_arbitration(env: Env, invoice_id: String) -> Result<bool, ContractError> {
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let mut dispute = storage::get_dispute(&env, &invoice_id).ok_or(ContractError::DisputeNotFound)?;
+        if invoice.status != InvoiceStatus::Disputed || dispute.resolution != DisputeResolution::Pending || dispute.vote_deadline == 0 {
+            return Err(ContractError::InvalidStatus);
+        }
+        let votes = Self::panel_votes(&env, &invoice_id);
+        let all_voted = storage::get_arbitrators(&env).iter().all(|a| storage::has_voted(&env, &invoice_id, &a));
+        if env.ledger().timestamp() <= dispute.vote_deadline && !all_voted { return Err(ContractError::AppealPending); }
+        if votes.len() < dispute.quorum { return Err(ContractError::QuorumNotReached); }
+
+        let upheld_votes = votes.iter().filter(|v| v.uphold).count() as u32;
+        let rejected_votes = votes.len() - upheld_votes;
+        InvoiceEvents::arbitration_tallied(&env, &invoice_id, upheld_votes, rejected_votes);
+        let upheld = upheld_votes > rejected_votes;
+        if upheld {
+            Self::uphold_dispute(&env, &invoice_id, &mut dispute, 0, 10000);
+        } else {
+            Self::apply_dispute_outcome(&env, &mut invoice, &mut dispute, false, &env.current_contract_address())?;
+        }
+        Ok(upheld)
+    }
+
+    /// Supplier contests an upheld dispute before its clawback executes
+    pub fn appeal_dispute(env: Env, invoice_id: String, supplier: Address) -> Result<(), ContractError> {
+        supplier.require_auth();
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
+        let mut dispute = storage::get_dispute(&env, &invoice_id).ok_or(ContractError::DisputeNotFound)?;
+        if dispute.resolution != DisputeResolution::Upheld { return Err(ContractError::InvalidStatus); }
+        let now = env.ledger().timestamp();
+        if now > dispute.appeal_deadline { return Err(ContractError::AppealWindowClosed); }
+
+        dispute.resolution = DisputeResolution::Appealed;
+        dispute.review_deadline = now + REVIEW_WINDOW_SECONDS;
+        storage::set_dispute(&env, &invoice_id, &dispute);
+        InvoiceEvents::dispute_appealed(&env, &invoice_id, &supplier, dispute.review_deadline);
+        Ok(())
+    }
+
+    /// Arbiter's final ruling on an appeal, within the review window
+    pub fn resolve_appeal(env: Env, invoice_id: String, arbiter: Address, uphold_dispute: bool) -> Result<(), ContractError> {
+        arbiter.require_auth();
+        if storage::get_appeal_arbiter(&env) != arbiter { return Err(ContractError::Unauthorized); }
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let mut dispute = storage::get_dispute(&env, &invoice_id).ok_or(ContractError::DisputeNotFound)?;
+        if dispute.resolution != DisputeResolution::Appealed { return Err(ContractError::InvalidStatus); }
+        if env.ledger().timestamp() > dispute.review_deadline { return Err(ContractError::AppealWindowClosed); }
+        Self::apply_dispute_outcome(&env, &mut invoice, &mut dispute, uphold_dispute, &arbiter)
+    }
+
+    /// Executes an upheld ruling once the appeal window lapses unused, or once the arbiter
+    /// lets the review window lapse (the original ruling stands). Callable by anyone.
+    pub fn finalize_dispute(env: Env, invoice_id: String) -> Result<(), ContractError> {
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let mut dispute = storage::get_dispute(&env, &invoice_id).ok_or(ContractError::DisputeNotFound)?;
+        let deadline = match dispute.resolution {
+            DisputeResolution::Upheld => dispute.appeal_deadline,
+            DisputeResolution::Appealed => dispute.review_deadline,
+            _ => return Err(ContractError::InvalidStatus),
+        };
+        if env.ledger().timestamp() <= deadline { return Err(ContractError::AppealPending); }
+        Self::apply_dispute_outcome(&env, &mut invoice, &mut dispute, true, &env.current_contract_address())
+    }
+
+    /// Supplier buys back a holder's position ahead of revocation. The refund is the holder's cost
+    /// basis, capped at the face value of the tokens they hold. Payments still escrowed below the
+    /// funding threshold are returned from the contract; the supplier covers the rest.
+    pub fn close_out(env: Env, invoice_id: String, supplier: Address, holder: Address) -> Result<i128, ContractError> {
+        supplier.require_auth();
+        Self::require_not_paused(&env)?;
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier || holder == supplier { return Err(ContractError::Unauthorized); }
+        if invoice.status != InvoiceStatus::Verified && invoice.status != InvoiceStatus::Funding { return Err(ContractError::InvalidStatus); }
+        let holding = storage::get_token_holding(&env, &invoice_id, &holder).ok_or(ContractError::HoldingNotFound)?;
+
+        let face_value = (holding.amount * invoice.amount) / invoice.total_tokens;
+        let refund = holding.acquired_price.min(face_value);
+        let usdc = TokenClient::new(&env, &storage::get_usdc_token(&env));
+        let (_, escrowed) = storage::get_funding_escrow(&env, &invoice_id, &holder);
+        if escrowed > 0 {
+            storage::remove_funding_escrow(&env, &invoice_id, &holder);
+            invoice.escrowed_payments -= escrowed;
+            usdc.transfer(&env.current_contract_address(), &holder, &escrowed);
+        }
+        if refund > escrowed {
+            usdc.transfer(&supplier, &holder, &(refund - escrowed));
+        }
+        let refunded = refund.max(escrowed);
+        Self::settle_investments(&env, &invoice_id, &holder, holding.amount, refunded, env.ledger().timestamp());
+        remove_token_holding(&env, &invoice_id, &holder);
+        let mut supplier_holding = storage::get_token_holding(&env, &invoice_id, &supplier).unwrap_or(TokenHolding {
+            invoice_id: invoice_id.clone(), holder: supplier.clone(), amount: 0, acquired_at: env.ledger().timestamp(), acquired_price: 0,
+        });
+        supplier_holding.amount += holding.amount;
+        set_token_holding(&env, &invoice_id, &supplier, &supplier_holding);
+
+        let returned = holding.amount.min(invoice.tokens_sold);
+        invoice.tokens_sold -= returned;
+        invoice.tokens_remaining += returned;
+        set_invoice(&env, &invoice_id, &invoice);
+        InvoiceEvents::holder_refunded(&env, &invoice_id, &holder, refunded);
+        Ok(refunded)
+    }
+
+    pub fn revoke(env: Env, invoice_id: String, supplier: Address) -> Result<(), ContractError> {
+        supplier.require_auth();
+        let mut invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
+        let now = env.ledger().timestamp();
+        state_machine::check(&invoice, &Event::Revoke, now)?;
+        // Sold (or transferred) tokens must be closed out first so no investor claim is wiped
+        if storage::get_all_holders(&env, &invoice_id).iter().any(|h| h != supplier) {
+            return Err(ContractError::HasExternalHolders);
+        }
+        storage::clear_token_holdings(&env, &invoice_id);
+        state_machine::transition(&mut invoice, Event::Revoke, now)?;
+        set_invoice(&env, &invoice_id, &invoice);
+        Self::record_transition(&env, &invoice, &supplier, 0);
+        InvoiceEvents::invoice_revoked(&env, &invoice_id);
+        Ok(())
+    }
+
+    /// First step of an admin handover: nominates `new_admin`, who must accept before anything
+    /// changes. Proposing again replaces the nominee.
+    pub fn propose_admin(env: Env, admin: Address, new_admin: Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_pending_admin(&env, &Some(new_admin.clone()));
+        InvoiceEvents::admin_proposed(&env, &admin, &new_admin);
+        Ok(())
+    }
+
+    /// Second step of an admin handover, signed by the nominee
+    pub fn accept_admin(env: Env, new_admin: Address) -> Result<(), ContractError> {
+        new_admin.require_auth();
+        if storage::get_pending_admin(&env) != Some(new_admin.clone()) { return Err(ContractError::Unauthorized); }
+        let previous = get_admin(&env);
+        set_admin(&env, &new_admin);
+        storage::set_pending_admin(&env, &None);
+        InvoiceEvents::admin_transferred(&env, &previous, &new_admin);
+        Ok(())
+    }
+
+    /// Lets `addr` act for the admin in one operational area (see `Role`)
+    pub fn grant_role(env: Env, admin: Address, role: Role, addr: Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_role(&env, &role, &addr, true);
+        InvoiceEvents::role_updated(&env, &role, &addr, true);
+        Ok(())
+    }
+
+    pub fn revoke_role(env: Env, admin: Address, role: Role, addr: Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_role(&env, &role, &addr, false);
+        InvoiceEvents::role_updated(&env, &role, &addr, false);
+        Ok(())
+    }
+
+    pub fn has_role(env: Env, role: Role, addr: Address) -> bool { storage::has_role(&env, &role, &addr) }
+
+    /// Emergency stop: blocks investing, trading, transfers, settlement and payout claims.
+    /// Views, disputes and admin functions keep working.
+    pub fn pause(env: Env, pauser: Address) -> Result<(), ContractError> {
+        Self::require_role(&env, &pauser, Role::Pauser)?;
+        storage::set_paused(&env, true);
+        InvoiceEvents::pause_changed(&env, &pauser, true);
+        Ok(())
+    }
+
+    pub fn unpause(env: Env, pauser: Address) -> Result<(), ContractError> {
+        Self::require_role(&env, &pauser, Role::Pauser)?;
+        storage::set_paused(&env, false);
+        InvoiceEvents::pause_changed(&env, &pauser, false);
+        Ok(())
+    }
+
+    pub fn is_paused(env: Env) -> bool { storage::is_paused(&env) }
+
+    pub fn set_investor_kyc(env: Env, admin: Address, investor: Address, approved: bool) -> Result<(), ContractError> {
+        let no_reference = String::from_str(&env, "");
+        Self::set_investor_kyc_with_reason(env, admin, investor, approved, 0, no_reference)
+    }
+
+    /// KYC change tied to a compliance reason code and off-chain report reference
+    pub fn set_investor_kyc_with_reason(env: Env, admin: Address, investor: Address, approved: bool, reason_code: u32, reference_hash: String) -> Result<(), ContractError> {
+        Self::require_role(&env, &admin, Role::KycManager)?;
+        let action_id = Self::apply_authorization(&env, &admin, &investor, AuthorizationKind::Kyc, approved, reason_code, reference_hash);
+        // A direct decision also answers the investor's queued request, if any
+        if storage::is_kyc_queued(&env, &investor) { Self::close_kyc_request(&env, &investor, approved, action_id); }
+        Ok(())
+    }
+
+    /// Applicant asks to be KYC-approved, joining the queue the KYC managers work through.
+    /// `metadata_hash` points at its off-chain KYC package. A rejected applicant may apply again.
+    pub fn request_kyc(env: Env, applicant: Address, metadata_hash: String) -> Result<(), ContractError> {
+        applicant.require_auth();
+        if get_kyc_status(&env, &applicant) { return Err(ContractError::InvalidStatus); }
+        if storage::get_kyc_request(&env, &applicant).is_some_and(|request| request.status == KycRequestStatus::Pending) {
+            return Err(ContractError::InvalidStatus);
+        }
+        storage::enqueue_kyc(&env, &applicant);
+        storage::set_kyc_request(&env, &KycRequest {
+            applicant: applicant.clone(), metadata_hash: metadata_hash.clone(), requested_at: env.ledger().timestamp(),
+            status: KycRequestStatus::Pending, decided_at: 0, action_id: 0,
+        });
+        InvoiceEvents::kyc_requested(&env, &applicant, &metadata_hash);
+        Ok(())
+    }
+
+    /// Approves or rejects a batch of queued KYC requests, logging each decision like
+    /// `set_investor_kyc_with_reason`. Applicants without a pending request are skipped.
+    /// Returns the number decided.
+    pub fn process_kyc_requests(env: Env, admin: Address, applicants: Vec<Address>, approve: bool, reason_code: u32, reference_hash: String) -> Result<u32, ContractError> {
+        Self::require_role(&env, &admin, Role::KycManager)?;
+        if applicants.len() > MAX_PAGE_SIZE { return Err(ContractError::InvalidAmount); }
+        let mut decided = 0;
+        for applicant in applicants.iter() {
+            if !storage::is_kyc_queued(&env, &applicant) { continue; }
+            let action_id = Self::apply_authorization(&env, &admin, &applicant, AuthorizationKind::Kyc, approve, reason_code, reference_hash.clone());
+            Self::close_kyc_request(&env, &applicant, approve, action_id);
+            decided += 1;
+        }
+        Ok(decided)
+    }
+
+    /// Applicant's latest KYC request, pending or decided
+    pub fn get_kyc_request(env: Env, applicant: Address) -> Option<KycRequest> { storage::get_kyc_request(&env, &applicant) }
+
+    /// Pending KYC requests in the queue slots `[start, start + limit)`, oldest first. Slots are
+    /// sequence numbers counted from the first request ever queued; slots of decided requests
+    /// are skipped, so a page can hold fewer than `limit`. `get_kyc_queue_bounds` gives the live range.
+    pub fn get_kyc_queue(env: Env, start: u64, limit: u32) -> Vec<KycRequest> {
+        let (head, tail) = storage::kyc_queue_bounds(&env);
+        let start = start.max(head);
+        let end = start.saturating_add(limit.min(MAX_PAGE_SIZE) as u64).min(tail);
+        let mut requests = Vec::new(&env);
+        for applicant in storage::get_kyc_queue(&env, start, end.max(start)).iter() {
+            if let Some(request) = storage::get_kyc_request(&env, &applicant) { requests.push_back(request); }
+        }
+        requests
+    }
+
+    /// First queue slot still in use and the slot the next request will take
+    pub fn get_kyc_queue_bounds(env: Env) -> (u64, u64) { storage::kyc_queue_bounds(&env) }
+
+    /// Reverts a mistaken KYC or relayer change within a day, provided it is still the latest
+    /// change for its address. The revert is itself logged (with `u32::MAX` as reason code) and
+    /// can be undone in turn to redo the original change. Returns the revert's action id.
+    pub fn undo_last_action(env: Env, admin: Address, action_id: u64) -> Result<u64, ContractError> {
+        Self::require_role(&env, &admin, Role::KycManager)?;
+        let addr = storage::get_authorization_action(&env, action_id).ok_or(ContractError::NotReversible)?;
+        let last = storage::get_authorization_log(&env, &addr).last().ok_or(ContractError::NotReversible)?;
+        // KYC managers can only undo KYC changes; relayer changes stay with the admin
+        if last.kind != AuthorizationKind::Kyc && get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if last.action_id != action_id || env.ledger().timestamp() > last.timestamp + UNDO_WINDOW_SECONDS {
+            return Err(ContractError::NotReversible);
+        }
+        Ok(Self::apply_authorization(&env, &admin, &addr, last.kind, last.previous, UNDO_REASON_CODE, String::from_str(&env, "")))
+    }
+
+    pub fn set_appeal_arbiter(env: Env, admin: Address, arbiter: Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_appeal_arbiter(&env, &arbiter);
+        Ok(())
+    }
+
+    /// Appoints (`active = true`) or removes an arbitrator on the dispute panel
+    pub fn set_arbitrator(env: Env, admin: Address, arbitrator: Address, active: bool) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        let mut arbitrators = storage::get_arbitrators(&env);
+        match arbitrators.first_index_of(&arbitrator) {
+            Some(i) if !active => { arbitrators.remove(i); }
+            None if active => arbitrators.push_back(arbitrator.clone()),
+            _ => {}
+        }
+        storage::set_arbitrators(&env, &arbitrators);
+        InvoiceEvents::arbitrator_set(&env, &arbitrator, active);
+        Ok(())
+    }
+
+    /// Votes needed for the panel to rule on disputes raised from now on. 0 disables the
+    /// panel and leaves disputes to the program admin.
+    pub fn set_arbitration_quorum(env: Env, admin: Address, quorum: u32) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_arbitration_quorum(&env, quorum);
+        Ok(())
+    }
+
+    /// Throttles buyers whose disputes keep being rejected: past `rejected_limit` rejections
+    /// within the window, a new dispute needs pre-clearance or a bond
+    pub fn set_dispute_limits(env: Env, admin: Address, limits: DisputeLimits) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if limits.bond < 0 || limits.window_seconds == 0 { return Err(ContractError::InvalidAmount); }
+        storage::set_dispute_limits(&env, &limits);
+        Ok(())
+    }
+
+    pub fn get_dispute_limits(env: Env) -> DisputeLimits { storage::get_dispute_limits(&env) }
+
+    /// Lets a throttled buyer dispute the invoice once without posting the bond
+    pub fn clear_dispute(env: Env, resolver: Address, invoice_id: String) -> Result<(), ContractError> {
+        Self::require_role(&env, &resolver, Role::DisputeResolver)?;
+        get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        storage::set_dispute_cleared(&env, &invoice_id, true);
+        Ok(())
+    }
+
+    /// Buyer's rejected disputes within the rolling window
+    pub fn get_recent_dispute_rejections(env: Env, buyer: Address) -> u32 { risk::recent_dispute_rejections(&env, &buyer) }
+
+    pub fn set_gc_bounty(env: Env, admin: Address, bounty: i128) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if bounty < 0 { return Err(ContractError::InvalidAmount); }
+        storage::set_gc_bounty(&env, bounty);
+        Ok(())
+    }
+
+    pub fn set_keeper_bounty(env: Env, admin: Address, bounty: i128) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if bounty < 0 { return Err(ContractError::InvalidAmount); }
+        storage::set_keeper_bounty(&env, bounty);
+        Ok(())
+    }
+
+    pub fn get_keeper_bounty(env: Env) -> i128 {
+        storage::get_keeper_bounty(&env)
+    }
+
+    /// Circuit breaker for secondary trades: fills priced more than `band_bps` away from fair
+    /// value (face value discounted to maturity) are rejected. 0 disables the band.
+    pub fn set_price_band(env: Env, admin: Address, band_bps: u32) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if band_bps > 10000 { return Err(ContractError::InvalidAmount); }
+        storage::set_price_band(&env, band_bps);
+        Ok(())
+    }
+
+    /// Seller opts their holding in an invoice out of the price band, e.g. to exit a troubled
+    /// position below fair value. Applies to their sell orders, bid fills and RFQs.
+    pub fn flag_distressed_sale(env: Env, invoice_id: String, seller: Address, distressed: bool) -> Result<(), ContractError> {
+        seller.require_auth();
+        storage::get_token_holding(&env, &invoice_id, &seller).ok_or(ContractError::HoldingNotFound)?;
+        storage::set_distressed_sale(&env, &invoice_id, &seller, distressed);
+        Ok(())
+    }
+
+    /// Supplier pays back dispute refunds owed to an invoice's investors, in clawback order.
+    /// Returns the supplier's remaining debt; new drafts are blocked until it reaches zero.
+    pub fn repay_refunds(env: Env, invoice_id: String, supplier: Address, amount: i128) -> Result<i128, ContractError> {
+        supplier.require_auth();
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.supplier != supplier { return Err(ContractError::Unauthorized); }
+        if amount <= 0 { return Err(ContractError::InvalidAmount); }
+
+        let token_client = TokenClient::new(&env, &storage::get_usdc_token(&env));
+        let mut remaining = amount;
+        let mut creditors = storage::get_refund_creditors(&env, &invoice_id);
+        while remaining > 0 && !creditors.is_empty() {
+            let investor = creditors.get_unchecked(0);
+            let owed = storage::get_refund_owed(&env, &invoice_id, &investor);
+            let paid = owed.min(remaining);
+            token_client.transfer(&supplier, &investor, &paid);
+            storage::set_refund_owed(&env, &invoice_id, &investor, owed - paid);
+            InvoiceEvents::holder_refunded(&env, &invoice_id, &investor, paid);
+            remaining -= paid;
+            if paid == owed { creditors.pop_front(); }
+        }
+        storage::set_refund_creditors(&env, &invoice_id, &creditors);
+        let debt = storage::get_supplier_debt(&env, &supplier) - (amount - remaining);
+        storage::set_supplier_debt(&env, &supplier, debt);
+        Ok(debt)
+    }
+
+    /// Settlement shares below `min_amount` of `token` are credited to the holder's dust balance
+    /// instead of being transferred (0 = always transfer)
+    pub fn set_min_payout(env: Env, admin: Address, token: Address, min_amount: i128) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if min_amount < 0 { return Err(ContractError::InvalidAmount); }
+        storage::set_min_payout(&env, &token, min_amount);
+        Ok(())
+    }
+
+    /// Withdraws the settlement dust a holder has accumulated in `token`. Returns the amount.
+    pub fn claim_dust(env: Env, holder: Address, token: Address) -> i128 {
+        holder.require_auth();
+        let amount = storage::take_dust(&env, &holder, &token);
+        if amount > 0 {
+            TokenClient::new(&env, &token).transfer(&env.current_contract_address(), &holder, &amount);
+            InvoiceEvents::dust_claimed(&env, &holder, &token, amount);
+        }
+        amount
+    }
+
+    /// Recover tokens sent to the contract by mistake. Only the balance above what the insurance
+    /// pools and prepayment escrow account for can be moved.
+    pub fn sweep_foreign_tokens(env: Env, admin: Address, token: Address, to: Address, amount: i128) -> Result<(), ContractError> {
+        Self::require_role(&env, &admin, Role::Treasurer)?;
+        if amount <= 0 { return Err(ContractError::InvalidAmount); }
+        if amount > Self::unaccounted_balance(&env, &token) { return Err(ContractError::ExceedsUnaccountedBalance); }
+        TokenClient::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+        InvoiceEvents::tokens_swept(&env, &token, &to, amount);
+        Ok(())
+    }
+
+    /// Per-investor cap on at-risk exposure across open invoices (0 = no limit)
+    pub fn set_exposure_limit(env: Env, admin: Address, investor: Address, max_exposure: i128) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if max_exposure < 0 { return Err(ContractError::InvalidAmount); }
+        storage::set_exposure_limit(&env, &investor, max_exposure);
+        Ok(())
+    }
+
+    /// Overrides the program's `max_investor_bps` for one invoice (0 = no cap); `None` falls back
+    /// to the program's. Applies to later purchases only.
+    pub fn set_concentration_cap(env: Env, admin: Address, invoice_id: String, max_investor_bps: Option<u32>) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if get_invoice(&env, &invoice_id).is_none() { return Err(ContractError::InvoiceNotFound); }
+        if max_investor_bps.unwrap_or(0) > 10000 { return Err(ContractError::InvalidAmount); }
+        storage::set_concentration_cap(&env, &invoice_id, max_investor_bps);
+        Ok(())
+    }
+
+    /// Tokens the investor may still acquire on the invoice under its concentration cap, net of
+    /// what they hold and have bid; `None` when the invoice is uncapped or they are its supplier
+    pub fn get_investor_capacity(env: Env, invoice_id: String, investor: Address) -> Result<Option<i128>, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        Ok(Self::concentration_capacity(&env, &invoice, &investor))
+    }
+
+    /// Per-buyer ceiling on approved but unsettled obligations across invoices, checked when the
+    /// buyer approves a draft (0 = no limit)
+    pub fn set_buyer_obligation_limit(env: Env, admin: Address, buyer: Address, max_obligations: i128) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if max_obligations < 0 { return Err(ContractError::InvalidAmount); }
+        storage::set_buyer_obligation_limit(&env, &buyer, max_obligations);
+        Ok(())
+    }
+
+    pub fn get_buyer_obligation_limit(env: Env, buyer: Address) -> i128 { storage::get_buyer_obligation_limit(&env, &buyer) }
+
+    /// Face value the buyer still owes on invoices it approved that are not yet settled or closed
+    pub fn get_buyer_obligations(env: Env, buyer: Address) -> i128 { storage::get_buyer_outstanding(&env, &buyer) }
+
+    pub fn set_relayer(env: Env, admin: Address, relayer: Address, authorized: bool) -> Result<(), ContractError> {
+        let no_reference = String::from_str(&env, "");
+        Self::set_relayer_with_reason(env, admin, relayer, authorized, 0, no_reference)
+    }
+
+    /// Relayer change tied to a compliance reason code and off-chain report reference
+    pub fn set_relayer_with_reason(env: Env, admin: Address, relayer: Address, authorized: bool, reason_code: u32, reference_hash: String) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        Self::apply_authorization(&env, &admin, &relayer, AuthorizationKind::Relayer, authorized, reason_code, reference_hash);
+        Ok(())
+    }
+
+    /// Global limits, used for suppliers without a tier or whose tier has no limits set
+    pub fn set_issuance_limits(env: Env, admin: Address, limits: IssuanceLimits) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_issuance_limits(&env, &limits);
+        Ok(())
+    }
+
+    /// Publish a new fee schedule version. Invoices already funding keep the version they pinned.
+    /// `schedule.version` is ignored; the new schedule takes the next version number.
+    pub fn set_fee_schedule(env: Env, admin: Address, schedule: FeeSchedule) -> Result<u32, ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        for bps in [schedule.insurance_cut_bps, schedule.protocol_fee_bps, schedule.trading_fee_bps, schedule.royalty_bps, schedule.withholding_bps] {
+            if bps > 10000 { return Err(ContractError::InvalidFeeSchedule); }
+        }
+        if schedule.royalty_bps > MAX_ROYALTY_BPS || schedule.trading_fee_bps + schedule.royalty_bps > 10000 { return Err(ContractError::InvalidFeeSchedule); }
+        let current = storage::get_fee_schedule(&env, storage::get_fee_schedule_version(&env)).unwrap();
+        let effective_from = schedule.effective_from.max(env.ledger().timestamp());
+        if effective_from < current.effective_from { return Err(ContractError::InvalidFeeSchedule); }
+
+        let schedule = FeeSchedule { version: current.version + 1, effective_from, ..schedule };
+        storage::add_fee_schedule(&env, &schedule);
+        InvoiceEvents::fee_schedule_updated(&env, &schedule);
+        Ok(schedule.version)
+    }
+
+    pub fn get_fee_schedule(env: Env, version: u32) -> Result<FeeSchedule, ContractError> { storage::get_fee_schedule(&env, version).ok_or(ContractError::InvalidFeeSchedule) }
+    pub fn get_active_fee_schedule(env: Env) -> FeeSchedule { storage::get_active_fee_schedule(&env, env.ledger().timestamp()) }
+
+    pub fn set_price_oracle(env: Env, admin: Address, oracle: Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_price_oracle(&env, &oracle);
+        Ok(())
+    }
+
+    /// Allow buying at face value on Verified invoices before any auction starts
+    pub fn set_pre_auction_invest(env: Env, admin: Address, allowed: bool) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_pre_auction_invest(&env, allowed);
+        Ok(())
+    }
+
+    pub fn set_swap_router(env: Env, admin: Address, router: Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_swap_router(&env, &router);
+        Ok(())
+    }
+
+    /// Holder's preferred payout token (must be allow-listed); pass the funding token to clear it
+    pub fn set_payout_preference(env: Env, holder: Address, token: Address) -> Result<(), ContractError> {
+        holder.require_auth();
+        if token == storage::get_usdc_token(&env) {
+            storage::remove_payout_preference(&env, &holder);
+            return Ok(());
+        }
+        if !storage::is_settlement_token_allowed(&env, &token) { return Err(ContractError::TokenNotAllowed); }
+        storage::set_payout_preference(&env, &holder, &token);
+        Ok(())
+    }
+
+    pub fn set_settlement_token_allowed(env: Env, admin: Address, token: Address, allowed: bool) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_settlement_token_allowed(&env, &token, allowed);
+        Ok(())
+    }
+
+    pub fn set_tier_limits(env: Env, admin: Address, tier: u32, limits: IssuanceLimits) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_tier_limits(&env, tier, &limits);
+        Ok(())
+    }
+
+    pub fn set_supplier_tier(env: Env, admin: Address, supplier: Address, tier: u32) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        storage::set_supplier_tier(&env, &supplier, tier);
+        Ok(())
+    }
+
+    /// Registers a white-label financing program run by `program_admin`, with its own rates,
+    /// KYC list and insurance pool. Returns the new program id (ids start at 1).
+    pub fn create_program(env: Env, admin: Address, program_admin: Address, rate_config: RateConfig) -> Result<u32, ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        let program_id = storage::increment_program_counter(&env);
+        storage::set_program(&env, &Program { id: program_id, admin: program_admin, rate_config, created_at: env.ledger().timestamp() });
+        Ok(program_id)
+    }
+
+    /// Enrolls a supplier in a program; invoices minted afterwards belong to it. Existing
+    /// invoices keep the program they were issued under.
+    pub fn set_supplier_program(env: Env, program_admin: Address, program_id: u32, supplier: Address) -> Result<(), ContractError> {
+        Self::require_program_admin(&env, program_id, &program_admin)?;
+        storage::set_supplier_program(&env, &supplier, program_id);
+        Ok(())
+    }
+
+    pub fn set_program_kyc(env: Env, program_admin: Address, program_id: u32, investor: Address, approved: bool) -> Result<(), ContractError> {
+        Self::require_program_admin(&env, program_id, &program_admin)?;
+        storage::set_program_kyc_status(&env, program_id, &investor, approved);
+        InvoiceEvents::kyc_updated(&env, &investor, approved);
+        Ok(())
+    }
+
+    /// Fee terms agreed between the protocol and a program's anchor, set by the global admin
+    pub fn set_program_fees(env: Env, admin: Address, program_id: u32, fees: ProgramFees) -> Result<(), ContractError> {
+        admin.require_auth();
+        if get_admin(&env) != admin { return Err(ContractError::Unauthorized); }
+        if program_id == 0 || storage::get_program(&env, program_id).is_none() { return Err(ContractError::ProgramNotFound); }
+        for bps in [fees.investment_fee_bps, fees.trading_fee_bps, fees.anchor_share_bps] {
+            if bps > 10000 { return Err(ContractError::InvalidFeeSchedule); }
+        }
+        // Maker rebates are funded by the taker fee; either fee leaves room for the royalty
+        let fee_ceiling = 10000 - MAX_ROYALTY_BPS;
+        if fees.trading_fee_bps > fee_ceiling || fees.maker_fee_bps > fee_ceiling as i32 || fees.maker_fee_bps < -(fees.trading_fee_bps as i32) {
+            return Err(ContractError::InvalidFeeSchedule);
+        }
+        storage::set_program_fees(&env, program_id, &fees);
+        InvoiceEvents::program_fees_updated(&env, program_id, &fees);
+        Ok(())
+    }
+
+    /// Withdraws accrued fees from a program's treasury; program 0 is the protocol treasury
+    pub fn withdraw_treasury(env: Env, program_admin: Address, program_id: u32, to: Address, amount: i128) -> Result<(), ContractError> {
+        // The protocol treasury (program 0) can also be drawn by a Treasurer
+        if program_id == 0 { Self::require_role(&env, &program_admin, Role::Treasurer)?; }
+        else { Self::require_program_admin(&env, program_id, &program_admin)?; }
+        if amount <= 0 { return Err(ContractError::InvalidAmount); }
+        let balance = storage::get_program_treasury(&env, program_id);
+        if amount > balance { return Err(ContractError::ExceedsTreasuryBalance); }
+        storage::set_program_treasury(&env, program_id, balance - amount);
+        TokenClient::new(&env, &storage::get_usdc_token(&env)).transfer(&env.current_contract_address(), &to, &amount);
+        InvoiceEvents::treasury_withdrawn(&env, program_id, &to, amount);
+        Ok(())
+    }
+
+    /// Withdraws protocol fees; the protocol treasury's side of `withdraw_treasury` for Treasurers
+    pub fn withdraw_fees(env: Env, treasurer: Address, to: Address, amount: i128) -> Result<(), ContractError> {
+        Self::withdraw_treasury(env, treasurer, 0, to, amount)
+    }
+
+    pub fn get_fee_config(env: Env) -> FeeConfig {
+        let schedule = storage::get_active_fee_schedule(&env, env.ledger().timestamp());
+        FeeConfig {
+            schedule_version: schedule.version,
+            protocol_fee_bps: schedule.protocol_fee_bps,
+            trading_fee_bps: schedule.trading_fee_bps,
+            reserve: storage::get_program_treasury(&env, 0),
+        }
+    }
+
+    pub fn set_program_rate_config(env: Env, program_admin: Address, program_id: u32, rate_config: RateConfig) -> Result<(), ContractError> {
+        Self::require_program_admin(&env, program_id, &program_admin)?;
+        if rate_config.max_early_discount_bps > 10000 || rate_config.max_investor_bps > 10000 { return Err(ContractError::InvalidFeeSchedule); }
+        if program_id == 0 { set_rate_config(&env, &rate_config); return Ok(()); }
+        let mut program = storage::get_program(&env, program_id).ok_or(ContractError::ProgramNotFound)?;
+        program.rate_config = rate_config;
+        storage::set_program(&env, &program);
+        Ok(())
+    }
+
+    /// Interest model for the program's invoices (Fixed uses the rate config's base rate)
+    pub fn set_program_rate_model(env: Env, program_admin: Address, program_id: u32, model: RateModel) -> Result<(), ContractError> {
+        Self::require_program_admin(&env, program_id, &program_admin)?;
+        if !interest::is_valid(&model) { return Err(ContractError::InvalidFeeSchedule); }
+        storage::set_program_rate_model(&env, program_id, &model);
+        Ok(())
+    }
+
+    /// Overrides the program's interest model for one invoice before it is funded; None reverts
+    /// to the program's model
+    pub fn set_invoice_rate_model(env: Env, invoice_id: String, program_admin: Address, model: Option<RateModel>) -> Result<(), ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        Self::require_program_admin(&env, invoice.program_id, &program_admin)?;
+        if invoice.status != InvoiceStatus::Draft && invoice.status != InvoiceStatus::Verified { return Err(ContractError::InvalidStatus); }
+        if model.as_ref().is_some_and(|m| !interest::is_valid(m)) { return Err(ContractError::InvalidFeeSchedule); }
+        storage::set_invoice_rate_model(&env, &invoice_id, &model);
+        Ok(())
+    }
+
+    /// Invoice behind a token symbol, for wallets and the secondary market UI
+    pub fn resolve_symbol(env: Env, symbol: String) -> Result<String, ContractError> { storage::get_symbol_invoice(&env, &symbol).ok_or(ContractError::InvoiceNotFound) }
+    pub fn get_benchmark_readings(env: Env, invoice_id: String) -> Option<BenchmarkReadings> { storage::get_benchmark_readings(&env, &invoice_id) }
+
+    pub fn get_rate_model(env: Env, invoice_id: String) -> Result<RateModel, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        Ok(interest::rate_model(&env, &invoice))
+    }
+
+    pub fn get_issuance_limits(env: Env, supplier: Address) -> IssuanceLimits { Self::limits_for_supplier(&env, &supplier) }
+    pub fn is_kyc_approved(env: Env, investor: Address) -> bool { get_kyc_status(&env, &investor) }
+    pub fn is_program_kyc_approved(env: Env, program_id: u32, investor: Address) -> bool { storage::get_program_kyc_status(&env, program_id, &investor) }
+    pub fn get_program(env: Env, program_id: u32) -> Result<Program, ContractError> { storage::get_program(&env, program_id).ok_or(ContractError::ProgramNotFound) }
+    pub fn get_supplier_program(env: Env, supplier: Address) -> u32 { storage::get_supplier_program(&env, &supplier) }
+    pub fn get_program_insurance_pool(env: Env, program_id: u32) -> i128 { storage::get_insurance_pool(&env, program_id) }
+    pub fn get_program_fees(env: Env, program_id: u32) -> ProgramFees { storage::get_program_fees(&env, program_id) }
+    pub fn get_treasury_balance(env: Env, program_id: u32) -> i128 { storage::get_program_treasury(&env, program_id) }
+    pub fn get_sweepable_balance(env: Env, token: Address) -> i128 { Self::unaccounted_balance(&env, &token) }
+    pub fn get_auction_result(env: Env, invoice_id: String) -> Result<AuctionResult, ContractError> { storage::get_auction_result(&env, &invoice_id).ok_or(ContractError::AuctionNotStarted) }
+    pub fn get_authorization_log(env: Env, addr: Address, start: u32, limit: u32) -> Vec<AuthorizationRecord> { Self::page(storage::get_authorization_log(&env, &addr), start, limit) }
+    pub fn get_exposure(env: Env, investor: Address) -> i128 { Self::exposure_of(&env, &investor) }
+    pub fn get_realized_loss(env: Env, investor: Address) -> i128 { storage::get_realized_loss(&env, &investor) }
+    pub fn get_invoice(env: Env, invoice_id: String) -> Result<Invoice, ContractError> { get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound) }
+    pub fn get_holding(env: Env, invoice_id: String, holder: Address) -> Result<TokenHolding, ContractError> { storage::get_token_holding(&env, &invoice_id, &holder).ok_or(ContractError::HoldingNotFound) }
+    pub fn get_holding_lots(env: Env, invoice_id: String, holder: Address) -> Result<Vec<TokenLot>, ContractError> { let holding = storage::get_token_holding(&env, &invoice_id, &holder).ok_or(ContractError::HoldingNotFound)?; Ok(storage::get_token_lots(&env, &holding)) }
+    pub fn get_receipt(env: Env, invoice_id: String, holder: Address) -> Result<RedemptionReceipt, ContractError> { storage::get_receipt(&env, &invoice_id, &holder).ok_or(ContractError::HoldingNotFound) }
+    pub fn get_invoice_history(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<StateRecord> { Self::page(storage::get_history(&env, &invoice_id), start, limit) }
+    /// Sequence number of the invoice's latest event; indexers compare it with the last one seen
+    pub fn get_event_seq(env: Env, invoice_id: String) -> u64 { storage::get_event_seq(&env, &invoice_id) }
+
+    /// Purchases of the invoice's tokens `[start, start + limit)`, primary and secondary, in order
+    pub fn get_investments_by_invoice(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<Investment> {
+        Self::load_investments(&env, Self::page(storage::get_invoice_investments(&env, &invoice_id), start, limit))
+    }
+
+    /// Investor's purchases `[start, start + limit)` across all invoices, with settlement
+    /// outcomes stamped once each invoice settles
+    pub fn get_investments_by_investor(env: Env, investor: Address, start: u32, limit: u32) -> Vec<Investment> {
+        Self::load_investments(&env, Self::page(storage::get_investor_investments(&env, &investor), start, limit))
+    }
+
+    /// Supplier's invoices `[start, start + limit)` in issuance order
+    pub fn get_invoices_by_supplier(env: Env, supplier: Address, start: u32, limit: u32) -> Vec<Invoice> {
+        Self::load_invoices(&env, Self::page(storage::get_supplier_invoices(&env, &supplier), start, limit))
+    }
+
+    /// Buyer's invoices `[start, start + limit)` in issuance order
+    pub fn get_invoices_by_buyer(env: Env, buyer: Address, start: u32, limit: u32) -> Vec<Invoice> {
+        Self::load_invoices(&env, storage::get_buyer_invoices(&env, &buyer, start, limit.min(MAX_PAGE_SIZE)))
+    }
+
+    /// Invoices currently in `status`, `[start, start + limit)`. A transition out of the status
+    /// moves its last invoice into the freed slot, so a page may shift while a client walks it.
+    pub fn get_invoices_by_status(env: Env, status: InvoiceStatus, start: u32, limit: u32) -> Vec<Invoice> {
+        Self::load_invoices(&env, storage::get_status_invoices(&env, &status, start, limit.min(MAX_PAGE_SIZE)))
+    }
+    pub fn get_dispute(env: Env, invoice_id: String) -> Result<Dispute, ContractError> { storage::get_dispute(&env, &invoice_id).ok_or(ContractError::DisputeNotFound) }
+    pub fn get_settlement_amount(env: Env, invoice_id: String) -> Result<i128, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        Ok(Self::calculate_settlement_amount(&env, &invoice))
+    }
+    /// Settlement amount at `at_timestamp` with its early-settlement discount broken out
+    pub fn get_settlement_quote(env: Env, invoice_id: String, at_timestamp: u64) -> Result<SettlementQuote, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        Ok(Self::settlement_quote_at(&env, &invoice, at_timestamp))
+    }
+    /// Buyer's payment-history score in bps (10000 = always on time, 5000 = no history)
+    pub fn get_buyer_score(env: Env, buyer: Address) -> u32 { risk::buyer_history_score(&env, &buyer) }
+    pub fn get_buyer_stats(env: Env, buyer: Address) -> BuyerStats { storage::get_buyer_stats(&env, &buyer) }
+    pub fn get_supplier_score(env: Env, supplier: Address) -> u32 { risk::supplier_history_score(&env, &supplier) }
+    pub fn get_supplier_stats(env: Env, supplier: Address) -> SupplierStats { storage::get_supplier_stats(&env, &supplier) }
+    pub fn get_risk_score(env: Env, invoice_id: String) -> Result<u32, ContractError> {
+        let invoice = get_invoice(&env, &invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        Ok(risk::risk_score(&env, &invoice))
+    }
+    pub fn get_balance_confirmation(env: Env, invoice_id: String) -> Option<BalanceConfirmation> { storage::get_balance_confirmation(&env, &invoice_id) }
+    pub fn get_prepaid_amount(env: Env, invoice_id: String) -> i128 { storage::get_prepaid_total(&env, &invoice_id) }
+    pub fn get_dispute_evidence(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<Evidence> { storage::get_evidence(&env, &invoice_id, start, limit.min(MAX_PAGE_SIZE)) }
+    pub fn get_dispute_votes(env: Env, invoice_id: String, start: u32, limit: u32) -> Vec<ArbitrationVote> { storage::get_votes(&env, &invoice_id, start, limit.min(MAX_PAGE_SIZE)) }
+    pub fn get_refund_owed(env: Env, invoice_id: String, investor: Address) -> i128 { storage::get_refund_owed(&env, &invoice_id, &investor) }
+    pub fn get_supplier_debt(env: Env, supplier: Address) -> i128 { storage::get_supplier_debt(&env, &supplier) }
+    pub fn get_dust_balance(env: Env, holder: Address, token: Address) -> i128 { storage::get_dust_balance(&env, &holder, &token) }
+    pub fn get_installment_account(env: Env, invoice_id: String, holder: Address) -> InstallmentAccount { storage::get_installment_account(&env, &invoice_id, &holder) }
+    pub fn verify_private_terms(env: Env, invoice_id: String, terms: Bytes, salt: BytesN<32>) -> bool { get_invoice(&env, &invoice_id).map(|inv| Self::terms_match(&env, &inv, &terms, &salt)).unwrap_or(false) }
+    pub fn get_private_terms(env: Env, invoice_id: String) -> Option<Bytes> { storage::get_private_terms(&env, &invoice_id) }
+    pub fn verify_document(env: Env, invoice_id: String, document_hash: String) -> bool { get_invoice(&env, &invoice_id).map(|inv| inv.document_hash == document_hash).unwrap_or(false) }
+
+
+    // ========================================================================
     // INTERNAL HELPERS
     // ========================================================================
 
-    fn generate_invoice_id(env: &Env) -> String {
-        let counter = storage::get_invoice_counter(env);
-        storage::set_invoice_counter(env, counter + 1);
-        let num = counter + 1001;
-        let mut digits = [0u8; 4];
-        let mut n = num;
-        for i in (0..4).rev() { digits[i] = b'0' + (n % 10) as u8; n /= 10; }
-        let mut id_bytes = [0u8; 8];
-        id_bytes[0] = b'I'; id_bytes[1] = b'N'; id_bytes[2] = b'V'; id_bytes[3] = b'-';
-        id_bytes[4] = digits[0]; id_bytes[5] = digits[1]; id_bytes[6] = digits[2]; id_bytes[7] = digits[3];
-        String::from_str(env, core::str::from_utf8(&id_bytes).unwrap())
+    fn generate_invoice_id(env: &Env) -> String {
+        let counter = storage::get_invoice_counter(env);
+        storage::set_invoice_counter(env, counter + 1);
+        Self::format_invoice_id(env, counter)
+    }
+
+    /// Up to 8 digits; the first 8999 invoices keep the original 4-digit form (INV-1001..INV-9999)
+    fn format_invoice_id(env: &Env, counter: u32) -> String {
+        let mut n = counter + 1001;
+        let mut id_bytes = *b"INV-00000000";
+        for i in (4..12).rev() { id_bytes[i] = b'0' + (n % 10) as u8; n /= 10; }
+        let padding = id_bytes[4..8].iter().take_while(|b| **b == b'0').count();
+        id_bytes.copy_within(4 + padding..12, 4);
+        String::from_str(env, core::str::from_utf8(&id_bytes[..12 - padding]).unwrap())
+    }
+
+    fn generate_order_id(env: &Env) -> String {
+        let counter = storage::get_order_counter(env);
+        storage::set_order_counter(env, counter + 1);
+        Self::format_order_id(env, counter)
+    }
+
+    fn format_order_id(env: &Env, counter: u32) -> String {
+        let num = counter + 1;
+        let mut digits = [0u8; 4];
+        let mut n = num;
+        for i in (0..4).rev() { digits[i] = b'0' + (n % 10) as u8; n /= 10; }
+        let mut id_bytes = [0u8; 8];
+        id_bytes[0] = b'O'; id_bytes[1] = b'R'; id_bytes[2] = b'D'; id_bytes[3] = b'-';
+        id_bytes[4] = digits[0]; id_bytes[5] = digits[1]; id_bytes[6] = digits[2]; id_bytes[7] = digits[3];
+        String::from_str(env, core::str::from_utf8(&id_bytes).unwrap())
+    }
+
+    /// Stores a new draft; `amount` is 0 for confidential drafts until the buyer reveals it.
+    /// `terms` is (currency, description, purchase_order, document_hash).
+    fn create_draft(env: &Env, supplier: Address, buyer: Address, amount: i128, amount_commitment: Option<BytesN<32>>, due_date: u64, terms: (String, String, String, String)) -> Result<String, ContractError> {
+        if storage::get_supplier_debt(env, &supplier) > 0 { return Err(ContractError::SupplierInDebt); }
+        let (currency, description, purchase_order, document_hash) = terms;
+        let invoice_id = Self::generate_invoice_id(env);
+        let program_id = storage::get_supplier_program(env, &supplier);
+        let invoice = Invoice {
+            id: invoice_id.clone(),
+            supplier: supplier.clone(),
+            buyer: buyer.clone(),
+            amount,
+            currency,
+            created_at: env.ledger().timestamp(),
+            due_date,
+            verified_at: 0,
+            settled_at: 0,
+            status: InvoiceStatus::Draft,
+            token_symbol: String::from_str(env, ""),
+            total_tokens: 0,
+            tokens_sold: 0,
+            tokens_remaining: 0,
+            description,
+            purchase_order,
+            document_hash,
+            repayment_received: 0,
+            buyer_signed_at: 0,
+            auction_start: 0,
+            auction_end: 0,
+            start_price: 0,
+            min_price: 0,
+            price_drop_rate: 0,
+            fee_version: 0,
+            settlement_token: None,
+            settlement_fx_rate: 0,
+            settlement_paid: 0,
+            program_id,
+            amount_commitment,
+            min_funding_tokens: 0,
+            escrowed_payments: 0,
+            private_terms_hash: None,
+            allowlist_root: None,
+            uniform_clearing: false,
+            rate_config: RateConfig::default(),
+            recovered_amount: 0,
+            auction_type: AuctionType::Dutch,
+        };
+        set_invoice(env, &invoice_id, &invoice);
+        storage::add_to_maturity_bucket(env, due_date, &invoice_id);
+        storage::add_supplier_invoice(env, &supplier, &invoice_id);
+        storage::add_buyer_invoice(env, &buyer, &invoice_id);
+        Self::record_transition(env, &invoice, &supplier, amount);
+        InvoiceEvents::invoice_created(env, &invoice_id, &supplier, &buyer, amount);
+        Ok(invoice_id)
+    }
+
+    /// Validates and applies new draft terms, recording them in the amendment history.
+    /// Returns the amendment's number (1 for the first). The caller saves the invoice.
+    fn amend_terms(env: &Env, invoice: &mut Invoice, terms: RevisedTerms) -> Result<u32, ContractError> {
+        let RevisedTerms { amount, due_date, description, purchase_order, document_hash } = terms;
+        if storage::get_supplier_debt(env, &invoice.supplier) > 0 { return Err(ContractError::SupplierInDebt); }
+        let confidential = invoice.amount_commitment.is_some();
+        if (confidential && amount != 0) || (!confidential && amount <= 0) { return Err(ContractError::InvalidAmount); }
+        Self::check_issuance_limits(env, &invoice.supplier, amount, due_date)?;
+
+        if due_date != invoice.due_date {
+            storage::remove_from_maturity_bucket(env, invoice.due_date, &invoice.id);
+            storage::add_to_maturity_bucket(env, due_date, &invoice.id);
+        }
+        invoice.amount = amount;
+        invoice.due_date = due_date;
+        invoice.description = description;
+        invoice.purchase_order = purchase_order;
+        invoice.document_hash = document_hash.clone();
+        invoice.buyer_signed_at = 0;
+        Ok(storage::add_amendment(env, &invoice.id, &DraftAmendment { amended_at: env.ledger().timestamp(), amount, due_date, document_hash }))
+    }
+
+    /// Buyer approval (Digital Handshake): verifies the invoice and mints its tokens to the
+    /// supplier. Confidential drafts must be approved with the amount and salt they commit to.
+    /// `expected_amendments` is the amendment count the buyer reviewed; a draft amended since
+    /// then is rejected rather than approved on terms the buyer hasn't seen
+    fn verify_and_tokenize(
+        env: &Env,
+        invoice_id: &String,
+        buyer: &Address,
+        reveal: Option<(i128, BytesN<32>)>,
+        expected_amendments: u32,
+    ) -> Result<(), ContractError> {
+        buyer.require_auth();
+        let mut invoice = get_invoice(env, invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if invoice.buyer != *buyer { return Err(ContractError::Unauthorized); }
+        let now = env.ledger().timestamp();
+        state_machine::check(&invoice, &Event::Approve, now)?;
+        if storage::amendment_count(env, invoice_id) != expected_amendments { return Err(ContractError::TermsMismatch); }
+        match (&invoice.amount_commitment, reveal) {
+            (None, None) => {}
+            (Some(commitment), Some((amount, salt))) => {
+                if amount <= 0 || Self::amount_commitment(env, amount, &salt) != *commitment { return Err(ContractError::InvalidCommitment); }
+                Self::check_issuance_limits(env, &invoice.supplier, amount, invoice.due_date)?;
+                invoice.amount = amount;
+            }
+            _ => return Err(ContractError::InvalidCommitment),
+        }
+        let obligation_limit = storage::get_buyer_obligation_limit(env, buyer);
+        if obligation_limit > 0 && storage::get_buyer_outstanding(env, buyer) + invoice.amount > obligation_limit {
+            return Err(ContractError::ObligationLimitExceeded);
+        }
+
+        state_machine::transition(&mut invoice, Event::Approve, now)?;
+        invoice.verified_at = now;
+        invoice.buyer_signed_at = now;
+        // Symbols must map back to exactly one invoice
+        let symbol = Self::generate_token_symbol(env, invoice_id);
+        if storage::get_symbol_invoice(env, &symbol).is_some_and(|owner| owner != *invoice_id) { return Err(ContractError::SymbolTaken); }
+        storage::set_symbol_invoice(env, &symbol, invoice_id);
+        invoice.token_symbol = symbol;
+        invoice.total_tokens = invoice.amount;
+        invoice.tokens_sold = 0;
+        invoice.tokens_remaining = invoice.amount;
+        set_invoice(env, invoice_id, &invoice);
+        interest::record_benchmark(env, &invoice, false);
+        Self::record_transition(env, &invoice, buyer, invoice.total_tokens);
+        let mut stats = storage::get_relationship_stats(env, &invoice.supplier, buyer);
+        stats.drafts_approved += 1;
+        storage::set_relationship_stats(env, &invoice.supplier, buyer, &stats);
+
+        let holding = TokenHolding {
+            invoice_id: invoice_id.clone(),
+            holder: invoice.supplier.clone(),
+            amount: invoice.total_tokens,
+            acquired_at: env.ledger().timestamp(),
+            acquired_price: invoice.amount,
+        };
+        set_token_holding(env, invoice_id, &invoice.supplier, &holding);
+        storage::enqueue_for_default(env, invoice.due_date, invoice_id);
+        InvoiceEvents::invoice_verified(env, invoice_id, buyer, invoice.total_tokens);
+        Ok(())
+    }
+
+    /// sha256(amount as 16 big-endian bytes || salt)
+    fn amount_commitment(env: &Env, amount: i128, salt: &BytesN<32>) -> BytesN<32> {
+        let mut preimage = Bytes::from_array(env, &amount.to_be_bytes());
+        preimage.append(&Bytes::from(salt.clone()));
+        env.crypto().sha256(&preimage).into()
+    }
+
+    /// sha256(terms || salt)
+    fn terms_match(env: &Env, invoice: &Invoice, terms: &Bytes, salt: &BytesN<32>) -> bool {
+        let mut preimage = terms.clone();
+        preimage.append(&Bytes::from(salt.clone()));
+        invoice.private_terms_hash.as_ref().is_some_and(|hash| BytesN::from(env.crypto().sha256(&preimage)) == *hash)
+    }
+
+    /// Next investment record ID (e.g. IVT-00000001)
+    fn generate_investment_id(env: &Env) -> String {
+        let counter = storage::get_investment_counter(env);
+        storage::set_investment_counter(env, counter + 1);
+        let mut n = counter + 1;
+        let mut id_bytes = *b"IVT-00000000";
+        for i in (4..12).rev() { id_bytes[i] = b'0' + (n % 10) as u8; n /= 10; }
+        String::from_str(env, core::str::from_utf8(&id_bytes).unwrap())
+    }
+
+    /// Records a purchase of `token_amount` tokens for `paid`, with its discount to face value
+    fn record_investment(env: &Env, invoice: &Invoice, investor: &Address, token_amount: i128, paid: i128) {
+        let face = (token_amount * invoice.amount) / invoice.total_tokens;
+        let discount_rate = if face > 0 { ((face - paid).max(0) * 10000 / face) as u32 } else { 0 };
+        storage::add_investment(env, &Investment {
+            id: Self::generate_investment_id(env), invoice_id: invoice.id.clone(), investor: investor.clone(),
+            token_amount, invested_amount: paid, discount_rate, invested_at: env.ledger().timestamp(),
+            settled_amount: 0, settled_at: 0,
+        });
+    }
+
+    /// Spreads a holder's settlement payout over their open investment records in the invoice.
+    /// Tokens still held are matched to the oldest purchases first; records whose tokens were
+    /// sold on are stamped settled with nothing received.
+    fn settle_investments(env: &Env, invoice_id: &String, holder: &Address, tokens: i128, received: i128, at: u64) {
+        let mut tokens_left = tokens;
+        for investment_id in storage::get_holding_investments(env, invoice_id, holder).iter() {
+            let Some(mut investment) = storage::get_investment(env, &investment_id) else { continue };
+            if investment.settled_at > 0 { continue; }
+            let matched = investment.token_amount.min(tokens_left);
+            tokens_left -= matched;
+            investment.settled_amount = if tokens > 0 { (received * matched) / tokens } else { 0 };
+            investment.settled_at = at;
+            storage::set_investment(env, &investment);
+        }
+    }
+
+    /// Ticker for an invoice's tokens: "SNG-" followed by the invoice ID (e.g. SNG-INV-1001)
+    fn generate_token_symbol(env: &Env, invoice_id: &String) -> String {
+        let mut symbol_bytes = [0u8; 32];
+        let len = 4 + invoice_id.len() as usize;
+        symbol_bytes[..4].copy_from_slice(b"SNG-");
+        invoice_id.copy_into_slice(&mut symbol_bytes[4..len]);
+        String::from_str(env, core::str::from_utf8(&symbol_bytes[..len]).unwrap())
+    }
+
+    fn record_transition(env: &Env, invoice: &Invoice, actor: &Address, amount: i128) {
+        storage::append_history(env, &invoice.id, &StateRecord {
+            status: invoice.status.clone(), timestamp: env.ledger().timestamp(), actor: actor.clone(), amount,
+        });
+        // A settled or recovered invoice needs no cover: its sub-pool joins the program's shared pool
+        if invoice.status.is_settled() || invoice.status == InvoiceStatus::Recovered { storage::release_invoice_insurance(env, invoice); }
+        hooks::notify(env, invoice);
+    }
+
+    fn limits_for_supplier(env: &Env, supplier: &Address) -> IssuanceLimits {
+        let tier = storage::get_supplier_tier(env, supplier);
+        storage::get_tier_limits(env, tier).unwrap_or_else(|| storage::get_issuance_limits(env))
+    }
+
+    fn check_issuance_limits(env: &Env, supplier: &Address, amount: i128, due_date: u64) -> Result<(), ContractError> {
+        let limits = Self::limits_for_supplier(env, supplier);
+        if limits.max_amount > 0 && amount > limits.max_amount { return Err(ContractError::AmountExceedsLimit); }
+        let tenor_days = due_date.saturating_sub(env.ledger().timestamp()) / 86400;
+        if limits.max_tenor_days > 0 && tenor_days > limits.max_tenor_days { return Err(ContractError::TenorExceedsLimit); }
+        Ok(())
+    }
+
+    fn calculate_settlement_amount(env: &Env, invoice: &Invoice) -> i128 {
+        Self::settlement_amount_at(env, invoice, env.ledger().timestamp())
+    }
+
+    fn settlement_amount_at(env: &Env, invoice: &Invoice, current_time: u64) -> i128 {
+        let quote = Self::settlement_quote_at(env, invoice, current_time);
+        quote.amount_due
+    }
+
+    fn settlement_quote_at(env: &Env, invoice: &Invoice, current_time: u64) -> SettlementQuote {
+        let mut interest = interest::interest(env, invoice, invoice.amount, invoice.created_at, current_time);
+        // Prepaid amounts stop accruing from the day they were deposited
+        for prepayment in storage::get_prepayments(env, &invoice.id).iter() {
+            interest -= interest::interest(env, invoice, prepayment.amount, prepayment.deposited_at, current_time);
+        }
+        let gross_amount = invoice.amount + interest;
+        let discount = interest::early_discount(env, invoice, gross_amount, current_time);
+        SettlementQuote {
+            gross_amount,
+            days_early: invoice.due_date.saturating_sub(current_time) / 86400,
+            discount,
+            amount_due: gross_amount - discount,
+        }
+    }
+
+    /// Shared primary-market purchase: `payer` funds the tokens, `holder` receives them
+    fn execute_investment(env: &Env, invoice_id: &String, payer: &Address, holder: &Address, token_amount: i128) -> Result<(), ContractError> {
+        Self::require_not_paused(env)?;
+        let _guard = ReentrancyGuard::acquire(env, invoice_id)?;
+        let mut invoice = get_invoice(env, invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let quote = Self::quote_invest(env, &invoice, token_amount, env.ledger().timestamp())?;
+        let (current_price, payment_amount, insurance_amount, supplier_payment) =
+            (quote.price, quote.payment_amount, quote.insurance_amount, quote.supplier_proceeds);
+        if invoice.fee_version == 0 {
+            invoice.fee_version = storage::get_active_fee_schedule(env, env.ledger().timestamp()).version;
+            invoice.rate_config = storage::get_program_rate_config(env, invoice.program_id);
+        }
+
+        let exposure_limit = storage::get_exposure_limit(env, holder);
+        if exposure_limit > 0 && Self::exposure_of(env, holder) + payment_amount > exposure_limit {
+            return Err(ContractError::ExposureLimitExceeded);
+        }
+        Self::check_concentration(env, &invoice, holder, token_amount)?;
+
+        // Moves the tokens before any funds do, so a short supplier holding fails first
+        let new_holder = Self::issue_tokens(env, &invoice, holder, token_amount, payment_amount)?;
+
+        let payment_token = storage::get_usdc_token(env);
+        let token_client = TokenClient::new(env, &payment_token);
+        token_client.transfer(payer, &env.current_contract_address(), &payment_amount);
+        if invoice.tokens_sold < invoice.min_funding_tokens || (invoice.uniform_clearing && invoice.auction_start > 0) {
+            // Held until the threshold is met (refundable if the auction expires short of it),
+            // or until a uniform-clearing auction closes
+            storage::add_funding_escrow(env, invoice_id, holder, token_amount, payment_amount);
+            invoice.escrowed_payments += payment_amount;
+        } else {
+            token_client.transfer(&env.current_contract_address(), &invoice.supplier, &supplier_payment);
+            storage::add_supplier_proceeds(env, &invoice.supplier, supplier_payment);
+            storage::add_invoice_insurance(env, &invoice, insurance_amount);
+            Self::credit_program_fee(env, invoice.program_id, quote.program_fee);
+            Self::credit_protocol_fee(env, quote.protocol_fee);
+        }
+
+        invoice.tokens_sold += token_amount;
+        invoice.tokens_remaining -= token_amount;
+        if invoice.escrowed_payments > 0 && invoice.tokens_sold >= invoice.min_funding_tokens && !invoice.uniform_clearing {
+            Self::release_funding_escrow(env, &mut invoice);
+        }
+        if invoice.auction_start > 0 {
+            Self::record_auction_fill(env, &invoice, current_price, token_amount, payment_amount, new_holder);
+        }
+        if invoice.uniform_clearing && invoice.tokens_remaining == 0 && invoice.escrowed_payments > 0 {
+            Self::clear_uniform_auction(env, &mut invoice);
+        }
+        let sold_out = invoice.tokens_remaining == 0;
+        if sold_out {
+            state_machine::transition(&mut invoice, Event::Fund, env.ledger().timestamp())?;
+            storage::set_auction_reserve(env, invoice_id, 0, 0);
+            InvoiceEvents::auction_ended(env, invoice_id, current_price, risk::risk_score(env, &invoice));
+        }
+        set_invoice(env, invoice_id, &invoice);
+        // Recorded after the save so subscribers notified of the transition read the new state
+        if sold_out { Self::record_transition(env, &invoice, holder, invoice.tokens_sold); }
+        InvoiceEvents::investment_made(env, invoice_id, holder, token_amount, payment_amount);
+        Ok(())
+    }
+
+    /// Locks `amount` of the sender's unlocked tokens behind a new pending transfer to `to`
+    fn offer_transfer(env: &Env, invoice_id: &String, from: &Address, to: &Address, amount: i128) -> Result<u64, ContractError> {
+        if amount <= 0 || from == to { return Err(ContractError::InvalidAmount); }
+        if storage::pending_transfer_count(env, to) >= MAX_PENDING_OFFERS || storage::offer_count_from(env, invoice_id, from) >= MAX_PENDING_OFFERS {
+            return Err(ContractError::LimitReached);
+        }
+        let holding = storage::get_token_holding(env, invoice_id, from).ok_or(ContractError::InsufficientTokens)?;
+        if Self::free_tokens(env, &holding) < amount { return Err(ContractError::InsufficientTokens); }
+        let now = env.ledger().timestamp();
+        let transfer = PendingTransfer {
+            id: storage::next_transfer_id(env), invoice_id: invoice_id.clone(), from: from.clone(), to: to.clone(),
+            amount, offered_at: now, reclaimable_at: now + TRANSFER_ACCEPTANCE_SECONDS,
+        };
+        storage::add_pending_transfer(env, &transfer);
+        InvoiceEvents::transfer_offered(env, invoice_id, transfer.id, from, to, amount);
+        Ok(transfer.id)
     }
 
-    fn generate_order_id(env: &Env) -> String {
-        let counter = storage::get_order_counter(env);
-        storage::set_order_counter(env, counter + 1);
-        let num = counter + 1;
-        let mut digits = [0u8; 4];
-        let mut n = num;
-        for i in (0..4).rev() { digits[i] = b'0' + (n % 10) as u8; n /= 10; }
-        let mut id_bytes = [0u8; 8];
-        id_bytes[0] = b'O'; id_bytes[1] = b'R'; id_bytes[2] = b'D'; id_bytes[3] = b'-';
-        id_bytes[4] = digits[0]; id_bytes[5] = digits[1]; id_bytes[6] = digits[2]; id_bytes[7] = digits[3];
-        String::from_str(env, core::str::from_utf8(&id_bytes).unwrap())
+    /// Moves primary tokens from the supplier's holding to the investor's at `payment`, recording
+    /// the purchase. Returns whether the investor is a new holder.
+    fn issue_tokens(env: &Env, invoice: &Invoice, holder: &Address, token_amount: i128, payment: i128) -> Result<bool, ContractError> {
+        let invoice_id = &invoice.id;
+        let mut supplier_holding = storage::get_token_holding(env, invoice_id, &invoice.supplier).ok_or(ContractError::InsufficientTokens)?;
+        let unlocked = supplier_holding.amount - storage::get_locked_tokens(env, invoice_id, &invoice.supplier);
+        if unlocked < token_amount { return Err(ContractError::InsufficientTokens); }
+        supplier_holding.amount -= token_amount;
+        if supplier_holding.amount == 0 {
+            remove_token_holding(env, invoice_id, &invoice.supplier);
+        } else {
+            set_token_holding(env, invoice_id, &invoice.supplier, &supplier_holding);
+        }
+
+        let existing_holding = storage::get_token_holding(env, invoice_id, holder);
+        let new_holder = existing_holding.is_none();
+        let investor_holding = match existing_holding {
+            Some(mut existing) => { existing.amount += token_amount; existing.acquired_price += payment; existing }
+            None => TokenHolding { invoice_id: invoice_id.clone(), holder: holder.clone(), amount: token_amount, acquired_at: env.ledger().timestamp(), acquired_price: payment }
+        };
+        set_token_holding(env, invoice_id, holder, &investor_holding);
+        storage::add_investor_invoice(env, holder, invoice_id);
+        Self::record_investment(env, invoice, holder, token_amount, payment);
+        Ok(new_holder)
     }
 
-    fn generate_token_symbol(env: &Env, _invoice_id: &String) -> String { String::from_str(env, "SNG-") }
+    /// Closes an English auction: bids fill best first up to the tokens the supplier still has for
+    /// sale, each paying its own price (or, under uniform clearing, the lowest filled price), and
+    /// the rest of every escrow is refunded. Nothing fills if the bids fall short of the funding
+    /// threshold. Filled payments are released to the supplier less premium and fees.
+    fn close_english_auction(env: &Env, invoice: &mut Invoice) -> Result<(), ContractError> {
+        let bids = storage::take_auction_bids(env, &invoice.id);
+        // Tokens the supplier has offered in a pending transfer can't be issued to bidders
+        let for_sale = storage::get_token_holding(env, &invoice.id, &invoice.supplier).map(|holding| holding.amount).unwrap_or(0)
+            - storage::get_locked_tokens(env, &invoice.id, &invoice.supplier);
+        let (mut fills, mut unfilled, mut clearing_price) = (Vec::new(env), invoice.tokens_remaining.min(for_sale), 0);
+        for bid in bids.iter() {
+            let tokens = bid.token_amount.min(unfilled);
+            if tokens > 0 { clearing_price = bid.price; }
+            unfilled -= tokens;
+            fills.push_back(tokens);
+        }
+        let filled = invoice.tokens_remaining.min(for_sale) - unfilled;
+        let threshold_met = invoice.tokens_sold + filled >= invoice.min_funding_tokens;
 
-    fn calculate_settlement_amount(env: &Env, invoice: &Invoice) -> i128 {
-        let current_time = env.ledger().timestamp();
-        let rate_config = get_rate_config(env);
-        let base_amount = invoice.amount;
-        let days_since_creation = (current_time - invoice.created_at) / 86400;
-        let interest_rate = if current_time > invoice.due_date { rate_config.penalty_rate } else { rate_config.base_interest_rate };
-        let interest = (base_amount * (interest_rate as i128) * (days_since_creation as i128)) / (10000 * 365);
-        base_amount + interest
+        let token_client = TokenClient::new(env, &storage::get_usdc_token(env));
+        for (index, bid) in bids.iter().enumerate() {
+            storage::release_from_escrow(env, bid.escrow);
+            let tokens = if threshold_met { fills.get(index as u32).unwrap_or(0) } else { 0 };
+            let price = if invoice.uniform_clearing { clearing_price } else { bid.price };
+            let payment = (tokens * price) / invoice.total_tokens;
+            if tokens > 0 {
+                let new_holder = Self::issue_tokens(env, invoice, &bid.bidder, tokens, payment)?;
+                invoice.tokens_sold += tokens;
+                invoice.tokens_remaining -= tokens;
+                invoice.escrowed_payments += payment;
+                Self::record_auction_fill(env, invoice, price, tokens, payment, new_holder);
+                InvoiceEvents::investment_made(env, &invoice.id, &bid.bidder, tokens, payment);
+            }
+            let refund = bid.escrow - payment;
+            if refund > 0 {
+                token_client.transfer(&env.current_contract_address(), &bid.bidder, &refund);
+                InvoiceEvents::holder_refunded(env, &invoice.id, &bid.bidder, refund);
+            }
+        }
+        if invoice.escrowed_payments > 0 && threshold_met { Self::release_funding_escrow(env, invoice); }
+        Ok(())
     }
 
-    fn distribute_settlement(env: &Env, invoice_id: &String, total_amount: i128) -> Result<(), ContractError> {
-        let invoice = get_invoice(env, invoice_id).ok_or(ContractError::InvoiceNotFound)?;
-        let payment_token = storage::get_usdc_token(env);
-        let token_client = TokenClient::new(env, &payment_token);
-        let holders = storage::get_all_holders(env, invoice_id);
+    /// Pays out escrowed investments once the funding threshold is met, taking the insurance
+    /// cut, program fee and protocol fee on the aggregate
+    fn release_funding_escrow(env: &Env, invoice: &mut Invoice) {
+        let total = invoice.escrowed_payments;
+        for holder in storage::get_all_holders(env, &invoice.id).iter() {
+            storage::remove_funding_escrow(env, &invoice.id, &holder);
+        }
+        let fees = Self::fee_schedule_for(env, invoice, env.ledger().timestamp());
+        let insurance_amount = (total * risk::insurance_premium_bps(env, invoice, fees.insurance_cut_bps) as i128) / 10000;
+        let program_fee = (total * storage::get_program_fees(env, invoice.program_id).investment_fee_bps as i128) / 10000;
+        let protocol_fee = (total * fees.protocol_fee_bps as i128) / 10000;
+        let supplier_payment = total - insurance_amount - program_fee - protocol_fee;
+        TokenClient::new(env, &storage::get_usdc_token(env)).transfer(&env.current_contract_address(), &invoice.supplier, &supplier_payment);
+        storage::add_supplier_proceeds(env, &invoice.supplier, supplier_payment);
+        storage::add_invoice_insurance(env, invoice, insurance_amount);
+        Self::credit_program_fee(env, invoice.program_id, program_fee);
+        Self::credit_protocol_fee(env, protocol_fee);
+        invoice.escrowed_payments = 0;
+        InvoiceEvents::funding_released(env, &invoice.id, total);
+    }
+
+    /// Hands an investor's escrowed tokens back to the supplier and takes their payment off the
+    /// invoice; the caller refunds or defers the payment
+    fn return_escrowed_tokens(env: &Env, invoice: &mut Invoice, mut holding: TokenHolding, tokens: i128, payment: i128) {
+        holding.amount -= tokens;
+        holding.acquired_price -= payment;
+        if holding.amount == 0 { remove_token_holding(env, &invoice.id, &holding.holder); } else { set_token_holding(env, &invoice.id, &holding.holder, &holding); }
+        let supplier_holding = match storage::get_token_holding(env, &invoice.id, &invoice.supplier) {
+            Some(mut existing) => { existing.amount += tokens; existing }
+            None => TokenHolding { invoice_id: invoice.id.clone(), holder: invoice.supplier.clone(), amount: tokens, acquired_at: env.ledger().timestamp(), acquired_price: invoice.amount },
+        };
+        set_token_holding(env, &invoice.id, &invoice.supplier, &supplier_holding);
+        invoice.escrowed_payments -= payment;
+        invoice.tokens_sold -= tokens;
+        invoice.tokens_remaining += tokens;
+    }
+
+    /// Unwinds a round that expired below its funding threshold: every escrowed purchase goes
+    /// back to the supplier and its payment becomes a refund the investor claims
+    fn unwind_funding_escrow(env: &Env, invoice: &mut Invoice) {
+        for holder in storage::get_all_holders(env, &invoice.id).iter() {
+            let (tokens, payment) = storage::defer_funding_refund(env, &invoice.id, &holder);
+            if payment == 0 { continue; }
+            let Some(holding) = storage::get_token_holding(env, &invoice.id, &holder) else { continue };
+            let tokens = tokens.min(holding.amount);
+            Self::return_escrowed_tokens(env, invoice, holding, tokens, payment);
+        }
+    }
+
+    /// Rebates each escrowed investor down to the auction's clearing price (its last fill, the
+    /// lowest price paid), then releases the rest of the escrow to the supplier
+    fn clear_uniform_auction(env: &Env, invoice: &mut Invoice) {
+        let Some(mut result) = storage::get_auction_result(env, &invoice.id) else { return };
+        let token_client = TokenClient::new(env, &storage::get_usdc_token(env));
+        let mut total_rebate = 0;
+        for holder in storage::get_all_holders(env, &invoice.id).iter() {
+            let (tokens, payment) = storage::get_funding_escrow(env, &invoice.id, &holder);
+            let rebate = payment - (tokens * result.clearing_price) / invoice.total_tokens;
+            if rebate <= 0 { continue; }
+            if let Some(mut holding) = storage::get_token_holding(env, &invoice.id, &holder) {
+                holding.acquired_price -= rebate;
+                set_token_holding(env, &invoice.id, &holder, &holding);
+            }
+            storage::remove_funding_escrow(env, &invoice.id, &holder);
+            invoice.escrowed_payments -= rebate;
+            total_rebate += rebate;
+            token_client.transfer(&env.current_contract_address(), &holder, &rebate);
+            InvoiceEvents::holder_refunded(env, &invoice.id, &holder, rebate);
+        }
+        // Proceeds (and the discount derived from them on sell-out) are what investors end up paying
+        result.total_proceeds -= total_rebate;
+        if result.finalized_at > 0 { Self::close_auction_result(env, invoice, &mut result, env.ledger().timestamp()); }
+        storage::set_auction_result(env, &result);
+        InvoiceEvents::auction_cleared(env, &invoice.id, result.clearing_price, total_rebate);
+        Self::release_funding_escrow(env, invoice);
+    }
+
+    /// Contract balance of `token` not backed by the insurance pools, program treasuries or prepayment escrow
+    fn unaccounted_balance(env: &Env, token: &Address) -> i128 {
+        let balance = TokenClient::new(env, token).balance(&env.current_contract_address());
+        let accounted = if *token == storage::get_usdc_token(env) {
+            storage::get_total_insurance_pools(env) + storage::get_total_treasuries(env) + storage::get_escrow_total(env)
+        } else { 0 } + storage::get_dust_total(env, token);
+        (balance - accounted).max(0)
+    }
+
+    /// Tokens `investor` may still take on the invoice under its concentration cap, if it has one
+    fn concentration_capacity(env: &Env, invoice: &Invoice, investor: &Address) -> Option<i128> {
+        let bps = storage::get_concentration_cap(env, &invoice.id).unwrap_or_else(|| interest::rate_config(env, invoice).max_investor_bps);
+        if bps == 0 || *investor == invoice.supplier { return None; }
+        let held = storage::get_token_holding(env, &invoice.id, investor).map(|holding| holding.amount).unwrap_or(0)
+            + storage::get_bidder_totals(env, &invoice.id, investor).0;
+        Some((invoice.total_tokens * bps as i128 / 10000 - held).max(0))
+    }
+
+    fn check_concentration(env: &Env, invoice: &Invoice, investor: &Address, token_amount: i128) -> Result<(), ContractError> {
+        match Self::concentration_capacity(env, invoice, investor) {
+            Some(capacity) if token_amount > capacity => Err(ContractError::ConcentrationLimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    /// Cost basis of the investor's holdings in invoices that are neither settled, revoked,
+    /// recovered, nor already written off through an insurance claim, plus the escrow in its
+    /// open auction bids
+    fn exposure_of(env: &Env, investor: &Address) -> i128 {
+        let mut exposure = 0;
+        for invoice_id in storage::get_investor_invoices(env, investor).iter() {
+            let open = get_invoice(env, &invoice_id)
+                .map(|inv| !inv.status.is_settled() && inv.status != InvoiceStatus::Revoked && inv.status != InvoiceStatus::Recovered)
+                .unwrap_or(false);
+            if !open || storage::is_insurance_claimed(env, &invoice_id, investor) { continue; }
+            if let Some(holding) = storage::get_token_holding(env, &invoice_id, investor) {
+                exposure += holding.acquired_price;
+            }
+        }
+        exposure + storage::get_bid_escrow(env, investor)
+    }
+
+    /// Takes the applicant's request off the KYC queue, recording the decision logged as `action_id`
+    fn close_kyc_request(env: &Env, applicant: &Address, approved: bool, action_id: u64) {
+        storage::dequeue_kyc(env, applicant);
+        let Some(mut request) = storage::get_kyc_request(env, applicant) else { return };
+        request.status = if approved { KycRequestStatus::Approved } else { KycRequestStatus::Rejected };
+        request.decided_at = env.ledger().timestamp();
+        request.action_id = action_id;
+        storage::set_kyc_request(env, &request);
+    }
+
+    /// Sets a KYC or relayer permission and appends it to the audit log. Returns the action id.
+    fn apply_authorization(env: &Env, admin: &Address, addr: &Address, kind: AuthorizationKind, granted: bool, reason_code: u32, reference_hash: String) -> u64 {
+        let previous = match kind {
+            AuthorizationKind::Kyc => get_kyc_status(env, addr),
+            AuthorizationKind::Relayer => storage::is_authorized_relayer(env, addr),
+        };
+        match kind {
+            AuthorizationKind::Kyc => set_kyc_status(env, addr, granted),
+            AuthorizationKind::Relayer => storage::set_authorized_relayer(env, addr, granted),
+        }
+        let action_id = storage::next_authorization_action_id(env);
+        let record = AuthorizationRecord { action_id, kind: kind.clone(), granted, previous, reason_code, reference_hash, actor: admin.clone(), timestamp: env.ledger().timestamp() };
+        storage::append_authorization_log(env, addr, &record);
+        InvoiceEvents::authorization_logged(env, addr, &record);
+        if kind == AuthorizationKind::Kyc { InvoiceEvents::kyc_updated(env, addr, granted); }
+        action_id
+    }
+
+    /// Folds a fill into the invoice's AuctionResult, finalizing it on sell-out
+    fn record_auction_fill(env: &Env, invoice: &Invoice, price: i128, tokens: i128, payment: i128, new_holder: bool) {
+        let mut result = storage::get_auction_result(env, &invoice.id).unwrap_or(AuctionResult {
+            invoice_id: invoice.id.clone(), clearing_price: 0, tokens_sold: 0, investor_count: 0,
+            total_proceeds: 0, duration: 0, avg_discount_bps: 0, finalized_at: 0,
+        });
+        result.clearing_price = price;
+        result.tokens_sold += tokens;
+        result.total_proceeds += payment;
+        if new_holder { result.investor_count += 1; }
+        if invoice.tokens_remaining == 0 {
+            Self::close_auction_result(env, invoice, &mut result, env.ledger().timestamp());
+        }
+        storage::set_auction_result(env, &result);
+    }
+
+    fn close_auction_result(env: &Env, invoice: &Invoice, result: &mut AuctionResult, ended_at: u64) {
+        let face_sold = (result.tokens_sold * invoice.amount) / invoice.total_tokens;
+        result.duration = ended_at - invoice.auction_start;
+        result.avg_discount_bps = if face_sold > 0 { ((face_sold - result.total_proceeds) * 10000 / face_sold) as u32 } else { 0 };
+        result.finalized_at = env.ledger().timestamp();
+    }
+
+    /// Pinned schedule once funding has started, otherwise whatever is active at `at`
+    fn fee_schedule_for(env: &Env, invoice: &Invoice, at: u64) -> FeeSchedule {
+        if invoice.fee_version > 0 {
+            if let Some(schedule) = storage::get_fee_schedule(env, invoice.fee_version) { return schedule; }
+        }
+        storage::get_active_fee_schedule(env, at)
+    }
+
+    fn price_at(invoice: &Invoice, at: u64) -> i128 {
+        if at >= invoice.auction_end { return invoice.min_price; }
+        let hours_elapsed = at.saturating_sub(invoice.auction_start) / 3600;
+        let total_drop = (invoice.start_price * invoice.price_drop_rate as i128 * hours_elapsed as i128) / 10000;
+        (invoice.start_price - total_drop).max(invoice.min_price)
+    }
+
+    fn quote_invest(env: &Env, invoice: &Invoice, token_amount: i128, at: u64) -> Result<InvestQuote, ContractError> {
+        match invoice.status {
+            // English auctions sell only through bids
+            InvoiceStatus::Funding if invoice.auction_type == AuctionType::English => return Err(ContractError::InvalidStatus),
+            InvoiceStatus::Funding => {}
+            InvoiceStatus::Verified if storage::allows_pre_auction_invest(env) => {}
+            InvoiceStatus::Verified => return Err(ContractError::AuctionNotStarted),
+            _ => return Err(ContractError::InvalidStatus),
+        }
+        if token_amount <= 0 { return Err(ContractError::InvalidAmount); }
+        if token_amount > invoice.tokens_remaining { return Err(ContractError::InsufficientTokens); }
+        if invoice.status == InvoiceStatus::Funding && Self::below_min_ticket(env, invoice, token_amount) { return Err(ContractError::InvalidAmount); }
+
+        Ok(Self::split_payment(env, invoice, token_amount, at))
+    }
+
+    /// Whether `token_amount` is under the auction's minimum ticket without taking every token left
+    fn below_min_ticket(env: &Env, invoice: &Invoice, token_amount: i128) -> bool {
+        token_amount < storage::get_auction_reserve(env, &invoice.id).1 && token_amount < invoice.tokens_remaining
+    }
+
+    /// Splits the price of `token_amount` at `at` into the insurance premium, fees and supplier proceeds
+    fn split_payment(env: &Env, invoice: &Invoice, token_amount: i128, at: u64) -> InvestQuote {
+        let price = if invoice.auction_start > 0 { Self::price_at(invoice, at) } else { invoice.amount };
+        let payment_amount = (token_amount * price) / invoice.total_tokens;
+        let fees = Self::fee_schedule_for(env, invoice, at);
+        let premium_bps = risk::insurance_premium_bps(env, invoice, fees.insurance_cut_bps);
+        let insurance_amount = (payment_amount * premium_bps as i128) / 10000;
+        let program_fee = (payment_amount * storage::get_program_fees(env, invoice.program_id).investment_fee_bps as i128) / 10000;
+        let protocol_fee = (payment_amount * fees.protocol_fee_bps as i128) / 10000;
+        InvestQuote {
+            price, payment_amount, insurance_amount, program_fee, protocol_fee,
+            supplier_proceeds: payment_amount - insurance_amount - program_fee - protocol_fee,
+        }
+    }
+
+    /// Past due, the buyer may close out short of the accrued interest as long as face value and
+    /// the late penalty for the days past due are covered.
+    /// Returns (required payment, total paid including prepayments).
+    fn settlement_totals(env: &Env, invoice: &Invoice, payment_amount: i128, at: u64) -> Result<(i128, i128), ContractError> {
+        let required_payment = Self::settlement_amount_at(env, invoice, at);
+        let minimum_payment = if at > invoice.due_date {
+            let penalty = interest::interest(env, invoice, invoice.amount, invoice.due_date, at);
+            (invoice.amount + penalty).min(required_payment)
+        } else { required_payment };
+        let total_paid = payment_amount + storage::get_prepaid_total(env, &invoice.id);
+        if payment_amount < 0 || total_paid < minimum_payment { return Err(ContractError::InsufficientPayment); }
+        Ok((required_payment, total_paid))
+    }
+
+    /// Underwriters' cut of the interest in a single-payment settlement. Taken only while the program's
+    /// pool has underwriters and the buyer settles in the funding token.
+    fn underwriter_cut(env: &Env, invoice: &Invoice, total_paid: i128) -> i128 {
+        if invoice.settlement_token.is_some() || storage::get_underwriter_shares(env, invoice.program_id) == 0 { return 0; }
+        ((total_paid - invoice.amount).max(0) * storage::get_underwriter_terms(env).yield_bps as i128) / 10000
+    }
+
+    /// Token the buyer settles in and its oracle rate against the funding token (RATE_SCALE when the same)
+    fn settlement_leg(env: &Env, invoice: &Invoice) -> Result<(Address, i128), ContractError> {
+        let funding_token = storage::get_usdc_token(env);
+        match &invoice.settlement_token {
+            None => Ok((funding_token, RATE_SCALE)),
+            Some(token) => {
+                let oracle = storage::get_price_oracle(env).ok_or(ContractError::OracleNotConfigured)?;
+                let rate = OracleClient::new(env, &oracle).rate(&funding_token, token);
+                if rate <= 0 { return Err(ContractError::OracleNotConfigured); }
+                Ok((token.clone(), rate))
+            }
+        }
+    }
+
+    /// `total_amount` and `face_value` are in the settlement token
+    fn compute_payouts(env: &Env, invoice: &Invoice, settlement_token: &Address, total_amount: i128, face_value: i128, at: u64) -> Vec<RedemptionReceipt> {
+        let mut payouts = Vec::new(env);
         let total_tokens = invoice.total_tokens;
-        for holder_address in holders.iter() {
-            if let Some(holding) = storage::get_token_holding(env, invoice_id, &holder_address) {
+        for holder_address in storage::get_all_holders(env, &invoice.id).iter() {
+            if let Some(holding) = storage::get_token_holding(env, &invoice.id, &holder_address) {
                 let share = (holding.amount * total_amount) / total_tokens;
-                token_client.transfer(&env.current_contract_address(), &holder_address, &share);
-                remove_token_holding(env, invoice_id, &holder_address);
-                InvoiceEvents::settlement_distributed(env, invoice_id, &holder_address, share);
+                let principal = (holding.amount * face_value) / total_tokens;
+                payouts.push_back(RedemptionReceipt {
+                    invoice_id: invoice.id.clone(), holder: holder_address.clone(), tokens_redeemed: holding.amount,
+                    amount_received: share, principal, interest: share - principal, redeemed_at: at,
+                    payout_token: settlement_token.clone(), payout_amount: share,
+                });
+            }
+        }
+        payouts
+    }
+
+    fn distribute_settlement(env: &Env, invoice_id: &String, payment_token: &Address, total_amount: i128, face_value: i128) -> Result<(), ContractError> {
+        let invoice = get_invoice(env, invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let min_payout = storage::get_min_payout(env, payment_token);
+        for mut receipt in Self::compute_payouts(env, &invoice, payment_token, total_amount, face_value, env.ledger().timestamp()).iter() {
+            // Shares too small to be worth a transfer accrue to the holder's dust balance
+            let (payout_token, payout_amount) = if receipt.amount_received > 0 && receipt.amount_received < min_payout {
+                storage::add_dust(env, &receipt.holder, payment_token, receipt.amount_received);
+                (payment_token.clone(), 0)
+            } else {
+                Self::route_payout(env, payment_token, &receipt.holder, receipt.amount_received)
+            };
+            receipt.payout_token = payout_token;
+            receipt.payout_amount = payout_amount;
+            Self::close_position(env, &invoice, &receipt);
+            InvoiceEvents::settlement_distributed(env, invoice_id, &receipt.holder, receipt.amount_received);
+        }
+        Ok(())
+    }
+
+    /// Retires a redeemed holding: realized returns/losses for investors, then the receipt
+    fn close_position(env: &Env, invoice: &Invoice, receipt: &RedemptionReceipt) {
+        // The supplier's holding carries face value as its cost basis, so only investors are tracked
+        if let Some(holding) = storage::get_token_holding(env, &invoice.id, &receipt.holder).filter(|_| receipt.holder != invoice.supplier) {
+            if holding.acquired_price > receipt.amount_received {
+                storage::add_realized_loss(env, &receipt.holder, holding.acquired_price - receipt.amount_received);
+            }
+            storage::record_settled_position(env, &receipt.holder, holding.acquired_price, receipt.amount_received);
+        }
+        Self::settle_investments(env, &invoice.id, &receipt.holder, receipt.tokens_redeemed, receipt.amount_received, receipt.redeemed_at);
+        remove_token_holding(env, &invoice.id, &receipt.holder);
+        storage::set_receipt(env, receipt);
+    }
+
+    /// Credits `holder` with their token share of installments received since their checkpoint.
+    /// Must run before a holding changes so earlier installments stay with the previous balance.
+    fn sync_installments(env: &Env, invoice: &Invoice, holder: &Address) -> InstallmentAccount {
+        let mut account = storage::get_installment_account(env, &invoice.id, holder);
+        if account.checkpoint == invoice.repayment_received { return account; }
+        let tokens = storage::get_token_holding(env, &invoice.id, holder).map(|h| h.amount).unwrap_or(0);
+        account.accrued += tokens * (invoice.repayment_received - account.checkpoint) / invoice.total_tokens;
+        account.checkpoint = invoice.repayment_received;
+        storage::set_installment_account(env, &invoice.id, holder, &account);
+        account
+    }
+
+    /// Pays out a holder's unclaimed installments at final settlement and retires the holding
+    fn close_installment_position(env: &Env, invoice: &Invoice, holder: &Address, at: u64) {
+        let account = Self::sync_installments(env, invoice, holder);
+        let Some(holding) = storage::get_token_holding(env, &invoice.id, holder) else { return };
+        storage::release_from_escrow(env, account.accrued);
+        let (payout_token, payout_amount) = Self::route_payout(env, &storage::get_usdc_token(env), holder, account.accrued);
+        let amount_received = account.withdrawn + account.accrued;
+        let principal = (holding.amount * invoice.amount) / invoice.total_tokens;
+        Self::close_position(env, invoice, &RedemptionReceipt {
+            invoice_id: invoice.id.clone(), holder: holder.clone(), tokens_redeemed: holding.amount,
+            amount_received, principal, interest: amount_received - principal, redeemed_at: at,
+            payout_token, payout_amount,
+        });
+        storage::remove_installment_account(env, &invoice.id, holder);
+        InvoiceEvents::settlement_distributed(env, &invoice.id, holder, account.accrued);
+    }
+
+    /// Pays `amount` of the settlement token to `holder`, swapping into their preferred token when a
+    /// router and oracle are configured. Falls back to the settlement token if the swap is unavailable or fails.
+    fn route_payout(env: &Env, settlement_token: &Address, holder: &Address, amount: i128) -> (Address, i128) {
+        let contract = env.current_contract_address();
+        let settlement_client = TokenClient::new(env, settlement_token);
+        if let (Some(preferred), Some(router), Some(oracle)) =
+            (storage::get_payout_preference(env, holder), storage::get_swap_router(env), storage::get_price_oracle(env)) {
+            if preferred != *settlement_token && amount > 0 && storage::is_settlement_token_allowed(env, &preferred) {
+                let rate = OracleClient::new(env, &oracle).rate(settlement_token, &preferred);
+                let min_out = (amount * rate / RATE_SCALE) * (10000 - MAX_SWAP_SLIPPAGE_BPS) / 10000;
+                settlement_client.approve(&contract, &router, &amount, &env.ledger().sequence());
+                if let Ok(Ok(delivered)) = SwapRouterClient::new(env, &router)
+                    .try_swap(&contract, settlement_token, &preferred, &amount, &min_out, holder) {
+                    return (preferred, delivered);
+                }
+                settlement_client.approve(&contract, &router, &0, &env.ledger().sequence());
+            }
+        }
+        settlement_client.transfer(&contract, holder, &amount);
+        (settlement_token.clone(), amount)
+    }
+
+    fn rule_on_dispute(env: &Env, invoice_id: &String, admin: &Address, is_valid: bool, clawback_after: u64, valid_bps: u32) -> Result<(), ContractError> {
+        admin.require_auth();
+        let mut invoice = get_invoice(env, invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if storage::get_program_admin(env, invoice.program_id).as_ref() != Some(admin) && !storage::has_role(env, &Role::DisputeResolver, admin) {
+            return Err(ContractError::Unauthorized);
+        }
+        if invoice.status != InvoiceStatus::Disputed { return Err(ContractError::InvalidStatus); }
+        let mut dispute = storage::get_dispute(env, invoice_id).ok_or(ContractError::DisputeNotFound)?;
+        if dispute.resolution != DisputeResolution::Pending { return Err(ContractError::InvalidStatus); }
+        // Panel disputes fall back to the admin only if the vote closed without a quorum
+        if dispute.vote_deadline > 0
+            && (env.ledger().timestamp() <= dispute.vote_deadline || Self::panel_votes(env, invoice_id).len() >= dispute.quorum) {
+            return Err(ContractError::InvalidStatus);
+        }
+
+        if is_valid {
+            Self::uphold_dispute(env, invoice_id, &mut dispute, clawback_after, valid_bps);
+            return Ok(());
+        }
+        Self::apply_dispute_outcome(env, &mut invoice, &mut dispute, false, admin)
+    }
+
+    /// Marks a dispute upheld; the clawback waits for the supplier's appeal window
+    fn uphold_dispute(env: &Env, invoice_id: &String, dispute: &mut Dispute, clawback_after: u64, valid_bps: u32) {
+        dispute.appeal_deadline = env.ledger().timestamp() + APPEAL_WINDOW_SECONDS;
+        dispute.clawback_after = clawback_after;
+        dispute.valid_bps = valid_bps;
+        dispute.resolution = DisputeResolution::Upheld;
+        storage::set_dispute(env, invoice_id, dispute);
+    }
+
+    /// Final dispute outcome: clawback and cancel the auction, or restore the invoice and resume it
+    fn apply_dispute_outcome(env: &Env, invoice: &mut Invoice, dispute: &mut Dispute, upheld: bool, actor: &Address) -> Result<(), ContractError> {
+        let now = env.ledger().timestamp();
+        if upheld && dispute.valid_bps < 10000 {
+            // Only part of the invoice was invalid: shrink it and let the rest proceed
+            Self::execute_proportional_clawback(env, invoice, dispute.valid_bps);
+            dispute.resolution = DisputeResolution::Valid;
+            Self::unfreeze_disputed(invoice, dispute, now)?;
+        } else if upheld {
+            Self::execute_clawback(env, invoice, dispute.clawback_after)?;
+            dispute.resolution = DisputeResolution::Valid;
+            // Cancel a frozen auction outright
+            if dispute.prior_status == InvoiceStatus::Funding { invoice.auction_end = now; }
+        } else {
+            dispute.resolution = DisputeResolution::Invalid;
+            Self::unfreeze_disputed(invoice, dispute, now)?;
+        }
+        dispute.resolved_at = now;
+        storage::set_dispute(env, &invoice.id, dispute);
+        let bond = storage::get_dispute_bond(env, &invoice.id);
+        if bond > 0 {
+            // A rejected dispute's bond compensates the supplier for the freeze
+            let recipient = if upheld { &invoice.buyer } else { &invoice.supplier };
+            storage::set_dispute_bond(env, &invoice.id, 0);
+            storage::release_from_escrow(env, bond);
+            TokenClient::new(env, &storage::get_usdc_token(env)).transfer(&env.current_contract_address(), recipient, &bond);
+            InvoiceEvents::dispute_bond_settled(env, &invoice.id, bond, upheld);
+        }
+        set_invoice(env, &invoice.id, invoice);
+        risk::record_buyer_dispute(env, invoice, upheld);
+        Self::record_transition(env, invoice, actor, 0);
+        InvoiceEvents::dispute_resolved(env, &invoice.id, upheld);
+        Ok(())
+    }
+
+    /// Buyers with too many recently rejected disputes need the dispute pre-cleared, or else post
+    /// the configured bond, which is held until the ruling
+    fn check_dispute_throttle(env: &Env, invoice_id: &String, buyer: &Address) -> Result<(), ContractError> {
+        let limits = storage::get_dispute_limits(env);
+        if limits.rejected_limit == 0 || risk::recent_dispute_rejections(env, buyer) < limits.rejected_limit { return Ok(()); }
+        if storage::is_dispute_cleared(env, invoice_id) {
+            storage::set_dispute_cleared(env, invoice_id, false);
+            return Ok(());
+        }
+        if limits.bond == 0 { return Err(ContractError::Unauthorized); }
+        TokenClient::new(env, &storage::get_usdc_token(env)).transfer(buyer, &env.current_contract_address(), &limits.bond);
+        storage::add_to_escrow(env, limits.bond);
+        storage::set_dispute_bond(env, invoice_id, limits.bond);
+        Ok(())
+    }
+
+    /// Restores the pre-dispute status and resumes a frozen auction with its original duration
+    fn unfreeze_disputed(invoice: &mut Invoice, dispute: &Dispute, now: u64) -> Result<(), ContractError> {
+        state_machine::transition(invoice, Event::RestoreFromDispute(dispute.prior_status.clone()), now)?;
+        if dispute.prior_status == InvoiceStatus::Funding {
+            let duration = invoice.auction_end - invoice.auction_start;
+            invoice.auction_start = now - dispute.auction_elapsed;
+            invoice.auction_end = invoice.auction_start + duration;
+        }
+        Ok(())
+    }
+
+    /// Moves the invoice to Overdue or Defaulted if its due date calls for it. Returns whether
+    /// the status changed.
+    fn apply_due_date_status(env: &Env, invoice: &mut Invoice) -> Result<bool, ContractError> {
+        match Self::pending_action(env, invoice, env.ledger().timestamp()) {
+            Some(KeeperAction::MarkDefaulted) => Self::mark_defaulted(env, invoice)?,
+            Some(KeeperAction::MarkOverdue) => {
+                state_machine::transition(invoice, Event::MarkOverdue, env.ledger().timestamp())?;
+                set_invoice(env, &invoice.id, invoice);
+                Self::record_transition(env, invoice, &env.current_contract_address(), invoice.amount);
             }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn poke_invoice(env: &Env, invoice: &mut Invoice, keeper: &Address) -> Result<i128, ContractError> {
+        if !Self::apply_due_date_status(env, invoice)? { return Ok(0); }
+        let bounty = Self::pay_bounty(env, invoice, keeper, storage::get_keeper_bounty(env));
+        if bounty > 0 { InvoiceEvents::keeper_rewarded(env, &invoice.id, keeper, bounty); }
+        Ok(bounty)
+    }
+
+    /// Pays up to `bounty` from the protocol treasury. Returns the amount paid.
+    fn pay_treasury_bounty(env: &Env, to: &Address, bounty: i128) -> i128 {
+        let bounty = bounty.min(storage::get_program_treasury(env, 0));
+        if bounty <= 0 { return 0; }
+        storage::set_program_treasury(env, 0, storage::get_program_treasury(env, 0) - bounty);
+        TokenClient::new(env, &storage::get_usdc_token(env)).transfer(&env.current_contract_address(), to, &bounty);
+        bounty
+    }
+
+    /// Whether a defaulted or written-off invoice still has a holder who can claim insurance,
+    /// or whose insurance payout is still being repaid from recoveries
+    fn has_open_claims(env: &Env, invoice: &Invoice) -> bool {
+        if invoice.status != InvoiceStatus::Defaulted && invoice.status != InvoiceStatus::WrittenOff { return false; }
+        storage::get_all_holders(env, &invoice.id).iter().any(|holder| holder != invoice.supplier
+            && (!storage::is_insurance_claimed(env, &invoice.id, &holder)
+                || (invoice.status == InvoiceStatus::Defaulted && storage::get_insurance_owed(env, &invoice.id, &holder) > 0)))
+    }
+
+    /// Pays up to `bounty` from the program's free insurance, then the protocol treasury. Never
+    /// touches the invoice's own earmark, which its holders claim against. Returns the amount paid.
+    fn pay_bounty(env: &Env, invoice: &Invoice, to: &Address, bounty: i128) -> i128 {
+        let pooled = bounty.min(storage::get_free_insurance(env, invoice.program_id)).max(0);
+        if pooled > 0 {
+            storage::withdraw_from_insurance_pool(env, invoice.program_id, pooled);
+            TokenClient::new(env, &storage::get_usdc_token(env)).transfer(&env.current_contract_address(), to, &pooled);
+        }
+        pooled + Self::pay_treasury_bounty(env, to, bounty - pooled)
+    }
+
+    /// Takes up to `amount` of insurance for an invoice, from its own sub-pool first and then
+    /// the program's shared pool. Returns the amount taken; the caller pays it out.
+    fn draw_insurance(env: &Env, invoice: &Invoice, amount: i128) -> i128 {
+        if amount <= 0 { return 0; }
+        let own = amount.min(storage::get_invoice_insurance(env, &invoice.id));
+        if own > 0 { storage::withdraw_invoice_insurance(env, invoice, own); }
+        let shared = (amount - own).min(storage::get_free_insurance(env, invoice.program_id));
+        if shared > 0 { storage::withdraw_from_insurance_pool(env, invoice.program_id, shared); }
+        own + shared.max(0)
+    }
+
+    fn mark_defaulted(env: &Env, invoice: &mut Invoice) -> Result<(), ContractError> {
+        state_machine::transition(invoice, Event::MarkDefaulted, env.ledger().timestamp())?;
+        set_invoice(env, &invoice.id, invoice);
+        risk::record_buyer_outcome(env, invoice);
+        Self::record_transition(env, invoice, &env.current_contract_address(), invoice.amount);
+        InvoiceEvents::invoice_defaulted(env, &invoice.id);
+        Ok(())
+    }
+
+    /// Face value of `token_amount` discounted to maturity at the program's base rate
+    fn fair_value(env: &Env, invoice: &Invoice, token_amount: i128) -> i128 {
+        let face = (token_amount * invoice.amount) / invoice.total_tokens;
+        let now = env.ledger().timestamp();
+        if now >= invoice.due_date { return face; }
+        let days = ((invoice.due_date - now) / 86400) as i128;
+        let rate = interest::rate_config(env, invoice).base_interest_rate as i128;
+        (face * 10000 * 365) / (10000 * 365 + rate * days)
+    }
+
+    fn within_price_band(env: &Env, payment: i128, fair: i128) -> bool {
+        let band = storage::get_price_band(env);
+        band == 0 || (payment - fair).abs() * 10000 <= fair * band as i128
+    }
+
+    fn check_price_band(env: &Env, invoice: &Invoice, seller: &Address, token_amount: i128, payment: i128) -> Result<(), ContractError> {
+        if storage::is_distressed_sale(env, &invoice.id, seller) { return Ok(()); }
+        if !Self::within_price_band(env, payment, Self::fair_value(env, invoice, token_amount)) { return Err(ContractError::OutsidePriceBand); }
+        Ok(())
+    }
+
+    fn load_investments(env: &Env, investment_ids: Vec<String>) -> Vec<Investment> {
+        let mut investments = Vec::new(env);
+        for investment_id in investment_ids.iter() {
+            if let Some(investment) = storage::get_investment(env, &investment_id) { investments.push_back(investment); }
+        }
+        investments
+    }
+
+    fn load_invoices(env: &Env, invoice_ids: Vec<String>) -> Vec<Invoice> {
+        let mut invoices = Vec::new(env);
+        for invoice_id in invoice_ids.iter() {
+            if let Some(invoice) = get_invoice(env, &invoice_id) { invoices.push_back(invoice); }
+        }
+        invoices
+    }
+
+    /// Positions `[start, start + limit)` of a range of `count`, with `limit` capped at MAX_PAGE_SIZE
+    fn export_range(start: u32, limit: u32, count: u32) -> core::ops::Range<u32> {
+        let start = start.min(count);
+        start..start.saturating_add(limit.min(MAX_PAGE_SIZE)).min(count)
+    }
+
+    /// Entries `[start, start + limit)` of `items`, with `limit` capped at MAX_PAGE_SIZE
+    fn page<T: IntoVal<Env, soroban_sdk::Val> + TryFromVal<Env, soroban_sdk::Val>>(items: Vec<T>, start: u32, limit: u32) -> Vec<T> {
+        let start = start.min(items.len());
+        let end = start.saturating_add(limit.min(MAX_PAGE_SIZE)).min(items.len());
+        items.slice(start..end)
+    }
+
+    /// Taker and maker fees on a fill of `payment`, and the protocol's part of the taker fee.
+    /// A negative maker fee is a rebate, never more than the program's taker fee it is paid from.
+    fn trading_fees(env: &Env, invoice: &Invoice, payment: i128) -> (i128, i128, i128) {
+        let fees = storage::get_program_fees(env, invoice.program_id);
+        let program_taker_fee = (payment * fees.trading_fee_bps as i128) / 10000;
+        let protocol_fee = Self::protocol_trading_fee(env, invoice, payment);
+        let maker_fee = ((payment * fees.maker_fee_bps as i128) / 10000).max(-program_taker_fee);
+        (program_taker_fee + protocol_fee, maker_fee, protocol_fee)
+    }
+
+    /// Fee schedule's protocol fee on a secondary fill of `payment`
+    fn protocol_trading_fee(env: &Env, invoice: &Invoice, payment: i128) -> i128 {
+        (payment * Self::fee_schedule_for(env, invoice, env.ledger().timestamp()).trading_fee_bps as i128) / 10000
+    }
+
+    /// Maker fee escrowed for a bid's unfilled remainder (rebates are not escrowed)
+    fn bid_fee_escrow(order: &BuyOrder) -> i128 {
+        if order.maker_fee_bps <= 0 { return 0; }
+        (order.tokens_remaining * order.price_per_token * order.maker_fee_bps as i128) / 10000
+    }
+
+    /// Royalty on a secondary sale of `payment`, credited to the invoice's insurance sub-pool. The
+    /// caller deducts it and the seller's `seller_fee` from the seller's proceeds, which must cover both.
+    fn take_royalty(env: &Env, invoice: &Invoice, seller: &Address, payment: i128, seller_fee: i128) -> Result<i128, ContractError> {
+        let bps = storage::get_royalty_override(env, &invoice.id)
+            .unwrap_or_else(|| Self::fee_schedule_for(env, invoice, env.ledger().timestamp()).royalty_bps);
+        let royalty = (payment * bps as i128) / 10000;
+        if seller_fee + royalty.max(0) > payment { return Err(ContractError::InvalidFeeSchedule); }
+        if royalty <= 0 { return Ok(0); }
+        storage::add_invoice_insurance(env, invoice, royalty);
+        InvoiceEvents::royalty_paid(env, &invoice.id, seller, royalty);
+        Ok(royalty)
+    }
+
+    /// Credits the fees collected on a fill: the protocol's part to the protocol treasury, the
+    /// rest split as a program fee
+    fn credit_trading_fees(env: &Env, program_id: u32, collected: i128, protocol_fee: i128) {
+        Self::credit_protocol_fee(env, protocol_fee);
+        Self::credit_program_fee(env, program_id, collected - protocol_fee);
+    }
+
+    fn credit_protocol_fee(env: &Env, fee: i128) {
+        if fee <= 0 { return; }
+        storage::set_program_treasury(env, 0, storage::get_program_treasury(env, 0) + fee);
+    }
+
+    /// Splits a program fee between the program's treasury and the protocol treasury
+    fn credit_program_fee(env: &Env, program_id: u32, fee: i128) {
+        if fee <= 0 { return; }
+        let anchor_share = (fee * storage::get_program_fees(env, program_id).anchor_share_bps as i128) / 10000;
+        storage::set_program_treasury(env, program_id, storage::get_program_treasury(env, program_id) + anchor_share);
+        storage::set_program_treasury(env, 0, storage::get_program_treasury(env, 0) + fee - anchor_share);
+    }
+
+    /// Tokens can change hands while the invoice is live and undisputed
+    fn is_tradeable(invoice: &Invoice) -> bool {
+        matches!(invoice.status, InvoiceStatus::Verified | InvoiceStatus::Funding | InvoiceStatus::Funded)
+    }
+
+    /// Cancels a bid and refunds its remaining escrow to the buyer
+    fn close_bid(env: &Env, order: &mut BuyOrder) -> i128 {
+        let refund = order.tokens_remaining * order.price_per_token + Self::bid_fee_escrow(order);
+        storage::release_from_escrow(env, refund);
+        TokenClient::new(env, &storage::get_usdc_token(env)).transfer(&env.current_contract_address(), &order.buyer, &refund);
+        order.status = OrderStatus::Cancelled;
+        storage::set_buy_order(env, &order.id, order);
+        InvoiceEvents::bid_cancelled(env, &order.id, &order.invoice_id, refund);
+        refund
+    }
+
+    /// Investing, trading, settlement and payout claims stop while the contract is paused
+    fn require_not_paused(env: &Env) -> Result<(), ContractError> {
+        if storage::is_paused(env) { return Err(ContractError::ContractPaused); }
+        Ok(())
+    }
+
+    /// Contract admin, or an address holding `role`
+    fn require_role(env: &Env, caller: &Address, role: Role) -> Result<(), ContractError> {
+        caller.require_auth();
+        if get_admin(env) != *caller && !storage::has_role(env, &role, caller) { return Err(ContractError::Unauthorized); }
+        Ok(())
+    }
+
+    fn require_program_admin(env: &Env, program_id: u32, admin: &Address) -> Result<(), ContractError> {
+        admin.require_auth();
+        let program_admin = storage::get_program_admin(env, program_id).ok_or(ContractError::ProgramNotFound)?;
+        if program_admin != *admin { return Err(ContractError::Unauthorized); }
+        Ok(())
+    }
+
+    /// Investors must be on the KYC list of the invoice's program
+    fn require_program_kyc(env: &Env, invoice_id: &String, investor: &Address) -> Result<(), ContractError> {
+        let invoice = get_invoice(env, invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        if !storage::get_program_kyc_status(env, invoice.program_id, investor) { return Err(ContractError::KYCRequired); }
+        Ok(())
+    }
+
+    /// Open invoices accept anyone; private placements need a valid Merkle proof
+    fn require_allowlisted(env: &Env, invoice_id: &String, investor: &Address, proof: Option<Vec<BytesN<32>>>) -> Result<(), ContractError> {
+        let invoice = get_invoice(env, invoice_id).ok_or(ContractError::InvoiceNotFound)?;
+        let Some(root) = invoice.allowlist_root else { return Ok(()) };
+        let proof = proof.ok_or(ContractError::NotAllowlisted)?;
+        let mut node: BytesN<32> = env.crypto().sha256(&investor.clone().to_xdr(env)).into();
+        for sibling in proof.iter() {
+            let (left, right) = if node < sibling { (node, sibling) } else { (sibling, node) };
+            let mut pair = Bytes::from(left);
+            pair.append(&Bytes::from(right));
+            node = env.crypto().sha256(&pair).into();
         }
+        if node != root { return Err(ContractError::NotAllowlisted); }
         Ok(())
     }
 
-    fn execute_clawback(env: &Env, invoice_id: &String) -> Result<(), ContractError> {
+    /// A dispute's votes cast by arbitrators still on the panel, one per arbitrator
+    fn panel_votes(env: &Env, invoice_id: &String) -> Vec<ArbitrationVote> {
+        let arbitrators = storage::get_arbitrators(env);
+        let mut votes = Vec::new(env);
+        for vote in storage::get_votes(env, invoice_id, 0, storage::vote_count(env, invoice_id)).iter() {
+            if arbitrators.contains(&vote.arbitrator) { votes.push_back(vote); }
+        }
+        votes
+    }
+
+    fn grace_period_seconds(env: &Env, invoice: &Invoice) -> u64 {
+        (interest::rate_config(env, invoice).grace_period_days as u64) * 86400
+    }
+
+    /// Transition due for an invoice at `now`, shared by check_status and get_actionable
+    fn pending_action(env: &Env, invoice: &Invoice, now: u64) -> Option<KeeperAction> {
+        let tracks_due_date = matches!(invoice.status,
+            InvoiceStatus::Verified | InvoiceStatus::Funded | InvoiceStatus::Funding | InvoiceStatus::Overdue);
+        if tracks_due_date {
+            if now > invoice.due_date + Self::grace_period_seconds(env, invoice) { return Some(KeeperAction::MarkDefaulted); }
+            if now > invoice.due_date && invoice.status != InvoiceStatus::Overdue { return Some(KeeperAction::MarkOverdue); }
+        }
+        if invoice.status == InvoiceStatus::Funding && invoice.auction_end > 0 && now >= invoice.auction_end && invoice.tokens_remaining > 0 {
+            return Some(KeeperAction::AuctionExpired);
+        }
+        None
+    }
+
+    /// Claws back tokens acquired after `acquired_after` (0 = all), plus the supplier's whole
+    /// holding. Each holding's lots date its acquisitions, so a holder who topped up after the
+    /// cutoff loses only the later tokens, refunded at the holding's average cost.
+    fn execute_clawback(env: &Env, invoice: &mut Invoice, acquired_after: u64) -> Result<(), ContractError> {
+        let invoice_id = &invoice.id.clone();
         let holders = storage::get_all_holders(env, invoice_id);
         for holder_address in holders.iter() {
-            if let Some(holding) = storage::get_token_holding(env, invoice_id, &holder_address) {
-                remove_token_holding(env, invoice_id, &holder_address);
-                InvoiceEvents::clawback_executed(env, invoice_id, &holder_address, holding.amount);
+            if let Some(mut holding) = storage::get_token_holding(env, invoice_id, &holder_address) {
+                let mut kept = Vec::new(env);
+                if holder_address != invoice.supplier {
+                    for lot in storage::get_token_lots(env, &holding).iter() {
+                        if lot.acquired_at <= acquired_after { kept.push_back(lot); }
+                    }
+                }
+                let clawed = holding.amount - kept.iter().map(|lot| lot.amount).sum::<i128>();
+                if clawed == 0 { continue; }
+                Self::sync_installments(env, invoice, &holder_address);
+                storage::release_locks(env, invoice_id, &holder_address);
+                let paid = (holding.acquired_price * clawed) / holding.amount;
+                if kept.is_empty() {
+                    remove_token_holding(env, invoice_id, &holder_address);
+                } else {
+                    holding.amount -= clawed;
+                    holding.acquired_price -= paid;
+                    set_token_holding(env, invoice_id, &holder_address, &holding);
+                    storage::set_token_lots(env, invoice_id, &holder_address, &kept);
+                }
+                InvoiceEvents::clawback_executed(env, invoice_id, &holder_address, clawed);
+                // Payments still escrowed below the funding threshold never reached the supplier
+                let (escrow_tokens, escrowed) = storage::get_funding_escrow(env, invoice_id, &holder_address);
+                let escrow_clawed = clawed.min(escrow_tokens);
+                let escrow_refund = if escrow_tokens > 0 { (escrowed * escrow_clawed) / escrow_tokens } else { 0 };
+                if escrow_refund > 0 {
+                    storage::remove_funding_escrow(env, invoice_id, &holder_address);
+                    if escrow_clawed < escrow_tokens {
+                        storage::add_funding_escrow(env, invoice_id, &holder_address, escrow_tokens - escrow_clawed, escrowed - escrow_refund);
+                    }
+                    invoice.escrowed_payments -= escrow_refund;
+                    TokenClient::new(env, &storage::get_usdc_token(env)).transfer(&env.current_contract_address(), &holder_address, &escrow_refund);
+                    InvoiceEvents::holder_refunded(env, invoice_id, &holder_address, escrow_refund);
+                }
+                if holder_address != invoice.supplier && !storage::is_insurance_claimed(env, invoice_id, &holder_address) {
+                    Self::refund_clawed_back(env, invoice, &holder_address, paid - escrow_refund);
+                }
+            }
+        }
+        // Orders don't escrow tokens, so clawed-back holdings leave them unfillable; close them
+        for order_id in storage::get_orders_for_invoice(env, invoice_id).iter() {
+            if let Some(mut order) = storage::get_sell_order(env, &order_id) {
+                if order.status == OrderStatus::Open || order.status == OrderStatus::PartiallyFilled {
+                    order.status = OrderStatus::Cancelled;
+                    storage::set_sell_order(env, &order_id, &order);
+                    InvoiceEvents::order_cancelled(env, &order_id, invoice_id);
+                }
+            }
+        }
+        for order_id in storage::get_buy_orders_for_invoice(env, invoice_id).iter() {
+            if let Some(mut order) = storage::get_buy_order(env, &order_id) {
+                if order.status == OrderStatus::Open || order.status == OrderStatus::PartiallyFilled {
+                    Self::close_bid(env, &mut order);
+                }
             }
         }
         Ok(())
     }
 
+    /// Claws back `valid_bps` of every holding. Investors are refunded the same share of what they
+    /// paid (escrowed payments first, then via refund_clawed_back); the invoice's face value and
+    /// token supply shrink with the clawed-back tokens.
+    fn execute_proportional_clawback(env: &Env, invoice: &mut Invoice, valid_bps: u32) {
+        let invoice_id = &invoice.id.clone();
+        let bps = valid_bps as i128;
+        let (mut clawed_total, mut clawed_sold) = (0i128, 0i128);
+        for holder_address in storage::get_all_holders(env, invoice_id).iter() {
+            let Some(mut holding) = storage::get_token_holding(env, invoice_id, &holder_address) else { continue };
+            let clawed = (holding.amount * bps) / 10000;
+            if clawed == 0 { continue; }
+            storage::release_locks(env, invoice_id, &holder_address);
+            let paid = (holding.acquired_price * bps) / 10000;
+            holding.amount -= clawed;
+            holding.acquired_price -= paid;
+            if holding.amount == 0 { remove_token_holding(env, invoice_id, &holder_address); }
+            else { set_token_holding(env, invoice_id, &holder_address, &holding); }
+            InvoiceEvents::clawback_executed(env, invoice_id, &holder_address, clawed);
+            clawed_total += clawed;
+            if holder_address == invoice.supplier { continue; }
+            clawed_sold += clawed;
+
+            let (escrow_tokens, escrowed) = storage::get_funding_escrow(env, invoice_id, &holder_address);
+            let escrow_refund = (escrowed * bps) / 10000;
+            if escrow_refund > 0 {
+                storage::remove_funding_escrow(env, invoice_id, &holder_address);
+                storage::add_funding_escrow(env, invoice_id, &holder_address, escrow_tokens - (escrow_tokens * bps) / 10000, escrowed - escrow_refund);
+                invoice.escrowed_payments -= escrow_refund;
+                TokenClient::new(env, &storage::get_usdc_token(env)).transfer(&env.current_contract_address(), &holder_address, &escrow_refund);
+                InvoiceEvents::holder_refunded(env, invoice_id, &holder_address, escrow_refund);
+            }
+            if !storage::is_insurance_claimed(env, invoice_id, &holder_address) {
+                Self::refund_clawed_back(env, invoice, &holder_address, paid - escrow_refund);
+            }
+        }
+        invoice.amount -= (invoice.amount * clawed_total) / invoice.total_tokens;
+        invoice.min_funding_tokens -= (invoice.min_funding_tokens * bps) / 10000;
+        invoice.total_tokens -= clawed_total;
+        invoice.tokens_sold -= clawed_sold;
+        // The supplier's reserve shrinks with its holding and stays off sale
+        let (reserved_tokens, min_ticket) = storage::get_auction_reserve(env, invoice_id);
+        let reserved_tokens = reserved_tokens - (reserved_tokens * bps) / 10000;
+        storage::set_auction_reserve(env, invoice_id, reserved_tokens, min_ticket);
+        invoice.tokens_remaining = invoice.total_tokens - invoice.tokens_sold - reserved_tokens;
+    }
+
+    /// Refunds what an investor paid for a clawed-back holding. The supplier already has the
+    /// money, so the invoice's insurance fronts it and any shortfall becomes supplier debt.
+    fn refund_clawed_back(env: &Env, invoice: &Invoice, investor: &Address, paid: i128) {
+        if paid <= 0 { return; }
+        let covered = Self::draw_insurance(env, invoice, paid);
+        if covered > 0 {
+            TokenClient::new(env, &storage::get_usdc_token(env)).transfer(&env.current_contract_address(), investor, &covered);
+            InvoiceEvents::holder_refunded(env, &invoice.id, investor, covered);
+        }
+        let shortfall = paid - covered;
+        if shortfall > 0 {
+            storage::set_refund_owed(env, &invoice.id, investor, storage::get_refund_owed(env, &invoice.id, investor) + shortfall);
+            let mut creditors = storage::get_refund_creditors(env, &invoice.id);
+            if !creditors.contains(investor) { creditors.push_back(investor.clone()); }
+            storage::set_refund_creditors(env, &invoice.id, &creditors);
+            storage::set_supplier_debt(env, &invoice.supplier, storage::get_supplier_debt(env, &invoice.supplier) + shortfall);
+            InvoiceEvents::refund_deferred(env, &invoice.id, investor, &invoice.supplier, shortfall);
+        }
+    }
+
+    /// Tokens of a holding that can change hands: not offered in a pending transfer, and not
+    /// bought with a payment still escrowed below the funding threshold (those stay put until
+    /// the threshold clears or the payment is refunded)
+    fn free_tokens(env: &Env, holding: &TokenHolding) -> i128 {
+        let (escrowed_tokens, _) = storage::get_funding_escrow(env, &holding.invoice_id, &holding.holder);
+        holding.amount - storage::get_locked_tokens(env, &holding.invoice_id, &holding.holder) - escrowed_tokens
+    }
+
     fn internal_transfer_tokens(env: &Env, invoice_id: &String, from: &Address, to: &Address, amount: i128) -> Result<(), ContractError> {
+        if let Some(invoice) = get_invoice(env, invoice_id).filter(|inv| inv.repayment_received > 0 && !inv.status.is_settled()) {
+            Self::sync_installments(env, &invoice, from);
+            Self::sync_installments(env, &invoice, to);
+        }
         let mut from_holding = storage::get_token_holding(env, invoice_id, from).ok_or(ContractError::InsufficientTokens)?;
-        if from_holding.amount < amount { return Err(ContractError::InsufficientTokens); }
+        if Self::free_tokens(env, &from_holding) < amount { return Err(ContractError::InsufficientTokens); }
+        // Cost basis moves with the tokens, so splitting a holding can't multiply it
+        let basis = (from_holding.acquired_price * amount) / from_holding.amount;
         from_holding.amount -= amount;
+        from_holding.acquired_price -= basis;
         if from_holding.amount == 0 { remove_token_holding(env, invoice_id, from); } 
         else { set_token_holding(env, invoice_id, from, &from_holding); }
 
         let to_holding = match storage::get_token_holding(env, invoice_id, to) {
-            Some(mut existing) => { existing.amount += amount; existing }
-            None => TokenHolding { invoice_id: invoice_id.clone(), holder: to.clone(), amount, acquired_at: env.ledger().timestamp(), acquired_price: from_holding.acquired_price }
+            Some(mut existing) => { existing.amount += amount; existing.acquired_price += basis; existing }
+            None => TokenHolding { invoice_id: invoice_id.clone(), holder: to.clone(), amount, acquired_at: env.ledger().timestamp(), acquired_price: basis }
         };
         set_token_holding(env, invoice_id, to, &to_holding);
+        storage::add_investor_invoice(env, to, invoice_id);
         Ok(())
     }
 }
@@ -0,0 +1,148 @@
+//! Storage layout migrations run by `migrate`
+//! Entries written before versioning decode through the frozen `*V0` layouts below; later ones
+//! are read as field maps, and each step adds the fields its version introduced with the value
+//! that reproduces how the contract behaved before.
+
+use soroban_sdk::{contracttype, Address, Env, IntoVal, Map, String, Symbol, TryFromVal, Val};
+
+use crate::storage::{self, DataKey, ProgramDataKey};
+use crate::types::{AuctionType, DayCount, FeeSchedule, GraceInterest, Invoice, InvoiceStatus, Program, RateConfig};
+
+/// Invoice layout of storage version 0. Frozen: never edit.
+#[derive(Clone)]
+#[contracttype]
+pub struct InvoiceV0 {
+    pub id: String,
+    pub supplier: Address,
+    pub buyer: Address,
+    pub amount: i128,
+    pub currency: String,
+    pub created_at: u64,
+    pub due_date: u64,
+    pub verified_at: u64,
+    pub settled_at: u64,
+    pub status: InvoiceStatus,
+    pub token_symbol: String,
+    pub total_tokens: i128,
+    pub tokens_sold: i128,
+    pub tokens_remaining: i128,
+    pub description: String,
+    pub purchase_order: String,
+    pub document_hash: String,
+    pub repayment_received: i128,
+    pub buyer_signed_at: u64,
+    pub auction_start: u64,
+    pub auction_end: u64,
+    pub start_price: i128,
+    pub min_price: i128,
+    pub price_drop_rate: u32,
+}
+
+/// Rate config layout of storage version 0. Frozen: never edit.
+#[derive(Clone)]
+#[contracttype]
+pub struct RateConfigV0 {
+    pub base_interest_rate: u32,
+    pub penalty_rate: u32,
+    pub grace_period_days: u32,
+    pub default_auction_duration: u64,
+    pub default_price_drop_rate: u32,
+    pub default_max_discount: u32,
+    pub insurance_cut_bps: u32,
+}
+
+fn field(env: &Env, name: &str) -> Symbol {
+    Symbol::new(env, name)
+}
+
+fn fields_of<T: IntoVal<Env, Val>>(env: &Env, value: &T) -> Map<Symbol, Val> {
+    Map::try_from_val(env, &value.into_val(env)).unwrap()
+}
+
+fn from_fields<T: TryFromVal<Env, Val>>(env: &Env, fields: Map<Symbol, Val>) -> T {
+    let value: Val = fields.into_val(env);
+    T::try_from_val(env, &value).unwrap_or_else(|_| panic!("layout mismatch"))
+}
+
+/// Brings a rate config's fields from the `from` layout to the current one
+fn upgrade_rate_config(env: &Env, mut fields: Map<Symbol, Val>, from: u32) -> RateConfig {
+    // 0 -> 1: the insurance cut moved to the fee schedule
+    if from < 1 { fields.remove(field(env, "insurance_cut_bps")); }
+    // 4 -> 5: grace-period interest treatment
+    if from < 5 { fields.set(field(env, "grace_interest"), GraceInterest::Penalty.into_val(env)); }
+    // 5 -> 6: day-count convention
+    if from < 6 { fields.set(field(env, "day_count"), DayCount::Act365.into_val(env)); }
+    // 7 -> 8: early-settlement discount curve
+    if from < 8 {
+        fields.set(field(env, "early_discount_bps_per_day"), 0u32.into_val(env));
+        fields.set(field(env, "max_early_discount_bps"), 0u32.into_val(env));
+    }
+    // 9 -> 10: per-investor concentration cap
+    if from < 10 { fields.set(field(env, "max_investor_bps"), 0u32.into_val(env)); }
+    from_fields(env, fields)
+}
+
+/// Rewrites the global and program rate configs in the current layout. Runs before any
+/// invoice is upgraded, since unpinned invoices take their program's config.
+pub fn upgrade_rate_configs(env: &Env, from: u32) {
+    if from == 0 {
+        if let Some(legacy) = env.storage().instance().get::<_, RateConfigV0>(&DataKey::RateConfig) {
+            if storage::get_fee_schedule(env, 1).is_none() {
+                storage::add_fee_schedule(env, &FeeSchedule {
+                    version: 1, effective_from: 0, insurance_cut_bps: legacy.insurance_cut_bps,
+                    protocol_fee_bps: 0, trading_fee_bps: 0, royalty_bps: 0, withholding_bps: 0,
+                });
+            }
+            storage::set_rate_config(env, &upgrade_rate_config(env, fields_of(env, &legacy), from));
+        }
+        return;
+    }
+    if let Some(fields) = env.storage().instance().get::<_, Map<Symbol, Val>>(&DataKey::RateConfig) {
+        storage::set_rate_config(env, &upgrade_rate_config(env, fields, from));
+    }
+    for program_id in 1..=storage::get_program_counter(env) {
+        let Some(mut program) = env.storage().persistent().get::<_, Map<Symbol, Val>>(&ProgramDataKey::Program(program_id)) else { continue };
+        let rate_config = Map::try_from_val(env, &program.get(field(env, "rate_config")).unwrap()).unwrap();
+        program.set(field(env, "rate_config"), upgrade_rate_config(env, rate_config, from).into_val(env));
+        storage::set_program(env, &from_fields::<Program>(env, program));
+    }
+}
+
+/// Rewrites the stored invoice from the `from` layout to the current one. Written directly,
+/// not through `set_invoice`, which would read the old entry back.
+pub fn upgrade_invoice(env: &Env, invoice_id: &String, from: u32) -> Option<Invoice> {
+    let key = DataKey::Invoice(invoice_id.clone());
+    let mut fields: Map<Symbol, Val> = if from == 0 {
+        fields_of(env, &env.storage().persistent().get::<_, InvoiceV0>(&key)?)
+    } else {
+        env.storage().persistent().get(&key)?
+    };
+    // 0 -> 1: fee versions, settlement currency, programs, confidential amounts, funding
+    // thresholds, private terms, allow-lists and uniform clearing
+    if from < 1 {
+        let none = Option::<Address>::None.into_val(env);
+        for name in ["fee_version", "program_id"] { fields.set(field(env, name), 0u32.into_val(env)); }
+        for name in ["settlement_fx_rate", "settlement_paid", "min_funding_tokens", "escrowed_payments"] {
+            fields.set(field(env, name), 0i128.into_val(env));
+        }
+        for name in ["settlement_token", "amount_commitment", "private_terms_hash", "allowlist_root"] { fields.set(field(env, name), none); }
+        fields.set(field(env, "uniform_clearing"), false.into_val(env));
+    }
+    // 3 -> 4: rate terms pinned on the invoice; until then it read its program's live config
+    let rate_config = if from < 4 {
+        let program_id = u32::try_from_val(env, &fields.get(field(env, "program_id")).unwrap()).unwrap();
+        storage::get_program_rate_config(env, program_id)
+    } else {
+        upgrade_rate_config(env, Map::try_from_val(env, &fields.get(field(env, "rate_config")).unwrap()).unwrap(), from)
+    };
+    fields.set(field(env, "rate_config"), rate_config.into_val(env));
+    // 6 -> 7: default recoveries
+    if from < 7 { fields.set(field(env, "recovered_amount"), 0i128.into_val(env)); }
+    // 8 -> 9: auction types
+    if from < 9 { fields.set(field(env, "auction_type"), AuctionType::Dutch.into_val(env)); }
+
+    let invoice: Invoice = from_fields(env, fields);
+    env.storage().persistent().set(&key, &invoice);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    Some(invoice)
+}
@@ -0,0 +1,31 @@
+//! External pricing and swap interfaces for cross-currency settlement
+
+use soroban_sdk::{contractclient, Address, Env};
+
+/// Fixed-point scale for oracle rates (7 decimals, matching Stellar assets)
+pub const RATE_SCALE: i128 = 10_000_000;
+
+/// Max shortfall against the oracle rate accepted from a payout swap
+pub const MAX_SWAP_SLIPPAGE_BPS: i128 = 100;
+
+#[allow(dead_code)]
+#[contractclient(name = "OracleClient")]
+pub trait ConversionOracle {
+    /// Units of `quote` per one unit of `base`, scaled by RATE_SCALE
+    fn rate(env: Env, base: Address, quote: Address) -> i128;
+}
+
+#[allow(dead_code)]
+#[contractclient(name = "BenchmarkOracleClient")]
+pub trait BenchmarkOracle {
+    /// Current published benchmark rate (e.g. SOFR, MCLR), annualized in basis points
+    fn benchmark_rate(env: Env) -> u32;
+}
+
+#[allow(dead_code)]
+#[contractclient(name = "SwapRouterClient")]
+pub trait SwapRouter {
+    /// Pull `amount_in` of `token_in` from `from` (pre-approved) and send at least `min_out` of `token_out` to `to`.
+    /// Returns the amount delivered.
+    fn swap(env: Env, from: Address, token_in: Address, token_out: Address, amount_in: i128, min_out: i128, to: Address) -> i128;
+}
@@ -0,0 +1,165 @@
+//! Risk scoring for the Sangini Invoice Contract
+//! Deterministic per-invoice score in basis points (0 = safest, 10000 = riskiest)
+
+use soroban_sdk::{Address, Env};
+
+use crate::storage;
+use crate::types::{Invoice, InvoiceStatus, PremiumTier};
+
+const MAX_SCORE: u32 = 10000;
+const NEUTRAL_SCORE: u32 = 5000;
+
+// Component weights in basis points, summing to 10000
+const BUYER_WEIGHT: u32 = 3500;
+const SUPPLIER_WEIGHT: u32 = 2000;
+const TENOR_WEIGHT: u32 = 2000;
+const INSURANCE_WEIGHT: u32 = 1500;
+const FUNDING_WEIGHT: u32 = 1000;
+
+/// Tenor at which the tenor component saturates
+const MAX_TENOR_DAYS: u64 = 365;
+
+/// Closed invoices after which a buyer's history counts in full
+const FULL_HISTORY_INVOICES: u32 = 10;
+/// Credit for a late or short settlement, relative to 10000 for on time and 0 for a default
+const LATE_SETTLEMENT_CREDIT: u32 = 5000;
+/// Deducted per dispute the buyer raised that was ruled invalid
+const REJECTED_DISPUTE_PENALTY: u32 = 500;
+/// Deducted per dispute upheld against one of the supplier's invoices
+const UPHELD_DISPUTE_PENALTY: u32 = 1000;
+
+/// Neutral score moved toward `record` by `closed` invoices, reaching it after FULL_HISTORY_INVOICES
+fn weighted_record(record: u32, closed: u32) -> u32 {
+    if closed == 0 { return NEUTRAL_SCORE; }
+    let weight = closed.min(FULL_HISTORY_INVOICES);
+    (NEUTRAL_SCORE * (FULL_HISTORY_INVOICES - weight) + record * weight) / FULL_HISTORY_INVOICES
+}
+
+/// Buyer creditworthiness from its payment history (higher is better). Starts neutral and
+/// moves toward its on-time record as invoices close, reaching it after FULL_HISTORY_INVOICES.
+pub fn buyer_history_score(env: &Env, buyer: &Address) -> u32 {
+    let stats = storage::get_buyer_stats(env, buyer);
+    let closed = stats.settled_on_time + stats.settled_late + stats.defaulted;
+    let record = (stats.settled_on_time * MAX_SCORE + stats.settled_late * LATE_SETTLEMENT_CREDIT).checked_div(closed).unwrap_or(0);
+    weighted_record(record, closed).saturating_sub(stats.disputes_rejected * REJECTED_DISPUTE_PENALTY)
+}
+
+/// Adds a settled or defaulted invoice to its buyer's and supplier's histories; other statuses
+/// are ignored
+pub fn record_buyer_outcome(env: &Env, invoice: &Invoice) {
+    let mut stats = storage::get_buyer_stats(env, &invoice.buyer);
+    let mut supplier_stats = storage::get_supplier_stats(env, &invoice.supplier);
+    match invoice.status {
+        InvoiceStatus::Settled => stats.settled_on_time += 1,
+        InvoiceStatus::SettledLate | InvoiceStatus::SettledShort => stats.settled_late += 1,
+        InvoiceStatus::Defaulted => stats.defaulted += 1,
+        _ => return,
+    }
+    if invoice.status.is_settled() {
+        stats.total_volume += invoice.amount;
+        supplier_stats.settled += 1;
+    } else {
+        supplier_stats.defaulted += 1;
+    }
+    storage::set_buyer_stats(env, &invoice.buyer, &stats);
+    storage::set_supplier_stats(env, &invoice.supplier, &supplier_stats);
+}
+
+/// Adds a ruled dispute to its buyer's history, and to its supplier's when upheld
+pub fn record_buyer_dispute(env: &Env, invoice: &Invoice, upheld: bool) {
+    let mut stats = storage::get_buyer_stats(env, &invoice.buyer);
+    stats.disputed += 1;
+    if upheld {
+        let mut supplier_stats = storage::get_supplier_stats(env, &invoice.supplier);
+        supplier_stats.disputes_upheld += 1;
+        storage::set_supplier_stats(env, &invoice.supplier, &supplier_stats);
+    } else {
+        stats.disputes_rejected += 1;
+        storage::add_dispute_rejection(env, &invoice.buyer, env.ledger().timestamp());
+    }
+    storage::set_buyer_stats(env, &invoice.buyer, &stats);
+}
+
+/// Buyer's rejected disputes within the configured rolling window
+pub fn recent_dispute_rejections(env: &Env, buyer: &Address) -> u32 {
+    let since = env.ledger().timestamp().saturating_sub(storage::get_dispute_limits(env).window_seconds);
+    storage::get_dispute_rejections(env, buyer).iter().filter(|rejected_at| *rejected_at > since).count() as u32
+}
+
+/// Buyer creditworthiness for an invoice (higher is better): the buyer's history, improved by
+/// a balance confirmation and prepayments on this invoice.
+pub fn buyer_score(env: &Env, invoice: &Invoice) -> u32 {
+    let mut score = buyer_history_score(env, &invoice.buyer);
+    if storage::get_balance_confirmation(env, &invoice.id).is_some() {
+        score += 1000;
+    }
+    if invoice.amount > 0 {
+        let prepaid = storage::get_prepaid_total(env, &invoice.id);
+        score += ((prepaid * 2000) / invoice.amount) as u32;
+    }
+    score.min(MAX_SCORE)
+}
+
+/// Supplier creditworthiness from the outcomes of its invoices (higher is better): its
+/// settlement record weighted like a buyer's, less upheld disputes, and halved while it owes
+/// dispute refunds.
+pub fn supplier_history_score(env: &Env, supplier: &Address) -> u32 {
+    let stats = storage::get_supplier_stats(env, supplier);
+    let closed = stats.settled + stats.defaulted;
+    let record = (stats.settled * MAX_SCORE).checked_div(closed).unwrap_or(0);
+    let score = weighted_record(record, closed).saturating_sub(stats.disputes_upheld * UPHELD_DISPUTE_PENALTY);
+    if storage::get_supplier_debt(env, supplier) > 0 { score / 2 } else { score }
+}
+
+/// Supplier creditworthiness for an invoice (higher is better)
+pub fn supplier_score(env: &Env, invoice: &Invoice) -> u32 {
+    supplier_history_score(env, &invoice.supplier)
+}
+
+/// Whole days left until the invoice is due
+pub fn tenor_days(env: &Env, invoice: &Invoice) -> u64 {
+    invoice.due_date.saturating_sub(env.ledger().timestamp()) / 86400
+}
+
+/// Remaining time to maturity, scaled to basis points of MAX_TENOR_DAYS
+fn tenor_risk(env: &Env, invoice: &Invoice) -> u32 {
+    ((tenor_days(env, invoice).min(MAX_TENOR_DAYS) * MAX_SCORE as u64) / MAX_TENOR_DAYS) as u32
+}
+
+/// Insurance premium (bps) of the first tier the invoice's buyer score and tenor qualify for,
+/// or `flat_bps` when no tier matches
+pub fn insurance_premium_bps(env: &Env, invoice: &Invoice, flat_bps: u32) -> u32 {
+    let score = buyer_score(env, invoice);
+    let tenor = tenor_days(env, invoice);
+    (0..storage::premium_tier_count(env)).filter_map(|index| storage::get_premium_tier(env, index))
+        .find(|tier: &PremiumTier| score >= tier.min_buyer_score && tenor <= tier.max_tenor_days as u64)
+        .map_or(flat_bps, |tier| tier.premium_bps)
+}
+
+/// Share of face value not covered by the insurance pool
+fn insurance_risk(env: &Env, invoice: &Invoice) -> u32 {
+    if invoice.amount <= 0 {
+        return MAX_SCORE;
+    }
+    let pool = storage::get_insurance_pool(env, invoice.program_id);
+    let coverage = ((pool * MAX_SCORE as i128) / invoice.amount).min(MAX_SCORE as i128) as u32;
+    MAX_SCORE - coverage
+}
+
+/// Share of tokens still unsold
+fn funding_risk(invoice: &Invoice) -> u32 {
+    if invoice.total_tokens <= 0 {
+        return MAX_SCORE;
+    }
+    ((invoice.tokens_remaining * MAX_SCORE as i128) / invoice.total_tokens) as u32
+}
+
+/// Weighted risk score for an invoice at the current ledger time
+pub fn risk_score(env: &Env, invoice: &Invoice) -> u32 {
+    let weighted = (MAX_SCORE - buyer_score(env, invoice)) * BUYER_WEIGHT
+        + (MAX_SCORE - supplier_score(env, invoice)) * SUPPLIER_WEIGHT
+        + tenor_risk(env, invoice) * TENOR_WEIGHT
+        + insurance_risk(env, invoice) * INSURANCE_WEIGHT
+        + funding_risk(invoice) * FUNDING_WEIGHT;
+    weighted / MAX_SCORE
+}
@@ -0,0 +1,90 @@
+//! Invoice lifecycle state machine for the Sangini Invoice Contract
+//! Every status change goes through `transition`, so the allowed moves live in one table
+//! instead of being re-checked in each entrypoint.
+
+use crate::errors::ContractError;
+use crate::types::{Invoice, InvoiceStatus};
+
+/// Something that happens to an invoice and may move it to another status
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// Buyer approves the draft
+    Approve,
+    /// Supplier opens the Dutch auction
+    StartAuction,
+    /// Every token sold, or the auction finalized with some sold
+    Fund,
+    /// Auction finalized with nothing sold; the invoice can be auctioned again
+    ClearAuction,
+    MarkOverdue,
+    MarkDefaulted,
+    /// Buyer pays in full (`short` when only face value was covered past the due date)
+    Settle { short: bool },
+    /// Settlement recorded before late/short statuses existed, reclassified by its timing
+    ReclassifyLegacy,
+    RaiseDispute,
+    /// Dispute closed; the invoice returns to the status it was frozen in
+    RestoreFromDispute(InvoiceStatus),
+    /// Supplier withdraws a draft (or a rejected one), or a verified invoice that went stale past its due date
+    Revoke,
+    /// Buyer turns down the draft
+    Reject,
+    /// Supplier sends an amended draft back to the buyer
+    Resubmit,
+    /// Holders approved a later due date; an overdue invoice is current again
+    Extend,
+    /// Collections recovered the defaulted invoice's face value
+    Recover,
+    /// Collections closed the defaulted invoice short of face value
+    WriteOff,
+}
+
+/// Status the invoice moves to if `event` happens at `now`, or why it can't
+pub fn next(invoice: &Invoice, event: &Event, now: u64) -> Result<InvoiceStatus, ContractError> {
+    use InvoiceStatus::*;
+    let status = &invoice.status;
+    match (event, status) {
+        (Event::Approve, Draft) => Ok(Verified),
+        (Event::StartAuction, Verified) => Ok(Funding),
+        (Event::Fund, Verified | Funding) => Ok(Funded),
+        (Event::ClearAuction, Funding) => Ok(Verified),
+        (Event::MarkOverdue, Verified | Funding | Funded) => Ok(Overdue),
+        (Event::MarkDefaulted, Verified | Funding | Funded | Overdue) => Ok(Defaulted),
+        (Event::Settle { .. }, Disputed) => Err(ContractError::InvoiceDisputed),
+        (Event::Settle { short }, Verified | Funding | Funded | Overdue) => Ok(settled(invoice, *short, now)),
+        (Event::ReclassifyLegacy, Settled) => Ok(settled(invoice, false, now)),
+        (Event::RaiseDispute, Verified | Funding | Funded | Overdue) => Ok(Disputed),
+        (Event::RestoreFromDispute(prior), Disputed) if is_disputable(prior) => Ok(prior.clone()),
+        (Event::Reject, Draft) => Ok(Rejected),
+        (Event::Resubmit, Rejected) => Ok(Draft),
+        (Event::Revoke, Draft | Rejected) => Ok(Revoked),
+        (Event::Revoke, Verified) if now > invoice.due_date => Ok(Revoked),
+        (Event::Revoke, _) => Err(ContractError::CannotRevoke),
+        (Event::Extend, Funded | Overdue) => Ok(Funded),
+        (Event::Recover, Defaulted) => Ok(Recovered),
+        (Event::WriteOff, Defaulted) => Ok(WrittenOff),
+        _ => Err(ContractError::InvalidStatus),
+    }
+}
+
+/// Fails with the same error `transition` would, without changing anything
+pub fn check(invoice: &Invoice, event: &Event, now: u64) -> Result<(), ContractError> {
+    next(invoice, event, now).map(|_| ())
+}
+
+/// Applies `event` to the invoice's status. Callers save the invoice and record the transition.
+pub fn transition(invoice: &mut Invoice, event: Event, now: u64) -> Result<(), ContractError> {
+    invoice.status = next(invoice, &event, now)?;
+    Ok(())
+}
+
+/// Statuses a buyer may dispute from (and so the ones a dispute can restore)
+fn is_disputable(status: &InvoiceStatus) -> bool {
+    matches!(status, InvoiceStatus::Verified | InvoiceStatus::Funding | InvoiceStatus::Funded | InvoiceStatus::Overdue)
+}
+
+fn settled(invoice: &Invoice, short: bool, settled_at: u64) -> InvoiceStatus {
+    if short { InvoiceStatus::SettledShort }
+    else if settled_at > invoice.due_date { InvoiceStatus::SettledLate }
+    else { InvoiceStatus::Settled }
+}
@@ -1,9 +1,9 @@
 //! Storage module for the Sangini Invoice Contract
 //! Handles all persistent data storage on-chain
 
-use soroban_sdk::{contracttype, Address, Env, String, Vec};
+use soroban_sdk::{contracttype, Address, Bytes, Env, IntoVal, String, TryFromVal, Val, Vec};
 
-use crate::types::{Dispute, Invoice, RateConfig, TokenHolding, SellOrder};
+use crate::types::{ArbitrationVote, AuctionBid, AuctionResult, AuthorizationRecord, BalanceConfirmation, BenchmarkReadings, BasketOrder, BuyerStats, BuyOrder, SupplierStats, InstallmentAccount, InvestorTotals, Dispute, DisputeLimits, KycRequest, Evidence, Investment, Invoice, InvoiceStatus, FeeSchedule, IssuanceLimits, OrderStatus, Prepayment, PendingUpgrade, Program, ProgramFees, RateConfig, RateModel, Role, RedemptionReceipt, Rfq, StateRecord, TokenHolding, TokenLot, SellOrder, DraftAmendment, DraftRejection, DueDateExtension, PendingTransfer, RelationshipStats, SubvendorDistribution, SubvendorSplit, ExtensionProposal, ExtensionVote, PremiumTier, UnderwriterStake, UnderwriterTerms};
 
 // ============================================================================
 // STORAGE KEYS
@@ -27,6 +27,242 @@ pub enum DataKey {
     OrdersByInvoice(String),
     AuthorizedRelayer(Address),
     InsuranceClaimed(InvoiceKey),
+    Prepayments(String),
+    BalanceConfirmation(String),
+    IssuanceLimits,
+    TierLimits(u32),
+    SupplierTier(Address),
+    Receipt(InvoiceKey),
+    History(String),
+    FeeScheduleVersion,
+    FeeSchedule(u32),
+    PriceOracle,
+    SettlementTokenAllowed(Address),
+    SwapRouter,
+    PreAuctionInvest,
+    PayoutPreference(Address),
+    MaturityBucket(u64),
+    InvestorInvoices(Address),
+    RealizedLoss(Address),
+    ExposureLimit(Address),
+    AuthorizationLog(Address),
+    Lock(String),                           // Temporary-storage reentrancy lock (see guard.rs)
+    EscrowTotal,
+    GcBounty,
+    DefaultQueue,                           // Legacy single-list default queue; migrate moves it into day buckets
+    AppealArbiter,
+    AuctionResult(String),
+    SupplierInvoices(Address),
+    SupplierProceeds(Address),
+    InvestorTotals(Address),
+    BuyOrder(String),
+    BuyOrdersByInvoice(String),
+    AuthorizationActionCounter,
+    AuthorizationAction(u64),
+    FundingEscrow(InvoiceKey),
+    PrivateTerms(String),
+    BasketOrder(String),
+}
+
+/// Keys for white-label program data, kept apart from `DataKey` (contract enums are
+/// capped at 50 variants)
+#[derive(Clone)]
+#[contracttype]
+pub enum ProgramDataKey {
+    ProgramCounter,
+    Program(u32),
+    SupplierProgram(Address),
+    ProgramKyc(ProgramKey),
+    ProgramInsurancePool(u32),
+    ProgramFees(u32),
+    ProgramTreasury(u32),
+    ProgramRateModel(u32),
+    ProgramKycCount(u32),
+    ProgramKycIndex(u32, u32),              // (program, position) in a program's KYC export index
+    ProgramKycIndexed(ProgramKey),
+}
+
+/// Keys for installment settlement and dispute refunds, kept apart from `DataKey` for the
+/// same reason
+#[derive(Clone)]
+#[contracttype]
+pub enum SettlementDataKey {
+    InstallmentAccount(InvoiceKey),
+    RefundOwed(InvoiceKey),
+    RefundCreditors(String),
+    SupplierDebt(Address),
+    MinPayout(Address),
+    DustBalance(Address, Address),          // (holder, token)
+    DustTotal(Address),
+    InvoiceRateModel(String),
+    BenchmarkReadings(String),
+    BuyerStats(Address),
+    InvoiceInsurance(String),
+    EarmarkedInsurance(u32),
+    InsuranceAtDefault(String),
+    InsuranceBackstop,
+    UnderwriterStake(u32, Address),         // (program_id, staker)
+    UnderwriterShares(u32),
+    UnderwriterEpoch(u32),                  // Bumped when a wiped-out pool is re-seeded
+    StakeEpoch(u32, Address),               // Pool epoch a stake's shares were minted in
+    UnderwriterTerms,
+    PremiumTiers,                           // Number of tiers; each under PremiumTier(index)
+    PremiumTier(u32),
+    BuyerObligationLimit(Address),
+    InsuranceOwed(InvoiceKey),              // Insurance paid to a holder, repaid from later recoveries
+    RecoveryReceived(InvoiceKey),           // Recoveries paid to a holder before they claimed insurance
+    SupplierStats(Address),
+}
+
+/// Keys for admin handover and operational roles
+#[derive(Clone)]
+#[contracttype]
+pub enum AccessDataKey {
+    PendingAdmin,
+    Role(Role, Address),
+    Paused,
+    KycRequest(Address),
+    KycQueue,                               // Single-list queue from before KycEntry; moved by migrate
+    KycQueueHead,                           // Sequence of the oldest queue slot still in use
+    KycQueueTail,                           // Sequence the next request is queued under
+    KycEntry(u64),                          // Applicant queued under a sequence; gone once decided
+    KycPosition(Address),                   // Sequence an applicant's pending request is queued under
+    KycIndexCount,
+    KycIndex(u32),
+    KycIndexed(Address),                    // Set once the address is in the export index
+}
+
+/// Keys for contract upgrades and the storage schema version
+#[derive(Clone)]
+#[contracttype]
+pub enum UpgradeDataKey {
+    PendingUpgrade,
+    UpgradeDelay,
+    StorageVersion,
+    MigrationCursor,                        // Next invoice counter a paged migration rewrites
+}
+
+/// Keys for the dispute arbitration panel
+#[derive(Clone)]
+#[contracttype]
+pub enum DisputeDataKey {
+    Arbitrators,
+    ArbitrationQuorum,
+    Evidence(String),                       // Number of documents; each under EvidenceEntry
+    EvidenceEntry(String, u32),
+    Votes(String),                          // Number of votes; each under Vote, indexed by VoteSlot
+    Vote(String, u32),
+    VoteSlot(InvoiceKey),
+    Limits,
+    Rejections(Address),
+    Clearance(String),
+    Bond(String),
+}
+
+/// Keys for secondary-market request-for-quote data
+#[derive(Clone)]
+#[contracttype]
+pub enum MarketDataKey {
+    Rfq(String),
+    PriceBand,
+    DistressedSale(InvoiceKey),
+    TokenSymbol(String),
+    RoyaltyOverride(String),
+}
+
+/// Keys for per-purchase investment records and their indexes
+#[derive(Clone)]
+#[contracttype]
+pub enum InvestmentDataKey {
+    InvestmentCounter,
+    Investment(String),
+    InvestmentsByInvoice(String),
+    InvestmentsByInvestor(Address),
+    InvestmentsByHolding(InvoiceKey),
+    ConcentrationCap(String),
+    Lots(InvoiceKey),
+}
+
+/// Keys for the invoice listing indexes (the supplier index predates these and lives in `DataKey`)
+/// and the per-invoice event sequence indexers follow
+#[derive(Clone)]
+#[contracttype]
+pub enum IndexDataKey {
+    InvoicesByBuyer(Address),               // Number of invoices; each under BuyerEntry, indexed by BuyerPosition
+    BuyerEntry(Address, u32),
+    BuyerPosition(String),
+    InvoicesByStatus(InvoiceStatus),        // Number of invoices; each under StatusEntry, indexed by StatusPosition
+    StatusEntry(InvoiceStatus, u32),
+    StatusPosition(String),
+    EventSeq(String),
+    BuyerOutstanding(Address),              // Unpaid face value of the buyer's live invoices, kept by set_invoice
+}
+
+/// Keys for holder-approved due-date extensions
+#[derive(Clone)]
+#[contracttype]
+pub enum ExtensionDataKey {
+    Proposal(String),
+    Votes(String),                          // Number of votes; each under Vote, indexed by VoteSlot
+    Vote(String, u32),
+    VoteSlot(InvoiceKey),
+    Applied(String),
+}
+
+/// Keys for buyer rejections of drafts and supplier/buyer draft history
+#[derive(Clone)]
+#[contracttype]
+pub enum DraftDataKey {
+    Rejection(String),
+    Relationship(Address, Address),         // (supplier, buyer)
+    Amendments(String),                     // Number of amendments; each under Amendment
+    Amendment(String, u32),
+}
+
+/// Keys for sub-vendor splits declared on drafts and the distributions made from them
+#[derive(Clone)]
+#[contracttype]
+pub enum SupplyChainDataKey {
+    SubvendorSplits(String),
+    Distributions(String),                  // Number of distributions; each under Distribution
+    Distribution(String, u32),
+    TransferCounter,
+    PendingTransfer(u64),
+    PendingByRecipient(Address),            // Number of offers awaiting the recipient
+    PendingEntry(Address, u32),             // Transfer id at a slot of the recipient's offers
+    PendingPosition(u64),                   // Slot of a transfer among its recipient's offers
+    LockedTokens(InvoiceKey),
+    OffersBySender(InvoiceKey),             // Number of offers out of a holding; each under SenderEntry
+    SenderEntry(InvoiceKey, u32),           // Transfer id at a slot of the holding's offers
+    SenderPosition(u64),                    // Slot of a transfer among its holding's offers
+}
+
+/// Keys for English auction bids
+#[derive(Clone)]
+#[contracttype]
+pub enum AuctionDataKey {
+    Bids(String),                           // Number of bids; each under Bid, ranked best first
+    Bid(String, u32),
+    BidderTotals(InvoiceKey),               // (tokens, escrow) of a bidder's open bids
+    BidEscrow(Address),                     // Escrow in a bidder's open bids across every invoice
+    Reserve(String),
+    FundingRefund(InvoiceKey),              // Escrowed payment owed back after a failed funding round
+}
+
+/// Keys for keeper incentives
+#[derive(Clone)]
+#[contracttype]
+pub enum KeeperDataKey {
+    KeeperBounty,
+    DefaultDays,
+    DefaultBucket(u64),                     // Invoices queued for default, by due day
+}
+
+/// Keys for external transition listeners
+#[derive(Clone)]
+#[contracttype]
+pub enum HookDataKey {
+    Subscribers,
 }
 
 #[derive(Clone)]
@@ -36,264 +272,2380 @@ pub struct InvoiceKey {
     pub holder: Address,
 }
 
+#[derive(Clone)]
+#[contracttype]
+pub struct ProgramKey {
+    pub program_id: u32,
+    pub addr: Address,
+}
+
+// ============================================================================
+// INDEXED LIST STORAGE
+// ============================================================================
+// Lists that grow with activity keep each item in its own entry under an `(id, index)` key and
+// their length under the list's key, so appends and pages only touch the entries they need.
+
+fn list_len<K: IntoVal<Env, Val>>(env: &Env, len_key: &K) -> u32 {
+    env.storage().persistent().get(len_key).unwrap_or(0)
+}
+
+fn set_list_len<K: IntoVal<Env, Val>>(env: &Env, len_key: &K, len: u32) {
+    if len == 0 { env.storage().persistent().remove(len_key); return; }
+    env.storage().persistent().set(len_key, &len);
+    env.storage().persistent().extend_ttl(len_key, 100_000, 200_000);
+}
+
+fn set_entry<K: IntoVal<Env, Val>, T: IntoVal<Env, Val>>(env: &Env, key: &K, value: &T) {
+    env.storage().persistent().set(key, value);
+    env.storage().persistent().extend_ttl(key, 100_000, 200_000);
+}
+
+/// Entries `[start, start + limit)` of a list of `len` entries
+fn list_page<K: IntoVal<Env, Val>, T: IntoVal<Env, Val> + TryFromVal<Env, Val>>(env: &Env, len: u32, start: u32, limit: u32, key: impl Fn(u32) -> K) -> Vec<T> {
+    let mut page = Vec::new(env);
+    for index in start.min(len)..start.saturating_add(limit).min(len) {
+        if let Some(item) = env.storage().persistent().get(&key(index)) { page.push_back(item); }
+    }
+    page
+}
+
+fn clear_list<L: IntoVal<Env, Val>, K: IntoVal<Env, Val>>(env: &Env, len_key: &L, key: impl Fn(u32) -> K) {
+    for index in 0..list_len(env, len_key) { env.storage().persistent().remove(&key(index)); }
+    env.storage().persistent().remove(len_key);
+}
+
 // ============================================================================
 // ADMIN STORAGE
 // ============================================================================
 
-pub fn has_admin(env: &Env) -> bool {
-    env.storage().instance().has(&DataKey::Admin)
+pub fn has_admin(env: &Env) -> bool {
+    env.storage().instance().has(&DataKey::Admin)
+}
+
+pub fn get_admin(env: &Env) -> Address {
+    env.storage().instance().get(&DataKey::Admin).unwrap()
+}
+
+pub fn set_admin(env: &Env, admin: &Address) {
+    env.storage().instance().set(&DataKey::Admin, admin);
+}
+
+pub fn get_pending_upgrade(env: &Env) -> Option<PendingUpgrade> {
+    env.storage().instance().get(&UpgradeDataKey::PendingUpgrade)
+}
+
+pub fn set_pending_upgrade(env: &Env, pending: &Option<PendingUpgrade>) {
+    match pending {
+        Some(upgrade) => env.storage().instance().set(&UpgradeDataKey::PendingUpgrade, upgrade),
+        None => env.storage().instance().remove(&UpgradeDataKey::PendingUpgrade),
+    }
+}
+
+/// Configured upgrade timelock (0 = the built-in minimum)
+pub fn get_upgrade_delay(env: &Env) -> u64 {
+    env.storage().instance().get(&UpgradeDataKey::UpgradeDelay).unwrap_or(0)
+}
+
+pub fn set_upgrade_delay(env: &Env, delay_seconds: u64) {
+    env.storage().instance().set(&UpgradeDataKey::UpgradeDelay, &delay_seconds);
+}
+
+/// Contracts notified of funded, settled and defaulted transitions
+pub fn get_subscribers(env: &Env) -> Vec<Address> {
+    env.storage().instance().get(&HookDataKey::Subscribers).unwrap_or(Vec::new(env))
+}
+
+pub fn set_subscribers(env: &Env, subscribers: &Vec<Address>) {
+    env.storage().instance().set(&HookDataKey::Subscribers, subscribers);
+}
+
+pub fn get_storage_version(env: &Env) -> u32 {
+    env.storage().instance().get(&UpgradeDataKey::StorageVersion).unwrap_or(0)
+}
+
+pub fn set_storage_version(env: &Env, version: u32) {
+    env.storage().instance().set(&UpgradeDataKey::StorageVersion, &version);
+}
+
+/// Progress of a migration spread over several `migrate` calls; None before the first
+pub fn get_migration_cursor(env: &Env) -> Option<u32> {
+    env.storage().instance().get(&UpgradeDataKey::MigrationCursor)
+}
+
+pub fn set_migration_cursor(env: &Env, cursor: Option<u32>) {
+    match cursor {
+        Some(cursor) => env.storage().instance().set(&UpgradeDataKey::MigrationCursor, &cursor),
+        None => env.storage().instance().remove(&UpgradeDataKey::MigrationCursor),
+    }
+}
+
+/// Admin nominated by `propose_admin`, awaiting `accept_admin`
+pub fn get_pending_admin(env: &Env) -> Option<Address> {
+    env.storage().instance().get(&AccessDataKey::PendingAdmin)
+}
+
+pub fn set_pending_admin(env: &Env, pending: &Option<Address>) {
+    match pending {
+        Some(addr) => env.storage().instance().set(&AccessDataKey::PendingAdmin, addr),
+        None => env.storage().instance().remove(&AccessDataKey::PendingAdmin),
+    }
+}
+
+pub fn is_paused(env: &Env) -> bool {
+    env.storage().instance().get(&AccessDataKey::Paused).unwrap_or(false)
+}
+
+pub fn set_paused(env: &Env, paused: bool) {
+    env.storage().instance().set(&AccessDataKey::Paused, &paused);
+}
+
+pub fn has_role(env: &Env, role: &Role, addr: &Address) -> bool {
+    env.storage().persistent().get(&AccessDataKey::Role(role.clone(), addr.clone())).unwrap_or(false)
+}
+
+pub fn set_role(env: &Env, role: &Role, addr: &Address, granted: bool) {
+    let key = AccessDataKey::Role(role.clone(), addr.clone());
+    if granted {
+        env.storage().persistent().set(&key, &true);
+        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    } else {
+        env.storage().persistent().remove(&key);
+    }
+}
+
+pub fn get_kyc_request(env: &Env, applicant: &Address) -> Option<KycRequest> {
+    env.storage().persistent().get(&AccessDataKey::KycRequest(applicant.clone()))
+}
+
+pub fn set_kyc_request(env: &Env, request: &KycRequest) {
+    let key = AccessDataKey::KycRequest(request.applicant.clone());
+    env.storage().persistent().set(&key, request);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Pending KYC requests are queued under increasing sequence numbers; deciding one frees its
+/// slot and the head moves past freed slots, so the queue stays in arrival order with no cap
+pub fn kyc_queue_bounds(env: &Env) -> (u64, u64) {
+    let instance = env.storage().instance();
+    (instance.get(&AccessDataKey::KycQueueHead).unwrap_or(0), instance.get(&AccessDataKey::KycQueueTail).unwrap_or(0))
+}
+
+/// Applicants in the queue slots `[start, end)`; freed slots are skipped
+pub fn get_kyc_queue(env: &Env, start: u64, end: u64) -> Vec<Address> {
+    let mut applicants = Vec::new(env);
+    for seq in start..end {
+        if let Some(applicant) = env.storage().persistent().get(&AccessDataKey::KycEntry(seq)) { applicants.push_back(applicant); }
+    }
+    applicants
+}
+
+pub fn is_kyc_queued(env: &Env, applicant: &Address) -> bool {
+    env.storage().persistent().has(&AccessDataKey::KycPosition(applicant.clone()))
+}
+
+pub fn enqueue_kyc(env: &Env, applicant: &Address) {
+    let (_, tail) = kyc_queue_bounds(env);
+    set_entry(env, &AccessDataKey::KycEntry(tail), applicant);
+    set_entry(env, &AccessDataKey::KycPosition(applicant.clone()), &tail);
+    env.storage().instance().set(&AccessDataKey::KycQueueTail, &(tail + 1));
+}
+
+pub fn dequeue_kyc(env: &Env, applicant: &Address) {
+    let store = env.storage().persistent();
+    let Some(seq) = store.get::<_, u64>(&AccessDataKey::KycPosition(applicant.clone())) else { return };
+    store.remove(&AccessDataKey::KycEntry(seq));
+    store.remove(&AccessDataKey::KycPosition(applicant.clone()));
+    let (mut head, tail) = kyc_queue_bounds(env);
+    if seq != head { return; }
+    while head < tail && !store.has(&AccessDataKey::KycEntry(head)) { head += 1; }
+    env.storage().instance().set(&AccessDataKey::KycQueueHead, &head);
+}
+
+/// Removes and returns the single-list queue kept before storage version 11
+pub fn take_legacy_kyc_queue(env: &Env) -> Vec<Address> {
+    let queue = env.storage().persistent().get(&AccessDataKey::KycQueue).unwrap_or(Vec::new(env));
+    env.storage().persistent().remove(&AccessDataKey::KycQueue);
+    queue
+}
+
+// ============================================================================
+// PAYMENT TOKEN STORAGE
+// ============================================================================
+
+/// Reviewer of supplier appeals; defaults to the admin
+pub fn get_appeal_arbiter(env: &Env) -> Address {
+    env.storage().instance().get(&DataKey::AppealArbiter).unwrap_or_else(|| get_admin(env))
+}
+
+pub fn set_appeal_arbiter(env: &Env, arbiter: &Address) {
+    env.storage().instance().set(&DataKey::AppealArbiter, arbiter);
+}
+
+pub fn get_usdc_token(env: &Env) -> Address {
+    env.storage().instance().get(&DataKey::UsdcToken).unwrap()
+}
+
+pub fn set_usdc_token(env: &Env, token: &Address) {
+    env.storage().instance().set(&DataKey::UsdcToken, token);
+}
+
+pub fn get_price_oracle(env: &Env) -> Option<Address> {
+    env.storage().instance().get(&DataKey::PriceOracle)
+}
+
+pub fn set_price_oracle(env: &Env, oracle: &Address) {
+    env.storage().instance().set(&DataKey::PriceOracle, oracle);
+}
+
+pub fn is_settlement_token_allowed(env: &Env, token: &Address) -> bool {
+    let key = DataKey::SettlementTokenAllowed(token.clone());
+    env.storage().persistent().get(&key).unwrap_or(false)
+}
+
+pub fn set_settlement_token_allowed(env: &Env, token: &Address, allowed: bool) {
+    let key = DataKey::SettlementTokenAllowed(token.clone());
+    env.storage().persistent().set(&key, &allowed);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Whether investors may buy at face value before an auction starts (off by default)
+pub fn allows_pre_auction_invest(env: &Env) -> bool {
+    env.storage().instance().get(&DataKey::PreAuctionInvest).unwrap_or(false)
+}
+
+pub fn set_pre_auction_invest(env: &Env, allowed: bool) {
+    env.storage().instance().set(&DataKey::PreAuctionInvest, &allowed);
+}
+
+pub fn get_swap_router(env: &Env) -> Option<Address> {
+    env.storage().instance().get(&DataKey::SwapRouter)
+}
+
+pub fn set_swap_router(env: &Env, router: &Address) {
+    env.storage().instance().set(&DataKey::SwapRouter, router);
+}
+
+pub fn get_payout_preference(env: &Env, holder: &Address) -> Option<Address> {
+    let key = DataKey::PayoutPreference(holder.clone());
+    env.storage().persistent().get(&key)
+}
+
+pub fn set_payout_preference(env: &Env, holder: &Address, token: &Address) {
+    let key = DataKey::PayoutPreference(holder.clone());
+    env.storage().persistent().set(&key, token);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn remove_payout_preference(env: &Env, holder: &Address) {
+    let key = DataKey::PayoutPreference(holder.clone());
+    env.storage().persistent().remove(&key);
+}
+
+// ============================================================================
+// RATE CONFIG STORAGE
+// ============================================================================
+
+pub fn get_rate_config(env: &Env) -> RateConfig {
+    env.storage()
+        .instance()
+        .get(&DataKey::RateConfig)
+        .unwrap_or_default()
+}
+
+pub fn set_rate_config(env: &Env, config: &RateConfig) {
+    env.storage().instance().set(&DataKey::RateConfig, config);
+}
+
+// ============================================================================
+// PROGRAM STORAGE
+// ============================================================================
+// Program 0 is the default program: it uses the global admin, rate config,
+// KYC list and insurance pool, so single-tenant deployments are unchanged.
+
+pub fn get_program_counter(env: &Env) -> u32 {
+    env.storage().instance().get(&ProgramDataKey::ProgramCounter).unwrap_or(0)
+}
+
+pub fn increment_program_counter(env: &Env) -> u32 {
+    let next = get_program_counter(env) + 1;
+    env.storage().instance().set(&ProgramDataKey::ProgramCounter, &next);
+    next
+}
+
+pub fn get_program(env: &Env, program_id: u32) -> Option<Program> {
+    env.storage().persistent().get(&ProgramDataKey::Program(program_id))
+}
+
+pub fn set_program(env: &Env, program: &Program) {
+    let key = ProgramDataKey::Program(program.id);
+    env.storage().persistent().set(&key, program);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Admin of a program; the global admin for program 0
+pub fn get_program_admin(env: &Env, program_id: u32) -> Option<Address> {
+    if program_id == 0 { return Some(get_admin(env)); }
+    get_program(env, program_id).map(|p| p.admin)
+}
+
+/// Rate config of a program; the global rate config for program 0
+pub fn get_program_rate_config(env: &Env, program_id: u32) -> RateConfig {
+    if program_id == 0 { return get_rate_config(env); }
+    get_program(env, program_id).map(|p| p.rate_config).unwrap_or_default()
+}
+
+/// Program a supplier's new invoices are issued under (0 if not enrolled)
+pub fn get_supplier_program(env: &Env, supplier: &Address) -> u32 {
+    env.storage().persistent().get(&ProgramDataKey::SupplierProgram(supplier.clone())).unwrap_or(0)
+}
+
+pub fn set_supplier_program(env: &Env, supplier: &Address, program_id: u32) {
+    let key = ProgramDataKey::SupplierProgram(supplier.clone());
+    env.storage().persistent().set(&key, &program_id);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn get_program_fees(env: &Env, program_id: u32) -> ProgramFees {
+    env.storage().instance().get(&ProgramDataKey::ProgramFees(program_id)).unwrap_or_default()
+}
+
+pub fn set_program_fees(env: &Env, program_id: u32, fees: &ProgramFees) {
+    env.storage().instance().set(&ProgramDataKey::ProgramFees(program_id), fees);
+}
+
+pub fn get_program_rate_model(env: &Env, program_id: u32) -> Option<RateModel> {
+    env.storage().instance().get(&ProgramDataKey::ProgramRateModel(program_id))
+}
+
+pub fn set_program_rate_model(env: &Env, program_id: u32, model: &RateModel) {
+    env.storage().instance().set(&ProgramDataKey::ProgramRateModel(program_id), model);
+}
+
+/// Per-invoice override of its program's rate model
+pub fn get_invoice_rate_model(env: &Env, invoice_id: &String) -> Option<RateModel> {
+    env.storage().persistent().get(&SettlementDataKey::InvoiceRateModel(invoice_id.clone()))
+}
+
+pub fn set_invoice_rate_model(env: &Env, invoice_id: &String, model: &Option<RateModel>) {
+    let key = SettlementDataKey::InvoiceRateModel(invoice_id.clone());
+    match model {
+        Some(model) => {
+            env.storage().persistent().set(&key, model);
+            env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+        }
+        None => env.storage().persistent().remove(&key),
+    }
+}
+
+pub fn get_benchmark_readings(env: &Env, invoice_id: &String) -> Option<BenchmarkReadings> {
+    env.storage().persistent().get(&SettlementDataKey::BenchmarkReadings(invoice_id.clone()))
+}
+
+pub fn set_benchmark_readings(env: &Env, invoice_id: &String, readings: &BenchmarkReadings) {
+    let key = SettlementDataKey::BenchmarkReadings(invoice_id.clone());
+    env.storage().persistent().set(&key, readings);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Accrued fees owed to a program; program 0 holds the protocol's share
+pub fn get_program_treasury(env: &Env, program_id: u32) -> i128 {
+    env.storage().instance().get(&ProgramDataKey::ProgramTreasury(program_id)).unwrap_or(0)
+}
+
+pub fn set_program_treasury(env: &Env, program_id: u32, balance: i128) {
+    env.storage().instance().set(&ProgramDataKey::ProgramTreasury(program_id), &balance);
+}
+
+/// Combined balance of every program treasury, including the protocol's
+pub fn get_total_treasuries(env: &Env) -> i128 {
+    (0..=get_program_counter(env)).map(|id| get_program_treasury(env, id)).sum()
+}
+
+// ============================================================================
+// FEE SCHEDULE STORAGE
+// ============================================================================
+
+pub fn get_fee_schedule_version(env: &Env) -> u32 {
+    env.storage().instance().get(&DataKey::FeeScheduleVersion).unwrap_or(0)
+}
+
+pub fn get_fee_schedule(env: &Env, version: u32) -> Option<FeeSchedule> {
+    env.storage().persistent().get(&DataKey::FeeSchedule(version))
+}
+
+/// Stores the schedule as the newest version
+pub fn add_fee_schedule(env: &Env, schedule: &FeeSchedule) {
+    let key = DataKey::FeeSchedule(schedule.version);
+    env.storage().persistent().set(&key, schedule);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    env.storage().instance().set(&DataKey::FeeScheduleVersion, &schedule.version);
+}
+
+/// Latest schedule whose effective_from has been reached
+pub fn get_active_fee_schedule(env: &Env, at: u64) -> FeeSchedule {
+    let mut version = get_fee_schedule_version(env);
+    while version > 1 {
+        if let Some(schedule) = get_fee_schedule(env, version) {
+            if schedule.effective_from <= at {
+                return schedule;
+            }
+        }
+        version -= 1;
+    }
+    get_fee_schedule(env, 1).unwrap()
+}
+
+// ============================================================================
+// ISSUANCE LIMITS STORAGE
+// ============================================================================
+
+pub fn get_issuance_limits(env: &Env) -> IssuanceLimits {
+    env.storage().instance().get(&DataKey::IssuanceLimits).unwrap_or_default()
+}
+
+pub fn set_issuance_limits(env: &Env, limits: &IssuanceLimits) {
+    env.storage().instance().set(&DataKey::IssuanceLimits, limits);
+}
+
+pub fn get_tier_limits(env: &Env, tier: u32) -> Option<IssuanceLimits> {
+    env.storage().instance().get(&DataKey::TierLimits(tier))
+}
+
+pub fn set_tier_limits(env: &Env, tier: u32, limits: &IssuanceLimits) {
+    env.storage().instance().set(&DataKey::TierLimits(tier), limits);
+}
+
+pub fn get_supplier_tier(env: &Env, supplier: &Address) -> u32 {
+    let key = DataKey::SupplierTier(supplier.clone());
+    env.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn set_supplier_tier(env: &Env, supplier: &Address, tier: u32) {
+    let key = DataKey::SupplierTier(supplier.clone());
+    env.storage().persistent().set(&key, &tier);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// COUNTERS
+// ============================================================================
+
+pub fn get_invoice_counter(env: &Env) -> u32 {
+    env.storage().instance().get(&DataKey::InvoiceCounter).unwrap_or(0)
+}
+
+pub fn set_invoice_counter(env: &Env, counter: u32) {
+    env.storage().instance().set(&DataKey::InvoiceCounter, &counter);
+}
+
+pub fn get_order_counter(env: &Env) -> u32 {
+    env.storage().instance().get(&DataKey::OrderCounter).unwrap_or(0)
+}
+
+pub fn set_order_counter(env: &Env, counter: u32) {
+    env.storage().instance().set(&DataKey::OrderCounter, &counter);
+}
+
+// ============================================================================
+// INVOICE STORAGE
+// ============================================================================
+
+pub fn get_invoice(env: &Env, invoice_id: &String) -> Option<Invoice> {
+    let key = DataKey::Invoice(invoice_id.clone());
+    env.storage().persistent().get(&key)
+}
+
+/// Saves an invoice, moving it between status indexes when its status changed and carrying
+/// any change in what it owes into its buyer's outstanding total. A disputed invoice leaves
+/// the default queue and rejoins it once the dispute is resolved.
+pub fn set_invoice(env: &Env, invoice_id: &String, invoice: &Invoice) {
+    let key = DataKey::Invoice(invoice_id.clone());
+    let previous = get_invoice(env, invoice_id);
+    let previous_status = previous.as_ref().map(|i| i.status.clone());
+    if previous_status.as_ref() != Some(&invoice.status) {
+        if invoice.status == InvoiceStatus::Disputed { dequeue_for_default(env, invoice.due_date, invoice_id); }
+        if previous_status == Some(InvoiceStatus::Disputed) { enqueue_for_default(env, invoice.due_date, invoice_id); }
+        if let Some(status) = previous_status { remove_status_entry(env, &status, invoice_id); }
+        add_status_entry(env, &invoice.status, invoice_id);
+    }
+    let delta = outstanding_of(invoice) - previous.as_ref().map_or(0, outstanding_of);
+    if delta != 0 { add_buyer_outstanding(env, &invoice.buyer, delta); }
+    env.storage().persistent().set(&key, invoice);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// INVOICE HISTORY STORAGE
+// ============================================================================
+
+pub fn get_history(env: &Env, invoice_id: &String) -> Vec<StateRecord> {
+    let key = DataKey::History(invoice_id.clone());
+    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+}
+
+pub fn append_history(env: &Env, invoice_id: &String, record: &StateRecord) {
+    let key = DataKey::History(invoice_id.clone());
+    let mut history = get_history(env, invoice_id);
+    history.push_back(record.clone());
+    env.storage().persistent().set(&key, &history);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// MATURITY INDEX STORAGE
+// ============================================================================
+
+/// Width of a maturity bucket (one week)
+pub const MATURITY_BUCKET_SECONDS: u64 = 604800;
+
+pub fn get_maturity_bucket(env: &Env, bucket: u64) -> Vec<String> {
+    let key = DataKey::MaturityBucket(bucket);
+    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+}
+
+pub fn add_to_maturity_bucket(env: &Env, due_date: u64, invoice_id: &String) {
+    let bucket = due_date / MATURITY_BUCKET_SECONDS;
+    let key = DataKey::MaturityBucket(bucket);
+    let mut ids = get_maturity_bucket(env, bucket);
+    ids.push_back(invoice_id.clone());
+    env.storage().persistent().set(&key, &ids);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn remove_from_maturity_bucket(env: &Env, due_date: u64, invoice_id: &String) {
+    let bucket = due_date / MATURITY_BUCKET_SECONDS;
+    let mut remaining = Vec::new(env);
+    for id in get_maturity_bucket(env, bucket).iter() {
+        if id != *invoice_id { remaining.push_back(id); }
+    }
+    env.storage().persistent().set(&DataKey::MaturityBucket(bucket), &remaining);
+}
+
+// ============================================================================
+// DRAFT REJECTION STORAGE
+// ============================================================================
+
+pub fn get_draft_rejection(env: &Env, invoice_id: &String) -> Option<DraftRejection> {
+    env.storage().persistent().get(&DraftDataKey::Rejection(invoice_id.clone()))
+}
+
+pub fn set_draft_rejection(env: &Env, invoice_id: &String, rejection: &DraftRejection) {
+    let key = DraftDataKey::Rejection(invoice_id.clone());
+    env.storage().persistent().set(&key, rejection);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn amendment_count(env: &Env, invoice_id: &String) -> u32 {
+    list_len(env, &DraftDataKey::Amendments(invoice_id.clone()))
+}
+
+pub fn get_amendments(env: &Env, invoice_id: &String, start: u32, limit: u32) -> Vec<DraftAmendment> {
+    list_page(env, amendment_count(env, invoice_id), start, limit, |index| DraftDataKey::Amendment(invoice_id.clone(), index))
+}
+
+/// Appends the amendment; returns the draft's amendment count
+pub fn add_amendment(env: &Env, invoice_id: &String, amendment: &DraftAmendment) -> u32 {
+    let count = amendment_count(env, invoice_id);
+    set_entry(env, &DraftDataKey::Amendment(invoice_id.clone(), count), amendment);
+    set_list_len(env, &DraftDataKey::Amendments(invoice_id.clone()), count + 1);
+    count + 1
+}
+
+pub fn clear_amendments(env: &Env, invoice_id: &String) {
+    clear_list(env, &DraftDataKey::Amendments(invoice_id.clone()), |index| DraftDataKey::Amendment(invoice_id.clone(), index));
+}
+
+pub fn get_relationship_stats(env: &Env, supplier: &Address, buyer: &Address) -> RelationshipStats {
+    env.storage().persistent().get(&DraftDataKey::Relationship(supplier.clone(), buyer.clone())).unwrap_or_default()
+}
+
+pub fn set_relationship_stats(env: &Env, supplier: &Address, buyer: &Address, stats: &RelationshipStats) {
+    let key = DraftDataKey::Relationship(supplier.clone(), buyer.clone());
+    env.storage().persistent().set(&key, stats);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// ENGLISH AUCTION STORAGE
+// ============================================================================
+
+pub fn auction_bid_count(env: &Env, invoice_id: &String) -> u32 {
+    list_len(env, &AuctionDataKey::Bids(invoice_id.clone()))
+}
+
+pub fn get_auction_bid(env: &Env, invoice_id: &String, rank: u32) -> Option<AuctionBid> {
+    env.storage().persistent().get(&AuctionDataKey::Bid(invoice_id.clone(), rank))
+}
+
+/// Bids ranked best first: highest price, then earliest
+pub fn get_auction_bids(env: &Env, invoice_id: &String, start: u32, limit: u32) -> Vec<AuctionBid> {
+    list_page(env, auction_bid_count(env, invoice_id), start, limit, |rank| AuctionDataKey::Bid(invoice_id.clone(), rank))
+}
+
+/// Rank a new bid at `price` takes: behind every bid at or above it
+pub fn auction_bid_rank(env: &Env, invoice_id: &String, price: i128) -> u32 {
+    let (mut low, mut high) = (0, auction_bid_count(env, invoice_id));
+    while low < high {
+        let mid = (low + high) / 2;
+        if get_auction_bid(env, invoice_id, mid).is_some_and(|bid| bid.price >= price) { low = mid + 1; } else { high = mid; }
+    }
+    low
+}
+
+/// Inserts the bid at `rank`, moving the bids ranked after it down one
+pub fn insert_auction_bid(env: &Env, invoice_id: &String, rank: u32, bid: &AuctionBid) {
+    let count = auction_bid_count(env, invoice_id);
+    for index in (rank..count).rev() {
+        if let Some(moved) = get_auction_bid(env, invoice_id, index) { set_entry(env, &AuctionDataKey::Bid(invoice_id.clone(), index + 1), &moved); }
+    }
+    set_entry(env, &AuctionDataKey::Bid(invoice_id.clone(), rank), bid);
+    set_list_len(env, &AuctionDataKey::Bids(invoice_id.clone()), count + 1);
+    add_bidder_totals(env, invoice_id, &bid.bidder, bid.token_amount, bid.escrow);
+}
+
+/// Removes every bid, returning them best first
+pub fn take_auction_bids(env: &Env, invoice_id: &String) -> Vec<AuctionBid> {
+    let bids: Vec<AuctionBid> = get_auction_bids(env, invoice_id, 0, auction_bid_count(env, invoice_id));
+    for bid in bids.iter() {
+        add_bid_escrow(env, &bid.bidder, -bid.escrow);
+        env.storage().persistent().remove(&AuctionDataKey::BidderTotals(InvoiceKey { invoice_id: invoice_id.clone(), holder: bid.bidder }));
+    }
+    clear_list(env, &AuctionDataKey::Bids(invoice_id.clone()), |rank| AuctionDataKey::Bid(invoice_id.clone(), rank));
+    bids
+}
+
+/// Removes the bidder's bids, keeping the others in rank order. Returns their escrow.
+pub fn remove_bidder_bids(env: &Env, invoice_id: &String, bidder: &Address) -> i128 {
+    let count = auction_bid_count(env, invoice_id);
+    let mut kept = 0;
+    for index in 0..count {
+        let Some(bid) = get_auction_bid(env, invoice_id, index) else { continue };
+        if bid.bidder == *bidder { continue; }
+        if kept != index { set_entry(env, &AuctionDataKey::Bid(invoice_id.clone(), kept), &bid); }
+        kept += 1;
+    }
+    for index in kept..count { env.storage().persistent().remove(&AuctionDataKey::Bid(invoice_id.clone(), index)); }
+    set_list_len(env, &AuctionDataKey::Bids(invoice_id.clone()), kept);
+    let (_, escrow) = get_bidder_totals(env, invoice_id, bidder);
+    add_bid_escrow(env, bidder, -escrow);
+    env.storage().persistent().remove(&AuctionDataKey::BidderTotals(InvoiceKey { invoice_id: invoice_id.clone(), holder: bidder.clone() }));
+    escrow
+}
+
+/// Removes the worst-ranked bid, if any
+pub fn pop_last_auction_bid(env: &Env, invoice_id: &String) -> Option<AuctionBid> {
+    let last = auction_bid_count(env, invoice_id).checked_sub(1)?;
+    let bid = get_auction_bid(env, invoice_id, last)?;
+    env.storage().persistent().remove(&AuctionDataKey::Bid(invoice_id.clone(), last));
+    set_list_len(env, &AuctionDataKey::Bids(invoice_id.clone()), last);
+    let (tokens, escrow) = get_bidder_totals(env, invoice_id, &bid.bidder);
+    let key = AuctionDataKey::BidderTotals(InvoiceKey { invoice_id: invoice_id.clone(), holder: bid.bidder.clone() });
+    if tokens == bid.token_amount {
+        env.storage().persistent().remove(&key);
+    } else {
+        set_entry(env, &key, &(tokens - bid.token_amount, escrow - bid.escrow));
+    }
+    add_bid_escrow(env, &bid.bidder, -bid.escrow);
+    Some(bid)
+}
+
+/// (tokens, escrow) the bidder has in the invoice's open bids
+pub fn get_bidder_totals(env: &Env, invoice_id: &String, bidder: &Address) -> (i128, i128) {
+    env.storage().persistent().get(&AuctionDataKey::BidderTotals(InvoiceKey { invoice_id: invoice_id.clone(), holder: bidder.clone() })).unwrap_or((0, 0))
+}
+
+fn add_bidder_totals(env: &Env, invoice_id: &String, bidder: &Address, tokens: i128, escrow: i128) {
+    let (held_tokens, held_escrow) = get_bidder_totals(env, invoice_id, bidder);
+    set_entry(env, &AuctionDataKey::BidderTotals(InvoiceKey { invoice_id: invoice_id.clone(), holder: bidder.clone() }), &(held_tokens + tokens, held_escrow + escrow));
+    add_bid_escrow(env, bidder, escrow);
+}
+
+/// Adds the invoice's open bids to their bidders' escrow totals; used to backfill the totals
+pub fn index_auction_bids(env: &Env, invoice_id: &String) {
+    for bid in get_auction_bids(env, invoice_id, 0, auction_bid_count(env, invoice_id)).iter() {
+        add_bid_escrow(env, &bid.bidder, bid.escrow);
+    }
+}
+
+/// Escrow the bidder has in open bids on any invoice
+pub fn get_bid_escrow(env: &Env, bidder: &Address) -> i128 {
+    env.storage().persistent().get(&AuctionDataKey::BidEscrow(bidder.clone())).unwrap_or(0)
+}
+
+fn add_bid_escrow(env: &Env, bidder: &Address, delta: i128) {
+    let key = AuctionDataKey::BidEscrow(bidder.clone());
+    let escrow = get_bid_escrow(env, bidder) + delta;
+    if escrow == 0 { env.storage().persistent().remove(&key); return; }
+    env.storage().persistent().set(&key, &escrow);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Tokens the supplier kept out of the invoice's auction and the minimum ticket size, (0, 0) if none
+pub fn get_auction_reserve(env: &Env, invoice_id: &String) -> (i128, i128) {
+    env.storage().persistent().get(&AuctionDataKey::Reserve(invoice_id.clone())).unwrap_or((0, 0))
+}
+
+pub fn set_auction_reserve(env: &Env, invoice_id: &String, reserved_tokens: i128, min_ticket: i128) {
+    let key = AuctionDataKey::Reserve(invoice_id.clone());
+    if reserved_tokens == 0 && min_ticket == 0 { env.storage().persistent().remove(&key); return; }
+    env.storage().persistent().set(&key, &(reserved_tokens, min_ticket));
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// SUB-VENDOR SPLIT STORAGE
+// ============================================================================
+
+pub fn get_subvendor_splits(env: &Env, invoice_id: &String) -> Vec<SubvendorSplit> {
+    env.storage().persistent().get(&SupplyChainDataKey::SubvendorSplits(invoice_id.clone())).unwrap_or(Vec::new(env))
+}
+
+pub fn set_subvendor_splits(env: &Env, invoice_id: &String, splits: &Vec<SubvendorSplit>) {
+    let key = SupplyChainDataKey::SubvendorSplits(invoice_id.clone());
+    env.storage().persistent().set(&key, splits);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn distribution_count(env: &Env, invoice_id: &String) -> u32 {
+    list_len(env, &SupplyChainDataKey::Distributions(invoice_id.clone()))
+}
+
+pub fn get_subvendor_distributions(env: &Env, invoice_id: &String, start: u32, limit: u32) -> Vec<SubvendorDistribution> {
+    list_page(env, distribution_count(env, invoice_id), start, limit, |index| SupplyChainDataKey::Distribution(invoice_id.clone(), index))
+}
+
+pub fn add_subvendor_distribution(env: &Env, invoice_id: &String, distribution: &SubvendorDistribution) {
+    let count = distribution_count(env, invoice_id);
+    set_entry(env, &SupplyChainDataKey::Distribution(invoice_id.clone(), count), distribution);
+    set_list_len(env, &SupplyChainDataKey::Distributions(invoice_id.clone()), count + 1);
+}
+
+pub fn next_transfer_id(env: &Env) -> u64 {
+    let next = env.storage().instance().get(&SupplyChainDataKey::TransferCounter).unwrap_or(0u64) + 1;
+    env.storage().instance().set(&SupplyChainDataKey::TransferCounter, &next);
+    next
+}
+
+pub fn get_pending_transfer(env: &Env, transfer_id: u64) -> Option<PendingTransfer> {
+    env.storage().persistent().get(&SupplyChainDataKey::PendingTransfer(transfer_id))
+}
+
+/// Number of transfers offered to `to` that it has not accepted and the sender has not reclaimed
+pub fn pending_transfer_count(env: &Env, to: &Address) -> u32 {
+    list_len(env, &SupplyChainDataKey::PendingByRecipient(to.clone()))
+}
+
+/// Ids of the transfers pending for `to`, in no particular order
+pub fn get_pending_transfer_ids(env: &Env, to: &Address, start: u32, limit: u32) -> Vec<u64> {
+    list_page(env, pending_transfer_count(env, to), start, limit, |slot| SupplyChainDataKey::PendingEntry(to.clone(), slot))
+}
+
+/// Saves the offer and locks its tokens in the sender's holding
+pub fn add_pending_transfer(env: &Env, transfer: &PendingTransfer) {
+    set_entry(env, &SupplyChainDataKey::PendingTransfer(transfer.id), transfer);
+    let count = pending_transfer_count(env, &transfer.to);
+    set_entry(env, &SupplyChainDataKey::PendingEntry(transfer.to.clone(), count), &transfer.id);
+    set_entry(env, &SupplyChainDataKey::PendingPosition(transfer.id), &count);
+    set_list_len(env, &SupplyChainDataKey::PendingByRecipient(transfer.to.clone()), count + 1);
+    let sender = InvoiceKey { invoice_id: transfer.invoice_id.clone(), holder: transfer.from.clone() };
+    let sent = list_len(env, &SupplyChainDataKey::OffersBySender(sender.clone()));
+    set_entry(env, &SupplyChainDataKey::SenderEntry(sender.clone(), sent), &transfer.id);
+    set_entry(env, &SupplyChainDataKey::SenderPosition(transfer.id), &sent);
+    set_list_len(env, &SupplyChainDataKey::OffersBySender(sender), sent + 1);
+    add_locked_tokens(env, &transfer.invoice_id, &transfer.from, transfer.amount);
+}
+
+/// Drops the offer and unlocks its tokens. The caller moves them (accept) or leaves them (reclaim).
+/// The recipient's last offer takes the freed slot.
+pub fn remove_pending_transfer(env: &Env, transfer: &PendingTransfer) {
+    let store = env.storage().persistent();
+    store.remove(&SupplyChainDataKey::PendingTransfer(transfer.id));
+    let last = pending_transfer_count(env, &transfer.to) - 1;
+    let slot: u32 = store.get(&SupplyChainDataKey::PendingPosition(transfer.id)).unwrap_or(last);
+    if slot != last {
+        let moved: u64 = store.get(&SupplyChainDataKey::PendingEntry(transfer.to.clone(), last)).unwrap();
+        set_entry(env, &SupplyChainDataKey::PendingEntry(transfer.to.clone(), slot), &moved);
+        set_entry(env, &SupplyChainDataKey::PendingPosition(moved), &slot);
+    }
+    store.remove(&SupplyChainDataKey::PendingEntry(transfer.to.clone(), last));
+    store.remove(&SupplyChainDataKey::PendingPosition(transfer.id));
+    set_list_len(env, &SupplyChainDataKey::PendingByRecipient(transfer.to.clone()), last);
+
+    let sender = InvoiceKey { invoice_id: transfer.invoice_id.clone(), holder: transfer.from.clone() };
+    let last = offer_count_from(env, &transfer.invoice_id, &transfer.from).saturating_sub(1);
+    let slot: u32 = store.get(&SupplyChainDataKey::SenderPosition(transfer.id)).unwrap_or(last);
+    if slot != last {
+        let moved: u64 = store.get(&SupplyChainDataKey::SenderEntry(sender.clone(), last)).unwrap();
+        set_entry(env, &SupplyChainDataKey::SenderEntry(sender.clone(), slot), &moved);
+        set_entry(env, &SupplyChainDataKey::SenderPosition(moved), &slot);
+    }
+    store.remove(&SupplyChainDataKey::SenderEntry(sender.clone(), last));
+    store.remove(&SupplyChainDataKey::SenderPosition(transfer.id));
+    set_list_len(env, &SupplyChainDataKey::OffersBySender(sender), last);
+    add_locked_tokens(env, &transfer.invoice_id, &transfer.from, -transfer.amount);
+}
+
+/// Number of pending offers out of the holder's holding in the invoice
+pub fn offer_count_from(env: &Env, invoice_id: &String, holder: &Address) -> u32 {
+    list_len(env, &SupplyChainDataKey::OffersBySender(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() }))
+}
+
+/// Cancels every pending offer out of the holding, unlocking its tokens. For when the holding
+/// is clawed back, redeemed or deleted underneath its offers.
+pub fn release_locks(env: &Env, invoice_id: &String, holder: &Address) {
+    let sender = InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() };
+    for slot in (0..offer_count_from(env, invoice_id, holder)).rev() {
+        let Some(transfer_id) = env.storage().persistent().get::<_, u64>(&SupplyChainDataKey::SenderEntry(sender.clone(), slot)) else { continue };
+        if let Some(transfer) = get_pending_transfer(env, transfer_id) { remove_pending_transfer(env, &transfer); }
+    }
+}
+
+/// Tokens in the holder's holding promised to pending transfers
+pub fn get_locked_tokens(env: &Env, invoice_id: &String, holder: &Address) -> i128 {
+    let key = SupplyChainDataKey::LockedTokens(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    env.storage().persistent().get(&key).unwrap_or(0)
+}
+
+fn add_locked_tokens(env: &Env, invoice_id: &String, holder: &Address, delta: i128) {
+    let key = SupplyChainDataKey::LockedTokens(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    let locked = get_locked_tokens(env, invoice_id, holder) + delta;
+    if locked == 0 { env.storage().persistent().remove(&key); return; }
+    env.storage().persistent().set(&key, &locked);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// DUE-DATE EXTENSION STORAGE
+// ============================================================================
+
+pub fn get_extension_proposal(env: &Env, invoice_id: &String) -> Option<ExtensionProposal> {
+    env.storage().persistent().get(&ExtensionDataKey::Proposal(invoice_id.clone()))
+}
+
+pub fn set_extension_proposal(env: &Env, invoice_id: &String, proposal: &ExtensionProposal) {
+    let key = ExtensionDataKey::Proposal(invoice_id.clone());
+    env.storage().persistent().set(&key, proposal);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Drops a decided proposal along with its votes
+pub fn remove_extension_proposal(env: &Env, invoice_id: &String) {
+    env.storage().persistent().remove(&ExtensionDataKey::Proposal(invoice_id.clone()));
+    for vote in get_extension_votes(env, invoice_id, 0, extension_vote_count(env, invoice_id)).iter() {
+        env.storage().persistent().remove(&ExtensionDataKey::VoteSlot(InvoiceKey { invoice_id: invoice_id.clone(), holder: vote.holder }));
+    }
+    clear_list(env, &ExtensionDataKey::Votes(invoice_id.clone()), |index| ExtensionDataKey::Vote(invoice_id.clone(), index));
+}
+
+pub fn extension_vote_count(env: &Env, invoice_id: &String) -> u32 {
+    list_len(env, &ExtensionDataKey::Votes(invoice_id.clone()))
+}
+
+pub fn get_extension_votes(env: &Env, invoice_id: &String, start: u32, limit: u32) -> Vec<ExtensionVote> {
+    list_page(env, extension_vote_count(env, invoice_id), start, limit, |index| ExtensionDataKey::Vote(invoice_id.clone(), index))
+}
+
+/// Records the holder's vote, replacing any earlier one in place
+pub fn set_extension_vote(env: &Env, invoice_id: &String, vote: &ExtensionVote) {
+    let slot_key = ExtensionDataKey::VoteSlot(InvoiceKey { invoice_id: invoice_id.clone(), holder: vote.holder.clone() });
+    let index = match env.storage().persistent().get(&slot_key) {
+        Some(index) => index,
+        None => {
+            let count = extension_vote_count(env, invoice_id);
+            set_entry(env, &slot_key, &count);
+            set_list_len(env, &ExtensionDataKey::Votes(invoice_id.clone()), count + 1);
+            count
+        }
+    };
+    set_entry(env, &ExtensionDataKey::Vote(invoice_id.clone(), index), vote);
+}
+
+pub fn get_due_date_extension(env: &Env, invoice_id: &String) -> Option<DueDateExtension> {
+    env.storage().persistent().get(&ExtensionDataKey::Applied(invoice_id.clone()))
+}
+
+pub fn set_due_date_extension(env: &Env, invoice_id: &String, extension: &DueDateExtension) {
+    let key = ExtensionDataKey::Applied(invoice_id.clone());
+    env.storage().persistent().set(&key, extension);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// DISPUTE STORAGE
+// ============================================================================
+
+pub fn get_dispute(env: &Env, invoice_id: &String) -> Option<Dispute> {
+    let key = DataKey::Dispute(invoice_id.clone());
+    env.storage().persistent().get(&key)
+}
+
+pub fn set_dispute(env: &Env, invoice_id: &String, dispute: &Dispute) {
+    let key = DataKey::Dispute(invoice_id.clone());
+    env.storage().persistent().set(&key, dispute);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Addresses appointed to vote on disputes
+pub fn get_arbitrators(env: &Env) -> Vec<Address> {
+    env.storage().instance().get(&DisputeDataKey::Arbitrators).unwrap_or(Vec::new(env))
+}
+
+pub fn set_arbitrators(env: &Env, arbitrators: &Vec<Address>) {
+    env.storage().instance().set(&DisputeDataKey::Arbitrators, arbitrators);
+}
+
+/// Votes a dispute needs before the panel can rule; 0 leaves disputes to the program admin
+pub fn get_arbitration_quorum(env: &Env) -> u32 {
+    env.storage().instance().get(&DisputeDataKey::ArbitrationQuorum).unwrap_or(0)
+}
+
+pub fn set_arbitration_quorum(env: &Env, quorum: u32) {
+    env.storage().instance().set(&DisputeDataKey::ArbitrationQuorum, &quorum);
+}
+
+pub fn evidence_count(env: &Env, invoice_id: &String) -> u32 {
+    list_len(env, &DisputeDataKey::Evidence(invoice_id.clone()))
+}
+
+pub fn get_evidence(env: &Env, invoice_id: &String, start: u32, limit: u32) -> Vec<Evidence> {
+    list_page(env, evidence_count(env, invoice_id), start, limit, |index| DisputeDataKey::EvidenceEntry(invoice_id.clone(), index))
+}
+
+pub fn add_evidence(env: &Env, invoice_id: &String, evidence: &Evidence) {
+    let count = evidence_count(env, invoice_id);
+    set_entry(env, &DisputeDataKey::EvidenceEntry(invoice_id.clone(), count), evidence);
+    set_list_len(env, &DisputeDataKey::Evidence(invoice_id.clone()), count + 1);
+}
+
+pub fn vote_count(env: &Env, invoice_id: &String) -> u32 {
+    list_len(env, &DisputeDataKey::Votes(invoice_id.clone()))
+}
+
+pub fn get_votes(env: &Env, invoice_id: &String, start: u32, limit: u32) -> Vec<ArbitrationVote> {
+    list_page(env, vote_count(env, invoice_id), start, limit, |index| DisputeDataKey::Vote(invoice_id.clone(), index))
+}
+
+pub fn has_voted(env: &Env, invoice_id: &String, arbitrator: &Address) -> bool {
+    env.storage().persistent().has(&DisputeDataKey::VoteSlot(InvoiceKey { invoice_id: invoice_id.clone(), holder: arbitrator.clone() }))
+}
+
+/// Records the arbitrator's vote, replacing any earlier one in place
+pub fn set_vote(env: &Env, invoice_id: &String, vote: &ArbitrationVote) {
+    let slot_key = DisputeDataKey::VoteSlot(InvoiceKey { invoice_id: invoice_id.clone(), holder: vote.arbitrator.clone() });
+    let index = match env.storage().persistent().get(&slot_key) {
+        Some(index) => index,
+        None => {
+            let count = vote_count(env, invoice_id);
+            set_entry(env, &slot_key, &count);
+            set_list_len(env, &DisputeDataKey::Votes(invoice_id.clone()), count + 1);
+            count
+        }
+    };
+    set_entry(env, &DisputeDataKey::Vote(invoice_id.clone(), index), vote);
+}
+
+/// Drops a dispute's evidence and panel votes
+pub fn clear_arbitration(env: &Env, invoice_id: &String) {
+    for vote in get_votes(env, invoice_id, 0, vote_count(env, invoice_id)).iter() {
+        env.storage().persistent().remove(&DisputeDataKey::VoteSlot(InvoiceKey { invoice_id: invoice_id.clone(), holder: vote.arbitrator }));
+    }
+    clear_list(env, &DisputeDataKey::Votes(invoice_id.clone()), |index| DisputeDataKey::Vote(invoice_id.clone(), index));
+    clear_list(env, &DisputeDataKey::Evidence(invoice_id.clone()), |index| DisputeDataKey::EvidenceEntry(invoice_id.clone(), index));
+}
+
+pub fn get_dispute_limits(env: &Env) -> DisputeLimits {
+    env.storage().instance().get(&DisputeDataKey::Limits).unwrap_or_default()
+}
+
+pub fn set_dispute_limits(env: &Env, limits: &DisputeLimits) {
+    env.storage().instance().set(&DisputeDataKey::Limits, limits);
+}
+
+/// When the buyer's rejected disputes were ruled on, oldest first
+pub fn get_dispute_rejections(env: &Env, buyer: &Address) -> Vec<u64> {
+    env.storage().persistent().get(&DisputeDataKey::Rejections(buyer.clone())).unwrap_or(Vec::new(env))
+}
+
+/// Records a rejection at `at`, dropping those that fell out of the window
+pub fn add_dispute_rejection(env: &Env, buyer: &Address, at: u64) {
+    let key = DisputeDataKey::Rejections(buyer.clone());
+    let since = at.saturating_sub(get_dispute_limits(env).window_seconds);
+    let mut rejections = Vec::new(env);
+    for rejected_at in get_dispute_rejections(env, buyer).iter() {
+        if rejected_at > since { rejections.push_back(rejected_at); }
+    }
+    rejections.push_back(at);
+    env.storage().persistent().set(&key, &rejections);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn is_dispute_cleared(env: &Env, invoice_id: &String) -> bool {
+    env.storage().persistent().get(&DisputeDataKey::Clearance(invoice_id.clone())).unwrap_or(false)
+}
+
+pub fn set_dispute_cleared(env: &Env, invoice_id: &String, cleared: bool) {
+    let key = DisputeDataKey::Clearance(invoice_id.clone());
+    if !cleared { env.storage().persistent().remove(&key); return; }
+    env.storage().persistent().set(&key, &true);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Bond the buyer posted to raise the invoice's open dispute (0 if none)
+pub fn get_dispute_bond(env: &Env, invoice_id: &String) -> i128 {
+    env.storage().persistent().get(&DisputeDataKey::Bond(invoice_id.clone())).unwrap_or(0)
+}
+
+pub fn set_dispute_bond(env: &Env, invoice_id: &String, bond: i128) {
+    let key = DisputeDataKey::Bond(invoice_id.clone());
+    if bond == 0 { env.storage().persistent().remove(&key); return; }
+    env.storage().persistent().set(&key, &bond);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// PREPAYMENT STORAGE
+// ============================================================================
+
+pub fn get_prepayments(env: &Env, invoice_id: &String) -> Vec<Prepayment> {
+    let key = DataKey::Prepayments(invoice_id.clone());
+    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+}
+
+pub fn add_prepayment(env: &Env, invoice_id: &String, prepayment: &Prepayment) {
+    let key = DataKey::Prepayments(invoice_id.clone());
+    let mut prepayments = get_prepayments(env, invoice_id);
+    prepayments.push_back(prepayment.clone());
+    env.storage().persistent().set(&key, &prepayments);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    set_escrow_total(env, get_escrow_total(env) + prepayment.amount);
+}
+
+pub fn get_prepaid_total(env: &Env, invoice_id: &String) -> i128 {
+    let mut total = 0;
+    for prepayment in get_prepayments(env, invoice_id).iter() {
+        total += prepayment.amount;
+    }
+    total
+}
+
+pub fn clear_prepayments(env: &Env, invoice_id: &String) {
+    let released = get_prepaid_total(env, invoice_id);
+    let key = DataKey::Prepayments(invoice_id.clone());
+    env.storage().persistent().remove(&key);
+    set_escrow_total(env, get_escrow_total(env) - released);
+}
+
+/// Prepayments, bid escrow and below-threshold investments held across all invoices, in the funding token
+pub fn get_escrow_total(env: &Env) -> i128 {
+    env.storage().instance().get(&DataKey::EscrowTotal).unwrap_or(0)
+}
+
+fn set_escrow_total(env: &Env, total: i128) {
+    env.storage().instance().set(&DataKey::EscrowTotal, &total);
+}
+
+pub fn add_to_escrow(env: &Env, amount: i128) {
+    set_escrow_total(env, get_escrow_total(env) + amount);
+}
+
+pub fn release_from_escrow(env: &Env, amount: i128) {
+    set_escrow_total(env, get_escrow_total(env) - amount);
+}
+
+// ============================================================================
+// BALANCE CONFIRMATION STORAGE
+// ============================================================================
+
+pub fn get_balance_confirmation(env: &Env, invoice_id: &String) -> Option<BalanceConfirmation> {
+    let key = DataKey::BalanceConfirmation(invoice_id.clone());
+    env.storage().persistent().get(&key)
+}
+
+pub fn set_balance_confirmation(env: &Env, invoice_id: &String, confirmation: &BalanceConfirmation) {
+    let key = DataKey::BalanceConfirmation(invoice_id.clone());
+    env.storage().persistent().set(&key, confirmation);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// TOKEN HOLDING STORAGE
+// ============================================================================
+
+pub fn get_token_holding(env: &Env, invoice_id: &String, holder: &Address) -> Option<TokenHolding> {
+    let key = DataKey::TokenHolding(InvoiceKey {
+        invoice_id: invoice_id.clone(),
+        holder: holder.clone(),
+    });
+    env.storage().persistent().get(&key)
+}
+
+/// Saves a holding, recording a lot for any tokens it gained and consuming its oldest lots
+/// for any it lost
+pub fn set_token_holding(env: &Env, invoice_id: &String, holder: &Address, holding: &TokenHolding) {
+    let key = DataKey::TokenHolding(InvoiceKey {
+        invoice_id: invoice_id.clone(),
+        holder: holder.clone(),
+    });
+    let previous = get_token_holding(env, invoice_id, holder);
+    let mut lots = previous.as_ref().map(|p| get_token_lots(env, p)).unwrap_or(Vec::new(env));
+    let change = holding.amount - previous.map(|p| p.amount).unwrap_or(0);
+    if change > 0 {
+        lots.push_back(TokenLot { amount: change, acquired_at: env.ledger().timestamp() });
+        set_token_lots(env, invoice_id, holder, &lots);
+    } else if change < 0 {
+        set_token_lots(env, invoice_id, holder, &consume_lots(&lots, -change));
+    }
+    env.storage().persistent().set(&key, holding);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    add_holder_to_list(env, invoice_id, holder);
+}
+
+pub fn remove_token_holding(env: &Env, invoice_id: &String, holder: &Address) {
+    release_locks(env, invoice_id, holder);
+    let key = DataKey::TokenHolding(InvoiceKey {
+        invoice_id: invoice_id.clone(),
+        holder: holder.clone(),
+    });
+    env.storage().persistent().remove(&key);
+    env.storage().persistent().remove(&InvestmentDataKey::Lots(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() }));
+    remove_holder_from_list(env, invoice_id, holder);
+}
+
+/// A holding's acquisition lots, oldest first. Tokens held before lots were tracked show up
+/// as one leading lot dated at the holding's first acquisition.
+pub fn get_token_lots(env: &Env, holding: &TokenHolding) -> Vec<TokenLot> {
+    let key = InvestmentDataKey::Lots(InvoiceKey { invoice_id: holding.invoice_id.clone(), holder: holding.holder.clone() });
+    let mut lots: Vec<TokenLot> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+    let untracked = holding.amount - lots.iter().map(|lot| lot.amount).sum::<i128>();
+    if untracked > 0 { lots.push_front(TokenLot { amount: untracked, acquired_at: holding.acquired_at }); }
+    lots
+}
+
+/// Replaces a holding's lots; for callers that remove specific lots rather than the oldest
+pub fn set_token_lots(env: &Env, invoice_id: &String, holder: &Address, lots: &Vec<TokenLot>) {
+    let key = InvestmentDataKey::Lots(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    if lots.is_empty() {
+        env.storage().persistent().remove(&key);
+        return;
+    }
+    env.storage().persistent().set(&key, lots);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// `lots` less `amount` tokens taken from the oldest first
+fn consume_lots(lots: &Vec<TokenLot>, amount: i128) -> Vec<TokenLot> {
+    let mut remaining = lots.clone();
+    let mut left = amount;
+    while left > 0 {
+        let Some(mut lot) = remaining.pop_front() else { break };
+        if lot.amount > left {
+            lot.amount -= left;
+            remaining.push_front(lot);
+            break;
+        }
+        left -= lot.amount;
+    }
+    remaining
+}
+
+// ============================================================================
+// HOLDER LIST STORAGE
+// ============================================================================
+
+pub fn get_all_holders(env: &Env, invoice_id: &String) -> Vec<Address> {
+    let key = DataKey::HolderList(invoice_id.clone());
+    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+}
+
+fn add_holder_to_list(env: &Env, invoice_id: &String, holder: &Address) {
+    let key = DataKey::HolderList(invoice_id.clone());
+    let mut holders: Vec<Address> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+    
+    let mut found = false;
+    for existing in holders.iter() {
+        if existing == *holder {
+            found = true;
+            break;
+        }
+    }
+    
+    if !found {
+        holders.push_back(holder.clone());
+        env.storage().persistent().set(&key, &holders);
+        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    }
+}
+
+fn remove_holder_from_list(env: &Env, invoice_id: &String, holder: &Address) {
+    let key = DataKey::HolderList(invoice_id.clone());
+    let holders: Vec<Address> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+    
+    let mut new_holders = Vec::new(env);
+    for existing in holders.iter() {
+        if existing != *holder {
+            new_holders.push_back(existing);
+        }
+    }
+    env.storage().persistent().set(&key, &new_holders);
+}
+
+pub fn clear_token_holdings(env: &Env, invoice_id: &String) {
+    let holders = get_all_holders(env, invoice_id);
+    for holder in holders.iter() {
+        release_locks(env, invoice_id, &holder);
+        let key = DataKey::TokenHolding(InvoiceKey {
+            invoice_id: invoice_id.clone(),
+            holder: holder.clone(),
+        });
+        env.storage().persistent().remove(&key);
+        env.storage().persistent().remove(&InvestmentDataKey::Lots(InvoiceKey { invoice_id: invoice_id.clone(), holder }));
+    }
+    let list_key = DataKey::HolderList(invoice_id.clone());
+    env.storage().persistent().remove(&list_key);
+}
+
+
+// ============================================================================
+// SUPPLIER INDEX STORAGE
+// ============================================================================
+
+pub fn get_supplier_invoices(env: &Env, supplier: &Address) -> Vec<String> {
+    let key = DataKey::SupplierInvoices(supplier.clone());
+    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+}
+
+pub fn add_supplier_invoice(env: &Env, supplier: &Address, invoice_id: &String) {
+    let key = DataKey::SupplierInvoices(supplier.clone());
+    let mut invoices = get_supplier_invoices(env, supplier);
+    invoices.push_back(invoice_id.clone());
+    env.storage().persistent().set(&key, &invoices);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// LISTING INDEX STORAGE
+// ============================================================================
+
+/// Number of invoices billed to the buyer
+pub fn buyer_invoice_count(env: &Env, buyer: &Address) -> u32 {
+    list_len(env, &IndexDataKey::InvoicesByBuyer(buyer.clone()))
+}
+
+/// Invoices billed to the buyer `[start, start + limit)`, in issuance order until one is purged
+pub fn get_buyer_invoices(env: &Env, buyer: &Address, start: u32, limit: u32) -> Vec<String> {
+    list_page(env, buyer_invoice_count(env, buyer), start, limit, |slot| IndexDataKey::BuyerEntry(buyer.clone(), slot))
+}
+
+pub fn add_buyer_invoice(env: &Env, buyer: &Address, invoice_id: &String) {
+    let count = buyer_invoice_count(env, buyer);
+    set_entry(env, &IndexDataKey::BuyerEntry(buyer.clone(), count), invoice_id);
+    set_entry(env, &IndexDataKey::BuyerPosition(invoice_id.clone()), &count);
+    set_list_len(env, &IndexDataKey::InvoicesByBuyer(buyer.clone()), count + 1);
+}
+
+/// Drops the invoice from its buyer's index; the buyer's last invoice takes the freed slot
+fn remove_buyer_invoice(env: &Env, buyer: &Address, invoice_id: &String) {
+    let store = env.storage().persistent();
+    let Some(slot) = store.get::<_, u32>(&IndexDataKey::BuyerPosition(invoice_id.clone())) else { return };
+    let last = buyer_invoice_count(env, buyer) - 1;
+    if slot != last {
+        let moved: String = store.get(&IndexDataKey::BuyerEntry(buyer.clone(), last)).unwrap();
+        set_entry(env, &IndexDataKey::BuyerEntry(buyer.clone(), slot), &moved);
+        set_entry(env, &IndexDataKey::BuyerPosition(moved), &slot);
+    }
+    store.remove(&IndexDataKey::BuyerEntry(buyer.clone(), last));
+    store.remove(&IndexDataKey::BuyerPosition(invoice_id.clone()));
+    set_list_len(env, &IndexDataKey::InvoicesByBuyer(buyer.clone()), last);
+}
+
+/// Number of invoices currently in `status`
+pub fn status_invoice_count(env: &Env, status: &InvoiceStatus) -> u32 {
+    list_len(env, &IndexDataKey::InvoicesByStatus(status.clone()))
+}
+
+/// Invoices currently in `status` `[start, start + limit)`. Leaving a status moves the
+/// bucket's last invoice into the freed slot, so the order is only stable between transitions.
+pub fn get_status_invoices(env: &Env, status: &InvoiceStatus, start: u32, limit: u32) -> Vec<String> {
+    list_page(env, status_invoice_count(env, status), start, limit, |slot| IndexDataKey::StatusEntry(status.clone(), slot))
+}
+
+fn add_status_entry(env: &Env, status: &InvoiceStatus, invoice_id: &String) {
+    let count = status_invoice_count(env, status);
+    set_entry(env, &IndexDataKey::StatusEntry(status.clone(), count), invoice_id);
+    set_entry(env, &IndexDataKey::StatusPosition(invoice_id.clone()), &count);
+    set_list_len(env, &IndexDataKey::InvoicesByStatus(status.clone()), count + 1);
+}
+
+fn remove_status_entry(env: &Env, status: &InvoiceStatus, invoice_id: &String) {
+    let store = env.storage().persistent();
+    let Some(slot) = store.get::<_, u32>(&IndexDataKey::StatusPosition(invoice_id.clone())) else { return };
+    let last = status_invoice_count(env, status) - 1;
+    if slot != last {
+        let moved: String = store.get(&IndexDataKey::StatusEntry(status.clone(), last)).unwrap();
+        set_entry(env, &IndexDataKey::StatusEntry(status.clone(), slot), &moved);
+        set_entry(env, &IndexDataKey::StatusPosition(moved), &slot);
+    }
+    store.remove(&IndexDataKey::StatusEntry(status.clone(), last));
+    store.remove(&IndexDataKey::StatusPosition(invoice_id.clone()));
+    set_list_len(env, &IndexDataKey::InvoicesByStatus(status.clone()), last);
+}
+
+/// Adds an invoice to the buyer and status indexes unless already there; used to backfill
+/// invoices issued before the indexes existed, one migration page at a time
+pub fn index_invoice(env: &Env, invoice: &Invoice) {
+    let store = env.storage().persistent();
+    if !store.has(&IndexDataKey::BuyerPosition(invoice.id.clone())) { add_buyer_invoice(env, &invoice.buyer, &invoice.id); }
+    if !store.has(&IndexDataKey::StatusPosition(invoice.id.clone())) { add_status_entry(env, &invoice.status, &invoice.id); }
+}
+
+/// Face value the invoice's buyer still owes on it: nothing while a draft, or once it is
+/// settled, revoked, recovered or written off
+pub fn outstanding_of(invoice: &Invoice) -> i128 {
+    let live = matches!(invoice.status,
+        InvoiceStatus::Verified | InvoiceStatus::Funding | InvoiceStatus::Funded
+        | InvoiceStatus::Overdue | InvoiceStatus::Disputed | InvoiceStatus::Defaulted);
+    if live { (invoice.amount - invoice.repayment_received - invoice.recovered_amount).max(0) } else { 0 }
+}
+
+pub fn get_buyer_outstanding(env: &Env, buyer: &Address) -> i128 {
+    env.storage().persistent().get(&IndexDataKey::BuyerOutstanding(buyer.clone())).unwrap_or(0)
+}
+
+pub fn add_buyer_outstanding(env: &Env, buyer: &Address, delta: i128) {
+    let key = IndexDataKey::BuyerOutstanding(buyer.clone());
+    let outstanding = get_buyer_outstanding(env, buyer) + delta;
+    if outstanding == 0 {
+        env.storage().persistent().remove(&key);
+    } else {
+        env.storage().persistent().set(&key, &outstanding);
+        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    }
+}
+
+/// Sequence number of the invoice's latest event (0 before its first)
+pub fn get_event_seq(env: &Env, invoice_id: &String) -> u64 {
+    env.storage().persistent().get(&IndexDataKey::EventSeq(invoice_id.clone())).unwrap_or(0)
+}
+
+/// Advances the invoice's event sequence and returns the number for the event being emitted
+pub fn next_event_seq(env: &Env, invoice_id: &String) -> u64 {
+    let key = IndexDataKey::EventSeq(invoice_id.clone());
+    let seq = get_event_seq(env, invoice_id) + 1;
+    env.storage().persistent().set(&key, &seq);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    seq
+}
+
+// ============================================================================
+// INVESTMENT RECORD STORAGE
+// ============================================================================
+
+pub fn get_investment_counter(env: &Env) -> u32 {
+    env.storage().instance().get(&InvestmentDataKey::InvestmentCounter).unwrap_or(0)
+}
+
+pub fn set_investment_counter(env: &Env, counter: u32) {
+    env.storage().instance().set(&InvestmentDataKey::InvestmentCounter, &counter);
+}
+
+pub fn get_investment(env: &Env, investment_id: &String) -> Option<Investment> {
+    env.storage().persistent().get(&InvestmentDataKey::Investment(investment_id.clone()))
+}
+
+pub fn set_investment(env: &Env, investment: &Investment) {
+    let key = InvestmentDataKey::Investment(investment.id.clone());
+    env.storage().persistent().set(&key, investment);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Stores a new record and adds it to its invoice's and investor's indexes
+pub fn add_investment(env: &Env, investment: &Investment) {
+    set_investment(env, investment);
+    let holding = InvoiceKey { invoice_id: investment.invoice_id.clone(), holder: investment.investor.clone() };
+    for key in [
+        InvestmentDataKey::InvestmentsByInvoice(investment.invoice_id.clone()),
+        InvestmentDataKey::InvestmentsByInvestor(investment.investor.clone()),
+        InvestmentDataKey::InvestmentsByHolding(holding),
+    ] {
+        let mut ids: Vec<String> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+        ids.push_back(investment.id.clone());
+        env.storage().persistent().set(&key, &ids);
+        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    }
+}
+
+/// Investment ids for an invoice, in purchase order
+pub fn get_invoice_investments(env: &Env, invoice_id: &String) -> Vec<String> {
+    env.storage().persistent().get(&InvestmentDataKey::InvestmentsByInvoice(invoice_id.clone())).unwrap_or(Vec::new(env))
+}
+
+/// Investment ids for one investor in one invoice, in purchase order
+pub fn get_holding_investments(env: &Env, invoice_id: &String, investor: &Address) -> Vec<String> {
+    let key = InvestmentDataKey::InvestmentsByHolding(InvoiceKey { invoice_id: invoice_id.clone(), holder: investor.clone() });
+    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+}
+
+/// Investment ids for an investor across all invoices, in purchase order
+pub fn get_investor_investments(env: &Env, investor: &Address) -> Vec<String> {
+    env.storage().persistent().get(&InvestmentDataKey::InvestmentsByInvestor(investor.clone())).unwrap_or(Vec::new(env))
+}
+
+pub fn get_supplier_proceeds(env: &Env, supplier: &Address) -> i128 {
+    let key = DataKey::SupplierProceeds(supplier.clone());
+    env.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn add_supplier_proceeds(env: &Env, supplier: &Address, amount: i128) {
+    let key = DataKey::SupplierProceeds(supplier.clone());
+    env.storage().persistent().set(&key, &(get_supplier_proceeds(env, supplier) + amount));
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// AUCTION RESULT STORAGE
+// ============================================================================
+
+pub fn get_auction_result(env: &Env, invoice_id: &String) -> Option<AuctionResult> {
+    let key = DataKey::AuctionResult(invoice_id.clone());
+    env.storage().persistent().get(&key)
+}
+
+pub fn set_auction_result(env: &Env, result: &AuctionResult) {
+    let key = DataKey::AuctionResult(result.invoice_id.clone());
+    env.storage().persistent().set(&key, result);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// DEFAULT QUEUE STORAGE
+// ============================================================================
+
+/// Invoices are queued for default in per-day buckets keyed by `due_date / DEFAULT_BUCKET_SECONDS`
+pub const DEFAULT_BUCKET_SECONDS: u64 = 86400;
+
+/// Due days with a non-empty default bucket, ascending. One entry per day rather than per
+/// invoice, so it stays small however many invoices fall due on the same day.
+pub fn get_default_days(env: &Env) -> Vec<u64> {
+    env.storage().persistent().get(&KeeperDataKey::DefaultDays).unwrap_or(Vec::new(env))
+}
+
+fn set_default_days(env: &Env, days: &Vec<u64>) {
+    env.storage().persistent().set(&KeeperDataKey::DefaultDays, days);
+    env.storage().persistent().extend_ttl(&KeeperDataKey::DefaultDays, 100_000, 200_000);
+}
+
+/// Invoices due on `day` that may still need defaulting, in the order they were queued
+pub fn get_default_bucket(env: &Env, day: u64) -> Vec<String> {
+    env.storage().persistent().get(&KeeperDataKey::DefaultBucket(day)).unwrap_or(Vec::new(env))
+}
+
+/// Saves a day's bucket, dropping it (and the day) once empty
+pub fn set_default_bucket(env: &Env, day: u64, bucket: &Vec<String>) {
+    let key = KeeperDataKey::DefaultBucket(day);
+    if bucket.is_empty() {
+        env.storage().persistent().remove(&key);
+        let mut days = get_default_days(env);
+        if let Ok(i) = days.binary_search(day) {
+            days.remove(i);
+            set_default_days(env, &days);
+        }
+        return;
+    }
+    env.storage().persistent().set(&key, bucket);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn enqueue_for_default(env: &Env, due_date: u64, invoice_id: &String) {
+    let day = due_date / DEFAULT_BUCKET_SECONDS;
+    let mut bucket = get_default_bucket(env, day);
+    if bucket.is_empty() {
+        let mut days = get_default_days(env);
+        if let Err(i) = days.binary_search(day) {
+            days.insert(i, day);
+            set_default_days(env, &days);
+        }
+    }
+    bucket.push_back(invoice_id.clone());
+    set_default_bucket(env, day, &bucket);
+}
+
+/// Takes an invoice out of the bucket for `due_date`, if it is queued there
+pub fn dequeue_for_default(env: &Env, due_date: u64, invoice_id: &String) {
+    let day = due_date / DEFAULT_BUCKET_SECONDS;
+    let mut bucket = get_default_bucket(env, day);
+    if let Some(i) = bucket.first_index_of(invoice_id) {
+        bucket.remove(i);
+        set_default_bucket(env, day, &bucket);
+    }
+}
+
+/// Entries of the single-list queue used before day buckets, for migration
+pub fn take_legacy_default_queue(env: &Env) -> Vec<(u64, String)> {
+    let queue = env.storage().persistent().get(&DataKey::DefaultQueue).unwrap_or(Vec::new(env));
+    env.storage().persistent().remove(&DataKey::DefaultQueue);
+    queue
+}
+
+// ============================================================================
+// GARBAGE COLLECTION
+// ============================================================================
+
+/// Deletes an invoice and its residual per-invoice keys. History and the event sequence are
+/// kept as the audit trail.
+pub fn purge_invoice(env: &Env, invoice: &Invoice) {
+    let store = env.storage().persistent();
+    for holder in get_all_holders(env, &invoice.id).iter() {
+        store.remove(&DataKey::InsuranceClaimed(InvoiceKey { invoice_id: invoice.id.clone(), holder: holder.clone() }));
+        store.remove(&SettlementDataKey::InsuranceOwed(InvoiceKey { invoice_id: invoice.id.clone(), holder: holder.clone() }));
+        store.remove(&SettlementDataKey::RecoveryReceived(InvoiceKey { invoice_id: invoice.id.clone(), holder }));
+    }
+    clear_token_holdings(env, &invoice.id);
+    take_auction_bids(env, &invoice.id);
+    store.remove(&AuctionDataKey::Reserve(invoice.id.clone()));
+    store.remove(&InvestmentDataKey::ConcentrationCap(invoice.id.clone()));
+    store.remove(&SupplyChainDataKey::SubvendorSplits(invoice.id.clone()));
+    clear_list(env, &SupplyChainDataKey::Distributions(invoice.id.clone()), |index| SupplyChainDataKey::Distribution(invoice.id.clone(), index));
+    for order_id in get_orders_for_invoice(env, &invoice.id).iter() {
+        store.remove(&DataKey::SellOrder(order_id));
+    }
+    store.remove(&DataKey::OrdersByInvoice(invoice.id.clone()));
+    // Open bids still hold escrow; keep them so their buyers can cancel
+    for order_id in get_buy_orders_for_invoice(env, &invoice.id).iter() {
+        if get_buy_order(env, &order_id).is_some_and(|o| o.status == OrderStatus::Cancelled || o.status == OrderStatus::Filled) {
+            store.remove(&DataKey::BuyOrder(order_id));
+        }
+    }
+    store.remove(&DataKey::BuyOrdersByInvoice(invoice.id.clone()));
+    store.remove(&DataKey::Dispute(invoice.id.clone()));
+    store.remove(&DataKey::BalanceConfirmation(invoice.id.clone()));
+    store.remove(&DataKey::AuctionResult(invoice.id.clone()));
+    store.remove(&DataKey::PrivateTerms(invoice.id.clone()));
+    store.remove(&MarketDataKey::TokenSymbol(invoice.token_symbol.clone()));
+    store.remove(&MarketDataKey::RoyaltyOverride(invoice.id.clone()));
+    store.remove(&SettlementDataKey::InsuranceAtDefault(invoice.id.clone()));
+    release_invoice_insurance(env, invoice);
+    clear_prepayments(env, &invoice.id);
+
+    remove_extension_proposal(env, &invoice.id);
+    store.remove(&DraftDataKey::Rejection(invoice.id.clone()));
+    clear_amendments(env, &invoice.id);
+    clear_arbitration(env, &invoice.id);
+    store.remove(&ExtensionDataKey::Applied(invoice.id.clone()));
+
+    remove_from_maturity_bucket(env, invoice.due_date, &invoice.id);
+    dequeue_for_default(env, invoice.due_date, &invoice.id);
+    remove_status_entry(env, &invoice.status, &invoice.id);
+    remove_buyer_invoice(env, &invoice.buyer, &invoice.id);
+    add_buyer_outstanding(env, &invoice.buyer, -outstanding_of(invoice));
+    let mut by_supplier = get_supplier_invoices(env, &invoice.supplier);
+    if let Some(i) = by_supplier.first_index_of(&invoice.id) {
+        by_supplier.remove(i);
+        store.set(&DataKey::SupplierInvoices(invoice.supplier.clone()), &by_supplier);
+    }
+    store.remove(&DataKey::Invoice(invoice.id.clone()));
+}
+
+/// Bounty paid from the protocol treasury to whoever collects a dead invoice
+pub fn get_gc_bounty(env: &Env) -> i128 {
+    env.storage().instance().get(&DataKey::GcBounty).unwrap_or(0)
+}
+
+pub fn set_gc_bounty(env: &Env, bounty: i128) {
+    env.storage().instance().set(&DataKey::GcBounty, &bounty);
+}
+
+/// Bounty paid from the insurance pool to whoever moves an invoice to Overdue or Defaulted
+pub fn get_keeper_bounty(env: &Env) -> i128 {
+    env.storage().instance().get(&KeeperDataKey::KeeperBounty).unwrap_or(0)
+}
+
+pub fn set_keeper_bounty(env: &Env, bounty: i128) {
+    env.storage().instance().set(&KeeperDataKey::KeeperBounty, &bounty);
+}
+
+// ============================================================================
+// REDEMPTION RECEIPT STORAGE
+// ============================================================================
+
+pub fn get_receipt(env: &Env, invoice_id: &String, holder: &Address) -> Option<RedemptionReceipt> {
+    let key = DataKey::Receipt(InvoiceKey {
+        invoice_id: invoice_id.clone(),
+        holder: holder.clone(),
+    });
+    env.storage().persistent().get(&key)
+}
+
+pub fn set_receipt(env: &Env, receipt: &RedemptionReceipt) {
+    let key = DataKey::Receipt(InvoiceKey {
+        invoice_id: receipt.invoice_id.clone(),
+        holder: receipt.holder.clone(),
+    });
+    env.storage().persistent().set(&key, receipt);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// KYC STORAGE
+// ============================================================================
+
+pub fn get_kyc_status(env: &Env, investor: &Address) -> bool {
+    let key = DataKey::KycStatus(investor.clone());
+    env.storage().persistent().get(&key).unwrap_or(false)
+}
+
+pub fn set_kyc_status(env: &Env, investor: &Address, approved: bool) {
+    let key = DataKey::KycStatus(investor.clone());
+    index_kyc_address(env, 0, investor);
+    env.storage().persistent().set(&key, &approved);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Addresses with a KYC status on a program's list (the global list for program 0), in the
+/// order they were indexed (for exports)
+pub fn get_kyc_index_count(env: &Env, program_id: u32) -> u32 {
+    if program_id == 0 { return env.storage().instance().get(&AccessDataKey::KycIndexCount).unwrap_or(0); }
+    list_len(env, &ProgramDataKey::ProgramKycCount(program_id))
+}
+
+pub fn get_kyc_index(env: &Env, program_id: u32, position: u32) -> Option<Address> {
+    if program_id == 0 { return env.storage().persistent().get(&AccessDataKey::KycIndex(position)); }
+    env.storage().persistent().get(&ProgramDataKey::ProgramKycIndex(program_id, position))
+}
+
+/// Appends the investor to a program's export index unless already there. Returns whether it was added.
+pub fn index_kyc_address(env: &Env, program_id: u32, investor: &Address) -> bool {
+    let position = get_kyc_index_count(env, program_id);
+    if program_id == 0 {
+        let marker = AccessDataKey::KycIndexed(investor.clone());
+        if env.storage().persistent().has(&marker) { return false; }
+        set_entry(env, &marker, &true);
+        set_entry(env, &AccessDataKey::KycIndex(position), investor);
+        env.storage().instance().set(&AccessDataKey::KycIndexCount, &(position + 1));
+    } else {
+        let marker = ProgramDataKey::ProgramKycIndexed(ProgramKey { program_id, addr: investor.clone() });
+        if env.storage().persistent().has(&marker) { return false; }
+        set_entry(env, &marker, &true);
+        set_entry(env, &ProgramDataKey::ProgramKycIndex(program_id, position), investor);
+        set_list_len(env, &ProgramDataKey::ProgramKycCount(program_id), position + 1);
+    }
+    true
+}
+
+/// Marks the global index entry at `position` as indexed. Entries written before the marker
+/// existed are marked by migrate so a backfill can't list them twice.
+pub fn mark_kyc_index_entry(env: &Env, position: u32) {
+    if let Some(investor) = get_kyc_index(env, 0, position) { set_entry(env, &AccessDataKey::KycIndexed(investor), &true); }
+}
+
+/// Whether the investor has any KYC status, approved or revoked, on a program's list
+pub fn has_program_kyc_status(env: &Env, program_id: u32, investor: &Address) -> bool {
+    if program_id == 0 { return env.storage().persistent().has(&DataKey::KycStatus(investor.clone())); }
+    env.storage().persistent().has(&ProgramDataKey::ProgramKyc(ProgramKey { program_id, addr: investor.clone() }))
+}
+
+/// KYC status on a program's own list; the global list for program 0
+pub fn get_program_kyc_status(env: &Env, program_id: u32, investor: &Address) -> bool {
+    if program_id == 0 { return get_kyc_status(env, investor); }
+    let key = ProgramDataKey::ProgramKyc(ProgramKey { program_id, addr: investor.clone() });
+    env.storage().persistent().get(&key).unwrap_or(false)
+}
+
+pub fn set_program_kyc_status(env: &Env, program_id: u32, investor: &Address, approved: bool) {
+    if program_id == 0 { return set_kyc_status(env, investor, approved); }
+    let key = ProgramDataKey::ProgramKyc(ProgramKey { program_id, addr: investor.clone() });
+    index_kyc_address(env, program_id, investor);
+    env.storage().persistent().set(&key, &approved);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// INVESTOR RISK STORAGE
+// ============================================================================
+
+/// Invoices the investor has held tokens in, in first-acquired order
+pub fn get_investor_invoices(env: &Env, investor: &Address) -> Vec<String> {
+    let key = DataKey::InvestorInvoices(investor.clone());
+    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+}
+
+pub fn add_investor_invoice(env: &Env, investor: &Address, invoice_id: &String) {
+    let key = DataKey::InvestorInvoices(investor.clone());
+    let mut invoices = get_investor_invoices(env, investor);
+    if !invoices.contains(invoice_id) {
+        invoices.push_back(invoice_id.clone());
+        env.storage().persistent().set(&key, &invoices);
+    }
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn get_realized_loss(env: &Env, investor: &Address) -> i128 {
+    let key = DataKey::RealizedLoss(investor.clone());
+    env.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn add_realized_loss(env: &Env, investor: &Address, loss: i128) {
+    let key = DataKey::RealizedLoss(investor.clone());
+    let total = get_realized_loss(env, investor) + loss;
+    env.storage().persistent().set(&key, &total);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn get_investor_totals(env: &Env, investor: &Address) -> InvestorTotals {
+    let key = DataKey::InvestorTotals(investor.clone());
+    env.storage().persistent().get(&key).unwrap_or_default()
+}
+
+fn set_investor_totals(env: &Env, investor: &Address, totals: &InvestorTotals) {
+    let key = DataKey::InvestorTotals(investor.clone());
+    env.storage().persistent().set(&key, totals);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn record_settled_position(env: &Env, investor: &Address, cost: i128, received: i128) {
+    let mut totals = get_investor_totals(env, investor);
+    totals.settled_cost += cost;
+    totals.settled_received += received;
+    set_investor_totals(env, investor, &totals);
+}
+
+pub fn record_insurance_recovery(env: &Env, investor: &Address, amount: i128) {
+    let mut totals = get_investor_totals(env, investor);
+    totals.insurance_recovered += amount;
+    set_investor_totals(env, investor, &totals);
+}
+
+/// Max at-risk exposure for the investor (0 = no limit)
+pub fn get_exposure_limit(env: &Env, investor: &Address) -> i128 {
+    let key = DataKey::ExposureLimit(investor.clone());
+    env.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn set_exposure_limit(env: &Env, investor: &Address, limit: i128) {
+    let key = DataKey::ExposureLimit(investor.clone());
+    env.storage().persistent().set(&key, &limit);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Invoice's own per-investor cap in bps of its supply, overriding its program's `max_investor_bps`
+pub fn get_concentration_cap(env: &Env, invoice_id: &String) -> Option<u32> {
+    env.storage().persistent().get(&InvestmentDataKey::ConcentrationCap(invoice_id.clone()))
+}
+
+pub fn set_concentration_cap(env: &Env, invoice_id: &String, max_investor_bps: Option<u32>) {
+    let key = InvestmentDataKey::ConcentrationCap(invoice_id.clone());
+    match max_investor_bps {
+        Some(bps) => {
+            env.storage().persistent().set(&key, &bps);
+            env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+        }
+        None => env.storage().persistent().remove(&key),
+    }
+}
+
+/// Ceiling on the buyer's approved but unsettled obligations (0 = no limit)
+pub fn get_buyer_obligation_limit(env: &Env, buyer: &Address) -> i128 {
+    env.storage().persistent().get(&SettlementDataKey::BuyerObligationLimit(buyer.clone())).unwrap_or(0)
+}
+
+pub fn set_buyer_obligation_limit(env: &Env, buyer: &Address, limit: i128) {
+    let key = SettlementDataKey::BuyerObligationLimit(buyer.clone());
+    env.storage().persistent().set(&key, &limit);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+// ============================================================================
+// AUTHORIZATION AUDIT STORAGE
+// ============================================================================
+
+pub fn get_authorization_log(env: &Env, addr: &Address) -> Vec<AuthorizationRecord> {
+    let key = DataKey::AuthorizationLog(addr.clone());
+    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+}
+
+pub fn append_authorization_log(env: &Env, addr: &Address, record: &AuthorizationRecord) {
+    let key = DataKey::AuthorizationLog(addr.clone());
+    let mut log = get_authorization_log(env, addr);
+    log.push_back(record.clone());
+    env.storage().persistent().set(&key, &log);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+
+    let action_key = DataKey::AuthorizationAction(record.action_id);
+    env.storage().persistent().set(&action_key, addr);
+    env.storage().persistent().extend_ttl(&action_key, 100_000, 200_000);
+}
+
+pub fn next_authorization_action_id(env: &Env) -> u64 {
+    let next = env.storage().instance().get(&DataKey::AuthorizationActionCounter).unwrap_or(0u64) + 1;
+    env.storage().instance().set(&DataKey::AuthorizationActionCounter, &next);
+    next
+}
+
+/// Address whose authorization log holds the given action
+pub fn get_authorization_action(env: &Env, action_id: u64) -> Option<Address> {
+    env.storage().persistent().get(&DataKey::AuthorizationAction(action_id))
+}
+
+// ============================================================================
+// INSURANCE POOL STORAGE
+// ============================================================================
+
+pub fn get_insurance_pool(env: &Env, program_id: u32) -> i128 {
+    let store = env.storage().instance();
+    if program_id == 0 {
+        store.get(&DataKey::InsurancePool).unwrap_or(0)
+    } else {
+        store.get(&ProgramDataKey::ProgramInsurancePool(program_id)).unwrap_or(0)
+    }
+}
+
+fn set_insurance_pool(env: &Env, program_id: u32, balance: i128) {
+    let store = env.storage().instance();
+    if program_id == 0 {
+        store.set(&DataKey::InsurancePool, &balance);
+    } else {
+        store.set(&ProgramDataKey::ProgramInsurancePool(program_id), &balance);
+    }
+}
+
+fn add_to_insurance_pool(env: &Env, program_id: u32, amount: i128) {
+    let current = get_insurance_pool(env, program_id);
+    set_insurance_pool(env, program_id, current + amount);
+}
+
+/// Withdraws from the part of the pool not earmarked for any invoice
+pub fn withdraw_from_insurance_pool(env: &Env, program_id: u32, amount: i128) -> bool {
+    if get_free_insurance(env, program_id) < amount {
+        return false;
+    }
+    set_insurance_pool(env, program_id, get_insurance_pool(env, program_id) - amount);
+    true
+}
+
+/// Insurance contributed by an invoice's sales and earmarked for its holders. It is counted in
+/// the program's pool and joins the shared part once the invoice settles or is collected.
+pub fn get_invoice_insurance(env: &Env, invoice_id: &String) -> i128 {
+    env.storage().persistent().get(&SettlementDataKey::InvoiceInsurance(invoice_id.clone())).unwrap_or(0)
+}
+
+fn set_invoice_insurance(env: &Env, invoice_id: &String, balance: i128) {
+    let key = SettlementDataKey::InvoiceInsurance(invoice_id.clone());
+    if balance == 0 {
+        env.storage().persistent().remove(&key);
+    } else {
+        env.storage().persistent().set(&key, &balance);
+        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    }
+}
+
+pub fn get_earmarked_insurance(env: &Env, program_id: u32) -> i128 {
+    env.storage().instance().get(&SettlementDataKey::EarmarkedInsurance(program_id)).unwrap_or(0)
+}
+
+fn set_earmarked_insurance(env: &Env, program_id: u32, amount: i128) {
+    env.storage().instance().set(&SettlementDataKey::EarmarkedInsurance(program_id), &amount);
+}
+
+/// Part of a program's pool not earmarked for a live invoice: the backstop every invoice shares
+pub fn get_free_insurance(env: &Env, program_id: u32) -> i128 {
+    get_insurance_pool(env, program_id) - get_earmarked_insurance(env, program_id)
+}
+
+pub fn add_invoice_insurance(env: &Env, invoice: &Invoice, amount: i128) {
+    add_to_insurance_pool(env, invoice.program_id, amount);
+    set_invoice_insurance(env, &invoice.id, get_invoice_insurance(env, &invoice.id) + amount);
+    set_earmarked_insurance(env, invoice.program_id, get_earmarked_insurance(env, invoice.program_id) + amount);
+}
+
+pub fn withdraw_invoice_insurance(env: &Env, invoice: &Invoice, amount: i128) -> bool {
+    let balance = get_invoice_insurance(env, &invoice.id);
+    if balance < amount {
+        return false;
+    }
+    set_invoice_insurance(env, &invoice.id, balance - amount);
+    set_earmarked_insurance(env, invoice.program_id, get_earmarked_insurance(env, invoice.program_id) - amount);
+    withdraw_from_insurance_pool(env, invoice.program_id, amount)
+}
+
+/// Moves an invoice's remaining sub-pool into the program's shared part
+pub fn release_invoice_insurance(env: &Env, invoice: &Invoice) {
+    let balance = get_invoice_insurance(env, &invoice.id);
+    if balance == 0 { return; }
+    set_invoice_insurance(env, &invoice.id, 0);
+    set_earmarked_insurance(env, invoice.program_id, get_earmarked_insurance(env, invoice.program_id) - balance);
+}
+
+/// Sub-pool balance when the invoice's first claim was made, the base for pro-rata payouts
+pub fn get_insurance_at_default(env: &Env, invoice_id: &String) -> Option<i128> {
+    env.storage().persistent().get(&SettlementDataKey::InsuranceAtDefault(invoice_id.clone()))
+}
+
+pub fn set_insurance_at_default(env: &Env, invoice_id: &String, balance: i128) {
+    let key = SettlementDataKey::InsuranceAtDefault(invoice_id.clone());
+    env.storage().persistent().set(&key, &balance);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Share of cost basis (bps) the shared pool tops defaulted holders up to (0 = no backstop)
+pub fn get_insurance_backstop(env: &Env) -> u32 {
+    env.storage().instance().get(&SettlementDataKey::InsuranceBackstop).unwrap_or(0)
+}
+
+pub fn set_insurance_backstop(env: &Env, coverage_bps: u32) {
+    env.storage().instance().set(&SettlementDataKey::InsuranceBackstop, &coverage_bps);
+}
+
+pub fn get_underwriter_terms(env: &Env) -> UnderwriterTerms {
+    env.storage().instance().get(&SettlementDataKey::UnderwriterTerms).unwrap_or_default()
+}
+
+pub fn set_underwriter_terms(env: &Env, terms: &UnderwriterTerms) {
+    env.storage().instance().set(&SettlementDataKey::UnderwriterTerms, terms);
+}
+
+/// Shares outstanding in a program's insurance pool, including those seeded for the balance the
+/// pool held before its first underwriter (owned by the protocol and never withdrawn)
+pub fn get_underwriter_shares(env: &Env, program_id: u32) -> i128 {
+    env.storage().instance().get(&SettlementDataKey::UnderwriterShares(program_id)).unwrap_or(0)
+}
+
+fn set_underwriter_shares(env: &Env, program_id: u32, shares: i128) {
+    env.storage().instance().set(&SettlementDataKey::UnderwriterShares(program_id), &shares);
+}
+
+fn get_underwriter_epoch(env: &Env, program_id: u32) -> u32 {
+    env.storage().instance().get(&SettlementDataKey::UnderwriterEpoch(program_id)).unwrap_or(0)
 }
 
-pub fn get_admin(env: &Env) -> Address {
-    env.storage().instance().get(&DataKey::Admin).unwrap()
+/// The staker's stake, empty if its shares were minted before the pool was last wiped out
+pub fn get_underwriter_stake(env: &Env, program_id: u32, staker: &Address) -> UnderwriterStake {
+    let epoch: u32 = env.storage().persistent().get(&SettlementDataKey::StakeEpoch(program_id, staker.clone())).unwrap_or(0);
+    if epoch != get_underwriter_epoch(env, program_id) { return UnderwriterStake::default(); }
+    env.storage().persistent().get(&SettlementDataKey::UnderwriterStake(program_id, staker.clone())).unwrap_or_default()
 }
 
-pub fn set_admin(env: &Env, admin: &Address) {
-    env.storage().instance().set(&DataKey::Admin, admin);
+fn set_underwriter_stake(env: &Env, program_id: u32, staker: &Address, stake: &UnderwriterStake) {
+    let key = SettlementDataKey::UnderwriterStake(program_id, staker.clone());
+    let epoch_key = SettlementDataKey::StakeEpoch(program_id, staker.clone());
+    if stake.shares == 0 {
+        env.storage().persistent().remove(&key);
+        env.storage().persistent().remove(&epoch_key);
+    } else {
+        env.storage().persistent().set(&key, stake);
+        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+        let epoch = get_underwriter_epoch(env, program_id);
+        if epoch == 0 { env.storage().persistent().remove(&epoch_key); } else { set_entry(env, &epoch_key, &epoch); }
+    }
 }
 
+/// Value of `shares` at the pool's free balance. Earmarked insurance belongs to its invoice's
+/// holders until the invoice settles and releases it, so premiums reach underwriters only then.
+pub fn underwriter_share_value(env: &Env, program_id: u32, shares: i128) -> i128 {
+    let total = get_underwriter_shares(env, program_id);
+    if total == 0 { return 0; }
+    (shares * get_free_insurance(env, program_id).max(0)) / total
+}
 
-// ============================================================================
-// PAYMENT TOKEN STORAGE
-// ============================================================================
+/// Adds staked capital to the pool and returns the shares minted. If claims have wiped out the
+/// free balance, the old shares are worthless: a new epoch starts and shares are minted 1:1.
+pub fn stake_insurance(env: &Env, program_id: u32, staker: &Address, amount: i128, unlock_at: u64) -> i128 {
+    let free = get_free_insurance(env, program_id);
+    let mut total = get_underwriter_shares(env, program_id);
+    if total > 0 && free <= 0 {
+        env.storage().instance().set(&SettlementDataKey::UnderwriterEpoch(program_id), &(get_underwriter_epoch(env, program_id) + 1));
+        total = 0;
+    }
+    let (total, minted) = if total == 0 { (free.max(0), amount) } else { (total, (amount * total) / free) };
+    set_underwriter_shares(env, program_id, total + minted);
+    add_to_insurance_pool(env, program_id, amount);
+    let mut stake = get_underwriter_stake(env, program_id, staker);
+    stake.shares += minted;
+    stake.unlock_at = unlock_at;
+    set_underwriter_stake(env, program_id, staker, &stake);
+    minted
+}
 
-pub fn get_usdc_token(env: &Env) -> Address {
-    env.storage().instance().get(&DataKey::UsdcToken).unwrap()
+/// Burns `shares` and takes their value out of the pool's free balance. Returns the value, or
+/// None when the free balance can't cover it (earmarked insurance is never paid to underwriters).
+pub fn unstake_insurance(env: &Env, program_id: u32, staker: &Address, shares: i128) -> Option<i128> {
+    let value = underwriter_share_value(env, program_id, shares);
+    if !withdraw_from_insurance_pool(env, program_id, value) { return None; }
+    set_underwriter_shares(env, program_id, get_underwriter_shares(env, program_id) - shares);
+    let mut stake = get_underwriter_stake(env, program_id, staker);
+    stake.shares -= shares;
+    set_underwriter_stake(env, program_id, staker, &stake);
+    Some(value)
 }
 
-pub fn set_usdc_token(env: &Env, token: &Address) {
-    env.storage().instance().set(&DataKey::UsdcToken, token);
+/// Credits the underwriters' cut of a settlement to the pool's free balance
+pub fn add_underwriter_yield(env: &Env, program_id: u32, amount: i128) {
+    add_to_insurance_pool(env, program_id, amount);
 }
 
-// ============================================================================
-// RATE CONFIG STORAGE
-// ============================================================================
+/// Risk-based insurance premiums, checked in order; none means the fee schedule's flat cut
+pub fn premium_tier_count(env: &Env) -> u32 {
+    list_len(env, &SettlementDataKey::PremiumTiers)
+}
 
-pub fn get_rate_config(env: &Env) -> RateConfig {
-    env.storage()
-        .instance()
-        .get(&DataKey::RateConfig)
-        .unwrap_or_default()
+pub fn get_premium_tier(env: &Env, index: u32) -> Option<PremiumTier> {
+    env.storage().persistent().get(&SettlementDataKey::PremiumTier(index))
 }
 
-pub fn set_rate_config(env: &Env, config: &RateConfig) {
-    env.storage().instance().set(&DataKey::RateConfig, config);
+pub fn get_premium_tiers(env: &Env, start: u32, limit: u32) -> Vec<PremiumTier> {
+    list_page(env, premium_tier_count(env), start, limit, SettlementDataKey::PremiumTier)
 }
 
-// ============================================================================
-// COUNTERS
-// ============================================================================
+pub fn set_premium_tiers(env: &Env, tiers: &Vec<PremiumTier>) {
+    clear_list(env, &SettlementDataKey::PremiumTiers, SettlementDataKey::PremiumTier);
+    for (index, tier) in tiers.iter().enumerate() { set_entry(env, &SettlementDataKey::PremiumTier(index as u32), &tier); }
+    set_list_len(env, &SettlementDataKey::PremiumTiers, tiers.len());
+}
 
-pub fn get_invoice_counter(env: &Env) -> u32 {
-    env.storage().instance().get(&DataKey::InvoiceCounter).unwrap_or(0)
+/// Insurance a defaulted holder was paid and has not yet repaid out of recoveries
+pub fn get_insurance_owed(env: &Env, invoice_id: &String, holder: &Address) -> i128 {
+    let key = SettlementDataKey::InsuranceOwed(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    env.storage().persistent().get(&key).unwrap_or(0)
 }
 
-pub fn set_invoice_counter(env: &Env, counter: u32) {
-    env.storage().instance().set(&DataKey::InvoiceCounter, &counter);
+pub fn set_insurance_owed(env: &Env, invoice_id: &String, holder: &Address, amount: i128) {
+    let key = SettlementDataKey::InsuranceOwed(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    if amount == 0 {
+        env.storage().persistent().remove(&key);
+    } else {
+        env.storage().persistent().set(&key, &amount);
+        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    }
 }
 
-pub fn get_order_counter(env: &Env) -> u32 {
-    env.storage().instance().get(&DataKey::OrderCounter).unwrap_or(0)
+pub fn get_recovery_received(env: &Env, invoice_id: &String, holder: &Address) -> i128 {
+    let key = SettlementDataKey::RecoveryReceived(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    env.storage().persistent().get(&key).unwrap_or(0)
 }
 
-pub fn set_order_counter(env: &Env, counter: u32) {
-    env.storage().instance().set(&DataKey::OrderCounter, &counter);
+pub fn add_recovery_received(env: &Env, invoice_id: &String, holder: &Address, amount: i128) {
+    let key = SettlementDataKey::RecoveryReceived(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    let received: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+    env.storage().persistent().set(&key, &(received + amount));
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
 }
 
-// ============================================================================
-// INVOICE STORAGE
-// ============================================================================
+/// Returns recovered money that reimburses an insurance payout to the program's free pool
+pub fn repay_insurance(env: &Env, program_id: u32, amount: i128) {
+    add_to_insurance_pool(env, program_id, amount);
+}
 
-pub fn get_invoice(env: &Env, invoice_id: &String) -> Option<Invoice> {
-    let key = DataKey::Invoice(invoice_id.clone());
-    env.storage().persistent().get(&key)
+/// Combined balance of every program's insurance pool
+pub fn get_total_insurance_pools(env: &Env) -> i128 {
+    (0..=get_program_counter(env)).map(|id| get_insurance_pool(env, id)).sum()
 }
 
-pub fn set_invoice(env: &Env, invoice_id: &String, invoice: &Invoice) {
-    let key = DataKey::Invoice(invoice_id.clone());
-    env.storage().persistent().set(&key, invoice);
+pub fn is_insurance_claimed(env: &Env, invoice_id: &String, holder: &Address) -> bool {
+    let key = DataKey::InsuranceClaimed(InvoiceKey {
+        invoice_id: invoice_id.clone(),
+        holder: holder.clone(),
+    });
+    env.storage().persistent().get(&key).unwrap_or(false)
+}
+
+pub fn set_insurance_claimed(env: &Env, invoice_id: &String, holder: &Address) {
+    let key = DataKey::InsuranceClaimed(InvoiceKey {
+        invoice_id: invoice_id.clone(),
+        holder: holder.clone(),
+    });
+    env.storage().persistent().set(&key, &true);
     env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
 }
 
 // ============================================================================
-// DISPUTE STORAGE
+// SELL ORDER STORAGE
 // ============================================================================
 
-pub fn get_dispute(env: &Env, invoice_id: &String) -> Option<Dispute> {
-    let key = DataKey::Dispute(invoice_id.clone());
+pub fn get_sell_order(env: &Env, order_id: &String) -> Option<SellOrder> {
+    let key = DataKey::SellOrder(order_id.clone());
     env.storage().persistent().get(&key)
 }
 
-pub fn set_dispute(env: &Env, invoice_id: &String, dispute: &Dispute) {
-    let key = DataKey::Dispute(invoice_id.clone());
-    env.storage().persistent().set(&key, dispute);
+pub fn set_sell_order(env: &Env, order_id: &String, order: &SellOrder) {
+    let key = DataKey::SellOrder(order_id.clone());
+    env.storage().persistent().set(&key, order);
     env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
 }
 
+pub fn get_orders_for_invoice(env: &Env, invoice_id: &String) -> Vec<String> {
+    let key = DataKey::OrdersByInvoice(invoice_id.clone());
+    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+}
 
-// ============================================================================
-// TOKEN HOLDING STORAGE
-// ============================================================================
+pub fn add_order_to_invoice(env: &Env, invoice_id: &String, order_id: &String) {
+    let key = DataKey::OrdersByInvoice(invoice_id.clone());
+    let mut orders: Vec<String> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+    orders.push_back(order_id.clone());
+    env.storage().persistent().set(&key, &orders);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
 
-pub fn get_token_holding(env: &Env, invoice_id: &String, holder: &Address) -> Option<TokenHolding> {
-    let key = DataKey::TokenHolding(InvoiceKey {
-        invoice_id: invoice_id.clone(),
-        holder: holder.clone(),
-    });
-    env.storage().persistent().get(&key)
+/// (tokens, payment) an investor bought while the invoice's funding threshold was unmet
+pub fn get_funding_escrow(env: &Env, invoice_id: &String, holder: &Address) -> (i128, i128) {
+    let key = DataKey::FundingEscrow(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    env.storage().persistent().get(&key).unwrap_or((0, 0))
 }
 
-pub fn set_token_holding(env: &Env, invoice_id: &String, holder: &Address, holding: &TokenHolding) {
-    let key = DataKey::TokenHolding(InvoiceKey {
-        invoice_id: invoice_id.clone(),
-        holder: holder.clone(),
-    });
-    env.storage().persistent().set(&key, holding);
+pub fn add_funding_escrow(env: &Env, invoice_id: &String, holder: &Address, tokens: i128, payment: i128) {
+    let (held_tokens, held_payment) = get_funding_escrow(env, invoice_id, holder);
+    let key = DataKey::FundingEscrow(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    env.storage().persistent().set(&key, &(held_tokens + tokens, held_payment + payment));
     env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
-    add_holder_to_list(env, invoice_id, holder);
+    add_to_escrow(env, payment);
 }
 
-pub fn remove_token_holding(env: &Env, invoice_id: &String, holder: &Address) {
-    let key = DataKey::TokenHolding(InvoiceKey {
-        invoice_id: invoice_id.clone(),
-        holder: holder.clone(),
-    });
+/// Drops a holder's escrow entry. The caller pays it out (refund or release) separately.
+pub fn remove_funding_escrow(env: &Env, invoice_id: &String, holder: &Address) {
+    let (_, payment) = get_funding_escrow(env, invoice_id, holder);
+    let key = DataKey::FundingEscrow(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
     env.storage().persistent().remove(&key);
-    remove_holder_from_list(env, invoice_id, holder);
+    release_from_escrow(env, payment);
 }
 
-// ============================================================================
-// HOLDER LIST STORAGE
-// ============================================================================
+/// Turns a holder's escrow entry into a refund owed to them, once their tokens went back to
+/// the supplier. The payment stays counted in the escrow total until it's claimed.
+pub fn defer_funding_refund(env: &Env, invoice_id: &String, holder: &Address) -> (i128, i128) {
+    let (tokens, payment) = get_funding_escrow(env, invoice_id, holder);
+    if payment == 0 { return (0, 0); }
+    env.storage().persistent().remove(&DataKey::FundingEscrow(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() }));
+    let key = AuctionDataKey::FundingRefund(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    let owed: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+    env.storage().persistent().set(&key, &(owed + payment));
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    (tokens, payment)
+}
 
-pub fn get_all_holders(env: &Env, invoice_id: &String) -> Vec<Address> {
-    let key = DataKey::HolderList(invoice_id.clone());
-    env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
+pub fn get_funding_refund(env: &Env, invoice_id: &String, holder: &Address) -> i128 {
+    let key = AuctionDataKey::FundingRefund(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    env.storage().persistent().get(&key).unwrap_or(0)
 }
 
-fn add_holder_to_list(env: &Env, invoice_id: &String, holder: &Address) {
-    let key = DataKey::HolderList(invoice_id.clone());
-    let mut holders: Vec<Address> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
-    
-    let mut found = false;
-    for existing in holders.iter() {
-        if existing == *holder {
-            found = true;
-            break;
-        }
-    }
-    
-    if !found {
-        holders.push_back(holder.clone());
-        env.storage().persistent().set(&key, &holders);
-        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+/// Drops a holder's deferred refund and releases it from the escrow total; the caller pays it
+pub fn take_funding_refund(env: &Env, invoice_id: &String, holder: &Address) -> i128 {
+    let owed = get_funding_refund(env, invoice_id, holder);
+    if owed > 0 {
+        env.storage().persistent().remove(&AuctionDataKey::FundingRefund(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() }));
+        release_from_escrow(env, owed);
     }
+    owed
 }
 
-fn remove_holder_from_list(env: &Env, invoice_id: &String, holder: &Address) {
-    let key = DataKey::HolderList(invoice_id.clone());
-    let holders: Vec<Address> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
-    
-    let mut new_holders = Vec::new(env);
-    for existing in holders.iter() {
-        if existing != *holder {
-            new_holders.push_back(existing);
-        }
-    }
-    env.storage().persistent().set(&key, &new_holders);
+pub fn get_installment_account(env: &Env, invoice_id: &String, holder: &Address) -> InstallmentAccount {
+    let key = SettlementDataKey::InstallmentAccount(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    env.storage().persistent().get(&key).unwrap_or_default()
 }
 
-pub fn clear_token_holdings(env: &Env, invoice_id: &String) {
-    let holders = get_all_holders(env, invoice_id);
-    for holder in holders.iter() {
-        let key = DataKey::TokenHolding(InvoiceKey {
-            invoice_id: invoice_id.clone(),
-            holder: holder.clone(),
-        });
+pub fn set_installment_account(env: &Env, invoice_id: &String, holder: &Address, account: &InstallmentAccount) {
+    let key = SettlementDataKey::InstallmentAccount(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    env.storage().persistent().set(&key, account);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn remove_installment_account(env: &Env, invoice_id: &String, holder: &Address) {
+    let key = SettlementDataKey::InstallmentAccount(InvoiceKey { invoice_id: invoice_id.clone(), holder: holder.clone() });
+    env.storage().persistent().remove(&key);
+}
+
+/// Refund still owed to an investor whose holding was clawed back in an upheld dispute
+pub fn get_refund_owed(env: &Env, invoice_id: &String, investor: &Address) -> i128 {
+    let key = SettlementDataKey::RefundOwed(InvoiceKey { invoice_id: invoice_id.clone(), holder: investor.clone() });
+    env.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn set_refund_owed(env: &Env, invoice_id: &String, investor: &Address, amount: i128) {
+    let key = SettlementDataKey::RefundOwed(InvoiceKey { invoice_id: invoice_id.clone(), holder: investor.clone() });
+    if amount > 0 {
+        env.storage().persistent().set(&key, &amount);
+        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    } else {
         env.storage().persistent().remove(&key);
     }
-    let list_key = DataKey::HolderList(invoice_id.clone());
-    env.storage().persistent().remove(&list_key);
 }
 
+/// Investors owed a refund on an invoice, in clawback order
+pub fn get_refund_creditors(env: &Env, invoice_id: &String) -> Vec<Address> {
+    env.storage().persistent().get(&SettlementDataKey::RefundCreditors(invoice_id.clone())).unwrap_or(Vec::new(env))
+}
 
-// ============================================================================
-// KYC STORAGE
-// ============================================================================
+pub fn set_refund_creditors(env: &Env, invoice_id: &String, creditors: &Vec<Address>) {
+    let key = SettlementDataKey::RefundCreditors(invoice_id.clone());
+    env.storage().persistent().set(&key, creditors);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
 
-pub fn get_kyc_status(env: &Env, investor: &Address) -> bool {
-    let key = DataKey::KycStatus(investor.clone());
-    env.storage().persistent().get(&key).unwrap_or(false)
+/// Unpaid dispute refunds across all of a supplier's invoices
+pub fn get_supplier_debt(env: &Env, supplier: &Address) -> i128 {
+    env.storage().persistent().get(&SettlementDataKey::SupplierDebt(supplier.clone())).unwrap_or(0)
 }
 
-pub fn set_kyc_status(env: &Env, investor: &Address, approved: bool) {
-    let key = DataKey::KycStatus(investor.clone());
-    env.storage().persistent().set(&key, &approved);
+pub fn set_supplier_debt(env: &Env, supplier: &Address, debt: i128) {
+    let key = SettlementDataKey::SupplierDebt(supplier.clone());
+    env.storage().persistent().set(&key, &debt);
     env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
 }
 
-// ============================================================================
-// INSURANCE POOL STORAGE
-// ============================================================================
+pub fn get_buyer_stats(env: &Env, buyer: &Address) -> BuyerStats {
+    env.storage().persistent().get(&SettlementDataKey::BuyerStats(buyer.clone())).unwrap_or_default()
+}
 
-pub fn get_insurance_pool(env: &Env) -> i128 {
-    env.storage().instance().get(&DataKey::InsurancePool).unwrap_or(0)
+pub fn set_buyer_stats(env: &Env, buyer: &Address, stats: &BuyerStats) {
+    let key = SettlementDataKey::BuyerStats(buyer.clone());
+    env.storage().persistent().set(&key, stats);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
 }
 
-pub fn add_to_insurance_pool(env: &Env, amount: i128) {
-    let current = get_insurance_pool(env);
-    env.storage().instance().set(&DataKey::InsurancePool, &(current + amount));
+pub fn get_supplier_stats(env: &Env, supplier: &Address) -> SupplierStats {
+    env.storage().persistent().get(&SettlementDataKey::SupplierStats(supplier.clone())).unwrap_or_default()
 }
 
-pub fn withdraw_from_insurance_pool(env: &Env, amount: i128) -> bool {
-    let current = get_insurance_pool(env);
-    if current < amount {
-        return false;
+pub fn set_supplier_stats(env: &Env, supplier: &Address, stats: &SupplierStats) {
+    let key = SettlementDataKey::SupplierStats(supplier.clone());
+    env.storage().persistent().set(&key, stats);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Smallest payout worth a transfer in `token`; smaller settlement shares accrue as dust
+pub fn get_min_payout(env: &Env, token: &Address) -> i128 {
+    env.storage().instance().get(&SettlementDataKey::MinPayout(token.clone())).unwrap_or(0)
+}
+
+pub fn set_min_payout(env: &Env, token: &Address, min_amount: i128) {
+    env.storage().instance().set(&SettlementDataKey::MinPayout(token.clone()), &min_amount);
+}
+
+pub fn get_dust_balance(env: &Env, holder: &Address, token: &Address) -> i128 {
+    env.storage().persistent().get(&SettlementDataKey::DustBalance(holder.clone(), token.clone())).unwrap_or(0)
+}
+
+/// Dust held for all holders in `token`, excluded from sweeps
+pub fn get_dust_total(env: &Env, token: &Address) -> i128 {
+    env.storage().instance().get(&SettlementDataKey::DustTotal(token.clone())).unwrap_or(0)
+}
+
+pub fn add_dust(env: &Env, holder: &Address, token: &Address, amount: i128) {
+    let key = SettlementDataKey::DustBalance(holder.clone(), token.clone());
+    env.storage().persistent().set(&key, &(get_dust_balance(env, holder, token) + amount));
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    env.storage().instance().set(&SettlementDataKey::DustTotal(token.clone()), &(get_dust_total(env, token) + amount));
+}
+
+/// Clears a holder's dust balance in `token` and returns it. The caller pays it out.
+pub fn take_dust(env: &Env, holder: &Address, token: &Address) -> i128 {
+    let balance = get_dust_balance(env, holder, token);
+    env.storage().persistent().remove(&SettlementDataKey::DustBalance(holder.clone(), token.clone()));
+    env.storage().instance().set(&SettlementDataKey::DustTotal(token.clone()), &(get_dust_total(env, token) - balance));
+    balance
+}
+
+/// Confidential terms published by a party through `reveal_private_terms`
+pub fn get_private_terms(env: &Env, invoice_id: &String) -> Option<Bytes> {
+    env.storage().persistent().get(&DataKey::PrivateTerms(invoice_id.clone()))
+}
+
+pub fn set_private_terms(env: &Env, invoice_id: &String, terms: &Bytes) {
+    let key = DataKey::PrivateTerms(invoice_id.clone());
+    env.storage().persistent().set(&key, terms);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn get_basket_order(env: &Env, order_id: &String) -> Option<BasketOrder> {
+    env.storage().persistent().get(&DataKey::BasketOrder(order_id.clone()))
+}
+
+pub fn set_basket_order(env: &Env, order_id: &String, order: &BasketOrder) {
+    let key = DataKey::BasketOrder(order_id.clone());
+    env.storage().persistent().set(&key, order);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+pub fn get_rfq(env: &Env, rfq_id: &String) -> Option<Rfq> {
+    env.storage().persistent().get(&MarketDataKey::Rfq(rfq_id.clone()))
+}
+
+pub fn set_rfq(env: &Env, rfq_id: &String, rfq: &Rfq) {
+    let key = MarketDataKey::Rfq(rfq_id.clone());
+    env.storage().persistent().set(&key, rfq);
+    env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+}
+
+/// Max deviation of a secondary fill from fair value, in bps (0 = no band)
+pub fn get_price_band(env: &Env) -> u32 {
+    env.storage().instance().get(&MarketDataKey::PriceBand).unwrap_or(0)
+}
+
+pub fn set_price_band(env: &Env, band_bps: u32) {
+    env.storage().instance().set(&MarketDataKey::PriceBand, &band_bps);
+}
+
+/// Per-invoice override of the fee schedule's secondary-sale royalty, in bps
+pub fn get_royalty_override(env: &Env, invoice_id: &String) -> Option<u32> {
+    env.storage().persistent().get(&MarketDataKey::RoyaltyOverride(invoice_id.clone()))
+}
+
+pub fn set_royalty_override(env: &Env, invoice_id: &String, royalty_bps: Option<u32>) {
+    let key = MarketDataKey::RoyaltyOverride(invoice_id.clone());
+    match royalty_bps {
+        Some(bps) => {
+            env.storage().persistent().set(&key, &bps);
+            env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+        }
+        None => env.storage().persistent().remove(&key),
     }
-    env.storage().instance().set(&DataKey::InsurancePool, &(current - amount));
-    true
 }
 
-pub fn is_insurance_claimed(env: &Env, invoice_id: &String, holder: &Address) -> bool {
-    let key = DataKey::InsuranceClaimed(InvoiceKey {
-        invoice_id: invoice_id.clone(),
-        holder: holder.clone(),
-    });
-    env.storage().persistent().get(&key).unwrap_or(false)
+/// Invoice a token symbol was issued to
+pub fn get_symbol_invoice(env: &Env, symbol: &String) -> Option<String> {
+    env.storage().persistent().get(&MarketDataKey::TokenSymbol(symbol.clone()))
 }
 
-pub fn set_insurance_claimed(env: &Env, invoice_id: &String, holder: &Address) {
-    let key = DataKey::InsuranceClaimed(InvoiceKey {
-        invoice_id: invoice_id.clone(),
-        holder: holder.clone(),
-    });
-    env.storage().persistent().set(&key, &true);
+pub fn set_symbol_invoice(env: &Env, symbol: &String, invoice_id: &String) {
+    let key = MarketDataKey::TokenSymbol(symbol.clone());
+    env.storage().persistent().set(&key, invoice_id);
     env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
 }
 
+/// Seller has flagged their holding for a distressed sale, exempting it from the price band
+pub fn is_distressed_sale(env: &Env, invoice_id: &String, seller: &Address) -> bool {
+    let key = MarketDataKey::DistressedSale(InvoiceKey { invoice_id: invoice_id.clone(), holder: seller.clone() });
+    env.storage().persistent().get(&key).unwrap_or(false)
+}
+
+pub fn set_distressed_sale(env: &Env, invoice_id: &String, seller: &Address, distressed: bool) {
+    let key = MarketDataKey::DistressedSale(InvoiceKey { invoice_id: invoice_id.clone(), holder: seller.clone() });
+    if distressed {
+        env.storage().persistent().set(&key, &true);
+        env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
+    } else {
+        env.storage().persistent().remove(&key);
+    }
+}
+
 // ============================================================================
-// SELL ORDER STORAGE
+// BUY ORDER STORAGE
 // ============================================================================
 
-pub fn get_sell_order(env: &Env, order_id: &String) -> Option<SellOrder> {
-    let key = DataKey::SellOrder(order_id.clone());
+pub fn get_buy_order(env: &Env, order_id: &String) -> Option<BuyOrder> {
+    let key = DataKey::BuyOrder(order_id.clone());
     env.storage().persistent().get(&key)
 }
 
-pub fn set_sell_order(env: &Env, order_id: &String, order: &SellOrder) {
-    let key = DataKey::SellOrder(order_id.clone());
+pub fn set_buy_order(env: &Env, order_id: &String, order: &BuyOrder) {
+    let key = DataKey::BuyOrder(order_id.clone());
     env.storage().persistent().set(&key, order);
     env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
 }
 
-pub fn get_orders_for_invoice(env: &Env, invoice_id: &String) -> Vec<String> {
-    let key = DataKey::OrdersByInvoice(invoice_id.clone());
+pub fn get_buy_orders_for_invoice(env: &Env, invoice_id: &String) -> Vec<String> {
+    let key = DataKey::BuyOrdersByInvoice(invoice_id.clone());
     env.storage().persistent().get(&key).unwrap_or(Vec::new(env))
 }
 
-pub fn add_order_to_invoice(env: &Env, invoice_id: &String, order_id: &String) {
-    let key = DataKey::OrdersByInvoice(invoice_id.clone());
-    let mut orders: Vec<String> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+pub fn add_buy_order_to_invoice(env: &Env, invoice_id: &String, order_id: &String) {
+    let key = DataKey::BuyOrdersByInvoice(invoice_id.clone());
+    let mut orders = get_buy_orders_for_invoice(env, invoice_id);
     orders.push_back(order_id.clone());
     env.storage().persistent().set(&key, &orders);
     env.storage().persistent().extend_ttl(&key, 100_000, 200_000);
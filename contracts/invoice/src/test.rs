@@ -3,12 +3,80 @@
 #![cfg(test)]
 
 use super::*;
+use crate::types::RateTier;
 use soroban_sdk::{
-    testutils::{Address as _, Ledger},
+    contract, contractimpl, symbol_short,
+    testutils::{Address as _, AuthorizedFunction, Ledger},
     token::{StellarAssetClient, TokenClient},
-    Address, Env, String,
+    xdr::ToXdr,
+    Address, Bytes, BytesN, Env, IntoVal, String, Symbol,
 };
 
+/// Fixed-rate conversion oracle for cross-currency tests
+#[contract]
+pub struct MockOracle;
+
+#[contractimpl]
+impl MockOracle {
+    pub fn set_rate(env: Env, rate: i128) {
+        env.storage().instance().set(&symbol_short!("rate"), &rate);
+    }
+
+    pub fn rate(env: Env, _base: Address, _quote: Address) -> i128 {
+        env.storage().instance().get(&symbol_short!("rate")).unwrap()
+    }
+
+    pub fn set_benchmark(env: Env, rate_bps: u32) {
+        env.storage().instance().set(&symbol_short!("bench"), &rate_bps);
+    }
+
+    pub fn benchmark_rate(env: Env) -> u32 {
+        env.storage().instance().get(&symbol_short!("bench")).unwrap()
+    }
+}
+
+/// Transition subscriber that counts the calls it receives, or fails them all once set failing
+#[contract]
+pub struct MockListener;
+
+#[contractimpl]
+impl MockListener {
+    pub fn set_failing(env: Env) {
+        env.storage().instance().set(&symbol_short!("failing"), &true);
+    }
+
+    pub fn on_invoice_transition(env: Env, _invoice_id: String, status: InvoiceStatus) {
+        if env.storage().instance().has(&symbol_short!("failing")) { panic!("listener failure"); }
+        let count: u32 = env.storage().instance().get(&symbol_short!("calls")).unwrap_or(0);
+        env.storage().instance().set(&symbol_short!("calls"), &(count + 1));
+        env.storage().instance().set(&symbol_short!("last"), &status);
+    }
+
+    pub fn calls(env: Env) -> u32 {
+        env.storage().instance().get(&symbol_short!("calls")).unwrap_or(0)
+    }
+
+    pub fn last(env: Env) -> InvoiceStatus {
+        env.storage().instance().get(&symbol_short!("last")).unwrap()
+    }
+}
+
+/// Swap router that converts at 0.90 out per 1 in from its own reserves
+#[contract]
+pub struct MockRouter;
+
+#[contractimpl]
+impl MockRouter {
+    pub fn swap(env: Env, from: Address, token_in: Address, token_out: Address, amount_in: i128, min_out: i128, to: Address) -> i128 {
+        let router = env.current_contract_address();
+        TokenClient::new(&env, &token_in).transfer_from(&router, &from, &router, &amount_in);
+        let amount_out = amount_in * 9 / 10;
+        assert!(amount_out >= min_out);
+        TokenClient::new(&env, &token_out).transfer(&router, &to, &amount_out);
+        amount_out
+    }
+}
+
 fn create_token_contract<'a>(env: &Env, admin: &Address) -> (TokenClient<'a>, StellarAssetClient<'a>) {
     let contract_address = env.register_stellar_asset_contract_v2(admin.clone());
     (
@@ -17,7 +85,7 @@ fn create_token_contract<'a>(env: &Env, admin: &Address) -> (TokenClient<'a>, St
     )
 }
 
-fn create_invoice_contract(env: &Env) -> SanginiInvoiceContractClient {
+fn create_invoice_contract<'a>(env: &Env) -> SanginiInvoiceContractClient<'a> {
     let contract_id = env.register(SanginiInvoiceContract, ());
     SanginiInvoiceContractClient::new(env, &contract_id)
 }
@@ -38,6 +106,7 @@ impl<'a> TestSetup<'a> {
     fn new() -> Self {
         let env = Env::default();
         env.mock_all_auths();
+        env.ledger().with_mut(|l| l.timestamp = 1_700_000_000);
 
         let admin = Address::generate(&env);
         let supplier = Address::generate(&env);
@@ -58,7 +127,10 @@ impl<'a> TestSetup<'a> {
             &1000,  // 10% base rate
             &2400,  // 24% penalty rate
             &30,    // 30 days grace
+            &500,   // 5% insurance cut
         );
+        // Most tests buy at face value without running an auction
+        contract.set_pre_auction_invest(&admin, &true);
 
         // Mint USDC to participants
         usdc_admin.mint(&buyer, &10_000_000_0000000);      // 10M USDC
@@ -88,6 +160,7 @@ impl<'a> TestSetup<'a> {
             &due_date,
             &String::from_str(&self.env, "Auto parts supply Q4"),
             &String::from_str(&self.env, "PO-2024-1234"),
+            &String::from_str(&self.env, "QmInvoiceDocHash"),
         )
     }
 }
@@ -110,13 +183,45 @@ fn test_mint_draft() {
     assert_eq!(invoice.total_tokens, 0); // Not minted yet
 }
 
+#[test]
+fn test_invoice_listing_indexes() {
+    let setup = TestSetup::new();
+    let first = setup.create_sample_invoice();
+    let second = setup.create_sample_invoice();
+    let third = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&second, &setup.buyer, &0);
+
+    let by_supplier = setup.contract.get_invoices_by_supplier(&setup.supplier, &1, &5);
+    assert_eq!(by_supplier.len(), 2);
+    assert_eq!(by_supplier.get(0).unwrap().id, second);
+    assert_eq!(setup.contract.get_invoices_by_buyer(&setup.buyer, &0, &10).len(), 3);
+    assert_eq!(setup.contract.get_invoices_by_buyer(&setup.supplier, &0, &10).len(), 0);
+
+    // Transitions move invoices between status indexes
+    let drafts = setup.contract.get_invoices_by_status(&InvoiceStatus::Draft, &0, &10);
+    assert_eq!(drafts.len(), 2);
+    assert_eq!(drafts.get(0).unwrap().id, first);
+    assert_eq!(drafts.get(1).unwrap().id, third);
+    let verified = setup.contract.get_invoices_by_status(&InvoiceStatus::Verified, &0, &10);
+    assert_eq!(verified.len(), 1);
+    assert_eq!(verified.get(0).unwrap().id, second);
+    assert_eq!(setup.contract.get_invoices_by_status(&InvoiceStatus::Draft, &2, &10).len(), 0);
+
+    // Leaving a status moves that status's last invoice into the freed slot
+    setup.contract.approve_invoice(&first, &setup.buyer, &0);
+    let drafts = setup.contract.get_invoices_by_status(&InvoiceStatus::Draft, &0, &10);
+    assert_eq!(drafts.len(), 1);
+    assert_eq!(drafts.get(0).unwrap().id, third);
+    assert_eq!(setup.contract.get_invoices_by_status(&InvoiceStatus::Verified, &1, &10).get(0).unwrap().id, first);
+}
+
 #[test]
 fn test_approve_invoice() {
     let setup = TestSetup::new();
     let invoice_id = setup.create_sample_invoice();
 
     // Buyer approves - THE DIGITAL HANDSHAKE
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
 
     let invoice = setup.contract.get_invoice(&invoice_id);
     
@@ -129,6 +234,93 @@ fn test_approve_invoice() {
     assert_eq!(holding.amount, 10_00_000_0000000);
 }
 
+#[test]
+fn test_confidential_draft_reveal_at_approval() {
+    let setup = TestSetup::new();
+    let amount: i128 = 10_00_000_0000000;
+    let salt = BytesN::from_array(&setup.env, &[7u8; 32]);
+    let commitment = setup.contract.compute_amount_commitment(&amount, &salt);
+    let terms = DraftTerms {
+        currency: String::from_str(&setup.env, "INR"),
+        due_date: setup.env.ledger().timestamp() + 90 * 24 * 60 * 60,
+        description: String::from_str(&setup.env, "Confidential terms"),
+        purchase_order: String::from_str(&setup.env, "PO-SECRET"),
+        document_hash: String::from_str(&setup.env, "QmDoc"),
+    };
+    let invoice_id = setup.contract.mint_confidential_draft(&setup.supplier, &setup.buyer, &commitment, &terms);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).amount, 0);
+
+    // Plain approval and a wrong reveal are both rejected
+    assert_eq!(setup.contract.try_approve_invoice(&invoice_id, &setup.buyer, &0), Err(Ok(ContractError::InvalidCommitment)));
+    let result = setup.contract.try_approve_confidential_invoice(&invoice_id, &setup.buyer, &(amount + 1), &salt, &0);
+    assert_eq!(result, Err(Ok(ContractError::InvalidCommitment)));
+
+    setup.contract.approve_confidential_invoice(&invoice_id, &setup.buyer, &amount, &salt, &0);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.status, InvoiceStatus::Verified);
+    assert_eq!(invoice.amount, amount);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.supplier).amount, amount);
+}
+
+#[test]
+fn test_private_terms_verify_and_reveal() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    let terms = Bytes::from_slice(&setup.env, b"Unit price 4,250/part; 2% early-payment rebate");
+    let salt = BytesN::from_array(&setup.env, &[42u8; 32]);
+    let mut preimage = terms.clone();
+    preimage.append(&Bytes::from(salt.clone()));
+    let terms_hash: BytesN<32> = setup.env.crypto().sha256(&preimage).into();
+    setup.contract.set_private_terms(&invoice_id, &setup.supplier, &terms_hash);
+
+    // Investors given the plaintext and salt off-chain can check them; nothing is published yet
+    assert!(setup.contract.verify_private_terms(&invoice_id, &terms, &salt));
+    assert!(!setup.contract.verify_private_terms(&invoice_id, &Bytes::from_slice(&setup.env, b"forged"), &salt));
+    assert!(!setup.contract.verify_private_terms(&invoice_id, &terms, &BytesN::from_array(&setup.env, &[0u8; 32])));
+    assert_eq!(setup.contract.get_private_terms(&invoice_id), None);
+
+    // Only the parties (or the admin) may reveal, and only the committed terms
+    let outsider = Address::generate(&setup.env);
+    assert!(setup.contract.try_reveal_private_terms(&invoice_id, &outsider, &terms, &salt).is_err());
+    let result = setup.contract.try_reveal_private_terms(&invoice_id, &setup.buyer, &Bytes::from_slice(&setup.env, b"forged"), &salt);
+    assert_eq!(result, Err(Ok(ContractError::TermsMismatch)));
+    setup.contract.reveal_private_terms(&invoice_id, &setup.buyer, &terms, &salt);
+    assert_eq!(setup.contract.get_private_terms(&invoice_id), Some(terms));
+}
+
+#[test]
+fn test_token_symbol_registry() {
+    let setup = TestSetup::new();
+    let first = setup.create_sample_invoice();
+    let second = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&first, &setup.buyer, &0);
+    setup.contract.approve_invoice(&second, &setup.buyer, &0);
+
+    let symbol = setup.contract.get_invoice(&second).token_symbol;
+    assert_eq!(symbol, String::from_str(&setup.env, "SNG-INV-1002"));
+    assert_eq!(setup.contract.resolve_symbol(&symbol), second);
+    assert_eq!(setup.contract.resolve_symbol(&String::from_str(&setup.env, "SNG-INV-1001")), first);
+    assert_eq!(setup.contract.try_resolve_symbol(&String::from_str(&setup.env, "SNG-INV-9999")), Err(Ok(ContractError::InvoiceNotFound)));
+
+    // A symbol already registered to another invoice is never reassigned
+    let third = setup.create_sample_invoice();
+    setup.env.as_contract(&setup.contract.address, || {
+        storage::set_symbol_invoice(&setup.env, &String::from_str(&setup.env, "SNG-INV-1003"), &first);
+    });
+    assert_eq!(setup.contract.try_approve_invoice(&third, &setup.buyer, &0), Err(Ok(ContractError::SymbolTaken)));
+}
+
+#[test]
+fn test_invoice_ids_widen_past_four_digits() {
+    let setup = TestSetup::new();
+    setup.env.as_contract(&setup.contract.address, || storage::set_invoice_counter(&setup.env, 8998));
+    assert_eq!(setup.create_sample_invoice(), String::from_str(&setup.env, "INV-9999"));
+    let wide = setup.create_sample_invoice();
+    assert_eq!(wide, String::from_str(&setup.env, "INV-10000"));
+    setup.contract.approve_invoice(&wide, &setup.buyer, &0);
+    assert_eq!(setup.contract.resolve_symbol(&String::from_str(&setup.env, "SNG-INV-10000")), wide);
+}
+
 #[test]
 #[should_panic(expected = "Error(Contract, #2)")] // Unauthorized
 fn test_approve_invoice_wrong_buyer() {
@@ -136,7 +328,52 @@ fn test_approve_invoice_wrong_buyer() {
     let invoice_id = setup.create_sample_invoice();
 
     // Wrong person tries to approve
-    setup.contract.approve_invoice(&invoice_id, &setup.investor);
+    setup.contract.approve_invoice(&invoice_id, &setup.investor, &0);
+}
+
+#[test]
+fn test_mint_draft_issuance_limits() {
+    let setup = TestSetup::new();
+    let limits = IssuanceLimits { max_tenor_days: 60, max_amount: 5_00_000_0000000, max_discount_bps: 0 };
+    setup.contract.set_issuance_limits(&setup.admin, &limits);
+
+    let due_date = setup.env.ledger().timestamp() + (30 * 24 * 60 * 60);
+    let result = setup.contract.try_mint_draft(
+        &setup.supplier, &setup.buyer, &10_00_000_0000000, &String::from_str(&setup.env, "INR"), &due_date,
+        &String::from_str(&setup.env, "Too large"), &String::from_str(&setup.env, "PO-1"), &String::from_str(&setup.env, "Qm"),
+    );
+    assert_eq!(result, Err(Ok(ContractError::AmountExceedsLimit)));
+
+    // The sample invoice has a 90-day tenor
+    let result = setup.contract.try_mint_draft(
+        &setup.supplier, &setup.buyer, &1_00_000_0000000, &String::from_str(&setup.env, "INR"), &(due_date + 60 * 24 * 60 * 60),
+        &String::from_str(&setup.env, "Too long"), &String::from_str(&setup.env, "PO-2"), &String::from_str(&setup.env, "Qm"),
+    );
+    assert_eq!(result, Err(Ok(ContractError::TenorExceedsLimit)));
+
+    // A higher tier gets its own limits
+    setup.contract.set_tier_limits(&setup.admin, &2, &IssuanceLimits { max_tenor_days: 180, max_amount: 0, max_discount_bps: 0 });
+    setup.contract.set_supplier_tier(&setup.admin, &setup.supplier, &2);
+    let invoice_id = setup.create_sample_invoice();
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Draft);
+}
+
+#[test]
+fn test_auction_discount_capped_by_tier() {
+    let setup = TestSetup::new();
+    // New suppliers (tier 0) may discount at most 5%
+    setup.contract.set_issuance_limits(&setup.admin, &IssuanceLimits { max_tenor_days: 0, max_amount: 0, max_discount_bps: 500 });
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    let result = setup.contract.try_start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    assert_eq!(result, Err(Ok(ContractError::InvalidAuctionParams)));
+
+    // Established tier gets a wider band
+    setup.contract.set_tier_limits(&setup.admin, &1, &IssuanceLimits { max_tenor_days: 0, max_amount: 0, max_discount_bps: 2000 });
+    setup.contract.set_supplier_tier(&setup.admin, &setup.supplier, &1);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Funding);
 }
 
 // ============================================================================
@@ -147,7 +384,7 @@ fn test_approve_invoice_wrong_buyer() {
 fn test_transfer_tokens_to_sub_vendor() {
     let setup = TestSetup::new();
     let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
 
     // Transfer 30% to sub-vendor
     let transfer_amount = 3_00_000_0000000; // 30% of 10L
@@ -170,39 +407,34 @@ fn test_transfer_tokens_to_sub_vendor() {
 fn test_invest_requires_kyc() {
     let setup = TestSetup::new();
     let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
 
     // Try to invest without KYC - should fail
-    let result = std::panic::catch_unwind(|| {
-        setup.contract.invest(
-            &invoice_id,
-            &setup.investor,
-            &1_00_000_0000000,
-            &98_000_0000000, // 2% discount
-        );
-    });
+    let result = setup.contract.try_invest(
+        &invoice_id,
+        &setup.investor,
+        &1_00_000_0000000,
+    );
 
-    assert!(result.is_err()); // Should panic with KYCRequired
+    assert_eq!(result, Err(Ok(ContractError::KYCRequired)));
 }
 
 #[test]
 fn test_invest_with_kyc() {
     let setup = TestSetup::new();
     let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
 
     // Admin approves investor KYC
     setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
 
-    // Now invest
-    let token_amount = 1_00_000_0000000;  // 1L tokens
-    let payment = 98_000_0000000;         // 2% discount
+    // Now invest in the full supply
+    let token_amount = 10_00_000_0000000;  // 10L tokens
 
     setup.contract.invest(
         &invoice_id,
         &setup.investor,
         &token_amount,
-        &payment,
     );
 
     // Check investor has tokens
@@ -214,6 +446,237 @@ fn test_invest_with_kyc() {
     assert_eq!(invoice.status, InvoiceStatus::Funded);
 }
 
+#[test]
+fn test_simulate_invest_matches_invest() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+
+    // 10 hours in at 0.5%/hour = 5% discount
+    let at = setup.env.ledger().timestamp() + 10 * 3600;
+    let quote = setup.contract.simulate_invest(&invoice_id, &1_00_000_0000000, &at);
+    assert_eq!(quote.price, 9_50_000_0000000);
+    assert_eq!(quote.payment_amount, 95_000_0000000);
+    assert_eq!(quote.insurance_amount, 4_750_0000000);
+    assert_eq!(quote.supplier_proceeds, 90_250_0000000);
+
+    setup.env.ledger().with_mut(|l| l.timestamp = at);
+    let investor_before = setup.usdc.balance(&setup.investor);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(investor_before - setup.usdc.balance(&setup.investor), quote.payment_amount);
+    assert_eq!(setup.usdc.balance(&setup.supplier), quote.supplier_proceeds);
+}
+
+#[test]
+fn test_fee_schedule_pinned_at_funding() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    assert_eq!(setup.contract.get_invoice(&invoice_id).fee_version, 1);
+
+    // Raise the insurance cut to 10% after funding started
+    let version = setup.contract.set_fee_schedule(&setup.admin, &FeeSchedule { insurance_cut_bps: 1000, ..Default::default() });
+    assert_eq!(version, 2);
+    assert_eq!(setup.contract.get_active_fee_schedule().insurance_cut_bps, 1000);
+
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), 5_000_0000000); // Still 5%
+
+    // A new invoice picks up the new schedule
+    let second_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&second_id, &setup.buyer, &0);
+    setup.contract.start_auction(&second_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    assert_eq!(setup.contract.get_invoice(&second_id).fee_version, 2);
+}
+
+#[test]
+fn test_rate_config_pinned_at_funding() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let unfunded_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&unfunded_id, &setup.buyer, &0);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    assert_eq!(setup.contract.get_invoice(&invoice_id).rate_config.penalty_rate, 2400);
+    assert_eq!(setup.contract.get_invoice(&unfunded_id).fee_version, 0);
+
+    // Double interest and penalty, and shorten the grace period to a day
+    let harsher = RateConfig { base_interest_rate: 2000, penalty_rate: 4800, grace_period_days: 1, ..RateConfig::default() };
+    setup.contract.set_program_rate_config(&setup.admin, &0, &harsher);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 73 * 24 * 60 * 60; });
+    let face = 10_00_000_0000000i128;
+    assert_eq!(setup.contract.get_settlement_amount(&invoice_id), face + face * 1000 * 73 / (10000 * 365));
+    assert_eq!(setup.contract.get_settlement_amount(&unfunded_id), face + face * 2000 * 73 / (10000 * 365));
+
+    // Past due by two days: only the unpinned invoice defaults under the new grace period
+    setup.env.ledger().with_mut(|l| { l.timestamp += 19 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.check_status(&invoice_id), InvoiceStatus::Overdue);
+    assert_eq!(setup.contract.check_status(&unfunded_id), InvoiceStatus::Defaulted);
+}
+
+#[test]
+fn test_grace_period_interest_treatment() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    let face = invoice.amount;
+    let term_days = ((invoice.due_date - invoice.created_at) / 86400) as i128;
+    let due_at = |treatment: GraceInterest| {
+        setup.contract.set_program_rate_config(&setup.admin, &0, &RateConfig { grace_interest: treatment, ..RateConfig::default() });
+        setup.contract.get_settlement_amount(&invoice_id)
+    };
+
+    // Ten days into the 30-day grace period
+    setup.env.ledger().with_mut(|l| { l.timestamp = invoice.due_date + 10 * 86400; });
+    let days = term_days + 10;
+    assert_eq!(due_at(GraceInterest::Penalty), face + face * 2400 * days / (10000 * 365));
+    assert_eq!(due_at(GraceInterest::Base), face + face * 1000 * days / (10000 * 365));
+    assert_eq!(due_at(GraceInterest::Zero), face + face * 1000 * term_days / (10000 * 365));
+
+    // Past the grace period the penalty rate applies whatever the treatment
+    setup.env.ledger().with_mut(|l| { l.timestamp = invoice.due_date + 31 * 86400; });
+    let days = term_days + 31;
+    assert_eq!(due_at(GraceInterest::Zero), face + face * 2400 * days / (10000 * 365));
+    assert_eq!(due_at(GraceInterest::Base), face + face * 2400 * days / (10000 * 365));
+}
+
+#[test]
+fn test_day_count_conventions() {
+    use crate::interest::day_count;
+    // 2024-01-31 to 2024-03-01: 30 actual days, but 31 under 30/360 (Jan 31 counts as the 30th)
+    let (jan31, mar1) = (1_706_659_200, 1_709_251_200);
+    assert_eq!(day_count(&DayCount::Act365, jan31, mar1), 30);
+    assert_eq!(day_count(&DayCount::Act360, jan31, mar1), 30);
+    assert_eq!(day_count(&DayCount::Thirty360, jan31, mar1), 31);
+    // 2024-02-28 to 2024-08-31: the 31st only rolls back when the start was the 30th or later
+    let (feb28, aug31) = (1_709_078_400, 1_725_062_400);
+    assert_eq!(day_count(&DayCount::Thirty360, feb28, aug31), 183);
+    assert_eq!(day_count(&DayCount::Thirty360, mar1, jan31), 0);
+
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    let face = invoice.amount;
+    setup.env.ledger().with_mut(|l| { l.timestamp = invoice.created_at + 45 * 86400; });
+    let due_under = |day_count: DayCount| {
+        setup.contract.set_program_rate_config(&setup.admin, &0, &RateConfig { day_count, ..RateConfig::default() });
+        setup.contract.get_settlement_amount(&invoice_id)
+    };
+    assert_eq!(due_under(DayCount::Act365), face + face * 1000 * 45 / (10000 * 365));
+    assert_eq!(due_under(DayCount::Act360), face + face * 1000 * 45 / (10000 * 360));
+    let days = day_count(&DayCount::Thirty360, invoice.created_at, invoice.created_at + 45 * 86400) as i128;
+    assert_eq!(due_under(DayCount::Thirty360), face + face * 1000 * days / (10000 * 360));
+}
+
+#[test]
+fn test_early_settlement_discount() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    setup.contract.set_program_rate_config(&setup.admin, &0, &RateConfig { early_discount_bps_per_day: 5, max_early_discount_bps: 200, ..RateConfig::default() });
+
+    // 20 days early: 100 bps off
+    let quote = setup.contract.get_settlement_quote(&invoice_id, &(invoice.due_date - 20 * 86400));
+    assert_eq!(quote.days_early, 20);
+    assert_eq!(quote.discount, quote.gross_amount * 100 / 10000);
+    assert_eq!(quote.amount_due, quote.gross_amount - quote.discount);
+    // Far ahead of the due date the cap applies; on or after it there is no discount
+    let quote = setup.contract.get_settlement_quote(&invoice_id, &(invoice.due_date - 60 * 86400));
+    assert_eq!(quote.discount, quote.gross_amount * 200 / 10000);
+    let quote = setup.contract.get_settlement_quote(&invoice_id, &invoice.due_date);
+    assert_eq!((quote.days_early, quote.discount), (0, 0));
+
+    // Settling early only takes the discounted amount
+    setup.env.ledger().with_mut(|l| { l.timestamp = invoice.due_date - 20 * 86400; });
+    let quote = setup.contract.get_settlement_quote(&invoice_id, &(invoice.due_date - 20 * 86400));
+    assert_eq!(setup.contract.get_settlement_amount(&invoice_id), quote.amount_due);
+    setup.contract.settle(&invoice_id, &setup.buyer, &quote.amount_due);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Settled);
+
+    let result = setup.contract.try_set_program_rate_config(&setup.admin, &0, &RateConfig { max_early_discount_bps: 10001, ..RateConfig::default() });
+    assert_eq!(result, Err(Ok(ContractError::InvalidFeeSchedule)));
+}
+
+#[test]
+fn test_investor_concentration_cap() {
+    let setup = TestSetup::new();
+    setup.contract.set_program_rate_config(&setup.admin, &0, &RateConfig { max_investor_bps: 2000, ..RateConfig::default() });
+    let result = setup.contract.try_set_program_rate_config(&setup.admin, &0, &RateConfig { max_investor_bps: 10001, ..RateConfig::default() });
+    assert_eq!(result, Err(Ok(ContractError::InvalidFeeSchedule)));
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let supply = setup.contract.get_invoice(&invoice_id).total_tokens;
+
+    // 20% of supply, counting what the investor already holds
+    setup.contract.invest(&invoice_id, &setup.investor, &(supply * 15 / 100));
+    assert_eq!(setup.contract.get_investor_capacity(&invoice_id, &setup.investor), Some(supply * 5 / 100));
+    let result = setup.contract.try_invest(&invoice_id, &setup.investor, &(supply * 10 / 100));
+    assert_eq!(result, Err(Ok(ContractError::ConcentrationLimitExceeded)));
+    assert_eq!(setup.contract.get_investor_capacity(&invoice_id, &setup.supplier), None);
+
+    // Secondary fills count against the same cap
+    let order_id = setup.contract.create_sell_order(&invoice_id, &setup.supplier, &(supply * 10 / 100), &1);
+    let result = setup.contract.try_fill_order(&order_id, &setup.investor, &(supply * 10 / 100));
+    assert_eq!(result, Err(Ok(ContractError::ConcentrationLimitExceeded)));
+    setup.contract.fill_order(&order_id, &setup.investor, &(supply * 5 / 100));
+    assert_eq!(setup.contract.get_investor_capacity(&invoice_id, &setup.investor), Some(0));
+
+    // The invoice's own cap overrides the program's; clearing it falls back again
+    setup.contract.set_concentration_cap(&setup.admin, &invoice_id, &Some(3000));
+    assert_eq!(setup.contract.get_investor_capacity(&invoice_id, &setup.investor), Some(supply * 10 / 100));
+    setup.contract.set_concentration_cap(&setup.admin, &invoice_id, &Some(0));
+    assert_eq!(setup.contract.get_investor_capacity(&invoice_id, &setup.investor), None);
+    setup.contract.set_concentration_cap(&setup.admin, &invoice_id, &None);
+    assert_eq!(setup.contract.get_investor_capacity(&invoice_id, &setup.investor), Some(0));
+}
+
+#[test]
+fn test_fee_schedule_future_effective_date() {
+    let setup = TestSetup::new();
+    let effective_from = setup.env.ledger().timestamp() + 86400;
+    setup.contract.set_fee_schedule(&setup.admin, &FeeSchedule { effective_from, insurance_cut_bps: 800, ..Default::default() });
+    assert_eq!(setup.contract.get_active_fee_schedule().version, 1);
+
+    setup.env.ledger().with_mut(|l| l.timestamp = effective_from);
+    assert_eq!(setup.contract.get_active_fee_schedule().version, 2);
+
+    let result = setup.contract.try_set_fee_schedule(&setup.admin, &FeeSchedule { insurance_cut_bps: 10001, ..Default::default() });
+    assert_eq!(result, Err(Ok(ContractError::InvalidFeeSchedule)));
+}
+
+#[test]
+fn test_invest_from_contract_credits_beneficial_owner() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    let vault = Address::generate(&setup.env);
+    let end_user = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&vault, &1_00_000_0000000);
+
+    // Vault must be an authorized relayer and the end user KYC'd
+    let result = setup.contract.try_invest_from_contract(&invoice_id, &vault, &end_user, &1_00_000_0000000, &None);
+    assert_eq!(result, Err(Ok(ContractError::Unauthorized)));
+    setup.contract.set_relayer(&setup.admin, &vault, &true);
+    let result = setup.contract.try_invest_from_contract(&invoice_id, &vault, &end_user, &1_00_000_0000000, &None);
+    assert_eq!(result, Err(Ok(ContractError::KYCRequired)));
+
+    setup.contract.set_investor_kyc(&setup.admin, &end_user, &true);
+    setup.contract.invest_from_contract(&invoice_id, &vault, &end_user, &1_00_000_0000000, &None);
+
+    assert_eq!(setup.usdc.balance(&vault), 0);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &end_user).amount, 1_00_000_0000000);
+    assert!(setup.contract.try_get_holding(&invoice_id, &vault).is_err());
+}
+
 // ============================================================================
 // PHASE 3: SETTLEMENT TESTS
 // ============================================================================
@@ -222,7 +685,7 @@ fn test_invest_with_kyc() {
 fn test_settlement_distribution() {
     let setup = TestSetup::new();
     let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
 
     // Setup: KYC investor and invest
     setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
@@ -230,7 +693,6 @@ fn test_settlement_distribution() {
         &invoice_id,
         &setup.investor,
         &3_00_000_0000000,  // 30% of tokens
-        &2_94_000_0000000,  // 2% discount
     );
 
     // Fast forward to due date
@@ -244,165 +706,2900 @@ fn test_settlement_distribution() {
     // Buyer settles
     setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
 
-    // Check invoice is settled
+    // Check invoice is settled (one day past due)
     let invoice = setup.contract.get_invoice(&invoice_id);
-    assert_eq!(invoice.status, InvoiceStatus::Settled);
+    assert_eq!(invoice.status, InvoiceStatus::SettledLate);
 }
 
-// ============================================================================
-// DISPUTE TESTS
-// ============================================================================
-
 #[test]
-fn test_raise_dispute() {
+fn test_rate_models() {
     let setup = TestSetup::new();
-    let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
+    let amount: i128 = 10_00_000_0000000;
+    let tiered = setup.create_sample_invoice();
+    let benchmark = setup.create_sample_invoice();
+    let fixed = setup.create_sample_invoice();
 
-    // Buyer raises dispute
-    setup.contract.raise_dispute(
-        &invoice_id,
-        &setup.buyer,
-        &String::from_str(&setup.env, "Goods were defective"),
-    );
+    // Tiered: 10% for the first 30 days, 20% after
+    let mut tiers = Vec::new(&setup.env);
+    tiers.push_back(RateTier { from_day: 0, rate_bps: 1000 });
+    tiers.push_back(RateTier { from_day: 30, rate_bps: 2000 });
+    setup.contract.set_invoice_rate_model(&tiered, &setup.admin, &Some(RateModel::Tiered(tiers.clone())));
+    let mut unsorted = tiers.clone();
+    unsorted.set(1, RateTier { from_day: 0, rate_bps: 2000 });
+    assert_eq!(setup.contract.try_set_invoice_rate_model(&tiered, &setup.admin, &Some(RateModel::Tiered(unsorted))), Err(Ok(ContractError::InvalidFeeSchedule)));
 
-    // Check invoice is disputed
-    let invoice = setup.contract.get_invoice(&invoice_id);
-    assert_eq!(invoice.status, InvoiceStatus::Disputed);
+    // Benchmark: oracle's 7% minus a 2% spread
+    let oracle_id = setup.env.register(MockOracle, ());
+    MockOracleClient::new(&setup.env, &oracle_id).set_benchmark(&700);
+    setup.contract.set_invoice_rate_model(&benchmark, &setup.admin, &Some(RateModel::Benchmark(oracle_id, -200)));
 
-    // Check dispute record
-    let dispute = setup.contract.get_dispute(&invoice_id);
-    assert_eq!(dispute.raised_by, setup.buyer);
-    assert_eq!(dispute.resolution, DisputeResolution::Pending);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 60 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.get_settlement_amount(&tiered), amount + amount * 1000 * 30 / (10000 * 365) + amount * 2000 * 30 / (10000 * 365));
+    assert_eq!(setup.contract.get_settlement_amount(&benchmark), amount + amount * 500 * 60 / (10000 * 365));
+    assert_eq!(setup.contract.get_settlement_amount(&fixed), amount + amount * 1000 * 60 / (10000 * 365));
+    assert_eq!(setup.contract.get_rate_model(&fixed), RateModel::Fixed);
 }
 
 #[test]
-fn test_resolve_dispute_valid_clawback() {
+fn test_floating_rate_samples_benchmark() {
     let setup = TestSetup::new();
+    let amount: i128 = 10_00_000_0000000;
+    let oracle_id = setup.env.register(MockOracle, ());
+    let oracle = MockOracleClient::new(&setup.env, &oracle_id);
     let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
-
-    // Setup investor
-    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
-    setup.contract.invest(
-        &invoice_id,
-        &setup.investor,
-        &1_00_000_0000000,
-        &98_000_0000000,
-    );
+    setup.contract.set_invoice_rate_model(&invoice_id, &setup.admin, &Some(RateModel::Benchmark(oracle_id.clone(), 100)));
 
-    // Buyer raises dispute
-    setup.contract.raise_dispute(
-        &invoice_id,
-        &setup.buyer,
-        &String::from_str(&setup.env, "Goods defective"),
-    );
+    oracle.set_benchmark(&600);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let verified_at = setup.env.ledger().timestamp();
 
-    // Admin resolves dispute as VALID (clawback)
-    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &true);
+    // The benchmark moves; interest accrues at the average of the two readings plus the spread
+    oracle.set_benchmark(&800);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 60 * 24 * 60 * 60; });
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    assert_eq!(settlement, amount + amount * 800 * 60 / (10000 * 365));
+    setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
 
-    // Investor should have no tokens (clawback executed)
-    let result = std::panic::catch_unwind(|| {
-        setup.contract.get_holding(&invoice_id, &setup.investor);
-    });
-    assert!(result.is_err()); // Holding not found
+    let readings = setup.contract.get_benchmark_readings(&invoice_id).unwrap();
+    assert_eq!(readings, BenchmarkReadings { verification_rate: 600, verified_at, settlement_rate: 800, settled_at: verified_at + 60 * 86400 });
+    // Later oracle moves don't rewrite a settled invoice's figures
+    oracle.set_benchmark(&2000);
+    assert_eq!(setup.contract.get_settlement_amount(&invoice_id), settlement);
 }
 
 #[test]
-fn test_resolve_dispute_invalid() {
+fn test_subscribers_notified_on_settlement() {
     let setup = TestSetup::new();
-    let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
+    let listener_id = setup.env.register(MockListener, ());
+    let listener = MockListenerClient::new(&setup.env, &listener_id);
+    let failing_id = setup.env.register(MockListener, ());
+    MockListenerClient::new(&setup.env, &failing_id).set_failing();
+    // A failing subscriber is registered first and must not block the others or the settlement
+    setup.contract.add_subscriber(&setup.admin, &failing_id);
+    setup.contract.add_subscriber(&setup.admin, &listener_id);
+    setup.contract.add_subscriber(&setup.admin, &listener_id);
+    assert_eq!(setup.contract.get_subscribers().len(), 2);
 
-    setup.contract.raise_dispute(
-        &invoice_id,
-        &setup.buyer,
-        &String::from_str(&setup.env, "Testing"),
-    );
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    assert_eq!(listener.calls(), 0);
 
-    // Admin resolves dispute as INVALID (unfreeze)
-    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &false);
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
+    assert_eq!(listener.calls(), 1);
+    assert_eq!(listener.last(), InvoiceStatus::Settled);
 
-    // Invoice should be back to FUNDED
-    let invoice = setup.contract.get_invoice(&invoice_id);
-    assert_eq!(invoice.status, InvoiceStatus::Funded);
+    setup.contract.remove_subscriber(&setup.admin, &listener_id);
+    assert_eq!(setup.contract.get_subscribers().len(), 1);
+    for _ in 1..crate::hooks::MAX_SUBSCRIBERS {
+        let extra = setup.env.register(MockListener, ());
+        setup.contract.add_subscriber(&setup.admin, &extra);
+    }
+    let result = setup.contract.try_add_subscriber(&setup.admin, &listener_id);
+    assert_eq!(result, Err(Ok(ContractError::LimitReached)));
 }
 
-// ============================================================================
-// REVOCATION TESTS
-// ============================================================================
-
 #[test]
-fn test_revoke_draft() {
+fn test_settle_on_time() {
     let setup = TestSetup::new();
     let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
 
-    // Supplier revokes draft
-    setup.contract.revoke(&invoice_id, &setup.supplier);
+    setup.env.ledger().with_mut(|l| {
+        l.timestamp = l.timestamp + (60 * 24 * 60 * 60); // 60 days
+    });
+
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
 
     let invoice = setup.contract.get_invoice(&invoice_id);
-    assert_eq!(invoice.status, InvoiceStatus::Revoked);
+    assert_eq!(invoice.status, InvoiceStatus::Settled);
+    assert!(invoice.status.is_settled());
 }
 
 #[test]
-fn test_revoke_stale_verified() {
+fn test_settle_short_after_due_date() {
     let setup = TestSetup::new();
     let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
 
-    // Fast forward past due date
+    // Before due date the full settlement amount (with accrued interest) is required
     setup.env.ledger().with_mut(|l| {
-        l.timestamp = l.timestamp + (100 * 24 * 60 * 60); // 100 days
+        l.timestamp = l.timestamp + (30 * 24 * 60 * 60); // 30 days
     });
+    let result = setup.contract.try_settle(&invoice_id, &setup.buyer, &10_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::InsufficientPayment)));
 
-    // Supplier revokes stale invoice
-    setup.contract.revoke(&invoice_id, &setup.supplier);
+    setup.env.ledger().with_mut(|l| {
+        l.timestamp = l.timestamp + (65 * 24 * 60 * 60); // 95 days total
+    });
+
+    // Past due, face value alone no longer covers the late penalty
+    let result = setup.contract.try_settle(&invoice_id, &setup.buyer, &10_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::InsufficientPayment)));
+
+    // Face value plus the penalty for the 5 days past due closes out short of the accrued interest
+    let penalty = 10_00_000_0000000 * 2400 * 5 / (10000 * 365);
+    setup.contract.settle(&invoice_id, &setup.buyer, &(10_00_000_0000000 + penalty));
 
     let invoice = setup.contract.get_invoice(&invoice_id);
-    assert_eq!(invoice.status, InvoiceStatus::Revoked);
+    assert_eq!(invoice.status, InvoiceStatus::SettledShort);
+    assert_eq!(invoice.repayment_received, 10_00_000_0000000 + penalty);
 }
 
-// ============================================================================
-// STATUS CHECK TESTS
-// ============================================================================
-
 #[test]
-fn test_check_status_overdue() {
+fn test_migrate_legacy_settled_status() {
     let setup = TestSetup::new();
     let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
 
-    // Fast forward past due date
-    setup.env.ledger().with_mut(|l| {
-        l.timestamp = l.timestamp + (91 * 24 * 60 * 60);
+    // Simulate an invoice settled late before the late/short statuses existed
+    let mut invoice = setup.contract.get_invoice(&invoice_id);
+    invoice.status = InvoiceStatus::Settled;
+    invoice.settled_at = invoice.due_date + 86400;
+    invoice.repayment_received = invoice.amount;
+    setup.env.as_contract(&setup.contract.address, || {
+        storage::set_invoice(&setup.env, &invoice_id, &invoice);
     });
 
-    let status = setup.contract.check_status(&invoice_id);
-    assert_eq!(status, InvoiceStatus::Overdue);
+    let status = setup.contract.migrate_settled_status(&invoice_id);
+    assert_eq!(status, InvoiceStatus::SettledLate);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::SettledLate);
+
+    // Already migrated invoices are rejected
+    let result = setup.contract.try_migrate_settled_status(&invoice_id);
+    assert_eq!(result, Err(Ok(ContractError::InvalidStatus)));
 }
 
 #[test]
-fn test_check_status_defaulted() {
+fn test_prepayment_reduces_interest_and_nets_at_settlement() {
     let setup = TestSetup::new();
     let invoice_id = setup.create_sample_invoice();
-    setup.contract.approve_invoice(&invoice_id, &setup.buyer);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // Half of face value is prepaid on day 0, so it never accrues interest
+    setup.contract.prepay(&invoice_id, &setup.buyer, &5_00_000_0000000);
+    assert_eq!(setup.contract.get_prepaid_amount(&invoice_id), 5_00_000_0000000);
 
-    // Fast forward past grace period (90 + 30 days)
     setup.env.ledger().with_mut(|l| {
-        l.timestamp = l.timestamp + (121 * 24 * 60 * 60);
+        l.timestamp = l.timestamp + (73 * 24 * 60 * 60); // 73 days = 1/5 year
     });
 
-    let status = setup.contract.check_status(&invoice_id);
-    assert_eq!(status, InvoiceStatus::Defaulted);
-}
+    // Interest accrues only on the unpaid half: 5L * 10% * 73/365 = 10,000
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    assert_eq!(settlement, 10_10_000_0000000);
 
-// ============================================================================
-// KYC TESTS
-// ============================================================================
+    let buyer_before = setup.usdc.balance(&setup.buyer);
+    setup.contract.settle(&invoice_id, &setup.buyer, &(settlement - 5_00_000_0000000));
+    assert_eq!(buyer_before - setup.usdc.balance(&setup.buyer), 5_10_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.supplier), settlement);
 
-#[test]
-fn test_kyc_status() {
-    let setup = TestSetup::new();
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.status, InvoiceStatus::Settled);
+    assert_eq!(invoice.repayment_received, settlement);
+    assert_eq!(setup.contract.get_prepaid_amount(&invoice_id), 0);
+}
+
+#[test]
+fn test_redemption_receipts_after_settlement() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+
+    setup.env.ledger().with_mut(|l| {
+        l.timestamp = l.timestamp + (73 * 24 * 60 * 60);
+    });
+    let settlement = setup.contract.get_settlement_amount(&invoice_id); // 10.2L
+    setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
+
+    // Holdings are gone but the payout history remains
+    assert!(setup.contract.try_get_holding(&invoice_id, &setup.investor).is_err());
+    let receipt = setup.contract.get_receipt(&invoice_id, &setup.investor);
+    assert_eq!(receipt.tokens_redeemed, 2_00_000_0000000);
+    assert_eq!(receipt.amount_received, 2_04_000_0000000);
+    assert_eq!(receipt.principal, 2_00_000_0000000);
+    assert_eq!(receipt.interest, 4_000_0000000);
+    assert_eq!(receipt.redeemed_at, setup.env.ledger().timestamp());
+
+    let supplier_receipt = setup.contract.get_receipt(&invoice_id, &setup.supplier);
+    assert_eq!(supplier_receipt.tokens_redeemed, 8_00_000_0000000);
+}
+
+#[test]
+fn test_simulate_settlement_matches_settle() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+
+    let settle_at = setup.env.ledger().timestamp() + (73 * 24 * 60 * 60);
+    let simulation = setup.contract.simulate_settlement(&invoice_id, &10_20_000_0000000, &settle_at);
+    assert_eq!(simulation.required_payment, 10_20_000_0000000);
+    assert_eq!(simulation.status, InvoiceStatus::Settled);
+    assert_eq!(simulation.payouts.len(), 2);
+
+    // Nothing moved
+    assert!(setup.contract.get_holding(&invoice_id, &setup.investor).amount > 0);
+
+    let result = setup.contract.try_simulate_settlement(&invoice_id, &10_00_000_0000000, &settle_at);
+    assert_eq!(result.err(), Some(Ok(ContractError::InsufficientPayment)));
+
+    setup.env.ledger().with_mut(|l| l.timestamp = settle_at);
+    setup.contract.settle(&invoice_id, &setup.buyer, &10_20_000_0000000);
+    for payout in simulation.payouts.iter() {
+        let receipt = setup.contract.get_receipt(&invoice_id, &payout.holder);
+        assert_eq!(receipt.amount_received, payout.amount_received);
+    }
+}
+
+#[test]
+fn test_cross_currency_settlement() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    let (eurc, eurc_admin) = create_token_contract(&setup.env, &setup.admin);
+    eurc_admin.mint(&setup.buyer, &10_00_000_0000000);
+
+    let oracle_id = setup.env.register(MockOracle, ());
+    MockOracleClient::new(&setup.env, &oracle_id).set_rate(&9_000_000); // 0.90 EURC per USDC
+    setup.contract.set_price_oracle(&setup.admin, &oracle_id);
+
+    // Only allow-listed tokens
+    let result = setup.contract.try_set_settlement_token(&invoice_id, &setup.buyer, &eurc.address);
+    assert_eq!(result, Err(Ok(ContractError::TokenNotAllowed)));
+    setup.contract.set_settlement_token_allowed(&setup.admin, &eurc.address, &true);
+    setup.contract.set_settlement_token(&invoice_id, &setup.buyer, &eurc.address);
+
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+
+    setup.env.ledger().with_mut(|l| {
+        l.timestamp = l.timestamp + (73 * 24 * 60 * 60);
+    });
+    // 10.2L USDC owed = 9.18L EURC
+    let result = setup.contract.try_settle(&invoice_id, &setup.buyer, &9_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::InsufficientPayment)));
+    setup.contract.settle(&invoice_id, &setup.buyer, &9_18_000_0000000);
+
+    assert_eq!(eurc.balance(&setup.investor), 1_83_600_0000000);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.status, InvoiceStatus::Settled);
+    assert_eq!(invoice.repayment_received, 10_20_000_0000000);
+    assert_eq!(invoice.settlement_paid, 9_18_000_0000000);
+    assert_eq!(invoice.settlement_fx_rate, 9_000_000);
+    let receipt = setup.contract.get_receipt(&invoice_id, &setup.investor);
+    assert_eq!(receipt.principal, 1_80_000_0000000);
+}
+
+#[test]
+fn test_payout_preference_swaps_or_falls_back() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    let (eurc, eurc_admin) = create_token_contract(&setup.env, &setup.admin);
+
+    let oracle_id = setup.env.register(MockOracle, ());
+    MockOracleClient::new(&setup.env, &oracle_id).set_rate(&9_000_000);
+    setup.contract.set_price_oracle(&setup.admin, &oracle_id);
+    setup.contract.set_settlement_token_allowed(&setup.admin, &eurc.address, &true);
+
+    let router_id = setup.env.register(MockRouter, ());
+    eurc_admin.mint(&router_id, &10_00_000_0000000);
+    setup.contract.set_swap_router(&setup.admin, &router_id);
+
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    setup.contract.set_payout_preference(&setup.investor, &eurc.address);
+
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
+
+    // Investor paid out in EURC at the oracle rate, supplier in USDC
+    let receipt = setup.contract.get_receipt(&invoice_id, &setup.investor);
+    assert_eq!(receipt.payout_token, eurc.address);
+    assert_eq!(receipt.payout_amount, 1_80_000_0000000);
+    assert_eq!(eurc.balance(&setup.investor), 1_80_000_0000000);
+    let supplier_receipt = setup.contract.get_receipt(&invoice_id, &setup.supplier);
+    assert_eq!(supplier_receipt.payout_token, setup.usdc.address);
+}
+
+#[test]
+fn test_payout_preference_falls_back_when_swap_fails() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    let (eurc, _) = create_token_contract(&setup.env, &setup.admin);
+
+    let oracle_id = setup.env.register(MockOracle, ());
+    MockOracleClient::new(&setup.env, &oracle_id).set_rate(&9_000_000);
+    setup.contract.set_price_oracle(&setup.admin, &oracle_id);
+    setup.contract.set_settlement_token_allowed(&setup.admin, &eurc.address, &true);
+
+    // Router holds no EURC, so the swap fails
+    let router_id = setup.env.register(MockRouter, ());
+    setup.contract.set_swap_router(&setup.admin, &router_id);
+
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    setup.contract.set_payout_preference(&setup.investor, &eurc.address);
+
+    let investor_before = setup.usdc.balance(&setup.investor);
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
+
+    let receipt = setup.contract.get_receipt(&invoice_id, &setup.investor);
+    assert_eq!(receipt.payout_token, setup.usdc.address);
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, receipt.amount_received);
+}
+
+#[test]
+fn test_prepay_cannot_exceed_face_value() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    setup.contract.prepay(&invoice_id, &setup.buyer, &6_00_000_0000000);
+    let result = setup.contract.try_prepay(&invoice_id, &setup.buyer, &5_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::InvalidAmount)));
+}
+
+#[test]
+fn test_buyer_balance_confirmation() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let hash = String::from_str(&setup.env, "QmBalanceConfirmation");
+
+    // Nothing to confirm before investors are in
+    let result = setup.contract.try_confirm_balance(&invoice_id, &setup.buyer, &10_00_000_0000000, &hash);
+    assert_eq!(result, Err(Ok(ContractError::InvalidStatus)));
+
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    setup.contract.prepay(&invoice_id, &setup.buyer, &1_00_000_0000000);
+
+    // Attested amount must match face value net of prepayments
+    let result = setup.contract.try_confirm_balance(&invoice_id, &setup.buyer, &10_00_000_0000000, &hash);
+    assert_eq!(result, Err(Ok(ContractError::InvalidAmount)));
+
+    setup.contract.confirm_balance(&invoice_id, &setup.buyer, &9_00_000_0000000, &hash);
+    let confirmation = setup.contract.get_balance_confirmation(&invoice_id).unwrap();
+    assert_eq!(confirmation.outstanding_amount, 9_00_000_0000000);
+    assert_eq!(confirmation.confirmation_hash, hash);
+    assert_eq!(confirmation.confirmed_at, setup.env.ledger().timestamp());
+}
+
+// ============================================================================
+// RISK SCORE TESTS
+// ============================================================================
+
+#[test]
+fn test_buyer_credit_history() {
+    let setup = TestSetup::new();
+    assert_eq!(setup.contract.get_buyer_score(&setup.buyer), 5000);
+
+    let paid = setup.create_sample_invoice();
+    let unpaid = setup.create_sample_invoice();
+    let disputed = setup.create_sample_invoice();
+    for invoice_id in [&paid, &unpaid, &disputed] { setup.contract.approve_invoice(invoice_id, &setup.buyer, &0); }
+
+    let settlement = setup.contract.get_settlement_amount(&paid);
+    setup.contract.settle(&paid, &setup.buyer, &settlement);
+    // One on-time settlement moves a tenth of the way from neutral to a perfect record
+    assert_eq!(setup.contract.get_buyer_score(&setup.buyer), 5500);
+
+    setup.contract.raise_dispute(&disputed, &setup.buyer, &String::from_str(&setup.env, "Not delivered"));
+    setup.contract.resolve_dispute(&disputed, &setup.admin, &false);
+    assert_eq!(setup.contract.get_buyer_score(&setup.buyer), 5000);
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.check_status(&unpaid), InvoiceStatus::Defaulted);
+
+    let stats = setup.contract.get_buyer_stats(&setup.buyer);
+    assert_eq!(stats, BuyerStats { settled_on_time: 1, settled_late: 0, defaulted: 1, disputed: 1, disputes_rejected: 1, total_volume: 10_00_000_0000000 });
+    // Record of 5000 over two closed invoices, less the rejected dispute
+    assert_eq!(setup.contract.get_buyer_score(&setup.buyer), 4500);
+    // The invoice risk score picks up the buyer's history
+    let fresh = setup.contract.get_risk_score(&setup.create_sample_invoice());
+    assert!(fresh > 5743);
+}
+
+#[test]
+fn test_supplier_credit_history() {
+    let setup = TestSetup::new();
+    assert_eq!(setup.contract.get_supplier_score(&setup.supplier), 5000);
+
+    let paid = setup.create_sample_invoice();
+    let unpaid = setup.create_sample_invoice();
+    let invalid = setup.create_sample_invoice();
+    for invoice_id in [&paid, &unpaid, &invalid] { setup.contract.approve_invoice(invoice_id, &setup.buyer, &0); }
+
+    let settlement = setup.contract.get_settlement_amount(&paid);
+    setup.contract.settle(&paid, &setup.buyer, &settlement);
+    assert_eq!(setup.contract.get_supplier_score(&setup.supplier), 5500);
+
+    // A dispute upheld against the supplier's invoice counts against it
+    setup.contract.raise_dispute(&invalid, &setup.buyer, &String::from_str(&setup.env, "Goods never shipped"));
+    setup.contract.resolve_dispute(&invalid, &setup.admin, &true);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 3 * 24 * 60 * 60 + 1; });
+    setup.contract.finalize_dispute(&invalid);
+    assert_eq!(setup.contract.get_supplier_score(&setup.supplier), 4500);
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.check_status(&unpaid), InvoiceStatus::Defaulted);
+    assert_eq!(setup.contract.get_supplier_stats(&setup.supplier), SupplierStats { settled: 1, defaulted: 1, disputes_upheld: 1 });
+    // Record of 5000 over two closed invoices, less the upheld dispute
+    assert_eq!(setup.contract.get_supplier_score(&setup.supplier), 4000);
+}
+
+#[test]
+fn test_risk_score_components() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // Neutral parties, 90-day tenor, no insurance, nothing sold
+    // (5000*3500 + 5000*2000 + 2465*2000 + 10000*1500 + 10000*1000) / 10000
+    assert_eq!(setup.contract.get_risk_score(&invoice_id), 5743);
+
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &5_00_000_0000000);
+    let after_invest = setup.contract.get_risk_score(&invoice_id);
+    assert!(after_invest < 5743);
+
+    setup.contract.confirm_balance(
+        &invoice_id,
+        &setup.buyer,
+        &10_00_000_0000000,
+        &String::from_str(&setup.env, "QmBalanceConfirmation"),
+    );
+    assert!(setup.contract.get_risk_score(&invoice_id) < after_invest);
+}
+
+// ============================================================================
+// DISPUTE TESTS
+// ============================================================================
+
+#[test]
+fn test_raise_dispute() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // Buyer raises dispute
+    setup.contract.raise_dispute(
+        &invoice_id,
+        &setup.buyer,
+        &String::from_str(&setup.env, "Goods were defective"),
+    );
+
+    // Check invoice is disputed
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.status, InvoiceStatus::Disputed);
+
+    // Check dispute record
+    let dispute = setup.contract.get_dispute(&invoice_id);
+    assert_eq!(dispute.raised_by, setup.buyer);
+    assert_eq!(dispute.resolution, DisputeResolution::Pending);
+}
+
+#[test]
+fn test_dispute_throttle_for_repeatedly_rejected_buyers() {
+    let setup = TestSetup::new();
+    let bond = 1_000_0000000;
+    setup.contract.set_dispute_limits(&setup.admin, &DisputeLimits { rejected_limit: 1, window_seconds: 30 * 86400, bond });
+    let reason = String::from_str(&setup.env, "Goods were defective");
+    let verified = || { let id = setup.create_sample_invoice(); setup.contract.approve_invoice(&id, &setup.buyer, &0); id };
+
+    // The first dispute is free; its rejection puts the buyer at the limit
+    let first = verified();
+    setup.contract.raise_dispute(&first, &setup.buyer, &reason);
+    setup.contract.resolve_dispute(&first, &setup.admin, &false);
+    assert_eq!(setup.contract.get_recent_dispute_rejections(&setup.buyer), 1);
+
+    // The next one takes a bond, paid to the supplier when this dispute is rejected too
+    let second = verified();
+    let buyer_before = setup.usdc.balance(&setup.buyer);
+    setup.contract.raise_dispute(&second, &setup.buyer, &reason);
+    assert_eq!(buyer_before - setup.usdc.balance(&setup.buyer), bond);
+    let supplier_before = setup.usdc.balance(&setup.supplier);
+    setup.contract.resolve_dispute(&second, &setup.admin, &false);
+    assert_eq!(setup.usdc.balance(&setup.supplier) - supplier_before, bond);
+
+    // Without a bond configured, only pre-cleared disputes go through
+    setup.contract.set_dispute_limits(&setup.admin, &DisputeLimits { rejected_limit: 1, window_seconds: 30 * 86400, bond: 0 });
+    let third = verified();
+    assert_eq!(setup.contract.try_raise_dispute(&third, &setup.buyer, &reason), Err(Ok(ContractError::Unauthorized)));
+    setup.contract.clear_dispute(&setup.admin, &third);
+    setup.contract.raise_dispute(&third, &setup.buyer, &reason);
+    setup.contract.resolve_dispute(&third, &setup.admin, &false);
+
+    // Rejections age out of the window
+    setup.env.ledger().with_mut(|l| { l.timestamp += 30 * 86400; });
+    assert_eq!(setup.contract.get_recent_dispute_rejections(&setup.buyer), 0);
+    let fourth = verified();
+    setup.contract.raise_dispute(&fourth, &setup.buyer, &reason);
+}
+
+#[test]
+fn test_settle_rejected_while_disputed() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Short shipment"));
+
+    let result = setup.contract.try_settle(&invoice_id, &setup.buyer, &10_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::InvoiceDisputed)));
+    let result = setup.contract.try_prepay(&invoice_id, &setup.buyer, &1_0000000);
+    assert_eq!(result, Err(Ok(ContractError::InvoiceDisputed)));
+}
+
+#[test]
+fn test_state_machine_transition_matrix() {
+    extern crate std;
+    use crate::state_machine::{next, Event};
+    use InvoiceStatus::*;
+    let setup = TestSetup::new();
+    let mut invoice = setup.contract.get_invoice(&setup.create_sample_invoice());
+    let before_due = invoice.due_date;
+    let statuses = [
+        Draft, Verified, Funding, Funded, Overdue, Settled, SettledLate, SettledShort, Defaulted, Disputed, Revoked,
+        Rejected, Recovered, WrittenOff,
+    ];
+    let mut events = std::vec![
+        Event::Approve, Event::StartAuction, Event::Fund, Event::ClearAuction, Event::MarkOverdue, Event::MarkDefaulted,
+        Event::Settle { short: false }, Event::Settle { short: true }, Event::ReclassifyLegacy, Event::RaiseDispute, Event::Revoke,
+        Event::Reject, Event::Resubmit, Event::Extend, Event::Recover, Event::WriteOff,
+    ];
+    for status in statuses.iter() { events.push(Event::RestoreFromDispute(status.clone())); }
+
+    // Every allowed move (checked before the due date); everything else must be rejected
+    let allowed = [
+        (Draft, Event::Approve, Verified),
+        (Draft, Event::Revoke, Revoked),
+        (Draft, Event::Reject, Rejected),
+        (Rejected, Event::Resubmit, Draft),
+        (Rejected, Event::Revoke, Revoked),
+        (Verified, Event::StartAuction, Funding),
+        (Verified, Event::Fund, Funded),
+        (Funding, Event::Fund, Funded),
+        (Funding, Event::ClearAuction, Verified),
+        (Verified, Event::MarkOverdue, Overdue),
+        (Funding, Event::MarkOverdue, Overdue),
+        (Funded, Event::MarkOverdue, Overdue),
+        (Verified, Event::MarkDefaulted, Defaulted),
+        (Funding, Event::MarkDefaulted, Defaulted),
+        (Funded, Event::MarkDefaulted, Defaulted),
+        (Overdue, Event::MarkDefaulted, Defaulted),
+        (Verified, Event::Settle { short: false }, Settled),
+        (Funding, Event::Settle { short: false }, Settled),
+        (Funded, Event::Settle { short: false }, Settled),
+        (Overdue, Event::Settle { short: false }, Settled),
+        (Verified, Event::Settle { short: true }, SettledShort),
+        (Funding, Event::Settle { short: true }, SettledShort),
+        (Funded, Event::Settle { short: true }, SettledShort),
+        (Overdue, Event::Settle { short: true }, SettledShort),
+        (Settled, Event::ReclassifyLegacy, Settled),
+        (Verified, Event::RaiseDispute, Disputed),
+        (Funding, Event::RaiseDispute, Disputed),
+        (Funded, Event::RaiseDispute, Disputed),
+        (Overdue, Event::RaiseDispute, Disputed),
+        (Disputed, Event::RestoreFromDispute(Verified), Verified),
+        (Disputed, Event::RestoreFromDispute(Funding), Funding),
+        (Disputed, Event::RestoreFromDispute(Funded), Funded),
+        (Disputed, Event::RestoreFromDispute(Overdue), Overdue),
+        (Funded, Event::Extend, Funded),
+        (Overdue, Event::Extend, Funded),
+        (Defaulted, Event::Recover, Recovered),
+        (Defaulted, Event::WriteOff, WrittenOff),
+    ];
+    for status in statuses.iter() {
+        invoice.status = status.clone();
+        for event in events.iter() {
+            let expected = allowed.iter().find(|(from, e, _)| from == status && e == event).map(|(_, _, to)| to.clone());
+            match (next(&invoice, event, before_due), expected) {
+                (Ok(to), Some(expected)) => assert_eq!(to, expected),
+                (Err(_), None) => {}
+                (got, expected) => panic!("{:?} on {:?}: got {:?}, expected {:?}", event, status, got, expected),
+            }
+        }
+    }
+
+    // Errors that carry more than InvalidStatus, and the moves that depend on the clock
+    invoice.status = Disputed;
+    assert_eq!(next(&invoice, &Event::Settle { short: false }, before_due), Err(ContractError::InvoiceDisputed));
+    invoice.status = Funded;
+    assert_eq!(next(&invoice, &Event::Revoke, before_due), Err(ContractError::CannotRevoke));
+    assert_eq!(next(&invoice, &Event::Approve, before_due), Err(ContractError::InvalidStatus));
+    assert_eq!(next(&invoice, &Event::Settle { short: false }, before_due + 1), Ok(SettledLate));
+    invoice.status = Verified;
+    assert_eq!(next(&invoice, &Event::Revoke, before_due), Err(ContractError::CannotRevoke));
+    assert_eq!(next(&invoice, &Event::Revoke, before_due + 1), Ok(Revoked));
+    invoice.status = Settled;
+    assert_eq!(next(&invoice, &Event::ReclassifyLegacy, before_due + 1), Ok(SettledLate));
+}
+
+#[test]
+fn test_resolve_dispute_valid_clawback() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // Setup investor
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(
+        &invoice_id,
+        &setup.investor,
+        &1_00_000_0000000,
+    );
+
+    // Buyer raises dispute
+    setup.contract.raise_dispute(
+        &invoice_id,
+        &setup.buyer,
+        &String::from_str(&setup.env, "Goods defective"),
+    );
+
+    // Admin resolves dispute as VALID; clawback runs once the appeal window lapses
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &true);
+    assert_eq!(setup.contract.try_finalize_dispute(&invoice_id), Err(Ok(ContractError::AppealPending)));
+    setup.env.ledger().with_mut(|l| { l.timestamp += 3 * 24 * 60 * 60 + 1; });
+    setup.contract.finalize_dispute(&invoice_id);
+
+    // Investor should have no tokens (clawback executed)
+    let result = setup.contract.try_get_holding(&invoice_id, &setup.investor);
+    assert_eq!(result.err(), Some(Ok(ContractError::HoldingNotFound)));
+}
+
+#[test]
+fn test_partial_clawback_spares_earlier_holders() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+
+    // Buyer flags a problem; a later investor buys in anyway
+    setup.env.ledger().with_mut(|l| { l.timestamp += 86400; });
+    let notified_at = setup.env.ledger().timestamp();
+    setup.env.ledger().with_mut(|l| { l.timestamp += 3600; });
+    let late_investor = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&late_investor, &1_00_000_0000000);
+    setup.contract.set_investor_kyc(&setup.admin, &late_investor, &true);
+    setup.contract.invest(&invoice_id, &late_investor, &1_00_000_0000000);
+    // The earlier investor tops up after the notice too; only the top-up is clawed back
+    let before_top_up = setup.usdc.balance(&setup.investor);
+    setup.contract.invest(&invoice_id, &setup.investor, &50_000_0000000);
+    let top_up_cost = before_top_up - setup.usdc.balance(&setup.investor);
+
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Partial shortfall"));
+    setup.contract.resolve_dispute_after(&invoice_id, &setup.admin, &notified_at);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 3 * 24 * 60 * 60 + 1; });
+    let before_clawback = setup.usdc.balance(&setup.investor);
+    setup.contract.finalize_dispute(&invoice_id);
+
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, 1_00_000_0000000);
+    let lots = setup.contract.get_holding_lots(&invoice_id, &setup.investor);
+    assert_eq!(lots.len(), 1);
+    assert!(lots.get(0).unwrap().acquired_at <= notified_at);
+    // Refunded at the holding's average cost, fronted by the insurance sub-pool or owed by the supplier
+    let refunded = setup.usdc.balance(&setup.investor) - before_clawback + setup.contract.get_refund_owed(&invoice_id, &setup.investor);
+    assert!(refunded > 0 && refunded <= top_up_cost);
+    assert!(setup.contract.try_get_holding(&invoice_id, &late_investor).is_err());
+    assert!(setup.contract.try_get_holding(&invoice_id, &setup.supplier).is_err());
+}
+
+#[test]
+fn test_clawback_cancels_open_sell_orders() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    let order_id = setup.contract.create_sell_order(&invoice_id, &setup.investor, &50_000_0000000, &1);
+
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Goods defective"));
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &true);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 3 * 24 * 60 * 60 + 1; });
+    setup.contract.finalize_dispute(&invoice_id);
+
+    assert_eq!(setup.contract.get_order(&order_id).status, OrderStatus::Cancelled);
+    assert_eq!(setup.contract.get_open_orders(&invoice_id, &0, &100).len(), 0);
+}
+
+#[test]
+fn test_investment_records_through_settlement() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let second = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&second, &1_000_000_0000000);
+    for investor in [&setup.investor, &second] { setup.contract.set_investor_kyc(&setup.admin, investor, &true); }
+
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    let order_id = setup.contract.create_sell_order(&invoice_id, &setup.investor, &40_000_0000000, &1);
+    setup.contract.fill_order(&order_id, &second, &40_000_0000000);
+
+    let records = setup.contract.get_investments_by_invoice(&invoice_id, &0, &10);
+    assert_eq!(records.len(), 2);
+    let primary = records.get(0).unwrap();
+    assert_eq!((primary.investor.clone(), primary.token_amount, primary.invested_amount), (setup.investor.clone(), 1_00_000_0000000, 1_00_000_0000000));
+    assert_eq!(primary.discount_rate, 0);
+    assert_eq!(primary.settled_at, 0);
+    assert_eq!(setup.contract.get_investments_by_investor(&second, &0, &10).get(0).unwrap().invested_amount, 40_000_0000000);
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 30 * 24 * 60 * 60; });
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    let (first_before, second_before) = (setup.usdc.balance(&setup.investor), setup.usdc.balance(&second));
+    setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
+
+    // Each record carries what its holder was paid for the tokens it still held
+    let primary = setup.contract.get_investments_by_investor(&setup.investor, &0, &10).get(0).unwrap();
+    assert_eq!(primary.settled_amount, setup.usdc.balance(&setup.investor) - first_before);
+    assert_eq!(primary.settled_at, setup.env.ledger().timestamp());
+    let secondary = setup.contract.get_investments_by_investor(&second, &0, &10).get(0).unwrap();
+    assert_eq!(secondary.settled_amount, setup.usdc.balance(&second) - second_before);
+    assert!(secondary.settled_amount > secondary.invested_amount);
+}
+
+#[test]
+fn test_buy_order_escrow_fill_and_cancel() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+
+    // Bid for 20,000 tokens at 1 escrows 20,000 USDC in the contract
+    let investor_before = setup.usdc.balance(&setup.investor);
+    let bid_id = setup.contract.create_buy_order(&invoice_id, &setup.investor, &20_000_0000000, &1);
+    assert_eq!(investor_before - setup.usdc.balance(&setup.investor), 20_000_0000000);
+    assert_eq!(setup.contract.get_sweepable_balance(&setup.usdc.address), 0);
+
+    // Supplier sells half into the bid, paid from escrow
+    setup.contract.fill_buy_order(&bid_id, &setup.supplier, &10_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 10_000_0000000);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, 10_000_0000000);
+    assert_eq!(setup.contract.get_buy_order(&bid_id).status, OrderStatus::PartiallyFilled);
+    assert_eq!(setup.contract.get_open_buy_orders(&invoice_id, &0, &100).len(), 1);
+
+    // Only the bidder cancels; the unfilled remainder is refunded
+    assert!(setup.contract.try_cancel_buy_order(&bid_id, &setup.supplier).is_err());
+    assert_eq!(setup.contract.cancel_buy_order(&bid_id, &setup.investor), 10_000_0000000);
+    assert_eq!(investor_before - setup.usdc.balance(&setup.investor), 10_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.contract.address), 0);
+    let result = setup.contract.try_fill_buy_order(&bid_id, &setup.supplier, &1);
+    assert_eq!(result, Err(Ok(ContractError::OrderNotActive)));
+}
+
+#[test]
+fn test_basket_order_fills_atomically() {
+    let setup = TestSetup::new();
+    let first = setup.create_sample_invoice();
+    let second = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&first, &setup.buyer, &0);
+    setup.contract.approve_invoice(&second, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+
+    let leg = |invoice_id: &String, token_amount: i128| BasketLeg { invoice_id: invoice_id.clone(), token_amount };
+    let duplicate = soroban_sdk::vec![&setup.env, leg(&first, 1), leg(&first, 1)];
+    let result = setup.contract.try_create_basket_order(&setup.supplier, &duplicate, &1);
+    assert_eq!(result, Err(Ok(ContractError::InvalidBasket)));
+
+    let legs = soroban_sdk::vec![&setup.env, leg(&first, 10_000_0000000), leg(&second, 30_000_0000000)];
+    let order_id = setup.contract.create_basket_order(&setup.supplier, &legs, &39_000_0000000);
+
+    // One payment moves every leg
+    setup.contract.fill_basket_order(&order_id, &setup.investor);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 39_000_0000000);
+    assert_eq!(setup.contract.get_holding(&first, &setup.investor).amount, 10_000_0000000);
+    assert_eq!(setup.contract.get_holding(&second, &setup.investor).amount, 30_000_0000000);
+    assert_eq!(setup.contract.get_basket_order(&order_id).status, OrderStatus::Filled);
+    let result = setup.contract.try_fill_basket_order(&order_id, &setup.investor);
+    assert_eq!(result, Err(Ok(ContractError::OrderNotActive)));
+}
+
+#[test]
+fn test_basket_order_reverts_when_a_leg_is_short() {
+    let setup = TestSetup::new();
+    let first = setup.create_sample_invoice();
+    let second = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&first, &setup.buyer, &0);
+    setup.contract.approve_invoice(&second, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.sub_vendor, &true);
+
+    let legs = soroban_sdk::vec![
+        &setup.env,
+        BasketLeg { invoice_id: first.clone(), token_amount: 10_000_0000000 },
+        BasketLeg { invoice_id: second.clone(), token_amount: 10_00_000_0000000 },
+    ];
+    let order_id = setup.contract.create_basket_order(&setup.supplier, &legs, &1_000_0000000);
+    // The seller moves part of the second leg away after listing
+    setup.contract.transfer_tokens(&second, &setup.supplier, &setup.sub_vendor, &1_0000000);
+
+    assert!(setup.contract.try_fill_basket_order(&order_id, &setup.investor).is_err());
+    assert!(setup.contract.try_get_holding(&first, &setup.investor).is_err());
+    assert_eq!(setup.usdc.balance(&setup.supplier), 0);
+    setup.contract.cancel_basket_order(&order_id, &setup.supplier);
+}
+
+#[test]
+fn test_installment_settlement() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &0, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.invest(&invoice_id, &setup.investor, &4_00_000_0000000);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 24 * 3600; });
+    setup.contract.finalize_auction(&invoice_id);
+
+    // First tranche: the investor holds 40% and can withdraw their share right away
+    assert_eq!(setup.contract.settle_partial(&invoice_id, &setup.buyer, &1_00_000_0000000), InvoiceStatus::Funded);
+    let investor_before = setup.usdc.balance(&setup.investor);
+    assert_eq!(setup.contract.claim_installments(&invoice_id, &setup.investor), 40_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, 40_000_0000000);
+    let result = setup.contract.try_settle(&invoice_id, &setup.buyer, &10_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::InstallmentConflict)));
+
+    // Shares already accrued stay with the seller when tokens change hands
+    setup.contract.transfer_tokens(&invoice_id, &setup.supplier, &setup.sub_vendor, &2_00_000_0000000);
+    assert_eq!(setup.contract.get_installment_account(&invoice_id, &setup.supplier).accrued, 60_000_0000000);
+    setup.contract.settle_partial(&invoice_id, &setup.buyer, &1_00_000_0000000);
+    assert_eq!(setup.contract.claim_installments(&invoice_id, &setup.sub_vendor), 20_000_0000000);
+
+    // The final tranche (capped at what is owed) settles and pays out unclaimed shares
+    let required = setup.contract.get_settlement_amount(&invoice_id);
+    assert_eq!(setup.contract.settle_partial(&invoice_id, &setup.buyer, &10_00_000_0000000), InvoiceStatus::Settled);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.repayment_received, required);
+    let receipt = setup.contract.get_receipt(&invoice_id, &setup.investor);
+    assert_eq!(receipt.amount_received, (required * 4) / 10);
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, (required * 4) / 10);
+    assert_eq!(setup.contract.get_sweepable_balance(&setup.usdc.address), 0);
+}
+
+#[test]
+fn test_rfq_executes_signed_quote() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let rfq_id = setup.contract.create_rfq(&invoice_id, &setup.supplier, &5_00_000_0000000);
+    let expires_at = setup.env.ledger().timestamp() + 600;
+
+    // Stale quotes are rejected
+    setup.env.ledger().with_mut(|l| { l.timestamp += 601; });
+    let result = setup.contract.try_execute_rfq(&rfq_id, &setup.supplier, &setup.investor, &1, &expires_at);
+    assert_eq!(result, Err(Ok(ContractError::OrderNotActive)));
+
+    // A fresh quote moves the whole block against the maker's authorization of its terms
+    let expires_at = setup.env.ledger().timestamp() + 600;
+    setup.contract.execute_rfq(&rfq_id, &setup.supplier, &setup.investor, &1, &expires_at);
+    let quote_args: soroban_sdk::Vec<soroban_sdk::Val> = (rfq_id.clone(), 1_i128, expires_at).into_val(&setup.env);
+    assert!(setup.env.auths().iter().any(|(addr, invocation)| *addr == setup.investor
+        && invocation.function == AuthorizedFunction::Contract((setup.contract.address.clone(), Symbol::new(&setup.env, "execute_rfq"), quote_args.clone()))));
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, 5_00_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 5_00_000_0000000);
+
+    // Each RFQ executes once
+    let result = setup.contract.try_execute_rfq(&rfq_id, &setup.supplier, &setup.investor, &1, &expires_at);
+    assert_eq!(result, Err(Ok(ContractError::OrderNotActive)));
+}
+
+#[test]
+fn test_maker_rebate_and_maker_fee_by_side() {
+    let setup = TestSetup::new();
+    let anchor = Address::generate(&setup.env);
+    let program_id = setup.contract.create_program(&setup.admin, &anchor, &RateConfig::default());
+    let rebate = ProgramFees { investment_fee_bps: 0, trading_fee_bps: 50, anchor_share_bps: 0, maker_fee_bps: -20 };
+    setup.contract.set_program_fees(&setup.admin, &program_id, &rebate);
+    let result = setup.contract.try_set_program_fees(&setup.admin, &program_id, &ProgramFees { maker_fee_bps: -60, ..rebate.clone() });
+    assert_eq!(result, Err(Ok(ContractError::InvalidFeeSchedule)));
+    setup.contract.set_supplier_program(&anchor, &program_id, &setup.supplier);
+    setup.contract.set_program_kyc(&anchor, &program_id, &setup.investor, &true);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // Resting sell order: the taker pays 0.5%, the maker earns a 0.2% rebate
+    let order_id = setup.contract.create_sell_order(&invoice_id, &setup.supplier, &10_000_0000000, &1);
+    let investor_before = setup.usdc.balance(&setup.investor);
+    setup.contract.fill_order(&order_id, &setup.investor, &10_000_0000000);
+    assert_eq!(investor_before - setup.usdc.balance(&setup.investor), 10_050_0000000);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 10_020_0000000);
+    assert_eq!(setup.contract.get_treasury_balance(&0), 30_0000000);
+
+    // A 0.1% maker fee is locked and escrowed with a bid; the selling taker pays 0.5%
+    setup.contract.set_program_fees(&setup.admin, &program_id, &ProgramFees { maker_fee_bps: 10, ..rebate });
+    let investor_before = setup.usdc.balance(&setup.investor);
+    let bid_id = setup.contract.create_buy_order(&invoice_id, &setup.investor, &10_000_0000000, &1);
+    assert_eq!(investor_before - setup.usdc.balance(&setup.investor), 10_010_0000000);
+    setup.contract.fill_buy_order(&bid_id, &setup.supplier, &4_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 10_020_0000000 + 3_980_0000000);
+    assert_eq!(setup.contract.get_treasury_balance(&0), 30_0000000 + 24_0000000);
+    assert_eq!(setup.contract.cancel_buy_order(&bid_id, &setup.investor), 6_006_0000000);
+    assert_eq!(setup.contract.get_sweepable_balance(&setup.usdc.address), 0);
+}
+
+#[test]
+fn test_price_band_rejects_off_market_fills() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+
+    // At face value the tokens trade ~2.4% above fair value (90 days at the 10% base rate)
+    setup.contract.set_price_band(&setup.admin, &100);
+    let order_id = setup.contract.create_sell_order(&invoice_id, &setup.supplier, &10_000_0000000, &1);
+    let result = setup.contract.try_fill_order(&order_id, &setup.investor, &5_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::OutsidePriceBand)));
+    let bid_id = setup.contract.create_buy_order(&invoice_id, &setup.investor, &5_000_0000000, &1);
+    let result = setup.contract.try_fill_buy_order(&bid_id, &setup.supplier, &5_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::OutsidePriceBand)));
+
+    // A wider band, or the seller flagging a distressed sale, lets fills through
+    setup.contract.set_price_band(&setup.admin, &500);
+    setup.contract.fill_order(&order_id, &setup.investor, &5_000_0000000);
+    setup.contract.set_price_band(&setup.admin, &100);
+    setup.contract.flag_distressed_sale(&invoice_id, &setup.supplier, &true);
+    setup.contract.fill_buy_order(&bid_id, &setup.supplier, &5_000_0000000);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, 10_000_0000000);
+}
+
+#[test]
+fn test_upheld_dispute_refunds_investors() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let investor_before = setup.usdc.balance(&setup.investor);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    let paid = investor_before - setup.usdc.balance(&setup.investor);
+    let pool = setup.contract.get_insurance_pool_balance();
+
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Goods never shipped"));
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &true);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 3 * 24 * 60 * 60 + 1; });
+    setup.contract.finalize_dispute(&invoice_id);
+
+    // The insurance pool fronts what it can; the rest is owed by the supplier
+    assert_eq!(setup.contract.get_insurance_pool_balance(), 0);
+    assert_eq!(investor_before - setup.usdc.balance(&setup.investor), paid - pool);
+    assert_eq!(setup.contract.get_refund_owed(&invoice_id, &setup.investor), paid - pool);
+    assert_eq!(setup.contract.get_supplier_debt(&setup.supplier), paid - pool);
+    let due_date = setup.env.ledger().timestamp() + 30 * 86400;
+    let text = String::from_str(&setup.env, "x");
+    let result = setup.contract.try_mint_draft(&setup.supplier, &setup.buyer, &1_000_0000000, &text, &due_date, &text, &text, &text);
+    assert_eq!(result, Err(Ok(ContractError::SupplierInDebt)));
+
+    // Repaying clears the debt, makes the investor whole and unblocks new drafts
+    assert_eq!(setup.contract.repay_refunds(&invoice_id, &setup.supplier, &(paid - pool)), 0);
+    assert_eq!(setup.usdc.balance(&setup.investor), investor_before);
+    assert_eq!(setup.contract.get_refund_owed(&invoice_id, &setup.investor), 0);
+    setup.create_sample_invoice();
+}
+
+#[test]
+fn test_split_holding_refunds_only_what_was_paid() {
+    extern crate std;
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let investor_before = setup.usdc.balance(&setup.investor);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    let paid = investor_before - setup.usdc.balance(&setup.investor);
+
+    // Splitting the holding across fresh addresses moves basis with the tokens
+    let mut holders = std::vec![setup.investor.clone()];
+    for _ in 0..5 {
+        let sybil = Address::generate(&setup.env);
+        setup.contract.transfer_tokens(&invoice_id, &setup.investor, &sybil, &1);
+        holders.push(sybil);
+    }
+    let basis: i128 = holders.iter().map(|h| setup.contract.get_holding(&invoice_id, h).acquired_price).sum();
+    assert_eq!(basis, paid);
+
+    let before: std::vec::Vec<i128> = holders.iter().map(|h| setup.usdc.balance(h)).collect();
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Goods never shipped"));
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &true);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 3 * 24 * 60 * 60 + 1; });
+    setup.contract.finalize_dispute(&invoice_id);
+    let refunded: i128 = holders.iter().zip(before.iter())
+        .map(|(h, b)| setup.usdc.balance(h) - b + setup.contract.get_refund_owed(&invoice_id, h))
+        .sum();
+    assert_eq!(refunded, paid);
+}
+
+#[test]
+fn test_settlement_dust_consolidates_across_invoices() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_min_payout(&setup.admin, &setup.usdc.address, &1_0000000);
+
+    // A micro-holder in two invoices: each settlement share is well under 1 USDC
+    let mut expected = 0;
+    for _ in 0..2 {
+        let invoice_id = setup.create_sample_invoice();
+        setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+        setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &0, &AuctionType::Dutch, &AuctionTerms::default());
+        setup.contract.invest(&invoice_id, &setup.investor, &5_000);
+        let investor_before = setup.usdc.balance(&setup.investor);
+        let amount = setup.contract.get_settlement_amount(&invoice_id);
+        setup.contract.settle(&invoice_id, &setup.buyer, &amount);
+
+        let receipt = setup.contract.get_receipt(&invoice_id, &setup.investor);
+        assert_eq!(receipt.payout_amount, 0);
+        assert_eq!(setup.usdc.balance(&setup.investor), investor_before);
+        expected += receipt.amount_received;
+    }
+    assert_eq!(setup.contract.get_dust_balance(&setup.investor, &setup.usdc.address), expected);
+    assert_eq!(setup.contract.get_sweepable_balance(&setup.usdc.address), 0);
+
+    // One transfer pays out the consolidated balance
+    let investor_before = setup.usdc.balance(&setup.investor);
+    assert_eq!(setup.contract.claim_dust(&setup.investor, &setup.usdc.address), expected);
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, expected);
+    assert_eq!(setup.contract.get_dust_balance(&setup.investor, &setup.usdc.address), 0);
+}
+
+#[test]
+fn test_list_queries_are_paged() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    for _ in 0..3 {
+        setup.contract.create_sell_order(&invoice_id, &setup.supplier, &1_000_0000000, &1);
+    }
+
+    assert_eq!(setup.contract.get_open_orders(&invoice_id, &0, &2).len(), 2);
+    assert_eq!(setup.contract.get_open_orders(&invoice_id, &2, &2).len(), 1);
+    assert_eq!(setup.contract.get_open_orders(&invoice_id, &5, &2).len(), 0);
+    // Oversized limits are clamped rather than rejected
+    assert_eq!(setup.contract.get_open_orders(&invoice_id, &0, &u32::MAX).len(), 3);
+    let history = setup.contract.get_invoice_history(&invoice_id, &1, &1);
+    assert_eq!(history.len(), 1);
+    assert_eq!(history.get(0).unwrap().status, InvoiceStatus::Verified);
+}
+
+#[test]
+fn test_proportional_dispute_shrinks_invoice() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    let paid = setup.contract.get_holding(&invoice_id, &setup.investor).acquired_price;
+    let before = setup.contract.get_invoice(&invoice_id);
+
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "40% of goods defective"));
+    assert_eq!(setup.contract.try_resolve_dispute_partial(&invoice_id, &setup.admin, &0), Err(Ok(ContractError::InvalidAmount)));
+    setup.contract.resolve_dispute_partial(&invoice_id, &setup.admin, &4000);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 3 * 24 * 60 * 60 + 1; });
+    setup.contract.finalize_dispute(&invoice_id);
+
+    // 40% of every holding and of the face value is gone; the rest is back in play
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.status, InvoiceStatus::Verified);
+    assert_eq!(invoice.amount, before.amount * 6 / 10);
+    assert_eq!(invoice.total_tokens, before.total_tokens * 6 / 10);
+    assert_eq!(invoice.tokens_sold, 60_000_0000000);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, 60_000_0000000);
+    let pool_refund = paid * 4 / 10 - setup.contract.get_refund_owed(&invoice_id, &setup.investor);
+    assert!(pool_refund > 0);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).acquired_price, paid - paid * 4 / 10);
+}
+
+#[test]
+fn test_supplier_appeal_of_upheld_dispute() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    let arbiter = Address::generate(&setup.env);
+    setup.contract.set_appeal_arbiter(&setup.admin, &arbiter);
+
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Goods defective"));
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &true);
+    setup.contract.appeal_dispute(&invoice_id, &setup.supplier);
+    assert_eq!(setup.contract.get_dispute(&invoice_id).resolution, DisputeResolution::Appealed);
+
+    // Only the arbiter rules; overturning restores the invoice and holdings survive
+    assert_eq!(setup.contract.try_resolve_appeal(&invoice_id, &setup.admin, &false), Err(Ok(ContractError::Unauthorized)));
+    setup.contract.resolve_appeal(&invoice_id, &arbiter, &false);
+    assert_eq!(setup.contract.get_dispute(&invoice_id).resolution, DisputeResolution::Invalid);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Verified);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, 1_00_000_0000000);
+}
+
+#[test]
+fn test_appeal_timeouts() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Goods defective"));
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &true);
+
+    // Appeal filed in time, but the arbiter lets the review window lapse: the ruling stands
+    setup.contract.appeal_dispute(&invoice_id, &setup.supplier);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 7 * 24 * 60 * 60 + 1; });
+    assert_eq!(setup.contract.try_resolve_appeal(&invoice_id, &setup.admin, &false), Err(Ok(ContractError::AppealWindowClosed)));
+    setup.contract.finalize_dispute(&invoice_id);
+    assert_eq!(setup.contract.get_dispute(&invoice_id).resolution, DisputeResolution::Valid);
+    assert!(setup.contract.try_get_holding(&invoice_id, &setup.supplier).is_err());
+}
+
+#[test]
+fn test_arbitration_panel_rules_by_quorum() {
+    let setup = TestSetup::new();
+    let (a1, a2, a3) = (Address::generate(&setup.env), Address::generate(&setup.env), Address::generate(&setup.env));
+    for a in [&a1, &a2, &a3] { setup.contract.set_arbitrator(&setup.admin, a, &true); }
+    setup.contract.set_arbitration_quorum(&setup.admin, &2);
+
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Goods defective"));
+    setup.contract.submit_evidence(&invoice_id, &setup.supplier, &String::from_str(&setup.env, "QmDeliveryProof"));
+    assert_eq!(setup.contract.get_dispute_evidence(&invoice_id, &0, &10).len(), 1);
+
+    // The panel owns the ruling while voting is open
+    assert_eq!(setup.contract.try_resolve_dispute(&invoice_id, &setup.admin, &true), Err(Ok(ContractError::InvalidStatus)));
+    assert_eq!(setup.contract.try_vote_on_dispute(&invoice_id, &setup.buyer, &true), Err(Ok(ContractError::Unauthorized)));
+    setup.contract.vote_on_dispute(&invoice_id, &a1, &true);
+    setup.contract.vote_on_dispute(&invoice_id, &a1, &false); // changed vote replaces the first
+    setup.contract.vote_on_dispute(&invoice_id, &a2, &false);
+    assert_eq!(setup.contract.get_dispute_votes(&invoice_id, &0, &10).len(), 2);
+    assert_eq!(setup.contract.try_tally_arbitration(&invoice_id), Err(Ok(ContractError::AppealPending)));
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 5 * 24 * 60 * 60 + 1; });
+    assert_eq!(setup.contract.try_vote_on_dispute(&invoice_id, &a3, &true), Err(Ok(ContractError::AppealWindowClosed)));
+    // A vote stops counting once its arbitrator leaves the panel
+    setup.contract.set_arbitrator(&setup.admin, &a2, &false);
+    assert_eq!(setup.contract.try_tally_arbitration(&invoice_id), Err(Ok(ContractError::QuorumNotReached)));
+    setup.contract.set_arbitrator(&setup.admin, &a2, &true);
+    assert!(!setup.contract.tally_arbitration(&invoice_id));
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Verified);
+    assert_eq!(setup.contract.get_dispute(&invoice_id).resolution, DisputeResolution::Invalid);
+}
+
+#[test]
+fn test_arbitration_without_quorum_falls_back_to_admin() {
+    let setup = TestSetup::new();
+    let arbitrator = Address::generate(&setup.env);
+    setup.contract.set_arbitrator(&setup.admin, &arbitrator, &true);
+    setup.contract.set_arbitration_quorum(&setup.admin, &2);
+
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Goods defective"));
+    setup.contract.vote_on_dispute(&invoice_id, &arbitrator, &true);
+
+    // Sole arbitrator voted, but one vote misses the quorum of two
+    assert_eq!(setup.contract.try_tally_arbitration(&invoice_id), Err(Ok(ContractError::QuorumNotReached)));
+    setup.env.ledger().with_mut(|l| { l.timestamp += 5 * 24 * 60 * 60 + 1; });
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &true);
+    assert_eq!(setup.contract.get_dispute(&invoice_id).resolution, DisputeResolution::Upheld);
+}
+
+#[test]
+fn test_resolve_dispute_invalid() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    setup.contract.raise_dispute(
+        &invoice_id,
+        &setup.buyer,
+        &String::from_str(&setup.env, "Testing"),
+    );
+
+    // Admin resolves dispute as INVALID (unfreeze)
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &false);
+
+    // Invoice returns to the status it had when the dispute was raised
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.status, InvoiceStatus::Verified);
+}
+
+#[test]
+fn test_dispute_freezes_auction_clock() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 5 * 60 * 60; });
+    let price_at_freeze = setup.contract.get_current_price(&invoice_id);
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Qty mismatch"));
+
+    // Three days in dispute: the auction would have ended, but it resumes at the frozen price
+    setup.env.ledger().with_mut(|l| { l.timestamp += 3 * 24 * 60 * 60; });
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &false);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.status, InvoiceStatus::Funding);
+    assert_eq!(setup.contract.get_current_price(&invoice_id), price_at_freeze);
+    assert_eq!(invoice.auction_end - setup.env.ledger().timestamp(), 19 * 60 * 60);
+}
+
+// ============================================================================
+// REVOCATION TESTS
+// ============================================================================
+
+#[test]
+fn test_revoke_draft() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+
+    // Supplier revokes draft
+    setup.contract.revoke(&invoice_id, &setup.supplier);
+
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.status, InvoiceStatus::Revoked);
+}
+
+#[test]
+fn test_revoke_stale_verified() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // Fast forward past due date
+    setup.env.ledger().with_mut(|l| {
+        l.timestamp = l.timestamp + (100 * 24 * 60 * 60); // 100 days
+    });
+
+    // Supplier revokes stale invoice
+    setup.contract.revoke(&invoice_id, &setup.supplier);
+
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.status, InvoiceStatus::Revoked);
+}
+
+#[test]
+fn test_revoke_requires_closing_out_external_holders() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+
+    let investor_start = setup.usdc.balance(&setup.investor);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+
+    // Sub-vendor received tokens as payment; supplier needs cash (beyond the net proceeds) to make everyone whole
+    let sub_vendor = Address::generate(&setup.env);
+    setup.contract.transfer_tokens(&invoice_id, &setup.supplier, &sub_vendor, &50_000_0000000);
+    setup.usdc_admin.mint(&setup.supplier, &1_00_000_0000000);
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 100 * 24 * 60 * 60; });
+    let result = setup.contract.try_revoke(&invoice_id, &setup.supplier);
+    assert_eq!(result, Err(Ok(ContractError::HasExternalHolders)));
+
+    setup.contract.close_out(&invoice_id, &setup.supplier, &setup.investor);
+    setup.contract.close_out(&invoice_id, &setup.supplier, &sub_vendor);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).tokens_sold, 0);
+    setup.contract.revoke(&invoice_id, &setup.supplier);
+
+    assert_eq!(setup.usdc.balance(&setup.investor), investor_start);
+    assert_eq!(setup.usdc.balance(&sub_vendor), 50_000_0000000);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Revoked);
+}
+
+#[test]
+fn test_close_out_returns_escrowed_payments_from_the_contract() {
+    let setup = TestSetup::new();
+    let pauser = Address::generate(&setup.env);
+    setup.contract.grant_role(&setup.admin, &Role::Pauser, &pauser);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_funding_threshold(&invoice_id, &setup.supplier, &5_00_000_0000000);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &0, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    let paid = setup.contract.get_holding(&invoice_id, &setup.investor).acquired_price;
+    assert_eq!(setup.contract.get_invoice(&invoice_id).escrowed_payments, paid);
+
+    setup.contract.pause(&pauser);
+    let result = setup.contract.try_close_out(&invoice_id, &setup.supplier, &setup.investor);
+    assert_eq!(result, Err(Ok(ContractError::ContractPaused)));
+    setup.contract.unpause(&pauser);
+
+    // The payment never reached the supplier, so the contract returns it rather than the supplier
+    let investor_before = setup.usdc.balance(&setup.investor);
+    assert_eq!(setup.contract.close_out(&invoice_id, &setup.supplier, &setup.investor), paid);
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, paid);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 0);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).escrowed_payments, 0);
+    assert!(setup.contract.try_get_holding(&invoice_id, &setup.investor).is_err());
+
+    let investment = setup.contract.get_investments_by_investor(&setup.investor, &0, &10).get(0).unwrap();
+    assert_eq!(investment.settled_amount, paid);
+    assert!(investment.settled_at > 0);
+}
+
+// ============================================================================
+// STATUS CHECK TESTS
+// ============================================================================
+
+#[test]
+fn test_check_status_overdue() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // Fast forward past due date
+    setup.env.ledger().with_mut(|l| {
+        l.timestamp = l.timestamp + (91 * 24 * 60 * 60);
+    });
+
+    let status = setup.contract.check_status(&invoice_id);
+    assert_eq!(status, InvoiceStatus::Overdue);
+}
+
+#[test]
+fn test_check_status_defaulted() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // Fast forward past grace period (90 + 30 days)
+    setup.env.ledger().with_mut(|l| {
+        l.timestamp = l.timestamp + (121 * 24 * 60 * 60);
+    });
+
+    let status = setup.contract.check_status(&invoice_id);
+    assert_eq!(status, InvoiceStatus::Defaulted);
+}
+
+#[test]
+fn test_invoice_events_carry_sequence_numbers() {
+    use soroban_sdk::{testutils::Events, TryFromVal, Val};
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    assert_eq!(setup.contract.get_event_seq(&invoice_id), 1);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let before = setup.contract.get_event_seq(&invoice_id);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+
+    // Every event the call emitted for the invoice continues the sequence without gaps
+    let mut expected = before;
+    for (contract, topics, data) in setup.env.events().all().iter() {
+        if contract != setup.contract.address || topics.len() < 2 { continue; }
+        let Ok(id) = String::try_from_val(&setup.env, &topics.get(1).unwrap()) else { continue };
+        if id != invoice_id { continue; }
+        let (seq, _): (u64, Val) = <(u64, Val)>::try_from_val(&setup.env, &data).unwrap();
+        expected += 1;
+        assert_eq!(seq, expected);
+    }
+    assert!(expected > before);
+    assert_eq!(setup.contract.get_event_seq(&invoice_id), expected);
+    assert_eq!(setup.contract.get_event_seq(&String::from_str(&setup.env, "INV-9999")), 0);
+
+    // Order book events are keyed by the invoice and continue its sequence
+    let last_seq = || {
+        let (_, topics, data) = setup.env.events().all().last().unwrap();
+        assert_eq!(String::try_from_val(&setup.env, &topics.get(1).unwrap()).unwrap(), invoice_id);
+        <(u64, Val)>::try_from_val(&setup.env, &data).unwrap().0
+    };
+    let order_id = setup.contract.create_sell_order(&invoice_id, &setup.investor, &10_000_0000000, &1);
+    assert_eq!(last_seq(), expected + 1);
+    setup.contract.cancel_order(&order_id, &setup.investor);
+    assert_eq!(last_seq(), expected + 2);
+}
+
+#[test]
+fn test_poke_pays_keeper_once_per_transition() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    setup.contract.stake_insurance(&setup.investor, &0, &10_0000000); // free pool bounties are paid from
+    let earmarked = setup.contract.get_invoice_insurance_balance(&invoice_id);
+    setup.contract.set_keeper_bounty(&setup.admin, &5_0000000);
+    let keeper = Address::generate(&setup.env);
+
+    // Nothing due yet: no transition, no bounty
+    assert_eq!(setup.contract.poke(&invoice_id, &keeper), 0);
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 91 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.poke(&invoice_id, &keeper), 5_0000000);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Overdue);
+    assert_eq!(setup.contract.poke(&invoice_id, &keeper), 0);
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 30 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.poke(&invoice_id, &keeper), 5_0000000);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Defaulted);
+    assert_eq!(setup.usdc.balance(&keeper), 10_0000000);
+    // The defaulted invoice's holders still have its whole earmark to claim
+    assert_eq!(setup.contract.get_invoice_insurance_balance(&invoice_id), earmarked);
+}
+
+#[test]
+fn test_poke_many_skips_unknown_and_caps_at_pool() {
+    let setup = TestSetup::new();
+    let funded = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&funded, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&funded, &setup.investor, &2_00_000_0000000);
+    let pool = 10_0000000;
+    setup.contract.stake_insurance(&setup.investor, &0, &pool);
+    let earmarked = setup.contract.get_invoice_insurance_balance(&funded);
+    let unfunded = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&unfunded, &setup.buyer, &0);
+    setup.contract.set_keeper_bounty(&setup.admin, &(pool * 2));
+    assert_eq!(setup.contract.try_set_keeper_bounty(&setup.admin, &-1), Err(Ok(ContractError::InvalidAmount)));
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 91 * 24 * 60 * 60; });
+    let keeper = Address::generate(&setup.env);
+    let ids = soroban_sdk::vec![&setup.env, funded.clone(), String::from_str(&setup.env, "INV-9999"), unfunded.clone()];
+    // The first transition drains the free pool, so the second is made unpaid; earmarks are untouched
+    assert_eq!(setup.contract.poke_many(&ids, &keeper), pool);
+    assert_eq!(setup.contract.get_invoice(&funded).status, InvoiceStatus::Overdue);
+    assert_eq!(setup.contract.get_invoice(&unfunded).status, InvoiceStatus::Overdue);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), earmarked);
+}
+
+#[test]
+fn test_gc_invoice_after_retention() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    let split = SubvendorSplit { subvendor: setup.sub_vendor.clone(), bps: 1000 };
+    setup.contract.set_subvendor_splits(&invoice_id, &setup.supplier, &soroban_sdk::vec![&setup.env, split]);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.distribute_to_subvendors(&invoice_id, &false);
+    setup.contract.set_concentration_cap(&setup.admin, &invoice_id, &Some(5000));
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    // The bounty is paid from the protocol treasury, funded here by a 2% protocol fee
+    setup.contract.set_fee_schedule(&setup.admin, &FeeSchedule { insurance_cut_bps: 500, protocol_fee_bps: 200, ..Default::default() });
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    setup.contract.set_gc_bounty(&setup.admin, &10_0000000);
+    let treasury = setup.contract.get_treasury_balance(&0);
+
+    let keeper = Address::generate(&setup.env);
+    assert_eq!(setup.contract.try_gc_invoice(&invoice_id, &keeper), Err(Ok(ContractError::NotCollectable)));
+
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
+    assert_eq!(setup.contract.try_gc_invoice(&invoice_id, &keeper), Err(Ok(ContractError::NotCollectable)));
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 366 * 24 * 60 * 60; });
+    let now = setup.env.ledger().timestamp();
+    assert_eq!(setup.contract.gc_invoice(&invoice_id, &keeper), 10_0000000);
+    assert_eq!(setup.usdc.balance(&keeper), 10_0000000);
+    assert_eq!(setup.contract.get_treasury_balance(&0), treasury - 10_0000000);
+    assert!(setup.contract.try_get_invoice(&invoice_id).is_err());
+    assert_eq!(setup.contract.try_resolve_symbol(&String::from_str(&setup.env, "SNG-INV-1001")), Err(Ok(ContractError::InvoiceNotFound)));
+    assert_eq!(setup.contract.get_maturities(&(now - 400 * 24 * 60 * 60), &now, &100).len(), 0);
+    assert!(!setup.contract.get_invoice_history(&invoice_id, &0, &100).is_empty());
+    setup.env.as_contract(&setup.contract.address, || {
+        assert!(storage::get_subvendor_splits(&setup.env, &invoice_id).is_empty());
+        assert_eq!(storage::distribution_count(&setup.env, &invoice_id), 0);
+        assert!(storage::get_concentration_cap(&setup.env, &invoice_id).is_none());
+    });
+}
+
+#[test]
+fn test_gc_waits_for_open_insurance_claims() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.check_status(&invoice_id), InvoiceStatus::Defaulted);
+
+    // Unclaimed insurance keeps the invoice, and so does a payout still owed back from recoveries
+    let keeper = Address::generate(&setup.env);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 366 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.try_gc_invoice(&invoice_id, &keeper), Err(Ok(ContractError::NotCollectable)));
+    setup.contract.claim_insurance(&invoice_id, &setup.investor);
+    assert_eq!(setup.contract.try_gc_invoice(&invoice_id, &keeper), Err(Ok(ContractError::NotCollectable)));
+
+    // Writing off closes collections; the invoice is collectable after its retention period
+    let collector = Address::generate(&setup.env);
+    setup.contract.grant_role(&setup.admin, &Role::Collector, &collector);
+    setup.contract.write_off(&invoice_id, &collector);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 366 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.gc_invoice(&invoice_id, &keeper), 0);
+    assert!(setup.contract.try_get_invoice(&invoice_id).is_err());
+}
+
+#[test]
+fn test_sweep_only_moves_unaccounted_tokens() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000); // funds the insurance pool
+    setup.contract.prepay(&invoice_id, &setup.buyer, &1_00_000_0000000);
+
+    // Someone sends USDC straight to the contract
+    setup.usdc.transfer(&setup.buyer, &setup.contract.address, &5_000_0000000);
+    assert_eq!(setup.contract.get_sweepable_balance(&setup.usdc.address), 5_000_0000000);
+
+    let recovery = Address::generate(&setup.env);
+    let result = setup.contract.try_sweep_foreign_tokens(&setup.admin, &setup.usdc.address, &recovery, &5_001_0000000);
+    assert_eq!(result, Err(Ok(ContractError::ExceedsUnaccountedBalance)));
+    setup.contract.sweep_foreign_tokens(&setup.admin, &setup.usdc.address, &recovery, &5_000_0000000);
+    assert_eq!(setup.usdc.balance(&recovery), 5_000_0000000);
+    assert_eq!(setup.contract.get_sweepable_balance(&setup.usdc.address), 0);
+}
+
+#[test]
+fn test_reentrancy_guard_is_scoped_and_released() {
+    let setup = TestSetup::new();
+    let invoice_id = String::from_str(&setup.env, "INV-1001");
+    let other_id = String::from_str(&setup.env, "INV-1002");
+    setup.env.as_contract(&setup.contract.address, || {
+        {
+            let _held = guard::ReentrancyGuard::acquire(&setup.env, &invoice_id).unwrap();
+            assert_eq!(guard::ReentrancyGuard::acquire(&setup.env, &invoice_id).err(), Some(ContractError::Reentrant));
+            assert!(guard::ReentrancyGuard::acquire(&setup.env, &other_id).is_ok());
+        }
+        assert!(guard::ReentrancyGuard::acquire(&setup.env, &invoice_id).is_ok());
+    });
+}
+
+#[test]
+fn test_authorization_log_records_reasons() {
+    let setup = TestSetup::new();
+    let report = String::from_str(&setup.env, "QmKycReport2024");
+    setup.contract.set_investor_kyc_with_reason(&setup.admin, &setup.investor, &true, &101, &report);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 86400; });
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &false);
+
+    let log = setup.contract.get_authorization_log(&setup.investor, &0, &100);
+    assert_eq!(log.len(), 2);
+    let granted = log.get(0).unwrap();
+    assert_eq!(granted.kind, AuthorizationKind::Kyc);
+    assert!(granted.granted);
+    assert_eq!(granted.reason_code, 101);
+    assert_eq!(granted.reference_hash, report);
+    assert_eq!(granted.actor, setup.admin);
+    let revoked = log.get(1).unwrap();
+    assert!(!revoked.granted);
+    assert_eq!(revoked.reason_code, 0);
+    assert!(!setup.contract.is_kyc_approved(&setup.investor));
+}
+
+#[test]
+fn test_upgrade_timelock_and_migrate() {
+    let setup = TestSetup::new();
+    let wasm_hash = BytesN::from_array(&setup.env, &[7u8; 32]);
+    assert_eq!(setup.contract.get_storage_version(), 12);
+    assert_eq!(setup.contract.migrate(&setup.admin, &10), 12);
+
+    let eta = setup.contract.schedule_upgrade(&setup.admin, &wasm_hash);
+    assert_eq!(eta, setup.env.ledger().timestamp() + 2 * 86400);
+    assert_eq!(setup.contract.try_upgrade(&setup.admin, &wasm_hash), Err(Ok(ContractError::InvalidStatus)));
+    assert_eq!(setup.contract.try_set_upgrade_delay(&setup.admin, &86400), Err(Ok(ContractError::InvalidAmount)));
+    assert_eq!(setup.contract.try_schedule_upgrade(&setup.investor, &wasm_hash), Err(Ok(ContractError::Unauthorized)));
+
+    // Only the scheduled hash can go through, and cancelling drops it
+    setup.env.ledger().with_mut(|l| { l.timestamp = eta; });
+    let other_hash = BytesN::from_array(&setup.env, &[8u8; 32]);
+    assert_eq!(setup.contract.try_upgrade(&setup.admin, &other_hash), Err(Ok(ContractError::InvalidStatus)));
+    setup.contract.cancel_upgrade(&setup.admin);
+    assert_eq!(setup.contract.get_pending_upgrade(), None);
+    assert_eq!(setup.contract.try_upgrade(&setup.admin, &wasm_hash), Err(Ok(ContractError::InvalidStatus)));
+}
+
+#[test]
+fn test_two_step_admin_transfer() {
+    let setup = TestSetup::new();
+    let new_admin = Address::generate(&setup.env);
+    setup.contract.propose_admin(&setup.admin, &new_admin);
+    assert_eq!(setup.contract.try_accept_admin(&setup.investor), Err(Ok(ContractError::Unauthorized)));
+
+    // The old admin keeps control until the nominee accepts
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.accept_admin(&new_admin);
+    assert_eq!(setup.contract.try_set_investor_kyc(&setup.admin, &setup.investor, &false), Err(Ok(ContractError::Unauthorized)));
+    setup.contract.set_investor_kyc(&new_admin, &setup.investor, &false);
+    assert_eq!(setup.contract.try_accept_admin(&new_admin), Err(Ok(ContractError::Unauthorized)));
+}
+
+#[test]
+fn test_roles_scope_operational_keys() {
+    let setup = TestSetup::new();
+    let kyc_manager = Address::generate(&setup.env);
+    let resolver = Address::generate(&setup.env);
+    setup.contract.grant_role(&setup.admin, &Role::KycManager, &kyc_manager);
+    setup.contract.grant_role(&setup.admin, &Role::DisputeResolver, &resolver);
+    assert!(setup.contract.has_role(&Role::KycManager, &kyc_manager));
+
+    // Each role only unlocks its own area
+    setup.contract.set_investor_kyc(&kyc_manager, &setup.investor, &true);
+    assert!(setup.contract.is_kyc_approved(&setup.investor));
+    assert_eq!(setup.contract.try_set_relayer(&kyc_manager, &setup.investor, &true), Err(Ok(ContractError::Unauthorized)));
+    assert_eq!(setup.contract.try_set_investor_kyc(&resolver, &setup.investor, &false), Err(Ok(ContractError::Unauthorized)));
+
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Goods defective"));
+    assert_eq!(setup.contract.try_resolve_dispute(&invoice_id, &kyc_manager, &false), Err(Ok(ContractError::Unauthorized)));
+    setup.contract.resolve_dispute(&invoice_id, &resolver, &false);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Verified);
+
+    setup.contract.revoke_role(&setup.admin, &Role::KycManager, &kyc_manager);
+    assert_eq!(setup.contract.try_set_investor_kyc(&kyc_manager, &setup.investor, &false), Err(Ok(ContractError::Unauthorized)));
+}
+
+#[test]
+fn test_pause_blocks_value_flows() {
+    let setup = TestSetup::new();
+    let pauser = Address::generate(&setup.env);
+    setup.contract.grant_role(&setup.admin, &Role::Pauser, &pauser);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    assert_eq!(setup.contract.try_pause(&setup.investor), Err(Ok(ContractError::Unauthorized)));
+    setup.contract.pause(&pauser);
+
+    assert_eq!(setup.contract.try_invest(&invoice_id, &setup.investor, &1_00_000_0000000), Err(Ok(ContractError::ContractPaused)));
+    assert_eq!(setup.contract.try_transfer_tokens(&invoice_id, &setup.supplier, &setup.investor, &1), Err(Ok(ContractError::ContractPaused)));
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    assert_eq!(setup.contract.try_settle(&invoice_id, &setup.buyer, &settlement), Err(Ok(ContractError::ContractPaused)));
+    // Disputes can still be raised while paused
+    setup.contract.raise_dispute(&invoice_id, &setup.buyer, &String::from_str(&setup.env, "Goods defective"));
+    setup.contract.resolve_dispute(&invoice_id, &setup.admin, &false);
+
+    setup.contract.unpause(&pauser);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert!(!setup.contract.is_paused());
+}
+
+#[test]
+fn test_undo_last_authorization_action() {
+    let setup = TestSetup::new();
+    let relayer = Address::generate(&setup.env);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_relayer(&setup.admin, &relayer, &true);
+    let mistake = setup.contract.get_authorization_log(&setup.investor, &0, &100).get(0).unwrap().action_id;
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &false);
+    let revoke = setup.contract.get_authorization_log(&setup.investor, &0, &100).get(1).unwrap().action_id;
+
+    // Only the latest change for an address can be undone
+    let result = setup.contract.try_undo_last_action(&setup.admin, &mistake);
+    assert_eq!(result, Err(Ok(ContractError::NotReversible)));
+
+    let undo = setup.contract.undo_last_action(&setup.admin, &revoke);
+    assert!(setup.contract.is_kyc_approved(&setup.investor));
+    let log = setup.contract.get_authorization_log(&setup.investor, &0, &100);
+    assert_eq!(log.len(), 3);
+    assert_eq!(log.get(2).unwrap().action_id, undo);
+    assert!(log.get(2).unwrap().granted);
+
+    // Undoing the undo redoes the revocation; past the window nothing can be undone
+    setup.contract.undo_last_action(&setup.admin, &undo);
+    assert!(!setup.contract.is_kyc_approved(&setup.investor));
+    setup.env.ledger().with_mut(|l| { l.timestamp += 86400 + 1; });
+    let relayer_action = setup.contract.get_authorization_log(&relayer, &0, &100).get(0).unwrap().action_id;
+    let result = setup.contract.try_undo_last_action(&setup.admin, &relayer_action);
+    assert_eq!(result, Err(Ok(ContractError::NotReversible)));
+}
+
+#[test]
+fn test_finalize_auction_with_unsold_tokens() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &0, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.invest(&invoice_id, &setup.investor, &4_00_000_0000000);
+
+    let result = setup.contract.try_finalize_auction(&invoice_id);
+    assert_eq!(result, Err(Ok(ContractError::AuctionStillRunning)));
+    setup.env.ledger().with_mut(|l| { l.timestamp += 24 * 3600; });
+    assert_eq!(setup.contract.finalize_auction(&invoice_id), InvoiceStatus::Funded);
+
+    // Unsold 60% stays with the supplier and the auction result is closed
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!(invoice.tokens_remaining, 0);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.supplier).amount, 6_00_000_0000000);
+    let auction = setup.contract.get_auction_result(&invoice_id);
+    assert_eq!(auction.duration, 24 * 3600);
+    assert!(auction.finalized_at > 0);
+}
+
+#[test]
+fn test_finalize_auction_with_no_sales_reverts_to_verified() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.env.ledger().with_mut(|l| { l.timestamp += 25 * 3600; });
+
+    assert_eq!(setup.contract.finalize_auction(&invoice_id), InvoiceStatus::Verified);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).auction_start, 0);
+    // The supplier can run a fresh auction
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+}
+
+#[test]
+fn test_private_placement_allowlist() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let outsider = Address::generate(&setup.env);
+    for investor in [&setup.investor, &setup.sub_vendor, &outsider] {
+        setup.contract.set_investor_kyc(&setup.admin, investor, &true);
+    }
+    setup.usdc_admin.mint(&outsider, &1_00_000_0000000);
+
+    // Two-leaf tree of invited investors; each leaf's proof is its sibling
+    let leaf = |addr: &Address| -> BytesN<32> { setup.env.crypto().sha256(&addr.clone().to_xdr(&setup.env)).into() };
+    let (a, b) = (leaf(&setup.investor), leaf(&setup.sub_vendor));
+    let (left, right) = if a < b { (a.clone(), b.clone()) } else { (b.clone(), a.clone()) };
+    let mut pair = Bytes::from(left);
+    pair.append(&Bytes::from(right));
+    let root: BytesN<32> = setup.env.crypto().sha256(&pair).into();
+    setup.contract.set_investor_allowlist(&invoice_id, &setup.supplier, &Some(root.clone()));
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &0, &AuctionType::Dutch, &AuctionTerms::default());
+
+    // Plain invest and foreign proofs are rejected; the invitee's proof is accepted
+    let result = setup.contract.try_invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::NotAllowlisted)));
+    let result = setup.contract.try_invest_with_proof(&invoice_id, &outsider, &1_00_000_0000000, &soroban_sdk::vec![&setup.env, b.clone()]);
+    assert_eq!(result, Err(Ok(ContractError::NotAllowlisted)));
+    setup.contract.invest_with_proof(&invoice_id, &setup.investor, &1_00_000_0000000, &soroban_sdk::vec![&setup.env, b.clone()]);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, 1_00_000_0000000);
+
+    // English auction bids on a private placement carry the proof too
+    let english = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&english, &setup.buyer, &0);
+    setup.contract.set_investor_allowlist(&english, &setup.supplier, &Some(root));
+    setup.contract.start_auction(&english, &setup.supplier, &24, &1000, &AuctionType::English, &AuctionTerms::default());
+    let face = setup.contract.get_invoice(&english).amount;
+    let result = setup.contract.try_place_auction_bid(&english, &setup.investor, &1_00_000_0000000, &face, &None);
+    assert_eq!(result, Err(Ok(ContractError::NotAllowlisted)));
+    let proof = Some(soroban_sdk::vec![&setup.env, b]);
+    assert_eq!(setup.contract.place_auction_bid(&english, &setup.investor, &1_00_000_0000000, &face, &proof), 0);
+}
+
+#[test]
+fn test_funding_threshold_escrow_and_refund() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.sub_vendor, &true);
+    setup.usdc_admin.mint(&setup.sub_vendor, &5_00_000_0000000);
+    setup.contract.set_funding_threshold(&invoice_id, &setup.supplier, &5_00_000_0000000);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &0, &AuctionType::Dutch, &AuctionTerms::default());
+
+    // Below the threshold the supplier receives nothing yet
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    setup.contract.invest(&invoice_id, &setup.sub_vendor, &1_00_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 0);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).escrowed_payments, 3_00_000_0000000);
+    assert_eq!(setup.contract.get_sweepable_balance(&setup.usdc.address), 0);
+
+    // Escrowed tokens can't change hands until the threshold clears
+    let escrowed = setup.contract.get_holding(&invoice_id, &setup.investor).amount;
+    let moved = setup.contract.try_transfer_tokens(&invoice_id, &setup.investor, &setup.sub_vendor, &1);
+    assert_eq!(moved, Err(Ok(ContractError::InsufficientTokens)));
+    let listed = setup.contract.try_create_sell_order(&invoice_id, &setup.investor, &escrowed, &1);
+    assert_eq!(listed, Err(Ok(ContractError::InsufficientTokens)));
+
+    // Refunds only open once the auction has expired
+    let result = setup.contract.try_claim_refund(&invoice_id, &setup.investor);
+    assert_eq!(result, Err(Ok(ContractError::NotRefundable)));
+    setup.env.ledger().with_mut(|l| { l.timestamp += 25 * 3600; });
+    let investor_before = setup.usdc.balance(&setup.investor);
+    assert_eq!(setup.contract.claim_refund(&invoice_id, &setup.investor), 2_00_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, 2_00_000_0000000);
+    assert!(setup.contract.try_get_holding(&invoice_id, &setup.investor).is_err());
+    assert_eq!(setup.contract.get_invoice(&invoice_id).escrowed_payments, 1_00_000_0000000);
+
+    // Finalizing doesn't wait for the other refund, which stays claimable
+    assert_eq!(setup.contract.finalize_auction(&invoice_id), InvoiceStatus::Verified);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!((invoice.tokens_sold, invoice.escrowed_payments), (0, 0));
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.supplier).amount, invoice.total_tokens);
+    assert!(setup.contract.try_get_holding(&invoice_id, &setup.sub_vendor).is_err());
+    let sub_vendor_before = setup.usdc.balance(&setup.sub_vendor);
+    assert_eq!(setup.contract.claim_refund(&invoice_id, &setup.sub_vendor), 1_00_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.sub_vendor) - sub_vendor_before, 1_00_000_0000000);
+    assert_eq!(setup.contract.try_claim_refund(&invoice_id, &setup.sub_vendor), Err(Ok(ContractError::NotRefundable)));
+}
+
+#[test]
+fn test_uniform_clearing_rebates_early_investors() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.sub_vendor, &true);
+    setup.usdc_admin.mint(&setup.sub_vendor, &5_00_000_0000000);
+    setup.contract.set_uniform_clearing(&invoice_id, &setup.supplier, &true);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+
+    let investor_before = setup.usdc.balance(&setup.investor);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 10 * 3600; });
+    let clearing_price = setup.contract.get_current_price(&invoice_id);
+    setup.contract.invest(&invoice_id, &setup.sub_vendor, &2_00_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 0);
+
+    // Expired: escrow can't be pulled back, only cleared
+    setup.env.ledger().with_mut(|l| { l.timestamp += 15 * 3600; });
+    assert_eq!(setup.contract.try_claim_refund(&invoice_id, &setup.investor), Err(Ok(ContractError::NotRefundable)));
+    setup.contract.finalize_auction(&invoice_id);
+
+    // Both investors end up paying the later, lower price
+    let total_tokens = setup.contract.get_invoice(&invoice_id).total_tokens;
+    let fair = 2_00_000_0000000 * clearing_price / total_tokens;
+    assert_eq!(investor_before - setup.usdc.balance(&setup.investor), fair);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).acquired_price, fair);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.sub_vendor).acquired_price, fair);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 2 * fair * 95 / 100);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).escrowed_payments, 0);
+    assert_eq!(setup.contract.get_auction_result(&invoice_id).total_proceeds, 2 * fair);
+}
+
+#[test]
+fn test_auction_reserve_and_min_ticket() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let total_tokens = setup.contract.get_invoice(&invoice_id).total_tokens;
+    let (reserve, ticket) = (total_tokens / 5, total_tokens / 10);
+    let start = |reserved: i128| setup.contract.try_start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms { reserved_tokens: reserved, min_ticket: ticket, royalty_bps: None });
+    assert_eq!(start(total_tokens), Err(Ok(ContractError::InvalidAuctionParams)));
+    start(reserve).unwrap().unwrap();
+    let params = setup.contract.get_auction_params(&invoice_id);
+    assert_eq!((params.reserved_tokens, params.min_ticket, params.auction_type), (reserve, ticket, AuctionType::Dutch));
+    assert_eq!(setup.contract.get_invoice(&invoice_id).tokens_remaining, total_tokens - reserve);
+
+    assert_eq!(setup.contract.try_invest(&invoice_id, &setup.investor, &(ticket - 1)), Err(Ok(ContractError::InvalidAmount)));
+    let for_sale = total_tokens - reserve;
+    assert_eq!(setup.contract.try_invest(&invoice_id, &setup.investor, &(for_sale + 1)), Err(Ok(ContractError::InsufficientTokens)));
+    setup.contract.invest(&invoice_id, &setup.investor, &(for_sale - ticket / 2));
+    // A last lot under the minimum may still sell, and sells the auction out
+    setup.contract.invest(&invoice_id, &setup.investor, &(ticket / 2));
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!((invoice.status, invoice.tokens_sold), (InvoiceStatus::Funded, for_sale));
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.supplier).amount, reserve);
+    assert_eq!(setup.contract.get_auction_params(&invoice_id).reserved_tokens, 0);
+
+    // The funding threshold must be reachable from the tokens on sale
+    let threshold = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&threshold, &setup.buyer, &0);
+    setup.contract.set_funding_threshold(&threshold, &setup.supplier, &(total_tokens - reserve + 1));
+    let result = setup.contract.try_start_auction(&threshold, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms { reserved_tokens: reserve, ..Default::default() });
+    assert_eq!(result, Err(Ok(ContractError::InvalidAuctionParams)));
+
+    // An auction that sells nothing puts the reserve back on offer
+    let unsold = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&unsold, &setup.buyer, &0);
+    setup.contract.start_auction(&unsold, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms { reserved_tokens: reserve, ..Default::default() });
+    setup.env.ledger().with_mut(|l| { l.timestamp += 24 * 3600; });
+    assert_eq!(setup.contract.finalize_auction(&unsold), InvoiceStatus::Verified);
+    assert_eq!(setup.contract.get_invoice(&unsold).tokens_remaining, total_tokens);
+    assert_eq!(setup.contract.try_get_auction_params(&unsold).err(), Some(Ok(ContractError::AuctionNotStarted)));
+}
+
+#[test]
+fn test_english_auction_fills_best_bids() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.sub_vendor, &true);
+    setup.usdc_admin.mint(&setup.sub_vendor, &10_00_000_0000000);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::English, &AuctionTerms::default());
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    let (face, lot) = (invoice.amount, 6_00_000_0000000);
+
+    // Only bids at or above the floor, and no buying at a decaying price
+    let below_floor = setup.contract.try_place_auction_bid(&invoice_id, &setup.investor, &lot, &(face * 8900 / 10000), &None);
+    assert_eq!(below_floor, Err(Ok(ContractError::InvalidAmount)));
+    assert_eq!(setup.contract.try_invest(&invoice_id, &setup.investor, &lot), Err(Ok(ContractError::InvalidStatus)));
+
+    let investor_before = setup.usdc.balance(&setup.investor);
+    assert_eq!(setup.contract.place_auction_bid(&invoice_id, &setup.investor, &lot, &(face * 9500 / 10000), &None), 0);
+    assert_eq!(setup.contract.place_auction_bid(&invoice_id, &setup.sub_vendor, &lot, &(face * 9700 / 10000), &None), 0);
+    assert_eq!(setup.contract.get_auction_bids(&invoice_id, &1, &1).get(0).unwrap().bidder, setup.investor);
+    assert_eq!(investor_before - setup.usdc.balance(&setup.investor), lot * 9500 / 10000);
+    assert_eq!(setup.contract.try_withdraw_auction_bids(&invoice_id, &setup.investor), Err(Ok(ContractError::InvalidStatus)));
+    assert_eq!(setup.contract.try_finalize_auction(&invoice_id), Err(Ok(ContractError::AuctionStillRunning)));
+
+    // A token the supplier has offered elsewhere stays out of the fills
+    setup.contract.offer_tokens(&invoice_id, &setup.supplier, &Address::generate(&setup.env), &1);
+
+    // The higher bid fills in full, the lower one with what is left, refunding the rest
+    setup.env.ledger().with_mut(|l| { l.timestamp = invoice.auction_end; });
+    assert_eq!(setup.contract.finalize_auction(&invoice_id), InvoiceStatus::Funded);
+    let rest = invoice.total_tokens - lot - 1;
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.sub_vendor).amount, lot);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, rest);
+    assert_eq!(investor_before - setup.usdc.balance(&setup.investor), rest * 9500 / 10000);
+    let proceeds = lot * 9700 / 10000 + rest * 9500 / 10000;
+    assert_eq!(setup.contract.get_auction_result(&invoice_id).total_proceeds, proceeds);
+    assert_eq!(setup.usdc.balance(&setup.supplier), proceeds - proceeds * 5 / 100);
+    assert!(setup.contract.get_auction_bids(&invoice_id, &0, &10).is_empty());
+}
+
+#[test]
+fn test_full_bid_book_evicts_worst_bid() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.sub_vendor, &true);
+    setup.usdc_admin.mint(&setup.sub_vendor, &10_00_000_0000000);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::English, &AuctionTerms::default());
+    let face = setup.contract.get_invoice(&invoice_id).amount;
+    let (lot, floor) = (1_000_0000000, face * 9000 / 10000);
+    for _ in 0..50 { setup.contract.place_auction_bid(&invoice_id, &setup.investor, &lot, &floor, &None); }
+
+    // A full book turns away bids that don't beat its worst one
+    let bid = |price: i128| setup.contract.try_place_auction_bid(&invoice_id, &setup.sub_vendor, &lot, &price, &None);
+    assert_eq!(bid(floor), Err(Ok(ContractError::InvalidAmount)));
+    let investor_before = setup.usdc.balance(&setup.investor);
+    assert_eq!(bid(face * 9500 / 10000), Ok(Ok(0)));
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, lot * 9000 / 10000);
+    assert_eq!(setup.contract.get_auction_bids(&invoice_id, &0, &100).len(), 50);
+
+    // Escrow in open bids counts toward the bidder's exposure limit
+    setup.contract.set_exposure_limit(&setup.admin, &setup.sub_vendor, &(lot * 9500 / 10000 + lot * 9000 / 10000));
+    assert_eq!(bid(face * 9600 / 10000), Err(Ok(ContractError::ExposureLimitExceeded)));
+}
+
+#[test]
+fn test_funding_threshold_releases_escrow_when_met() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_funding_threshold(&invoice_id, &setup.supplier, &3_00_000_0000000);
+
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+
+    // Escrow released in one go, less the 5% insurance cut
+    assert_eq!(setup.usdc.balance(&setup.supplier), 3_80_000_0000000);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).escrowed_payments, 0);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), 20_000_0000000);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.supplier), 4_75_000_0000000);
+}
+
+#[test]
+fn test_invest_requires_auction_by_default() {
+    let setup = TestSetup::new();
+    setup.contract.set_pre_auction_invest(&setup.admin, &false);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+
+    let result = setup.contract.try_invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::AuctionNotStarted)));
+
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, 1_00_000_0000000);
+}
+
+#[test]
+fn test_invest_beyond_supplier_holding_moves_no_funds() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+
+    // Supplier pays a sub-vendor with 40% of the tokens; tokens_remaining still shows 100%
+    let sub_vendor = Address::generate(&setup.env);
+    setup.contract.transfer_tokens(&invoice_id, &setup.supplier, &sub_vendor, &4_00_000_0000000);
+
+    let investor_before = setup.usdc.balance(&setup.investor);
+    let supplier_before = setup.usdc.balance(&setup.supplier);
+    let pool_before = setup.contract.get_insurance_pool_balance();
+    let result = setup.contract.try_invest(&invoice_id, &setup.investor, &7_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::InsufficientTokens)));
+    assert_eq!(setup.usdc.balance(&setup.investor), investor_before);
+    assert_eq!(setup.usdc.balance(&setup.supplier), supplier_before);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), pool_before);
+
+    // Whatever the supplier still holds can be sold
+    setup.contract.invest(&invoice_id, &setup.investor, &6_00_000_0000000);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.investor).amount, 6_00_000_0000000);
+    assert!(setup.contract.try_get_holding(&invoice_id, &setup.supplier).is_err());
+}
+
+#[test]
+fn test_buyer_obligation_limit_blocks_approval() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    assert_eq!(setup.contract.try_set_buyer_obligation_limit(&setup.admin, &setup.buyer, &-1), Err(Ok(ContractError::InvalidAmount)));
+    setup.contract.set_buyer_obligation_limit(&setup.admin, &setup.buyer, &15_00_000_0000000);
+
+    let first = setup.create_sample_invoice();
+    let second = setup.create_sample_invoice();
+    assert_eq!(setup.contract.get_buyer_obligations(&setup.buyer), 0); // Drafts owe nothing yet
+    setup.contract.approve_invoice(&first, &setup.buyer, &0);
+    assert_eq!(setup.contract.get_buyer_obligations(&setup.buyer), 10_00_000_0000000);
+    assert_eq!(setup.contract.try_approve_invoice(&second, &setup.buyer, &0), Err(Ok(ContractError::ObligationLimitExceeded)));
+
+    // Settling the first frees the room
+    setup.contract.invest(&first, &setup.investor, &1_00_000_0000000);
+    setup.contract.settle(&first, &setup.buyer, &setup.contract.get_settlement_amount(&first));
+    assert_eq!(setup.contract.get_buyer_obligations(&setup.buyer), 0);
+    setup.contract.approve_invoice(&second, &setup.buyer, &0);
+}
+
+#[test]
+fn test_exposure_limit_and_realized_loss() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_exposure_limit(&setup.admin, &setup.investor, &3_00_000_0000000);
+
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    let exposure = setup.contract.get_exposure(&setup.investor);
+    assert_eq!(exposure, 2_00_000_0000000);
+
+    let result = setup.contract.try_invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::ExposureLimitExceeded)));
+
+    // Default and claim: the uncovered cost basis becomes a realized loss
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 24 * 60 * 60; });
+    setup.contract.check_status(&invoice_id);
+    let payout = setup.contract.claim_insurance(&invoice_id, &setup.investor);
+    assert_eq!(setup.contract.get_realized_loss(&setup.investor), exposure - payout);
+    assert_eq!(setup.contract.get_exposure(&setup.investor), 0);
+}
+
+#[test]
+fn test_insurance_sub_pools_pay_pro_rata() {
+    let setup = TestSetup::new();
+    let second = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&second, &10_00_000_0000000);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &second, &true);
+    let defaulting = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&defaulting, &setup.buyer, &0);
+    let other = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&other, &setup.buyer, &0);
+    setup.contract.invest(&defaulting, &setup.investor, &2_00_000_0000000);
+    setup.contract.invest(&defaulting, &second, &1_00_000_0000000);
+    setup.contract.invest(&other, &setup.investor, &4_00_000_0000000);
+    assert_eq!(setup.contract.get_invoice_insurance_balance(&defaulting), 15_000_0000000);
+    assert_eq!(setup.contract.get_invoice_insurance_balance(&other), 20_000_0000000);
+
+    // Each holder gets their token share of the defaulted invoice's own sub-pool
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 24 * 60 * 60; });
+    setup.contract.check_status(&defaulting);
+    assert_eq!(setup.contract.claim_insurance(&defaulting, &setup.investor), 10_000_0000000);
+    assert_eq!(setup.contract.claim_insurance(&defaulting, &second), 5_000_0000000);
+    assert_eq!(setup.contract.get_invoice_insurance_balance(&defaulting), 0);
+    // The other invoice's cover is untouched
+    assert_eq!(setup.contract.get_invoice_insurance_balance(&other), 20_000_0000000);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), 20_000_0000000);
+}
+
+#[test]
+fn test_insurance_backstop_tops_up_from_shared_pool() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    assert_eq!(setup.contract.try_set_insurance_backstop(&setup.admin, &10001), Err(Ok(ContractError::InvalidAmount)));
+    setup.contract.set_insurance_backstop(&setup.admin, &1000); // 10% of cost basis
+
+    // A settled invoice's sub-pool joins the shared pool
+    let settled = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&settled, &setup.buyer, &0);
+    setup.contract.invest(&settled, &setup.investor, &1_00_000_0000000);
+    let defaulting = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&defaulting, &setup.buyer, &0);
+    setup.contract.invest(&defaulting, &setup.investor, &2_00_000_0000000);
+    setup.contract.settle(&settled, &setup.buyer, &setup.contract.get_settlement_amount(&settled));
+    assert_eq!(setup.contract.get_invoice_insurance_balance(&settled), 0);
+
+    // Own sub-pool pays 10,000; the shared 5,000 tops up towards the 20,000 target
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 24 * 60 * 60; });
+    setup.contract.check_status(&defaulting);
+    assert_eq!(setup.contract.claim_insurance(&defaulting, &setup.investor), 15_000_0000000);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), 0);
+}
+
+#[test]
+fn test_amend_draft_before_verification() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    let original_due = setup.contract.get_invoice(&invoice_id).due_date;
+    let amend = |supplier: &Address, amount: i128, due_date: u64, document_hash: &str| setup.contract.try_amend_draft(&invoice_id, supplier, &RevisedTerms {
+        amount,
+        due_date,
+        description: String::from_str(&setup.env, "Auto parts supply Q4"),
+        purchase_order: String::from_str(&setup.env, "PO-2024-1234"),
+        document_hash: String::from_str(&setup.env, document_hash),
+    });
+    assert_eq!(amend(&setup.buyer, 9_00_000_0000000, original_due, "QmFixed"), Err(Ok(ContractError::Unauthorized)));
+    assert_eq!(amend(&setup.supplier, -1, original_due, "QmFixed"), Err(Ok(ContractError::InvalidAmount)));
+    assert_eq!(amend(&setup.supplier, 9_00_000_0000000, original_due, "QmFixed"), Ok(Ok(1)));
+    let new_due = original_due + 14 * 86400;
+    assert_eq!(amend(&setup.supplier, 9_50_000_0000000, new_due, "QmFixedAgain"), Ok(Ok(2)));
+
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!((invoice.amount, invoice.due_date, invoice.status), (9_50_000_0000000, new_due, InvoiceStatus::Draft));
+    let history = setup.contract.get_amendments(&invoice_id, &0, &10);
+    assert_eq!(history.get(0).unwrap().document_hash, String::from_str(&setup.env, "QmFixed"));
+    assert_eq!(history.get(1).unwrap().amount, 9_50_000_0000000);
+    let due_in = |ts: u64| setup.contract.get_maturities(&ts, &ts, &10).iter().any(|m| m.invoice_id == invoice_id);
+    assert!(due_in(new_due) && !due_in(original_due));
+
+    // The buyer approves the terms they reviewed; an approval made before the last amendment fails
+    assert_eq!(setup.contract.try_approve_invoice(&invoice_id, &setup.buyer, &1), Err(Ok(ContractError::TermsMismatch)));
+    // Terms are frozen once the buyer approves
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &2);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).total_tokens, 9_50_000_0000000);
+    assert_eq!(amend(&setup.supplier, 9_00_000_0000000, new_due, "QmLate"), Err(Ok(ContractError::InvalidStatus)));
+}
+
+#[test]
+fn test_distribute_to_subvendors() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    let (steel, paint) = (Address::generate(&setup.env), Address::generate(&setup.env));
+    let split = |subvendor: &Address, bps: u32| SubvendorSplit { subvendor: subvendor.clone(), bps };
+    let declare = |splits| setup.contract.try_set_subvendor_splits(&invoice_id, &setup.supplier, &splits);
+    assert_eq!(declare(soroban_sdk::vec![&setup.env, split(&steel, 6000), split(&paint, 5000)]), Err(Ok(ContractError::InvalidAmount)));
+    assert_eq!(declare(soroban_sdk::vec![&setup.env, split(&steel, 1000), split(&steel, 500)]), Err(Ok(ContractError::InvalidAmount)));
+    assert_eq!(declare(soroban_sdk::vec![&setup.env, split(&steel, u32::MAX), split(&paint, 1)]), Err(Ok(ContractError::InvalidAmount)));
+    assert_eq!(declare(soroban_sdk::vec![&setup.env, split(&steel, 3000), split(&paint, 500)]), Ok(Ok(())));
+    // Nothing to distribute before verification
+    assert_eq!(setup.contract.try_distribute_to_subvendors(&invoice_id, &false), Err(Ok(ContractError::InvalidStatus)));
+
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    assert_eq!(declare(Vec::new(&setup.env)), Err(Ok(ContractError::InvalidStatus)));
+    let total_tokens = setup.contract.get_invoice(&invoice_id).total_tokens;
+    assert_eq!(setup.contract.distribute_to_subvendors(&invoice_id, &false), total_tokens * 3500 / 10000);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &steel).amount, total_tokens * 3000 / 10000);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &paint).amount, total_tokens * 500 / 10000);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.supplier).amount, total_tokens * 6500 / 10000);
+    let record = setup.contract.get_subvendor_distributions(&invoice_id, &0, &10);
+    assert_eq!((record.len(), record.get(1).unwrap().subvendor), (2, paint));
+
+    // Shares are passed on once
+    assert_eq!(setup.contract.try_distribute_to_subvendors(&invoice_id, &false), Err(Ok(ContractError::InvalidStatus)));
+}
+
+#[test]
+fn test_subvendor_acceptance_and_reclaim() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    let (steel, paint) = (Address::generate(&setup.env), Address::generate(&setup.env));
+    let splits = soroban_sdk::vec![&setup.env,
+        SubvendorSplit { subvendor: steel.clone(), bps: 2000 }, SubvendorSplit { subvendor: paint.clone(), bps: 1000 }];
+    setup.contract.set_subvendor_splits(&invoice_id, &setup.supplier, &splits);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let total_tokens = setup.contract.get_invoice(&invoice_id).total_tokens;
+    setup.contract.distribute_to_subvendors(&invoice_id, &true);
+
+    // Offered shares stay with the supplier, locked, until accepted
+    let locked = total_tokens * 3000 / 10000;
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.supplier).amount, total_tokens);
+    assert_eq!(setup.contract.get_locked_tokens(&invoice_id, &setup.supplier), locked);
+    let unlocked = total_tokens - locked;
+    let overdrawn = setup.contract.try_transfer_tokens(&invoice_id, &setup.supplier, &setup.investor, &(unlocked + 1));
+    assert_eq!(overdrawn, Err(Ok(ContractError::InsufficientTokens)));
+
+    let steel_offer = setup.contract.get_pending_transfers(&steel, &0, &10).get(0).unwrap();
+    assert_eq!(setup.contract.try_accept_tokens(&steel_offer.id, &paint), Err(Ok(ContractError::Unauthorized)));
+    setup.contract.accept_tokens(&steel_offer.id, &steel);
+    assert_eq!(setup.contract.get_holding(&invoice_id, &steel).amount, total_tokens * 2000 / 10000);
+    assert!(setup.contract.get_pending_transfers(&steel, &0, &10).is_empty());
+
+    // The unaccepted offer can be taken back once the acceptance window passes
+    let paint_offer = setup.contract.get_subvendor_distributions(&invoice_id, &0, &10).get(1).unwrap().transfer_id;
+    assert_eq!(setup.contract.try_reclaim_tokens(&paint_offer, &setup.supplier), Err(Ok(ContractError::InvalidStatus)));
+    setup.env.ledger().with_mut(|l| { l.timestamp += 7 * 86400; });
+    assert_eq!(setup.contract.reclaim_tokens(&paint_offer, &setup.supplier), total_tokens * 1000 / 10000);
+    assert_eq!(setup.contract.get_locked_tokens(&invoice_id, &setup.supplier), 0);
+    assert_eq!(setup.contract.try_accept_tokens(&paint_offer, &paint), Err(Ok(ContractError::HoldingNotFound)));
+    assert_eq!(setup.contract.get_holding(&invoice_id, &setup.supplier).amount, total_tokens * 8000 / 10000);
+
+    // Accepting one of several offers leaves the others listed
+    let first = setup.contract.offer_tokens(&invoice_id, &setup.supplier, &steel, &1);
+    let second = setup.contract.offer_tokens(&invoice_id, &setup.supplier, &steel, &2);
+    setup.contract.accept_tokens(&first, &steel);
+    let pending = setup.contract.get_pending_transfers(&steel, &0, &10);
+    assert_eq!((pending.len(), pending.get(0).unwrap().id), (1, second));
+
+    // A recipient holds at most 20 offers
+    for _ in 1..20 { setup.contract.offer_tokens(&invoice_id, &setup.supplier, &steel, &1); }
+    assert_eq!(setup.contract.try_offer_tokens(&invoice_id, &setup.supplier, &steel, &1), Err(Ok(ContractError::LimitReached)));
+
+    // Settlement redeems the supplier's holding, cancelling its open offers
+    setup.contract.settle(&invoice_id, &setup.buyer, &setup.contract.get_settlement_amount(&invoice_id));
+    assert!(setup.contract.get_pending_transfers(&steel, &0, &10).is_empty());
+    assert_eq!(setup.contract.get_locked_tokens(&invoice_id, &setup.supplier), 0);
+}
+
+#[test]
+fn test_buyer_rejects_draft_and_supplier_resubmits() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    let reason = String::from_str(&setup.env, "Quantity on PO-2024-1234 is 800, not 1000");
+    assert_eq!(setup.contract.try_reject_invoice(&invoice_id, &setup.supplier, &reason), Err(Ok(ContractError::Unauthorized)));
+    setup.contract.reject_invoice(&invoice_id, &setup.buyer, &reason);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Rejected);
+    assert_eq!(setup.contract.get_draft_rejection(&invoice_id).unwrap().reason, reason);
+    assert_eq!(setup.contract.try_approve_invoice(&invoice_id, &setup.buyer, &0), Err(Ok(ContractError::InvalidStatus)));
+
+    // The supplier amends the amount and sends it back for approval
+    let due_date = setup.env.ledger().timestamp() + 60 * 86400;
+    let amended = |amount: i128| setup.contract.try_resubmit_invoice(&invoice_id, &setup.supplier, &RevisedTerms {
+        amount,
+        due_date,
+        description: String::from_str(&setup.env, "Auto parts supply Q4"),
+        purchase_order: String::from_str(&setup.env, "PO-2024-1234"),
+        document_hash: String::from_str(&setup.env, "QmAmendedDocHash"),
+    });
+    assert_eq!(amended(0), Err(Ok(ContractError::InvalidAmount)));
+    amended(8_00_000_0000000).unwrap().unwrap();
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!((invoice.status, invoice.amount, invoice.due_date), (InvoiceStatus::Draft, 8_00_000_0000000, due_date));
+    assert_eq!(amended(8_00_000_0000000), Err(Ok(ContractError::InvalidStatus)));
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &1);
+
+    let stats = setup.contract.get_relationship_stats(&setup.supplier, &setup.buyer);
+    assert_eq!((stats.drafts_approved, stats.drafts_rejected, stats.resubmitted), (1, 1, 1));
+    assert_eq!(setup.contract.get_amendments(&invoice_id, &0, &10).len(), 1);
+
+    // A rejected draft can also just be withdrawn
+    let abandoned = setup.create_sample_invoice();
+    setup.contract.reject_invoice(&abandoned, &setup.buyer, &reason);
+    setup.contract.revoke(&abandoned, &setup.supplier);
+    assert_eq!(setup.contract.get_invoice(&abandoned).status, InvoiceStatus::Revoked);
+}
+
+#[test]
+fn test_due_date_extension_approved_by_holders() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let second = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&second, &10_00_000_0000000);
+    setup.contract.set_investor_kyc(&setup.admin, &second, &true);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.invest(&invoice_id, &setup.investor, &7_00_000_0000000);
+    setup.contract.invest(&invoice_id, &second, &3_00_000_0000000);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    let (face, original_due) = (invoice.amount, invoice.due_date);
+
+    setup.env.ledger().with_mut(|l| { l.timestamp = original_due + 5 * 86400; });
+    assert_eq!(setup.contract.check_status(&invoice_id), InvoiceStatus::Overdue);
+    let new_due = original_due + 30 * 86400;
+    assert_eq!(setup.contract.try_propose_extension(&invoice_id, &setup.investor, &new_due, &200), Err(Ok(ContractError::Unauthorized)));
+    assert_eq!(setup.contract.try_propose_extension(&invoice_id, &setup.buyer, &original_due, &200), Err(Ok(ContractError::InvalidAmount)));
+    setup.contract.propose_extension(&invoice_id, &setup.buyer, &new_due, &200);
+    assert_eq!(setup.contract.try_propose_extension(&invoice_id, &setup.buyer, &new_due, &200), Err(Ok(ContractError::InvalidStatus)));
+
+    // 30% against is not enough to decide; the 70% holder's approval clears the two-thirds bar
+    setup.contract.vote_extension(&invoice_id, &second, &false);
+    assert_eq!(setup.contract.try_finalize_extension(&invoice_id), Err(Ok(ContractError::QuorumNotReached)));
+    assert_eq!(setup.contract.try_vote_extension(&invoice_id, &setup.supplier, &true), Err(Ok(ContractError::HoldingNotFound)));
+    setup.contract.vote_extension(&invoice_id, &setup.investor, &true);
+    assert!(setup.contract.finalize_extension(&invoice_id));
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    assert_eq!((invoice.status, invoice.due_date), (InvoiceStatus::Funded, new_due));
+    assert!(setup.contract.get_extension_proposal(&invoice_id).is_none());
+    let due_in = |from: u64, to: u64| setup.contract.get_maturities(&from, &to, &10).iter().any(|m| m.invoice_id == invoice_id);
+    assert!(due_in(new_due, new_due) && !due_in(original_due, original_due));
+    let queued = |due: u64| setup.env.as_contract(&setup.contract.address, || {
+        storage::get_default_bucket(&setup.env, due / storage::DEFAULT_BUCKET_SECONDS).contains(&invoice_id)
+    });
+    assert!(queued(new_due) && !queued(original_due));
+
+    // Base rate throughout, plus the bonus for the days past the original due date
+    setup.env.ledger().with_mut(|l| { l.timestamp = original_due + 10 * 86400; });
+    let days = ((original_due - invoice.created_at) / 86400 + 10) as i128;
+    assert_eq!(setup.contract.get_settlement_amount(&invoice_id), face + face * 1000 * days / (10000 * 365) + face * 200 * 10 / (10000 * 365));
+}
+
+#[test]
+fn test_due_date_extension_lapses_without_approval() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    assert_eq!(setup.contract.try_propose_extension(&invoice_id, &setup.buyer, &u64::MAX, &0), Err(Ok(ContractError::InvalidStatus)));
+    setup.contract.invest(&invoice_id, &setup.investor, &10_00_000_0000000);
+    let due_date = setup.contract.get_invoice(&invoice_id).due_date;
+
+    let voting_ends = setup.contract.propose_extension(&invoice_id, &setup.buyer, &(due_date + 86400), &0);
+    setup.contract.vote_extension(&invoice_id, &setup.investor, &false);
+    setup.env.ledger().with_mut(|l| { l.timestamp = voting_ends + 1; });
+    assert_eq!(setup.contract.try_vote_extension(&invoice_id, &setup.investor, &true), Err(Ok(ContractError::AppealWindowClosed)));
+    assert!(!setup.contract.finalize_extension(&invoice_id));
+    assert_eq!(setup.contract.get_invoice(&invoice_id).due_date, due_date);
+    assert!(setup.contract.get_due_date_extension(&invoice_id).is_none());
+    // The buyer may try again once the previous proposal is decided
+    setup.contract.propose_extension(&invoice_id, &setup.buyer, &(due_date + 86400), &0);
+}
+
+#[test]
+fn test_recovery_repays_insurance_then_pays_holders() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let collector = Address::generate(&setup.env);
+    setup.contract.grant_role(&setup.admin, &Role::Collector, &collector);
+    setup.usdc_admin.mint(&collector, &10_00_000_0000000);
+
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    assert_eq!(setup.contract.try_record_recovery(&invoice_id, &collector, &1_0000000), Err(Ok(ContractError::NotDefaulted)));
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 86400; });
+    setup.contract.check_status(&invoice_id);
+    let payout = setup.contract.claim_insurance(&invoice_id, &setup.investor);
+    assert_eq!(setup.contract.try_record_recovery(&invoice_id, &setup.investor, &1_0000000), Err(Ok(ContractError::Unauthorized)));
+
+    // The investor's 20% share of the first recovery first repays their insurance payout
+    let pool_before = setup.contract.get_insurance_pool_balance();
+    let investor_before = setup.usdc.balance(&setup.investor);
+    let supplier_before = setup.usdc.balance(&setup.supplier);
+    let first = 1_00_000_0000000;
+    assert_eq!(setup.contract.record_recovery(&invoice_id, &collector, &first), InvoiceStatus::Defaulted);
+    let share = first / 5;
+    assert_eq!(setup.contract.get_insurance_pool_balance() - pool_before, payout.min(share));
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, share - payout.min(share));
+    assert_eq!(setup.usdc.balance(&setup.supplier) - supplier_before, first - share);
+
+    // Recovering the rest of face value closes the invoice as Recovered
+    let rest = setup.contract.get_invoice(&invoice_id).amount - first;
+    assert_eq!(setup.contract.record_recovery(&invoice_id, &collector, &rest), InvoiceStatus::Recovered);
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, first / 5 + rest / 5 - payout);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).recovered_amount, first + rest);
+    assert_eq!(setup.contract.try_write_off(&invoice_id, &collector), Err(Ok(ContractError::InvalidStatus)));
+}
+
+#[test]
+fn test_recovery_before_claim_reduces_payout() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let collector = Address::generate(&setup.env);
+    setup.contract.grant_role(&setup.admin, &Role::Collector, &collector);
+    setup.usdc_admin.mint(&collector, &10_00_000_0000000);
+    let underwriter = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&underwriter, &10_00_000_0000000);
+    setup.contract.stake_insurance(&underwriter, &0, &10_00_000_0000000);
+    setup.contract.set_insurance_backstop(&setup.admin, &10000); // full cost basis
+
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    let cost = setup.contract.get_holding(&invoice_id, &setup.investor).acquired_price;
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 86400; });
+    setup.contract.check_status(&invoice_id);
+
+    // 3 units split 20/80 pay the holders 0 and 2; the leftover unit goes to the pool
+    let pool_before = setup.contract.get_insurance_pool_balance();
+    setup.contract.record_recovery(&invoice_id, &collector, &3);
+    assert_eq!(setup.contract.get_insurance_pool_balance() - pool_before, 1);
+
+    // The investor's 20% of a recovery is netted out of the claim, which only covers the rest
+    let first = 1_00_000_0000000;
+    let investor_before = setup.usdc.balance(&setup.investor);
+    setup.contract.record_recovery(&invoice_id, &collector, &first);
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, first / 5);
+    assert_eq!(setup.contract.claim_insurance(&invoice_id, &setup.investor), cost - first / 5);
+    assert_eq!(setup.usdc.balance(&setup.investor) - investor_before, cost);
+}
+
+#[test]
+fn test_backstop_covers_at_most_face_value() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let underwriter = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&underwriter, &10_00_000_0000000);
+    setup.contract.stake_insurance(&underwriter, &0, &10_00_000_0000000);
+    setup.contract.set_insurance_backstop(&setup.admin, &10000); // full cost basis
+
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    let invoice = setup.contract.get_invoice(&invoice_id);
+    let face_value = 2_00_000_0000000 * invoice.amount / invoice.total_tokens;
+    // A basis above face value (as left by transfers before basis moved pro rata) isn't covered
+    setup.env.as_contract(&setup.contract.address, || {
+        let mut holding = storage::get_token_holding(&setup.env, &invoice_id, &setup.investor).unwrap();
+        holding.acquired_price = face_value * 3;
+        storage::set_token_holding(&setup.env, &invoice_id, &setup.investor, &holding);
+    });
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 86400; });
+    setup.contract.check_status(&invoice_id);
+    assert_eq!(setup.contract.claim_insurance(&invoice_id, &setup.investor), face_value);
+}
+
+#[test]
+fn test_write_off_keeps_insurance_claimable() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    assert_eq!(setup.contract.try_write_off(&invoice_id, &setup.admin), Err(Ok(ContractError::InvalidStatus)));
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 86400; });
+    setup.contract.check_status(&invoice_id);
+    setup.contract.write_off(&invoice_id, &setup.admin);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::WrittenOff);
+    assert!(setup.contract.claim_insurance(&invoice_id, &setup.investor) > 0);
+    assert_eq!(setup.contract.try_record_recovery(&invoice_id, &setup.admin, &1_0000000), Err(Ok(ContractError::NotDefaulted)));
+}
+
+#[test]
+fn test_risk_based_insurance_premium_tiers() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let tier = |min_buyer_score, max_tenor_days, premium_bps| PremiumTier { min_buyer_score, max_tenor_days, premium_bps };
+    let too_high = soroban_sdk::vec![&setup.env, tier(0, 365, 10001)];
+    assert_eq!(setup.contract.try_set_premium_tiers(&setup.admin, &too_high), Err(Ok(ContractError::InvalidFeeSchedule)));
+    let tiers = soroban_sdk::vec![&setup.env, tier(9000, 365, 100), tier(5000, 60, 200), tier(5000, 120, 500)];
+    setup.contract.set_premium_tiers(&setup.admin, &tiers);
+
+    // Neutral buyer, 90 days out: the 120-day tier applies to the whole face value before the auction
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let face = setup.contract.get_invoice(&invoice_id).amount;
+    let quote = setup.contract.quote_insurance_premium(&invoice_id);
+    assert_eq!((quote.buyer_score, quote.tenor_days, quote.premium_bps), (5000, 90, 500));
+    assert_eq!(quote.sale.payment_amount, face);
+    assert_eq!(quote.sale.insurance_amount, face * 500 / 10000);
+    assert_eq!(quote.sale.supplier_proceeds, face - quote.sale.insurance_amount);
+
+    // Closer to maturity the cheaper tier applies at invest time
+    setup.env.ledger().with_mut(|l| { l.timestamp += 35 * 86400; });
+    assert_eq!(setup.contract.quote_insurance_premium(&invoice_id).premium_bps, 200);
+    let payment = setup.contract.simulate_invest(&invoice_id, &1_00_000_0000000, &setup.env.ledger().timestamp()).payment_amount;
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(setup.contract.get_invoice_insurance_balance(&invoice_id), payment * 200 / 10000);
+
+    // No matching tier falls back to the fee schedule's flat cut
+    setup.contract.set_premium_tiers(&setup.admin, &soroban_sdk::vec![&setup.env, tier(9000, 365, 100)]);
+    assert_eq!(setup.contract.get_premium_tiers(&0, &10), soroban_sdk::vec![&setup.env, tier(9000, 365, 100)]);
+    let flat_bps = setup.contract.get_active_fee_schedule().insurance_cut_bps;
+    assert_eq!(setup.contract.quote_insurance_premium(&invoice_id).premium_bps, flat_bps);
+}
+
+#[test]
+fn test_underwriters_earn_released_premiums_and_interest_cut() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_underwriter_terms(&setup.admin, &UnderwriterTerms { lockup_seconds: 86400, yield_bps: 5000 });
+    let underwriter = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&underwriter, &1_00_000_0000000);
+    let staked = 50_000_0000000;
+    assert_eq!(setup.contract.stake_insurance(&underwriter, &0, &staked), staked);
+    assert_eq!(setup.contract.try_stake_insurance(&underwriter, &7, &staked), Err(Ok(ContractError::ProgramNotFound)));
+
+    // Premiums stay earmarked for their invoices and don't count towards the shares yet
+    let settled = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&settled, &setup.buyer, &0);
+    setup.contract.invest(&settled, &setup.investor, &1_00_000_0000000);
+    let open = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&open, &setup.buyer, &0);
+    setup.contract.invest(&open, &setup.investor, &1_00_000_0000000);
+    assert_eq!(setup.contract.get_underwriter_value(&0, &underwriter), staked);
+
+    // Settling releases the premium and pays half the interest into the pool
+    setup.env.ledger().with_mut(|l| { l.timestamp += 30 * 86400; });
+    let amount = setup.contract.get_settlement_amount(&settled);
+    let interest = amount - setup.contract.get_invoice(&settled).amount;
+    let premium = setup.contract.get_invoice_insurance_balance(&settled);
+    let simulated: i128 = setup.contract.simulate_settlement(&settled, &amount, &setup.env.ledger().timestamp()).payouts.iter().map(|p| p.amount_received).sum();
+    setup.contract.settle(&settled, &setup.buyer, &amount);
+    assert!((0..=1).contains(&(amount - interest / 2 - simulated))); // Per-holder rounding
+    let value = setup.contract.get_underwriter_value(&0, &underwriter);
+    assert_eq!(value, staked + premium + interest / 2);
+
+    // Only the free balance is withdrawn; the open invoice's premium stays earmarked
+    let stake = setup.contract.get_underwriter_stake(&0, &underwriter);
+    assert_eq!(setup.contract.try_unstake_insurance(&underwriter, &0, &(stake.shares + 1)), Err(Ok(ContractError::InvalidAmount)));
+    assert_eq!(setup.contract.unstake_insurance(&underwriter, &0, &stake.shares), value);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), setup.contract.get_invoice_insurance_balance(&open));
+    assert_eq!(setup.contract.get_underwriter_stake(&0, &underwriter).shares, 0);
+}
+
+#[test]
+fn test_wiped_out_pool_reseeds_shares() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let (early, late) = (Address::generate(&setup.env), Address::generate(&setup.env));
+    setup.usdc_admin.mint(&early, &10_0000000);
+    setup.usdc_admin.mint(&late, &20_0000000);
+    setup.contract.stake_insurance(&early, &0, &10_0000000);
+
+    // A keeper bounty drains the whole free balance
+    setup.contract.set_keeper_bounty(&setup.admin, &10_0000000);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 91 * 24 * 60 * 60; });
+    setup.contract.poke(&invoice_id, &Address::generate(&setup.env));
+    assert_eq!(setup.contract.get_underwriter_value(&0, &early), 0);
+
+    // The next stake starts over at 1:1 and the worthless shares no longer count
+    assert_eq!(setup.contract.stake_insurance(&late, &0, &20_0000000), 20_0000000);
+    assert_eq!(setup.contract.get_underwriter_value(&0, &late), 20_0000000);
+    assert_eq!(setup.contract.get_underwriter_stake(&0, &early).shares, 0);
+}
+
+#[test]
+fn test_underwriter_lockup_and_seeded_pool() {
+    let setup = TestSetup::new();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    setup.contract.settle(&invoice_id, &setup.buyer, &setup.contract.get_settlement_amount(&invoice_id));
+    let seed = setup.contract.get_insurance_pool_balance();
+    assert!(seed > 0);
+
+    // The balance from before the first stake belongs to the protocol, not the first underwriter
+    let underwriter = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&underwriter, &1_00_000_0000000);
+    let shares = setup.contract.stake_insurance(&underwriter, &0, &10_000_0000000);
+    assert_eq!(setup.contract.get_underwriter_value(&0, &underwriter), 10_000_0000000);
+
+    // Default 30-day lockup, restarted by every stake
+    setup.env.ledger().with_mut(|l| { l.timestamp += 20 * 86400; });
+    setup.contract.stake_insurance(&underwriter, &0, &1_0000000);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 20 * 86400; });
+    assert_eq!(setup.contract.try_unstake_insurance(&underwriter, &0, &shares), Err(Ok(ContractError::InvalidStatus)));
+    setup.env.ledger().with_mut(|l| { l.timestamp += 10 * 86400; });
+    assert_eq!(setup.contract.unstake_insurance(&underwriter, &0, &shares), 10_000_0000000);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), seed + 1_0000000);
+}
+
+#[test]
+fn test_auction_result_recorded_on_sell_out() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let second = Address::generate(&setup.env);
+    setup.usdc_admin.mint(&second, &10_00_000_0000000);
+    setup.contract.set_investor_kyc(&setup.admin, &second, &true);
+
+    setup.contract.invest(&invoice_id, &setup.investor, &4_00_000_0000000);
+    assert_eq!(setup.contract.get_auction_result(&invoice_id).finalized_at, 0);
+
+    setup.env.ledger().with_mut(|l| { l.timestamp += 10 * 60 * 60; });
+    let clearing = setup.contract.get_current_price(&invoice_id);
+    setup.contract.invest(&invoice_id, &second, &6_00_000_0000000);
+
+    let result = setup.contract.get_auction_result(&invoice_id);
+    assert_eq!(result.clearing_price, clearing);
+    assert_eq!(result.tokens_sold, 10_00_000_0000000);
+    assert_eq!(result.investor_count, 2);
+    assert_eq!(result.duration, 10 * 60 * 60);
+    assert_eq!(result.finalized_at, setup.env.ledger().timestamp());
+    let discount = (10_00_000_0000000 - result.total_proceeds) * 10000 / 10_00_000_0000000;
+    assert_eq!(result.avg_discount_bps as i128, discount);
+    assert!(result.avg_discount_bps > 0);
+}
+
+#[test]
+fn test_investor_summary() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 10 * 60 * 60; });
+    setup.contract.invest(&invoice_id, &setup.investor, &2_00_000_0000000);
+    let cost = setup.contract.get_holding(&invoice_id, &setup.investor).acquired_price;
+    assert_eq!(setup.contract.get_investor_summary(&setup.investor).principal_outstanding, cost);
+
+    let settlement = setup.contract.get_settlement_amount(&invoice_id);
+    setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
+
+    let received = setup.contract.get_receipt(&invoice_id, &setup.investor).amount_received;
+    let summary = setup.contract.get_investor_summary(&setup.investor);
+    assert_eq!(summary.principal_outstanding, 0);
+    assert_eq!(summary.realized_returns, received - cost);
+    assert_eq!(summary.realized_losses, 0);
+    assert_eq!(summary.avg_yield_bps as i128, (received - cost) * 10000 / cost);
+    assert!(summary.avg_yield_bps > 0);
+    // Supplier's retained tokens are not booked as an investor loss
+    assert_eq!(setup.contract.get_realized_loss(&setup.supplier), 0);
+}
+
+#[test]
+fn test_supplier_summary() {
+    let setup = TestSetup::new();
+    let _draft = setup.create_sample_invoice();
+    let auctioned = setup.create_sample_invoice();
+    let funded = setup.create_sample_invoice();
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+
+    setup.contract.approve_invoice(&auctioned, &setup.buyer, &0);
+    setup.contract.start_auction(&auctioned, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.contract.invest(&auctioned, &setup.investor, &2_00_000_0000000);
+
+    setup.contract.approve_invoice(&funded, &setup.buyer, &0);
+    setup.usdc_admin.mint(&setup.investor, &10_00_000_0000000);
+    setup.contract.invest(&funded, &setup.investor, &10_00_000_0000000);
+
+    let summary = setup.contract.get_supplier_summary(&setup.supplier);
+    assert_eq!(summary.draft_count, 1);
+    assert_eq!(summary.draft_amount, 10_00_000_0000000);
+    assert_eq!(summary.auction_count, 1);
+    assert_eq!(summary.auction_tokens_sold, 2_00_000_0000000);
+    assert_eq!(summary.auction_tokens_total, 10_00_000_0000000);
+    assert_eq!(summary.awaiting_count, 1);
+    assert_eq!(summary.awaiting_amount, 10_00_000_0000000);
+    assert_eq!(summary.lifetime_proceeds, setup.usdc.balance(&setup.supplier));
+}
+
+#[test]
+fn test_get_maturities() {
+    let setup = TestSetup::new();
+    let now = setup.env.ledger().timestamp();
+    let near = setup.create_sample_invoice(); // due in 90 days
+    let far = setup.contract.mint_draft(
+        &setup.supplier, &setup.buyer, &1_00_000_0000000, &String::from_str(&setup.env, "INR"),
+        &(now + 200 * 24 * 60 * 60), &String::from_str(&setup.env, "Far"),
+        &String::from_str(&setup.env, "PO-2"), &String::from_str(&setup.env, "hash"),
+    );
+
+    let next_quarter = setup.contract.get_maturities(&now, &(now + 100 * 24 * 60 * 60), &100);
+    assert_eq!(next_quarter.len(), 1);
+    assert_eq!(next_quarter.get(0).unwrap().invoice_id, near);
+
+    let next_year = setup.contract.get_maturities(&now, &(now + 365 * 24 * 60 * 60), &100);
+    assert_eq!(next_year.len(), 2);
+    assert_eq!(next_year.get(1).unwrap().invoice_id, far);
+    assert_eq!(next_year.get(1).unwrap().amount, 1_00_000_0000000);
+    assert_eq!(setup.contract.get_maturities(&now, &(now + 365 * 24 * 60 * 60), &1).len(), 1);
+
+    assert_eq!(setup.contract.try_get_maturities(&now, &(now - 1), &100), Err(Ok(ContractError::InvalidAmount)));
+    assert_eq!(setup.contract.try_get_maturities(&0, &now, &100), Err(Ok(ContractError::InvalidAmount)));
+}
+
+#[test]
+fn test_get_actionable() {
+    let setup = TestSetup::new();
+    let auctioned = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&auctioned, &setup.buyer, &0);
+    setup.contract.start_auction(&auctioned, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    let quiet = setup.create_sample_invoice();
+    assert_eq!(setup.contract.get_actionable(&ActionableCursor::Start, &10).invoices.len(), 0);
+
+    // Auction window closes with tokens unsold
+    setup.env.ledger().with_mut(|l| { l.timestamp += 25 * 60 * 60; });
+    let actionable = setup.contract.get_actionable(&ActionableCursor::Start, &10).invoices;
+    assert_eq!(actionable.len(), 1);
+    assert_eq!(actionable.get(0).unwrap().invoice_id, auctioned);
+    assert_eq!(actionable.get(0).unwrap().action, KeeperAction::AuctionExpired);
+
+    // Past due: auctioned invoice becomes overdue; draft is never flagged
+    setup.env.ledger().with_mut(|l| { l.timestamp += 91 * 24 * 60 * 60; });
+    let actionable = setup.contract.get_actionable(&ActionableCursor::Start, &10).invoices;
+    assert_eq!(actionable.len(), 1);
+    assert_eq!(actionable.get(0).unwrap().action, KeeperAction::MarkOverdue);
+    assert!(actionable.iter().all(|a| a.invoice_id != quiet));
+
+    // Acting on it clears the overdue flag until the grace period lapses
+    setup.contract.check_status(&auctioned);
+    assert_eq!(setup.contract.get_actionable(&ActionableCursor::Start, &10).invoices.len(), 0);
+    setup.env.ledger().with_mut(|l| { l.timestamp += 30 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.get_actionable(&ActionableCursor::Start, &10).invoices.get(0).unwrap().action, KeeperAction::MarkDefaulted);
+    assert_eq!(setup.contract.get_actionable(&ActionableCursor::Start, &0).invoices.len(), 0);
+
+    // Pages resume from the returned cursor
+    let second = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&second, &setup.buyer, &0);
+    setup.contract.start_auction(&second, &setup.supplier, &1, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+    setup.env.ledger().with_mut(|l| { l.timestamp += 2 * 60 * 60; });
+    let first_page = setup.contract.get_actionable(&ActionableCursor::Start, &1);
+    assert_eq!(first_page.invoices.get(0).unwrap().invoice_id, auctioned);
+    let second_page = setup.contract.get_actionable(&first_page.next, &1);
+    assert_eq!(second_page.invoices.get(0).unwrap().invoice_id, second);
+    assert_eq!(second_page.invoices.get(0).unwrap().action, KeeperAction::AuctionExpired);
+    assert_eq!(setup.contract.get_actionable(&second_page.next, &1).next, ActionableCursor::Done);
+}
+
+#[test]
+fn test_process_defaults_walks_queue_in_due_order() {
+    let setup = TestSetup::new();
+    let now = setup.env.ledger().timestamp();
+    let mint = |days: u64| {
+        let id = setup.contract.mint_draft(
+            &setup.supplier, &setup.buyer, &1_00_000_0000000, &String::from_str(&setup.env, "INR"),
+            &(now + days * 24 * 60 * 60), &String::from_str(&setup.env, "Batch"),
+            &String::from_str(&setup.env, "PO"), &String::from_str(&setup.env, "hash"),
+        );
+        setup.contract.approve_invoice(&id, &setup.buyer, &0);
+        id
+    };
+    let late = mint(60);
+    let early = mint(30);
+    let paid = mint(40);
+    let future = mint(200);
+    setup.contract.settle(&paid, &setup.buyer, &setup.contract.get_settlement_amount(&paid));
+
+    // 30-day grace: early and late are past it, future is not
+    setup.env.ledger().with_mut(|l| { l.timestamp += 100 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.process_defaults(&1), 1);
+    assert_eq!(setup.contract.get_invoice(&early).status, InvoiceStatus::Defaulted);
+    assert_eq!(setup.contract.get_invoice(&late).status, InvoiceStatus::Verified);
+
+    // Settled entry is dropped without counting as a default; the walk stops at `future`
+    assert_eq!(setup.contract.process_defaults(&10), 1);
+    assert_eq!(setup.contract.get_invoice(&late).status, InvoiceStatus::Defaulted);
+    assert_eq!(setup.contract.get_invoice(&future).status, InvoiceStatus::Verified);
+}
+
+#[test]
+fn test_disputed_invoices_leave_the_default_queue() {
+    let setup = TestSetup::new();
+    let disputed = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&disputed, &setup.buyer, &0);
+    let later = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&later, &setup.buyer, &0);
+    setup.contract.raise_dispute(&disputed, &setup.buyer, &String::from_str(&setup.env, "Short shipment"));
+
+    // The disputed invoice doesn't hold up the one behind it
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.process_defaults(&1), 1);
+    assert_eq!(setup.contract.get_invoice(&later).status, InvoiceStatus::Defaulted);
+    assert_eq!(setup.contract.get_invoice(&disputed).status, InvoiceStatus::Disputed);
+
+    // Rejecting the dispute puts it back in the queue
+    setup.contract.resolve_dispute(&disputed, &setup.admin, &false);
+    assert_eq!(setup.contract.process_defaults(&10), 1);
+    assert_eq!(setup.contract.get_invoice(&disputed).status, InvoiceStatus::Defaulted);
+}
+
+#[test]
+fn test_migrate_moves_legacy_default_queue_into_buckets() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let due_date = setup.contract.get_invoice(&invoice_id).due_date;
+    setup.contract.request_kyc(&setup.investor, &String::from_str(&setup.env, "QmKycPackage"));
+
+    // A deployment from before day buckets kept the queue as one list, and the KYC queue too
+    setup.env.as_contract(&setup.contract.address, || {
+        storage::dequeue_for_default(&setup.env, due_date, &invoice_id);
+        let legacy = Vec::from_array(&setup.env, [(due_date, invoice_id.clone())]);
+        setup.env.storage().persistent().set(&storage::DataKey::DefaultQueue, &legacy);
+        storage::dequeue_kyc(&setup.env, &setup.investor);
+        let legacy_kyc = Vec::from_array(&setup.env, [setup.investor.clone()]);
+        setup.env.storage().persistent().set(&storage::AccessDataKey::KycQueue, &legacy_kyc);
+        storage::set_storage_version(&setup.env, 2);
+    });
+    assert!(setup.contract.get_kyc_queue(&0, &10).is_empty());
+    setup.env.ledger().with_mut(|l| { l.timestamp += 121 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.process_defaults(&10), 0);
+
+    assert_eq!(setup.contract.migrate(&setup.admin, &10), 12);
+    assert_eq!(setup.contract.process_defaults(&10), 1);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Defaulted);
+    assert_eq!(setup.contract.get_kyc_queue(&0, &10).get(0).unwrap().applicant, setup.investor);
+}
+
+#[test]
+fn test_migrate_rewrites_version_0_entries_in_pages() {
+    let setup = TestSetup::new();
+    let first = setup.create_sample_invoice();
+    let second = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&second, &setup.buyer, &0);
+
+    // Rewind both invoices and the rate config to the layout from before versioning
+    setup.env.as_contract(&setup.contract.address, || {
+        for id in [&first, &second] {
+            let i = storage::get_invoice(&setup.env, id).unwrap();
+            let legacy = migration::InvoiceV0 {
+                id: i.id, supplier: i.supplier, buyer: i.buyer, amount: i.amount, currency: i.currency,
+                created_at: i.created_at, due_date: i.due_date, verified_at: i.verified_at, settled_at: i.settled_at,
+                status: i.status, token_symbol: i.token_symbol, total_tokens: i.total_tokens,
+                tokens_sold: i.tokens_sold, tokens_remaining: i.tokens_remaining, description: i.description,
+                purchase_order: i.purchase_order, document_hash: i.document_hash,
+                repayment_received: i.repayment_received, buyer_signed_at: i.buyer_signed_at,
+                auction_start: i.auction_start, auction_end: i.auction_end, start_price: i.start_price,
+                min_price: i.min_price, price_drop_rate: i.price_drop_rate,
+            };
+            setup.env.storage().persistent().set(&storage::DataKey::Invoice(id.clone()), &legacy);
+        }
+        let c = storage::get_rate_config(&setup.env);
+        let legacy = migration::RateConfigV0 {
+            base_interest_rate: c.base_interest_rate, penalty_rate: c.penalty_rate,
+            grace_period_days: c.grace_period_days, default_auction_duration: c.default_auction_duration,
+            default_price_drop_rate: c.default_price_drop_rate, default_max_discount: c.default_max_discount,
+            insurance_cut_bps: 500,
+        };
+        setup.env.storage().instance().set(&storage::DataKey::RateConfig, &legacy);
+        setup.env.storage().persistent().remove(&storage::IndexDataKey::BuyerOutstanding(setup.buyer.clone()));
+        storage::set_storage_version(&setup.env, 0);
+    });
+
+    // One invoice per call; the version only moves once both are rewritten
+    assert_eq!(setup.contract.migrate(&setup.admin, &1), 0);
+    assert_eq!(setup.contract.migrate(&setup.admin, &1), 12);
+    assert_eq!(setup.contract.get_buyer_obligations(&setup.buyer), setup.contract.get_invoice(&second).amount);
+
+    let invoice = setup.contract.get_invoice(&second);
+    assert_eq!(invoice.status, InvoiceStatus::Verified);
+    assert_eq!(invoice.auction_type, AuctionType::Dutch);
+    assert_eq!(invoice.recovered_amount, 0);
+    assert_eq!(invoice.rate_config.day_count, DayCount::Act365);
+    assert_eq!(invoice.rate_config.max_investor_bps, 0);
+    assert_eq!(setup.contract.get_invoice(&first).status, InvoiceStatus::Draft);
+    // The index backfill skips invoices already listed
+    assert_eq!(setup.contract.get_invoices_by_status(&InvoiceStatus::Draft, &0, &10).len(), 1);
+    assert_eq!(setup.contract.get_invoices_by_buyer(&setup.buyer, &0, &10).len(), 2);
+    setup.env.as_contract(&setup.contract.address, || {
+        assert_eq!(storage::get_fee_schedule(&setup.env, 1).unwrap().insurance_cut_bps, 500);
+    });
+    // The rewritten entries are usable again
+    setup.contract.start_auction(&second, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+}
+
+#[test]
+fn test_invoice_history() {
+    let setup = TestSetup::new();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    setup.contract.start_auction(&invoice_id, &setup.supplier, &24, &1000, &AuctionType::Dutch, &AuctionTerms::default());
+
+    setup.env.ledger().with_mut(|l| {
+        l.timestamp = l.timestamp + (91 * 24 * 60 * 60);
+    });
+    setup.contract.check_status(&invoice_id);
+
+    let history = setup.contract.get_invoice_history(&invoice_id, &0, &100);
+    assert_eq!(history.len(), 4);
+    assert_eq!(history.get(0).unwrap().status, InvoiceStatus::Draft);
+    assert_eq!(history.get(0).unwrap().actor, setup.supplier);
+    assert_eq!(history.get(1).unwrap().status, InvoiceStatus::Verified);
+    assert_eq!(history.get(1).unwrap().actor, setup.buyer);
+    assert_eq!(history.get(2).unwrap().status, InvoiceStatus::Funding);
+    let overdue = history.get(3).unwrap();
+    assert_eq!(overdue.status, InvoiceStatus::Overdue);
+    assert_eq!(overdue.actor, setup.contract.address);
+    assert_eq!(overdue.timestamp, setup.env.ledger().timestamp());
+}
+
+// ============================================================================
+// KYC TESTS
+// ============================================================================
+
+#[test]
+fn test_kyc_status() {
+    let setup = TestSetup::new();
 
     // Initially not approved
     assert!(!setup.contract.is_kyc_approved(&setup.investor));
@@ -415,3 +3612,679 @@ fn test_kyc_status() {
     setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &false);
     assert!(!setup.contract.is_kyc_approved(&setup.investor));
 }
+
+#[test]
+fn test_kyc_request_queue() {
+    let setup = TestSetup::new();
+    let kyc_manager = Address::generate(&setup.env);
+    setup.contract.grant_role(&setup.admin, &Role::KycManager, &kyc_manager);
+    let (first, second, third) = (Address::generate(&setup.env), Address::generate(&setup.env), Address::generate(&setup.env));
+    let package = String::from_str(&setup.env, "QmKycPackage");
+    for applicant in [&first, &second, &third] { setup.contract.request_kyc(applicant, &package); }
+    assert_eq!(setup.contract.try_request_kyc(&first, &package), Err(Ok(ContractError::InvalidStatus)));
+    assert_eq!(setup.contract.get_kyc_queue(&0, &10).len(), 3);
+
+    let reference = String::from_str(&setup.env, "QmReview");
+    let approved = soroban_sdk::vec![&setup.env, first.clone(), third.clone()];
+    let rejected = soroban_sdk::vec![&setup.env, second.clone(), third.clone()];
+    assert_eq!(setup.contract.try_process_kyc_requests(&setup.investor, &approved, &true, &0, &reference), Err(Ok(ContractError::Unauthorized)));
+    assert_eq!(setup.contract.process_kyc_requests(&kyc_manager, &approved, &true, &0, &reference), 2);
+    // Already decided requests are skipped
+    assert_eq!(setup.contract.process_kyc_requests(&kyc_manager, &rejected, &false, &7, &reference), 1);
+
+    assert!(setup.contract.is_kyc_approved(&first) && setup.contract.is_kyc_approved(&third));
+    assert!(!setup.contract.is_kyc_approved(&second));
+    let decision = setup.contract.get_kyc_request(&second).unwrap();
+    assert_eq!(decision.status, KycRequestStatus::Rejected);
+    assert_eq!(setup.contract.get_authorization_log(&second, &0, &100).get(0).unwrap().action_id, decision.action_id);
+    assert!(setup.contract.get_kyc_queue(&0, &10).is_empty());
+
+    // Approved applicants have nothing to ask for; rejected ones may apply again
+    assert_eq!(setup.contract.try_request_kyc(&first, &package), Err(Ok(ContractError::InvalidStatus)));
+    setup.contract.request_kyc(&second, &package);
+    assert_eq!(setup.contract.get_kyc_request(&second).unwrap().status, KycRequestStatus::Pending);
+    assert_eq!(setup.contract.get_kyc_queue_bounds(), (3, 4));
+
+    // Deciding directly also answers the queued request; the queue keeps arrival order around it
+    let fourth = Address::generate(&setup.env);
+    setup.contract.request_kyc(&fourth, &package);
+    setup.contract.set_investor_kyc(&kyc_manager, &second, &true);
+    assert_eq!(setup.contract.get_kyc_request(&second).unwrap().status, KycRequestStatus::Approved);
+    let queue = setup.contract.get_kyc_queue(&0, &10);
+    assert_eq!((queue.len(), queue.get(0).unwrap().applicant), (1, fourth));
+    assert_eq!(setup.contract.get_kyc_queue_bounds(), (4, 5));
+}
+
+#[test]
+fn test_migration_exports() {
+    let setup = TestSetup::new();
+    let revoked = Address::generate(&setup.env);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &revoked, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &revoked, &false);
+    let draft_id = setup.create_sample_invoice();
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let cancelled = setup.contract.create_sell_order(&invoice_id, &setup.supplier, &1_000_0000000, &1);
+    setup.contract.cancel_order(&cancelled, &setup.supplier);
+    let ask = setup.contract.create_sell_order(&invoice_id, &setup.supplier, &1_000_0000000, &1);
+    let bid = setup.contract.create_buy_order(&invoice_id, &setup.investor, &1_000_0000000, &1);
+
+    let summary = setup.contract.get_export_summary();
+    assert_eq!((summary.invoice_count, summary.order_count, summary.kyc_count), (2, 3, 2));
+    assert_eq!(summary.escrow_total, 1_000_0000000);
+
+    let invoices = setup.contract.export_invoices(&0, &10);
+    assert_eq!(invoices.len(), 2);
+    assert_eq!((invoices.get(0).unwrap().invoice_id, invoices.get(0).unwrap().status), (draft_id, InvoiceStatus::Draft));
+    assert_eq!((invoices.get(1).unwrap().invoice_id, invoices.get(1).unwrap().status), (invoice_id, InvoiceStatus::Verified));
+    assert_eq!(setup.contract.export_invoices(&1, &10).len(), 1);
+    assert!(setup.contract.export_invoices(&5, &10).is_empty());
+
+    // Each address is listed once, with its current status
+    let kyc = setup.contract.export_kyc(&0, &10);
+    assert_eq!(kyc.len(), 2);
+    assert!(kyc.get(0).unwrap().investor == setup.investor && kyc.get(0).unwrap().approved);
+    assert!(kyc.get(1).unwrap().investor == revoked && !kyc.get(1).unwrap().approved);
+
+    // Cancelled orders are left out
+    let orders = setup.contract.export_open_orders(&0, &10);
+    assert_eq!(orders.sell_orders.len(), 1);
+    assert_eq!(orders.sell_orders.get(0).unwrap().id, ask);
+    assert_eq!(orders.buy_orders.get(0).unwrap().id, bid);
+    assert_eq!(setup.contract.export_open_orders(&2, &1).sell_orders.len(), 0);
+
+    let insurance = setup.contract.export_insurance(&0, &10);
+    assert_eq!(insurance.len(), 1);
+    assert_eq!(insurance.get(0).unwrap().pool_balance, setup.contract.get_insurance_pool_balance());
+}
+
+#[test]
+fn test_backfill_kyc_index() {
+    let setup = TestSetup::new();
+    let anchor = Address::generate(&setup.env);
+    let program_id = setup.contract.create_program(&setup.admin, &anchor, &RateConfig::default());
+    let (legacy, indexed, stranger) = (Address::generate(&setup.env), Address::generate(&setup.env), Address::generate(&setup.env));
+    setup.contract.set_investor_kyc(&setup.admin, &indexed, &true);
+    setup.contract.set_program_kyc(&anchor, &program_id, &setup.investor, &true);
+
+    // Statuses written before the export index, and an index entry from before its marker
+    setup.env.as_contract(&setup.contract.address, || {
+        let store = setup.env.storage().persistent();
+        store.set(&storage::DataKey::KycStatus(legacy.clone()), &true);
+        store.set(&storage::ProgramDataKey::ProgramKyc(storage::ProgramKey { program_id, addr: legacy.clone() }), &false);
+        store.remove(&storage::AccessDataKey::KycIndexed(indexed.clone()));
+        storage::set_storage_version(&setup.env, 11);
+    });
+    assert_eq!(setup.contract.migrate(&setup.admin, &10), 12);
+    assert_eq!(setup.contract.get_export_summary().kyc_count, 1);
+
+    let investors = Vec::from_array(&setup.env, [legacy.clone(), indexed.clone(), stranger.clone()]);
+    assert!(setup.contract.try_backfill_kyc_index(&anchor, &0, &investors).is_err());
+    assert_eq!(setup.contract.backfill_kyc_index(&setup.admin, &0, &investors), 1);
+    assert_eq!(setup.contract.backfill_kyc_index(&setup.admin, &0, &investors), 0);
+    let kyc = setup.contract.export_kyc(&0, &10);
+    assert_eq!(kyc.len(), 2);
+    assert!(kyc.get(0).unwrap().investor == indexed && kyc.get(1).unwrap().investor == legacy && kyc.get(1).unwrap().approved);
+
+    // Program lists export on their own, revoked entries included
+    assert_eq!(setup.contract.backfill_kyc_index(&anchor, &program_id, &investors), 1);
+    assert_eq!(setup.contract.get_program_kyc_count(&program_id), 2);
+    let program_kyc = setup.contract.export_program_kyc(&program_id, &0, &10);
+    assert!(program_kyc.get(0).unwrap().investor == setup.investor && program_kyc.get(0).unwrap().approved);
+    assert!(program_kyc.get(1).unwrap().investor == legacy && !program_kyc.get(1).unwrap().approved);
+    assert_eq!(setup.contract.export_program_kyc(&program_id, &1, &10).len(), 1);
+}
+
+// ============================================================================
+// PROGRAM (MULTI-TENANT) TESTS
+// ============================================================================
+
+#[test]
+fn test_program_partitioning() {
+    let setup = TestSetup::new();
+    let anchor = Address::generate(&setup.env);
+    let rates = RateConfig { grace_period_days: 10, ..RateConfig::default() };
+    let program_id = setup.contract.create_program(&setup.admin, &anchor, &rates);
+    assert_eq!(program_id, 1);
+    assert_eq!(setup.contract.get_program(&program_id).admin, anchor);
+
+    // Only the program admin manages its suppliers
+    assert!(setup.contract.try_set_supplier_program(&setup.admin, &program_id, &setup.supplier).is_err());
+    setup.contract.set_supplier_program(&anchor, &program_id, &setup.supplier);
+
+    let invoice_id = setup.create_sample_invoice();
+    assert_eq!(setup.contract.get_invoice(&invoice_id).program_id, program_id);
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // Global KYC does not carry over to the program
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    let result = setup.contract.try_invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(result, Err(Ok(ContractError::KYCRequired)));
+
+    setup.contract.set_program_kyc(&anchor, &program_id, &setup.investor, &true);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+
+    // Insurance cut lands in the program's pool
+    assert_eq!(setup.contract.get_program_insurance_pool(&program_id), 5_000_0000000);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), 0);
+
+    // The program's 10-day grace period applies, not the global 30 days
+    setup.env.ledger().with_mut(|l| { l.timestamp += 101 * 24 * 60 * 60; });
+    assert_eq!(setup.contract.process_defaults(&10), 1);
+    assert_eq!(setup.contract.get_invoice(&invoice_id).status, InvoiceStatus::Defaulted);
+}
+
+#[test]
+fn test_protocol_fees_accrue_and_withdraw() {
+    let setup = TestSetup::new();
+    // 2% protocol fee on primary investments, 0.3% on secondary fills
+    let version = setup.contract.set_fee_schedule(&setup.admin, &FeeSchedule { insurance_cut_bps: 500, protocol_fee_bps: 200, trading_fee_bps: 30, ..Default::default() });
+    let config = setup.contract.get_fee_config();
+    assert_eq!((config.schedule_version, config.protocol_fee_bps, config.trading_fee_bps, config.reserve), (version, 200, 30, 0));
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.sub_vendor, &true);
+
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let quote = setup.contract.simulate_invest(&invoice_id, &1_00_000_0000000, &setup.env.ledger().timestamp());
+    assert_eq!(quote.protocol_fee, 2_000_0000000);
+    assert_eq!(quote.supplier_proceeds, 1_00_000_0000000 - 5_000_0000000 - 2_000_0000000);
+    let supplier_before = setup.usdc.balance(&setup.supplier);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.supplier) - supplier_before, quote.supplier_proceeds);
+    assert_eq!(setup.contract.get_fee_config().reserve, 2_000_0000000);
+
+    // The taker pays the protocol's trading fee on top of the price
+    let order_id = setup.contract.create_sell_order(&invoice_id, &setup.investor, &10_000_0000000, &1);
+    setup.usdc_admin.mint(&setup.sub_vendor, &10_030_0000000);
+    setup.contract.fill_order(&order_id, &setup.sub_vendor, &10_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.sub_vendor), 0);
+    assert_eq!(setup.contract.get_fee_config().reserve, 2_030_0000000);
+
+    let outsider = Address::generate(&setup.env);
+    assert!(setup.contract.try_withdraw_fees(&outsider, &outsider, &1).is_err());
+    let result = setup.contract.try_withdraw_fees(&setup.admin, &setup.admin, &2_030_0000001);
+    assert_eq!(result, Err(Ok(ContractError::ExceedsTreasuryBalance)));
+    let treasury = Address::generate(&setup.env);
+    setup.contract.withdraw_fees(&setup.admin, &treasury, &2_030_0000000);
+    assert_eq!(setup.usdc.balance(&treasury), 2_030_0000000);
+    assert_eq!(setup.contract.get_fee_config().reserve, 0);
+}
+
+#[test]
+fn test_secondary_royalty_funds_insurance_pool() {
+    let setup = TestSetup::new();
+    setup.contract.set_fee_schedule(&setup.admin, &FeeSchedule { insurance_cut_bps: 500, royalty_bps: 100, ..Default::default() }); // 1% royalty
+    setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+    setup.contract.set_investor_kyc(&setup.admin, &setup.sub_vendor, &true);
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+    let override_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&override_id, &setup.buyer, &0);
+    let result = setup.contract.try_start_auction(&override_id, &setup.supplier, &24, &0, &AuctionType::Dutch, &AuctionTerms { royalty_bps: Some(MAX_ROYALTY_BPS + 1), ..Default::default() });
+    assert_eq!(result, Err(Ok(ContractError::InvalidFeeSchedule)));
+    setup.contract.start_auction(&override_id, &setup.supplier, &24, &0, &AuctionType::Dutch, &AuctionTerms { royalty_bps: Some(300), ..Default::default() });
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    setup.contract.invest(&override_id, &setup.investor, &1_00_000_0000000);
+    // The royalty is capped, and later schedules don't move the rate frozen at auction start
+    assert_eq!(setup.contract.try_set_fee_schedule(&setup.admin, &FeeSchedule { insurance_cut_bps: 500, royalty_bps: MAX_ROYALTY_BPS + 1, ..Default::default() }), Err(Ok(ContractError::InvalidFeeSchedule)));
+    setup.contract.set_fee_schedule(&setup.admin, &FeeSchedule { insurance_cut_bps: 500, ..Default::default() });
+    let pool_before = setup.contract.get_insurance_pool_balance();
+
+    // Schedule royalty: 1% of the 10,000 sale comes out of the seller's proceeds
+    let order_id = setup.contract.create_sell_order(&invoice_id, &setup.investor, &10_000_0000000, &1);
+    setup.usdc_admin.mint(&setup.sub_vendor, &20_000_0000000);
+    let seller_before = setup.usdc.balance(&setup.investor);
+    setup.contract.fill_order(&order_id, &setup.sub_vendor, &10_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.investor) - seller_before, 9_900_0000000);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), pool_before + 100_0000000);
+
+    // The supplier's override replaces the schedule's rate
+    let order_id = setup.contract.create_sell_order(&override_id, &setup.investor, &10_000_0000000, &1);
+    let seller_before = setup.usdc.balance(&setup.investor);
+    setup.contract.fill_order(&order_id, &setup.sub_vendor, &10_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.investor) - seller_before, 9_700_0000000);
+    assert_eq!(setup.contract.get_insurance_pool_balance(), pool_before + 400_0000000);
+    assert_eq!(setup.usdc.balance(&setup.sub_vendor), 0);
+    assert_eq!(setup.contract.get_sweepable_balance(&setup.usdc.address), 0);
+}
+
+#[test]
+fn test_program_fee_split_and_withdrawal() {
+    let setup = TestSetup::new();
+    let anchor = Address::generate(&setup.env);
+    let program_id = setup.contract.create_program(&setup.admin, &anchor, &RateConfig::default());
+    setup.contract.set_program_fees(&setup.admin, &program_id, &ProgramFees { investment_fee_bps: 100, trading_fee_bps: 50, anchor_share_bps: 6000, maker_fee_bps: 0 });
+    setup.contract.set_supplier_program(&anchor, &program_id, &setup.supplier);
+    setup.contract.set_program_kyc(&anchor, &program_id, &setup.investor, &true);
+    setup.contract.set_program_kyc(&anchor, &program_id, &setup.sub_vendor, &true);
+
+    let invoice_id = setup.create_sample_invoice();
+    setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+
+    // 1L investment: 1% fee = 1,000, anchor gets 60%
+    let quote = setup.contract.simulate_invest(&invoice_id, &1_00_000_0000000, &setup.env.ledger().timestamp());
+    assert_eq!(quote.program_fee, 1_000_0000000);
+    assert_eq!(quote.supplier_proceeds, 1_00_000_0000000 - 5_000_0000000 - 1_000_0000000);
+    setup.contract.invest(&invoice_id, &setup.investor, &1_00_000_0000000);
+    assert_eq!(setup.contract.get_treasury_balance(&program_id), 600_0000000);
+    assert_eq!(setup.contract.get_treasury_balance(&0), 400_0000000);
+
+    // Secondary fill of 10,000: the taker pays a 0.5% trading fee on top
+    let order_id = setup.contract.create_sell_order(&invoice_id, &setup.investor, &10_000_0000000, &1);
+    setup.usdc_admin.mint(&setup.sub_vendor, &10_050_0000000);
+    let seller_before = setup.usdc.balance(&setup.investor);
+    setup.contract.fill_order(&order_id, &setup.sub_vendor, &10_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.investor) - seller_before, 10_000_0000000);
+    assert_eq!(setup.usdc.balance(&setup.sub_vendor), 0);
+    assert_eq!(setup.contract.get_treasury_balance(&program_id), 630_0000000);
+    assert_eq!(setup.contract.get_treasury_balance(&0), 420_0000000);
+
+    // Treasury funds are not sweepable; only each treasury's admin can withdraw
+    assert_eq!(setup.contract.get_sweepable_balance(&setup.usdc.address), 0);
+    assert!(setup.contract.try_withdraw_treasury(&setup.admin, &program_id, &setup.admin, &1).is_err());
+    let result = setup.contract.try_withdraw_treasury(&anchor, &program_id, &anchor, &631_0000000);
+    assert_eq!(result, Err(Ok(ContractError::ExceedsTreasuryBalance)));
+    setup.contract.withdraw_treasury(&anchor, &program_id, &anchor, &630_0000000);
+    assert_eq!(setup.usdc.balance(&anchor), 630_0000000);
+    setup.contract.withdraw_treasury(&setup.admin, &0, &setup.admin, &420_0000000);
+    assert_eq!(setup.contract.get_treasury_balance(&0), 0);
+}
+
+#[test]
+fn test_error_code_table_matches_contract_errors() {
+    extern crate std;
+    use crate::error_codes::{decode, describe, ERROR_CODES};
+    for info in ERROR_CODES.iter() {
+        let error = ContractError::try_from(soroban_sdk::Error::from_contract_error(info.code)).unwrap();
+        assert_eq!(error.info(), info);
+    }
+    assert!(describe(14).is_none() && describe(20).is_none() && describe(28).is_none() && describe(47).is_none());
+    assert_eq!(describe(53).unwrap().name, "SymbolTaken");
+    assert_eq!(describe(54).unwrap().name, "LimitReached");
+    assert_eq!(describe(52).unwrap().name, "ConcentrationLimitExceeded");
+    assert_eq!(describe(29).unwrap().name, "ObligationLimitExceeded");
+    assert_eq!(decode("HostError: Error(Contract, #9)").unwrap().name, "InsufficientPayment");
+    assert!(decode("HostError: Error(Auth, InvalidAction)").is_none());
+    assert_eq!(
+        std::format!("{}", ContractError::ContractPaused),
+        "ContractPaused (#51): Contract is paused; investing, trading, settlement and claims are blocked"
+    );
+}
+
+// ============================================================================
+// PROPERTY TESTS
+// ============================================================================
+
+mod props {
+    extern crate std;
+
+    use super::*;
+    use proptest::prelude::*;
+    use std::vec::Vec as StdVec;
+
+    const INVESTORS: usize = 3;
+
+    /// One investment: which investor, what share of the remaining tokens (bps) and how many
+    /// minutes into the auction
+    fn investment() -> impl Strategy<Value = (usize, u32, u64)> {
+        (0..INVESTORS, 1u32..=10000, 0u64..=72 * 60)
+    }
+
+    /// Contract USDC must always cover the pools, treasuries, escrow and dust it owes
+    fn assert_solvent(setup: &TestSetup) {
+        let contract_id = setup.contract.address.clone();
+        let usdc = setup.usdc.address.clone();
+        let owed = setup.env.as_contract(&contract_id, || {
+            storage::get_total_insurance_pools(&setup.env) + storage::get_total_treasuries(&setup.env)
+                + storage::get_escrow_total(&setup.env) + storage::get_dust_total(&setup.env, &usdc)
+        });
+        assert!(setup.usdc.balance(&contract_id) >= owed);
+    }
+
+    fn total_usdc(setup: &TestSetup, investors: &[Address]) -> i128 {
+        let mut total = setup.usdc.balance(&setup.contract.address) + setup.usdc.balance(&setup.supplier) + setup.usdc.balance(&setup.buyer);
+        for investor in investors { total += setup.usdc.balance(investor); }
+        total
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(24))]
+
+        #[test]
+        fn auction_price_never_rises(duration_hours in 1u64..=168, max_discount_bps in 0u32..=5000, checkpoints in proptest::collection::vec(0u64..=200 * 3600, 1..8)) {
+            let setup = TestSetup::new();
+            let invoice_id = setup.create_sample_invoice();
+            setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+            setup.contract.start_auction(&invoice_id, &setup.supplier, &duration_hours, &max_discount_bps, &AuctionType::Dutch, &AuctionTerms::default());
+            let invoice = setup.contract.get_invoice(&invoice_id);
+            let start = setup.env.ledger().timestamp();
+
+            let mut offsets = checkpoints.clone();
+            offsets.sort();
+            let mut last = invoice.start_price;
+            for offset in offsets {
+                setup.env.ledger().with_mut(|l| l.timestamp = start + offset);
+                let price = setup.contract.get_current_price(&invoice_id);
+                prop_assert!(price <= last);
+                prop_assert!(price >= invoice.min_price && price <= invoice.start_price);
+                last = price;
+            }
+        }
+
+        #[test]
+        fn invest_and_settle_conserve_usdc(
+            amount in 1_0000000i128..=1_000_000_000_0000000,
+            duration_hours in 1u64..=72,
+            max_discount_bps in 0u32..=5000,
+            investments in proptest::collection::vec(investment(), 1..5),
+            settle_after_days in 0u64..=120,
+        ) {
+            let setup = TestSetup::new();
+            let investors: StdVec<Address> = (0..INVESTORS).map(|_| Address::generate(&setup.env)).collect();
+            for investor in &investors {
+                setup.contract.set_investor_kyc(&setup.admin, investor, &true);
+                setup.usdc_admin.mint(investor, &amount);
+            }
+            setup.usdc_admin.mint(&setup.buyer, &(amount * 2));
+            let due_date = setup.env.ledger().timestamp() + 90 * 86400;
+            let invoice_id = setup.contract.mint_draft(
+                &setup.supplier, &setup.buyer, &amount, &String::from_str(&setup.env, "INR"), &due_date,
+                &String::from_str(&setup.env, "Property test"), &String::from_str(&setup.env, "PO-PROP"), &String::from_str(&setup.env, "QmProp"),
+            );
+            setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+            setup.contract.start_auction(&invoice_id, &setup.supplier, &duration_hours, &max_discount_bps, &AuctionType::Dutch, &AuctionTerms::default());
+            let start = setup.env.ledger().timestamp();
+            let total = total_usdc(&setup, &investors);
+            let before: StdVec<i128> = investors.iter().map(|i| setup.usdc.balance(i)).collect();
+
+            let mut minutes: StdVec<_> = investments.clone();
+            minutes.sort_by_key(|(_, _, m)| *m);
+            for (who, share_bps, minute) in minutes {
+                let invoice = setup.contract.get_invoice(&invoice_id);
+                if invoice.status != InvoiceStatus::Funding || start + minute * 60 >= invoice.auction_end { break; }
+                let tokens = (invoice.tokens_remaining * share_bps as i128 / 10000).max(1).min(invoice.tokens_remaining);
+                setup.env.ledger().with_mut(|l| l.timestamp = start + minute * 60);
+                setup.contract.invest(&invoice_id, &investors[who], &tokens);
+
+                let invoice = setup.contract.get_invoice(&invoice_id);
+                prop_assert_eq!(invoice.tokens_sold + invoice.tokens_remaining, invoice.total_tokens);
+                let held: i128 = core::iter::once(&setup.supplier).chain(investors.iter())
+                    .filter_map(|h| setup.contract.try_get_holding(&invoice_id, h).ok().and_then(|r| r.ok()))
+                    .map(|h| h.amount)
+                    .sum();
+                prop_assert_eq!(held, invoice.total_tokens);
+                prop_assert_eq!(total_usdc(&setup, &investors), total);
+                assert_solvent(&setup);
+            }
+
+            setup.env.ledger().with_mut(|l| l.timestamp = start + settle_after_days * 86400);
+            let settlement = setup.contract.get_settlement_amount(&invoice_id);
+            prop_assert!(settlement >= amount);
+            setup.contract.settle(&invoice_id, &setup.buyer, &settlement);
+            prop_assert!(setup.contract.get_invoice(&invoice_id).status.is_settled());
+            prop_assert_eq!(total_usdc(&setup, &investors), total);
+            assert_solvent(&setup);
+            // Bought at or below face and repaid in full: no investor ends up worse off
+            for (investor, start_balance) in investors.iter().zip(before.iter()) {
+                prop_assert!(setup.usdc.balance(investor) >= *start_balance);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// DIFFERENTIAL TESTS
+// ============================================================================
+
+/// Plain-Rust restatement of the pricing and settlement rules, written from the spec rather
+/// than from the contract code. The harness below replays random scenarios through both and
+/// fails on the first figure where they disagree.
+mod reference_model {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    const DAY: u64 = 86400;
+    const HOUR: u64 = 3600;
+
+    pub struct Terms {
+        pub amount: i128,
+        pub created_at: u64,
+        pub due_date: u64,
+        pub base_rate_bps: i128,
+        pub penalty_rate_bps: i128,
+        pub insurance_cut_bps: i128,
+        pub drop_bps_per_hour: i128,
+        pub max_discount_bps: i128,
+    }
+
+    pub struct Model {
+        pub terms: Terms,
+        pub auction_start: u64,
+        /// Tokens per participant; index 0 is the supplier, who starts with all of them
+        pub holdings: Vec<i128>,
+        pub insurance_pool: i128,
+    }
+
+    pub struct Fill {
+        pub price: i128,
+        pub payment: i128,
+        pub insurance: i128,
+        pub supplier_proceeds: i128,
+    }
+
+    impl Model {
+        pub fn new(terms: Terms, auction_start: u64, investors: usize) -> Self {
+            let mut holdings = std::vec![0; investors + 1];
+            holdings[0] = terms.amount;
+            Model { terms, auction_start, holdings, insurance_pool: 0 }
+        }
+
+        /// Dutch auction: full face value, dropping a fixed share of it per whole hour, floored
+        /// at the maximum discount. Discounts round down, in the supplier's favour.
+        pub fn price_at(&self, t: u64) -> i128 {
+            let hours = (t.saturating_sub(self.auction_start) / HOUR) as i128;
+            let floor = self.terms.amount - self.terms.amount * self.terms.max_discount_bps / 10000;
+            let dropped = self.terms.amount - self.terms.amount * self.terms.drop_bps_per_hour * hours / 10000;
+            dropped.max(floor)
+        }
+
+        /// Investor `who` (1-based) buys `tokens` from the supplier's unsold stock at time `t`
+        pub fn invest(&mut self, who: usize, tokens: i128, t: u64) -> Fill {
+            let price = self.price_at(t);
+            let payment = tokens * price / self.terms.amount;
+            let insurance = payment * self.terms.insurance_cut_bps / 10000;
+            self.holdings[0] -= tokens;
+            self.holdings[who] += tokens;
+            self.insurance_pool += insurance;
+            Fill { price, payment, insurance, supplier_proceeds: payment - insurance }
+        }
+
+        /// Face value plus simple interest for whole days since issuance; past the due date the
+        /// penalty rate applies to the whole period
+        pub fn settlement_amount(&self, t: u64) -> i128 {
+            let days = (t.saturating_sub(self.terms.created_at) / DAY) as i128;
+            let rate = if t > self.terms.due_date { self.terms.penalty_rate_bps } else { self.terms.base_rate_bps };
+            self.terms.amount + self.terms.amount * rate * days / (10000 * 365)
+        }
+
+        /// Each holder's share of a settlement, pro rata to tokens held
+        pub fn payouts(&self, paid: i128) -> Vec<i128> {
+            self.holdings.iter().map(|tokens| tokens * paid / self.terms.amount).collect()
+        }
+    }
+}
+
+mod differential {
+    extern crate std;
+
+    use super::reference_model::{Model, Terms};
+    use super::*;
+    use proptest::prelude::*;
+    use std::vec::Vec as StdVec;
+
+    const INVESTORS: usize = 3;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(24))]
+
+        #[test]
+        fn contract_matches_reference_model(
+            amount in 1_0000000i128..=1_000_000_000_0000000,
+            duration_hours in 1u64..=96,
+            max_discount_bps in 0u32..=5000,
+            fills in proptest::collection::vec((1..=INVESTORS, 1u32..=10000, 0u64..=96 * 60), 1..6),
+            settle_after_days in 0u64..=150,
+        ) {
+            let setup = TestSetup::new();
+            let investors: StdVec<Address> = (0..INVESTORS).map(|_| Address::generate(&setup.env)).collect();
+            for investor in &investors {
+                setup.contract.set_investor_kyc(&setup.admin, investor, &true);
+                setup.usdc_admin.mint(investor, &amount);
+            }
+            setup.usdc_admin.mint(&setup.buyer, &(amount * 2));
+            let created_at = setup.env.ledger().timestamp();
+            let due_date = created_at + 90 * 86400;
+            let invoice_id = setup.contract.mint_draft(
+                &setup.supplier, &setup.buyer, &amount, &String::from_str(&setup.env, "INR"), &due_date,
+                &String::from_str(&setup.env, "Differential"), &String::from_str(&setup.env, "PO-DIFF"), &String::from_str(&setup.env, "QmDiff"),
+            );
+            setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+            setup.contract.start_auction(&invoice_id, &setup.supplier, &duration_hours, &max_discount_bps, &AuctionType::Dutch, &AuctionTerms::default());
+            let invoice = setup.contract.get_invoice(&invoice_id);
+            let mut model = Model::new(Terms {
+                amount, created_at, due_date, base_rate_bps: 1000, penalty_rate_bps: 2400, insurance_cut_bps: 500,
+                drop_bps_per_hour: invoice.price_drop_rate as i128, max_discount_bps: max_discount_bps as i128,
+            }, invoice.auction_start, INVESTORS);
+            let participants: StdVec<Address> = core::iter::once(setup.supplier.clone()).chain(investors.iter().cloned()).collect();
+
+            let mut fills = fills.clone();
+            fills.sort_by_key(|(_, _, minute)| *minute);
+            for (who, share_bps, minute) in fills {
+                let t = invoice.auction_start + minute * 60;
+                let unsold = model.holdings[0];
+                if t >= invoice.auction_end || unsold == 0 { break; }
+                let tokens = (unsold * share_bps as i128 / 10000).max(1);
+                setup.env.ledger().with_mut(|l| l.timestamp = t);
+
+                let quote = setup.contract.simulate_invest(&invoice_id, &tokens, &t);
+                let investor_before = setup.usdc.balance(&participants[who]);
+                let supplier_before = setup.usdc.balance(&setup.supplier);
+                setup.contract.invest(&invoice_id, &participants[who], &tokens);
+                let fill = model.invest(who, tokens, t);
+
+                prop_assert_eq!(quote.price, fill.price);
+                prop_assert_eq!(quote.payment_amount, fill.payment);
+                prop_assert_eq!(quote.insurance_amount, fill.insurance);
+                prop_assert_eq!(investor_before - setup.usdc.balance(&participants[who]), fill.payment);
+                prop_assert_eq!(setup.usdc.balance(&setup.supplier) - supplier_before, fill.supplier_proceeds);
+                prop_assert_eq!(setup.contract.get_insurance_pool_balance(), model.insurance_pool);
+            }
+
+            let settle_at = created_at + settle_after_days * 86400;
+            setup.env.ledger().with_mut(|l| l.timestamp = settle_at);
+            let required = setup.contract.get_settlement_amount(&invoice_id);
+            prop_assert_eq!(required, model.settlement_amount(settle_at));
+
+            let before: StdVec<i128> = participants.iter().map(|p| setup.usdc.balance(p)).collect();
+            setup.contract.settle(&invoice_id, &setup.buyer, &required);
+            for ((participant, start_balance), expected) in participants.iter().zip(before.iter()).zip(model.payouts(required).iter()) {
+                // The supplier's payout on unsold tokens lands on the same balance
+                prop_assert_eq!(setup.usdc.balance(participant) - start_balance, *expected);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// BUDGET BENCHMARKS
+// ============================================================================
+
+/// CPU instruction and memory budgets of hot entrypoints, checked against ceilings so a feature
+/// that makes them markedly more expensive fails here rather than on the network. Test
+/// contracts run natively, so the figures cover host work (storage, crypto, token calls) but
+/// not Wasm execution; keep the ceilings well under the network's per-transaction limits.
+mod budget {
+    use super::*;
+
+    /// Per-transaction network limits (protocol 22). Settlement cost grows with the holder count and
+    /// is the first entrypoint to approach them, at a few dozen holders.
+    const NETWORK_CPU_LIMIT: u64 = 100_000_000;
+    const NETWORK_MEM_LIMIT: u64 = 41_943_040;
+
+    /// Ceilings sit roughly 50% above the figures measured when they were set
+    const INVEST_CPU: u64 = 2_500_000;
+    const INVEST_MEM: u64 = 600_000;
+    const SETTLE_BASE_CPU: u64 = 2_000_000;
+    const SETTLE_CPU_PER_HOLDER: u64 = 2_500_000;
+    const SETTLE_BASE_MEM: u64 = 500_000;
+    const SETTLE_MEM_PER_HOLDER: u64 = 800_000;
+    const OPEN_ORDERS_CPU_PER_ORDER: u64 = 60_000;
+    const OPEN_ORDERS_MEM_PER_ORDER: u64 = 7_000;
+
+    /// Runs `f` on a fresh unlimited budget and returns the (cpu, mem) it used
+    fn measure(env: &Env, f: impl FnOnce()) -> (u64, u64) {
+        let mut budget = env.cost_estimate().budget();
+        budget.reset_unlimited();
+        f();
+        (budget.cpu_instruction_cost(), budget.memory_bytes_cost())
+    }
+
+    /// Invoice with `holders` investors holding equal slices of its first half
+    fn funded_invoice(setup: &TestSetup, holders: u32) -> String {
+        let invoice_id = setup.create_sample_invoice();
+        setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+        let slice = 5_00_000_0000000 / holders as i128;
+        for _ in 0..holders {
+            let investor = Address::generate(&setup.env);
+            setup.contract.set_investor_kyc(&setup.admin, &investor, &true);
+            setup.usdc_admin.mint(&investor, &slice);
+            setup.contract.invest(&invoice_id, &investor, &slice);
+        }
+        invoice_id
+    }
+
+    #[test]
+    fn budget_invest() {
+        let setup = TestSetup::new();
+        let invoice_id = funded_invoice(&setup, 3);
+        setup.contract.set_investor_kyc(&setup.admin, &setup.investor, &true);
+        let (cpu, mem) = measure(&setup.env, || setup.contract.invest(&invoice_id, &setup.investor, &1_000_0000000));
+        assert!(cpu < INVEST_CPU, "invest cpu {cpu}");
+        assert!(mem < INVEST_MEM, "invest mem {mem}");
+        assert!(cpu < NETWORK_CPU_LIMIT && mem < NETWORK_MEM_LIMIT);
+    }
+
+    #[test]
+    fn budget_settle_by_holder_count() {
+        for holders in [1u32, 10, 25] {
+            let setup = TestSetup::new();
+            let invoice_id = funded_invoice(&setup, holders);
+            let settlement = setup.contract.get_settlement_amount(&invoice_id);
+            let (cpu, mem) = measure(&setup.env, || setup.contract.settle(&invoice_id, &setup.buyer, &settlement));
+            let holders = holders as u64;
+            assert!(cpu < SETTLE_BASE_CPU + SETTLE_CPU_PER_HOLDER * holders, "settle cpu {cpu} with {holders} holders");
+            assert!(mem < SETTLE_BASE_MEM + SETTLE_MEM_PER_HOLDER * holders, "settle mem {mem} with {holders} holders");
+            assert!(cpu < NETWORK_CPU_LIMIT && mem < NETWORK_MEM_LIMIT);
+        }
+    }
+
+    #[test]
+    fn budget_get_open_orders_by_order_count() {
+        for orders in [10u32, 50, 100] {
+            let setup = TestSetup::new();
+            let invoice_id = setup.create_sample_invoice();
+            setup.contract.approve_invoice(&invoice_id, &setup.buyer, &0);
+            for _ in 0..orders {
+                setup.contract.create_sell_order(&invoice_id, &setup.supplier, &1_000_0000000, &1);
+            }
+            let (cpu, mem) = measure(&setup.env, || { setup.contract.get_open_orders(&invoice_id, &0, &orders); });
+            let orders = orders as u64;
+            assert!(cpu < OPEN_ORDERS_CPU_PER_ORDER * orders, "get_open_orders cpu {cpu} with {orders} orders");
+            assert!(mem < OPEN_ORDERS_MEM_PER_ORDER * orders, "get_open_orders mem {mem} with {orders} orders");
+            assert!(cpu < NETWORK_CPU_LIMIT && mem < NETWORK_MEM_LIMIT);
+        }
+    }
+}
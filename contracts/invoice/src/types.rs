@@ -1,6 +1,6 @@
 //! Type definitions for the Sangini Invoice Contract
 
-use soroban_sdk::{contracttype, Address, String};
+use soroban_sdk::{contracttype, Address, BytesN, String, Vec};
 
 /// Invoice lifecycle states
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -11,10 +11,22 @@ pub enum InvoiceStatus {
     Funding,    // Auction is active, accepting investments
     Funded,     // All tokens sold, awaiting settlement
     Overdue,    // Past due date, penalty applies
-    Settled,    // Buyer paid, funds distributed
+    Settled,    // Buyer paid in full on or before due date, funds distributed
+    SettledLate,  // Buyer paid in full after due date
+    SettledShort, // Buyer paid at least face value but short of accrued interest
     Defaulted,  // Past grace period, no payment
     Disputed,   // Buyer raised dispute, frozen
     Revoked,    // Stale invoice revoked by supplier
+    Rejected,   // Draft turned down by the buyer; the supplier may amend and resubmit it
+    Recovered,  // Defaulted, then collections recovered the full face value
+    WrittenOff, // Defaulted and closed with collections short of face value
+}
+
+impl InvoiceStatus {
+    /// True for any of the terminal settlement states
+    pub fn is_settled(&self) -> bool {
+        matches!(self, InvoiceStatus::Settled | InvoiceStatus::SettledLate | InvoiceStatus::SettledShort)
+    }
 }
 
 /// Dispute resolution outcomes
@@ -22,6 +34,8 @@ pub enum InvoiceStatus {
 #[contracttype]
 pub enum DisputeResolution {
     Pending,    // Awaiting admin decision
+    Upheld,     // Ruled valid; supplier may appeal until appeal_deadline
+    Appealed,   // Supplier contested the ruling; arbiter reviews until review_deadline
     Valid,      // Dispute upheld, clawback executed
     Invalid,    // Dispute rejected, invoice unfrozen
 }
@@ -82,6 +96,95 @@ pub struct Invoice {
     pub start_price: i128,       // Starting price (face value, 0% discount)
     pub min_price: i128,         // Minimum price supplier accepts (max discount)
     pub price_drop_rate: u32,    // Basis points drop per hour (e.g., 50 = 0.5%/hour)
+    
+    // Fee schedule version pinned when funding starts (0 until then)
+    pub fee_version: u32,
+    
+    // Cross-currency settlement (None = settle in the funding token)
+    pub settlement_token: Option<Address>,
+    pub settlement_fx_rate: i128, // Oracle rate used at settlement, scaled by 1e7
+    pub settlement_paid: i128,    // Amount paid in the settlement token
+
+    // Financing program the invoice was issued under (0 = default program)
+    pub program_id: u32,
+
+    // Confidential drafts: sha256(amount || salt); amount stays 0 until the buyer reveals it
+    pub amount_commitment: Option<BytesN<32>>,
+
+    // Funding threshold: payments stay in escrow until this many tokens are sold (0 = off)
+    pub min_funding_tokens: i128,
+    pub escrowed_payments: i128,  // Investor payments awaiting the threshold
+
+    // sha256 of the confidential commercial terms; description/purchase_order stay public
+    pub private_terms_hash: Option<BytesN<32>>,
+
+    // Private placement: Merkle root of invited investors (None = open to any KYC'd investor)
+    pub allowlist_root: Option<BytesN<32>>,
+
+    // Uniform clearing: auction payments stay escrowed and every investor pays the final price
+    pub uniform_clearing: bool,
+
+    // Program rate terms pinned alongside fee_version when funding starts; until then
+    // (fee_version 0) the program's live config applies
+    pub rate_config: RateConfig,
+
+    // Collected from the buyer after default and distributed to holders
+    pub recovered_amount: i128,
+
+    // Pricing of the primary auction, chosen when it starts
+    pub auction_type: AuctionType,
+}
+
+/// How a primary auction sells the invoice's tokens
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum AuctionType {
+    Dutch,                       // Price decays from face value to the floor; investors buy at the current price
+    English,                     // Investors bid at or above the floor; the best bids fill when the auction closes
+}
+
+/// A primary auction's terms, as returned by get_auction_params
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct AuctionParams {
+    pub auction_type: AuctionType,
+    pub auction_start: u64,
+    pub auction_end: u64,
+    pub start_price: i128,
+    pub min_price: i128,         // Floor price
+    pub reserved_tokens: i128,   // Tokens the supplier kept out of the sale
+    pub min_ticket: i128,        // Smallest purchase or bid in tokens (a smaller final lot may still sell)
+}
+
+/// Commercial terms of a new draft, besides its parties and amount
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct DraftTerms {
+    pub currency: String,
+    pub due_date: u64,
+    pub description: String,
+    pub purchase_order: String,
+    pub document_hash: String,   // IPFS CID for invoice document
+}
+
+/// Optional terms a supplier sets when opening the primary auction
+#[derive(Clone, Debug, Default)]
+#[contracttype]
+pub struct AuctionTerms {
+    pub reserved_tokens: i128,   // Tokens the supplier keeps out of the sale
+    pub min_ticket: i128,        // Smallest purchase or bid in tokens (a smaller final lot may still sell)
+    pub royalty_bps: Option<u32>, // Secondary-sale royalty; None takes the fee schedule's
+}
+
+/// Escrowed bid in an English auction
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct AuctionBid {
+    pub bidder: Address,
+    pub token_amount: i128,
+    pub price: i128,             // For the whole invoice, like the Dutch auction price
+    pub escrow: i128,            // Payment for token_amount at price
+    pub placed_at: u64,
 }
 
 /// Dispute data
@@ -94,6 +197,74 @@ pub struct Dispute {
     pub raised_at: u64,          // Unix timestamp
     pub resolution: DisputeResolution,
     pub resolved_at: u64,        // 0 if not resolved
+    pub prior_status: InvoiceStatus, // Status restored if the dispute is rejected
+    pub auction_elapsed: u64,    // Seconds of a live auction already run when frozen (0 if none)
+    pub appeal_deadline: u64,    // Last moment the supplier can appeal an upheld ruling (0 until upheld)
+    pub review_deadline: u64,    // Last moment the arbiter can rule on an appeal (0 until appealed)
+    pub clawback_after: u64,     // Only holdings acquired after this time are clawed back (0 = all)
+    pub vote_deadline: u64,      // Last moment arbitrators can vote (0 = no arbitration panel)
+    pub quorum: u32,             // Votes needed for the panel's ruling to stand (snapshot at raise)
+    pub valid_bps: u32,          // Share of each holding clawed back when upheld (10000 = all)
+}
+
+/// Throttle on buyers whose disputes keep being rejected
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct DisputeLimits {
+    pub rejected_limit: u32,     // Rejected disputes within the window that trigger the throttle (0 = off)
+    pub window_seconds: u64,     // Rolling window rejections are counted over
+    pub bond: i128,              // Bond a throttled buyer posts to dispute (0 = only pre-cleared disputes)
+}
+
+impl Default for DisputeLimits {
+    fn default() -> Self {
+        DisputeLimits { rejected_limit: 0, window_seconds: 90 * 86400, bond: 0 }
+    }
+}
+
+/// Document submitted to the arbitration panel by either party
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct Evidence {
+    pub submitted_by: Address,
+    pub document_hash: String,   // IPFS hash of the evidence document
+    pub submitted_at: u64,
+}
+
+/// An arbitrator's vote on a pending dispute
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct ArbitrationVote {
+    pub arbitrator: Address,
+    pub uphold: bool,            // true = dispute is valid
+    pub voted_at: u64,
+}
+
+/// Buyer's request to move an invoice's due date back, put to a token-weighted holder vote
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct ExtensionProposal {
+    pub new_due_date: u64,
+    pub bonus_rate_bps: u32,     // Extra interest for holders on the days past the original due date
+    pub proposed_at: u64,
+    pub voting_ends: u64,
+}
+
+/// A holder's vote on an extension proposal. Its weight is the holder's balance at the tally.
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct ExtensionVote {
+    pub holder: Address,
+    pub approve: bool,
+    pub voted_at: u64,
+}
+
+/// Due date an invoice had before its first approved extension, and the bonus rate of the latest
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct DueDateExtension {
+    pub original_due_date: u64,
+    pub bonus_rate_bps: u32,
 }
 
 /// Token holding for an address
@@ -107,6 +278,230 @@ pub struct TokenHolding {
     pub acquired_price: i128,    // Price paid (for discount tracking)
 }
 
+/// Tokens a holding took on in one acquisition. A holding's lots are consumed oldest first
+/// when it shrinks, so the remaining lots are its most recent acquisitions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct TokenLot {
+    pub amount: i128,
+    pub acquired_at: u64,
+}
+
+/// Buyer prepayment held in escrow until settlement
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct Prepayment {
+    pub amount: i128,
+    pub deposited_at: u64,       // Interest stops accruing on this amount from here
+}
+
+/// Buyer re-attestation of the amount still owed after funding
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct BalanceConfirmation {
+    pub invoice_id: String,
+    pub outstanding_amount: i128, // Face value less any prepayments
+    pub confirmation_hash: String, // Hash of the signed balance confirmation letter
+    pub confirmed_at: u64,
+}
+
+/// Per-holder payout record kept after settlement removes the holding
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct RedemptionReceipt {
+    pub invoice_id: String,
+    pub holder: Address,
+    pub tokens_redeemed: i128,
+    pub amount_received: i128,
+    pub principal: i128,         // Face-value portion of the payout
+    pub interest: i128,          // amount_received - principal (negative on short settlement)
+    pub redeemed_at: u64,
+    pub payout_token: Address,   // Token actually delivered (holder preference or settlement token)
+    pub payout_amount: i128,     // Amount delivered in payout_token (0 = credited to the holder's dust balance)
+}
+
+/// A holder's share of installments paid through `settle_partial`
+#[derive(Clone, Debug, Default)]
+#[contracttype]
+pub struct InstallmentAccount {
+    pub accrued: i128,           // Credited but not yet withdrawn
+    pub checkpoint: i128,        // repayment_received when `accrued` was last brought up to date
+    pub withdrawn: i128,         // Paid out through claim_installments
+}
+
+/// Result of an investment dry run
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct InvestQuote {
+    pub price: i128,             // Auction price for the full token supply
+    pub payment_amount: i128,    // Investor pays
+    pub insurance_amount: i128,  // Cut kept for the insurance pool
+    pub program_fee: i128,       // Program fee split between anchor and protocol treasuries
+    pub protocol_fee: i128,      // Fee schedule's protocol fee, credited to the protocol treasury
+    pub supplier_proceeds: i128, // Supplier receives
+}
+
+/// Insurance premium for invoices whose buyer scores at least `min_buyer_score` and that mature
+/// within `max_tenor_days`
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PremiumTier {
+    pub min_buyer_score: u32,
+    pub max_tenor_days: u32,
+    pub premium_bps: u32,
+}
+
+/// Insurance premium an invoice would pay if its remaining tokens sold now
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct PremiumQuote {
+    pub buyer_score: u32,
+    pub tenor_days: u32,         // Days left to the due date
+    pub premium_bps: u32,        // Matching tier's premium, or the fee schedule's insurance cut
+    pub sale: InvestQuote,       // Remaining tokens at the current price (face value before the auction)
+}
+
+/// What the buyer would owe settling at a given time
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct SettlementQuote {
+    pub gross_amount: i128,      // Face value plus accrued interest, net of prepayment accrual
+    pub days_early: u64,         // Whole days before the due date
+    pub discount: i128,          // Early-settlement discount, borne by holders pro-rata
+    pub amount_due: i128,
+}
+
+/// Result of a settlement dry run
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct SettlementSimulation {
+    pub required_payment: i128,  // Settlement amount with interest at the simulated time
+    pub total_paid: i128,        // Payment plus escrowed prepayments
+    pub status: InvoiceStatus,   // Terminal status the settlement would produce
+    pub payouts: Vec<RedemptionReceipt>,
+}
+
+/// Append-only lifecycle entry for audits
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct StateRecord {
+    pub status: InvoiceStatus,   // Status after the transition
+    pub timestamp: u64,
+    pub actor: Address,          // Caller, or the contract itself for permissionless updates
+    pub amount: i128,            // Key amount for the step (face value, tokens sold, repayment, ...)
+}
+
+/// Wasm upgrade waiting out its timelock
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PendingUpgrade {
+    pub wasm_hash: BytesN<32>,
+    pub eta: u64,                // Earliest time `upgrade` may run
+}
+
+/// Operational role that can act in place of the admin for one area
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Role {
+    KycManager,       // Investor KYC changes and their undo
+    DisputeResolver,  // Rulings on disputes in any program
+    Pauser,           // Emergency pause
+    Treasurer,        // Protocol treasury withdrawals and sweeps
+    Collector,        // Recoveries on defaulted invoices and write-offs
+}
+
+/// Permission touched by an authorization change
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum AuthorizationKind {
+    Kyc,
+    Relayer,
+}
+
+/// Audit entry for a KYC or relayer change, tied to an off-chain review
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct AuthorizationRecord {
+    pub action_id: u64,          // Global sequence number, used by undo_last_action
+    pub kind: AuthorizationKind,
+    pub granted: bool,           // New value of the permission
+    pub previous: bool,          // Value before the change
+    pub reason_code: u32,        // Compliance reason code (0 = unspecified)
+    pub reference_hash: String,  // Hash of / link to the off-chain review report
+    pub actor: Address,          // Admin that made the change
+    pub timestamp: u64,
+}
+
+/// Id ranges and global balances for paging through the migration export views
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct ExportSummary {
+    pub invoice_count: u32,      // export_invoices positions run 0..invoice_count
+    pub order_count: u32,        // export_open_orders positions run 0..order_count
+    pub program_count: u32,      // export_insurance program ids run 0..=program_count
+    pub kyc_count: u32,          // export_kyc positions run 0..kyc_count
+    pub total_insurance_pools: i128,
+    pub total_treasuries: i128,
+    pub escrow_total: i128,
+    pub insurance_backstop_bps: u32,
+}
+
+/// Invoice id, status and insurance balance, as exported for a redeployment
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct InvoiceSnapshot {
+    pub invoice_id: String,
+    pub status: InvoiceStatus,
+    pub program_id: u32,
+    pub insurance_balance: i128, // Premiums earmarked for the invoice
+}
+
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct KycSnapshot {
+    pub investor: Address,
+    pub approved: bool,
+}
+
+/// Open sell and buy orders among a range of order ids
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct OpenOrdersSnapshot {
+    pub sell_orders: Vec<SellOrder>,
+    pub buy_orders: Vec<BuyOrder>,
+}
+
+/// A program's insurance pool accounting
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct InsuranceSnapshot {
+    pub program_id: u32,
+    pub pool_balance: i128,
+    pub earmarked: i128,         // Held for live invoices; the rest backs every invoice
+    pub underwriter_shares: i128,
+}
+
+/// Where an applicant's KYC request stands
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum KycRequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// Applicant's request to be KYC-approved, anchoring the off-chain onboarding review
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct KycRequest {
+    pub applicant: Address,
+    pub metadata_hash: String,   // Hash of / link to the applicant's off-chain KYC package
+    pub requested_at: u64,
+    pub status: KycRequestStatus,
+    pub decided_at: u64,         // 0 while pending
+    pub action_id: u64,          // Authorization log entry of the decision (0 while pending)
+}
+
 /// Rate configuration for interest and penalties
 #[derive(Clone, Debug)]
 #[contracttype]
@@ -117,7 +512,29 @@ pub struct RateConfig {
     pub default_auction_duration: u64, // Default auction duration in seconds (7 days)
     pub default_price_drop_rate: u32,  // Default basis points drop per hour
     pub default_max_discount: u32,     // Default max discount in basis points
-    pub insurance_cut_bps: u32,        // Basis points taken for insurance (500 = 5%)
+    pub grace_interest: GraceInterest, // Interest accrued while past due but within the grace period
+    pub day_count: DayCount,           // How accrual days and the year are counted
+    pub early_discount_bps_per_day: u32, // Off the settlement amount per whole day paid before the due date
+    pub max_early_discount_bps: u32,   // Cap on the early-settlement discount
+    pub max_investor_bps: u32,         // Most of an invoice's supply one investor may hold via primary buys and fills (0 = no cap)
+}
+
+/// Day-count convention for interest accrual
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum DayCount {
+    Act365,     // Actual days over a 365-day year
+    Act360,     // Actual days over a 360-day year
+    Thirty360,  // 30-day months over a 360-day year (30/360 ISDA)
+}
+
+/// Interest treatment for a settlement made during the grace period
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum GraceInterest {
+    Penalty,    // Penalty rate over the whole window, as once the grace period ends
+    Base,       // The invoice's pre-maturity rate, as if still before the due date
+    Zero,       // Pre-maturity rate up to the due date, nothing for the days after it
 }
 
 impl Default for RateConfig {
@@ -129,11 +546,121 @@ impl Default for RateConfig {
             default_auction_duration: 604800,   // 7 days in seconds
             default_price_drop_rate: 50,        // 0.5% per hour
             default_max_discount: 1500,         // 15% max discount
-            insurance_cut_bps: 500,             // 5% insurance cut
+            grace_interest: GraceInterest::Penalty,
+            day_count: DayCount::Act365,
+            early_discount_bps_per_day: 0,
+            max_early_discount_bps: 0,
+            max_investor_bps: 0,
+        }
+    }
+}
+
+/// How an invoice's interest rate before maturity is derived
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum RateModel {
+    Fixed,                       // Program's base_interest_rate
+    Tiered(Vec<RateTier>),       // Rate steps with invoice age
+    Benchmark(Address, i32),     // Benchmark oracle's rate plus a spread in basis points
+}
+
+/// Benchmark readings behind a floating-rate invoice, kept for audit (0 until sampled)
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct BenchmarkReadings {
+    pub verification_rate: u32,  // Benchmark in basis points when the buyer verified
+    pub verified_at: u64,
+    pub settlement_rate: u32,    // Benchmark in basis points when the invoice settled
+    pub settled_at: u64,
+}
+
+/// One step of a tiered rate model
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct RateTier {
+    pub from_day: u32,           // Invoice age (days) at which this rate starts
+    pub rate_bps: u32,           // Annual rate in basis points
+}
+
+/// White-label financing program (anchor or bank) with its own admin, rates,
+/// KYC list and insurance pool. Program 0 is the deployment's default program.
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct Program {
+    pub id: u32,
+    pub admin: Address,                // Manages the program's suppliers, KYC and rates
+    pub rate_config: RateConfig,
+    pub created_at: u64,
+}
+
+/// Fees a program charges on its investments and secondary trades, split between
+/// the program's (anchor's) treasury and the protocol treasury (program 0)
+#[derive(Clone, Debug, Default)]
+#[contracttype]
+pub struct ProgramFees {
+    pub investment_fee_bps: u32,       // Taken from supplier proceeds on primary investments
+    pub trading_fee_bps: u32,          // Taker fee on fills, paid by the side filling a resting order
+    pub anchor_share_bps: u32,         // Share of each fee credited to the program; the rest goes to the protocol
+    pub maker_fee_bps: i32,            // Fee on the resting order's side; negative = rebate out of the taker fee
+}
+
+/// Versioned fee parameters. Invoices pin the version active when funding starts.
+#[derive(Clone, Debug, Default)]
+#[contracttype]
+pub struct FeeSchedule {
+    pub version: u32,
+    pub effective_from: u64,           // Unix timestamp the schedule applies from
+    pub insurance_cut_bps: u32,        // Basis points taken for insurance (500 = 5%)
+    pub protocol_fee_bps: u32,         // Protocol fee on primary investments, taken from supplier proceeds
+    pub trading_fee_bps: u32,          // Protocol fee on secondary fills, paid by the taker on top of the program's fee
+    pub royalty_bps: u32,              // Royalty on secondary sales, taken from seller proceeds for the insurance pool
+    pub withholding_bps: u32,          // Tax withheld from holder payouts
+}
+
+/// Protocol fee rates in force and the fees accrued to the protocol treasury
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct FeeConfig {
+    pub schedule_version: u32,         // Active fee schedule; funded invoices keep the one they pinned
+    pub protocol_fee_bps: u32,
+    pub trading_fee_bps: u32,
+    pub reserve: i128,                 // Protocol treasury balance available to `withdraw_fees`
+}
+
+/// An underwriter's shares in a program's insurance pool
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct UnderwriterStake {
+    pub shares: i128,
+    pub unlock_at: u64,                // Earliest unstake time; every new stake restarts the lockup
+}
+
+/// Terms offered to underwriters staking into the insurance pools
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct UnderwriterTerms {
+    pub lockup_seconds: u64,
+    pub yield_bps: u32,                // Cut of settlement interest paid into a pool that has underwriters
+}
+
+impl Default for UnderwriterTerms {
+    fn default() -> Self {
+        UnderwriterTerms {
+            lockup_seconds: 2_592_000,     // 30 days
+            yield_bps: 0,
         }
     }
 }
 
+/// Exposure limits checked when a draft is minted or auctioned (0 = no limit)
+#[derive(Clone, Debug, Default)]
+#[contracttype]
+pub struct IssuanceLimits {
+    pub max_tenor_days: u64,     // Max days between creation and due date
+    pub max_amount: i128,        // Max invoice face value
+    pub max_discount_bps: u32,   // Max auction discount (within the global 5000 bps cap)
+}
+
 /// Sell order for secondary market
 #[derive(Clone, Debug)]
 #[contracttype]
@@ -146,9 +673,60 @@ pub struct SellOrder {
     pub tokens_remaining: i128,  // For partial fills
     pub created_at: u64,
     pub status: OrderStatus,
+    pub program_id: u32,         // Program of the underlying invoice
+}
+
+/// One invoice's holding within a basket order
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct BasketLeg {
+    pub invoice_id: String,
+    pub token_amount: i128,
+}
+
+/// Holdings across several invoices sold together for one total price, all-or-nothing
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct BasketOrder {
+    pub id: String,
+    pub seller: Address,
+    pub legs: Vec<BasketLeg>,
+    pub total_price: i128,       // Paid for the whole basket in payment currency
+    pub created_at: u64,
+    pub status: OrderStatus,     // Open, Filled or Cancelled (no partial fills)
 }
 
-/// Investment record
+/// Holder's request for quotes on a block of tokens; makers answer off-chain with signed quotes
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct Rfq {
+    pub id: String,
+    pub invoice_id: String,
+    pub holder: Address,
+    pub token_amount: i128,      // Block size, executed in full
+    pub created_at: u64,
+    pub status: OrderStatus,     // Open, Filled or Cancelled
+    pub program_id: u32,         // Program of the underlying invoice
+}
+
+/// Standing bid on invoice tokens; the USDC for the unfilled remainder is escrowed
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct BuyOrder {
+    pub id: String,
+    pub invoice_id: String,
+    pub buyer: Address,
+    pub token_amount: i128,
+    pub price_per_token: i128,   // Price per token in payment currency
+    pub tokens_remaining: i128,  // Escrow held = tokens_remaining * price_per_token (+ maker fee)
+    pub created_at: u64,
+    pub status: OrderStatus,
+    pub program_id: u32,         // Program of the underlying invoice
+    pub maker_fee_bps: i32,      // Locked when posted, since a positive maker fee is escrowed with the bid
+}
+
+/// One purchase of invoice tokens, primary or secondary, stamped with its share of the
+/// holder's payout when the invoice settles
 #[derive(Clone, Debug)]
 #[contracttype]
 pub struct Investment {
@@ -156,13 +734,197 @@ pub struct Investment {
     pub invoice_id: String,
     pub investor: Address,
     pub token_amount: i128,
-    pub invested_amount: i128,   // Payment amount
+    pub invested_amount: i128,   // Payment amount, including any trading fee the buyer paid
     pub discount_rate: u32,      // Basis points
     pub invested_at: u64,
     pub settled_amount: i128,    // 0 if not settled
     pub settled_at: u64,         // 0 if not settled
 }
 
+/// Outcome of an auction, accumulated per fill and finalized when the last token sells
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct AuctionResult {
+    pub invoice_id: String,
+    pub clearing_price: i128,    // Auction price at the final fill
+    pub tokens_sold: i128,
+    pub investor_count: u32,     // Distinct buyers during the auction
+    pub total_proceeds: i128,    // Sum of investor payments
+    pub duration: u64,           // Seconds from auction start to sell-out
+    pub avg_discount_bps: u32,   // Proceeds-weighted discount to face value
+    pub finalized_at: u64,       // 0 while the auction is still running
+}
+
+/// Running per-investor totals behind get_investor_summary
+#[derive(Clone, Debug, Default)]
+#[contracttype]
+pub struct InvestorTotals {
+    pub settled_cost: i128,      // Cost basis of positions closed by settlement
+    pub settled_received: i128,  // Payouts received on those positions
+    pub insurance_recovered: i128,
+}
+
+/// A buyer's payment record across all its invoices, behind get_buyer_score
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct BuyerStats {
+    pub settled_on_time: u32,
+    pub settled_late: u32,        // Includes short settlements, which are always past due
+    pub defaulted: u32,
+    pub disputed: u32,            // Disputes ruled on, either way
+    pub disputes_rejected: u32,   // Disputes ruled invalid
+    pub total_volume: i128,       // Face value of settled invoices
+}
+
+/// A supplier's record across all its invoices, behind get_supplier_score
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct SupplierStats {
+    pub settled: u32,             // Settled in any form, on time or not
+    pub defaulted: u32,
+    pub disputes_upheld: u32,     // Disputes ruled valid against the supplier's invoice
+}
+
+/// Buyer's latest rejection of a draft
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct DraftRejection {
+    pub reason: String,
+    pub rejected_at: u64,
+}
+
+/// A supplier's change to a draft's terms before verification
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct DraftAmendment {
+    pub amended_at: u64,
+    pub amount: i128,            // 0 for confidential drafts, whose amount stays committed
+    pub due_date: u64,
+    pub document_hash: String,
+}
+
+/// New terms for a draft the supplier amends or resubmits
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct RevisedTerms {
+    pub amount: i128,            // Must be 0 for confidential drafts, whose amount stays committed
+    pub due_date: u64,
+    pub description: String,
+    pub purchase_order: String,
+    pub document_hash: String,
+}
+
+/// Draft outcomes between one supplier and one buyer
+#[derive(Clone, Debug, Default)]
+#[contracttype]
+pub struct RelationshipStats {
+    pub drafts_approved: u32,
+    pub drafts_rejected: u32,
+    pub resubmitted: u32,       // Rejected drafts the supplier amended and sent back
+}
+
+/// Share of the invoice's tokens the supplier owes a sub-vendor further down its supply chain
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct SubvendorSplit {
+    pub subvendor: Address,
+    pub bps: u32,                // Of the invoice's total tokens
+}
+
+/// Tokens passed to a sub-vendor by `distribute_to_subvendors`
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct SubvendorDistribution {
+    pub subvendor: Address,
+    pub amount: i128,
+    pub distributed_at: u64,
+    pub transfer_id: u64,        // Offer awaiting the sub-vendor's acceptance (0 = transferred directly)
+}
+
+/// Tokens offered to a recipient that stay locked in the sender's holding until accepted
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct PendingTransfer {
+    pub id: u64,
+    pub invoice_id: String,
+    pub from: Address,
+    pub to: Address,
+    pub amount: i128,
+    pub offered_at: u64,
+    pub reclaimable_at: u64,     // The sender may take the offer back from here on
+}
+
+/// Investor yield report returned by get_investor_summary
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct InvestorSummary {
+    pub principal_outstanding: i128, // Cost basis still at risk in open invoices
+    pub realized_returns: i128,  // Settlement payouts minus cost on settled positions (net of shortfalls)
+    pub realized_losses: i128,   // Shortfalls plus uncovered defaults
+    pub insurance_recoveries: i128,
+    pub avg_yield_bps: i32,      // Cost-weighted return across settled positions
+}
+
+/// Supplier cash-flow overview returned by get_supplier_summary
+#[derive(Clone, Debug, Default)]
+#[contracttype]
+pub struct SupplierSummary {
+    pub draft_count: u32,        // Drafts awaiting buyer approval
+    pub draft_amount: i128,
+    pub auction_count: u32,      // Live auctions
+    pub auction_tokens_total: i128,
+    pub auction_tokens_sold: i128, // Funding progress across live auctions
+    pub awaiting_count: u32,     // Funded or overdue, awaiting buyer settlement
+    pub awaiting_amount: i128,
+    pub lifetime_proceeds: i128, // Net USDC received from investors
+}
+
+/// Upcoming cash flow entry returned by get_maturities
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct MaturityEntry {
+    pub invoice_id: String,
+    pub due_date: u64,
+    pub amount: i128,            // Face value
+    pub status: InvoiceStatus,
+}
+
+/// Pending state transition a keeper can act on
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum KeeperAction {
+    MarkOverdue,    // Past due date, call check_status
+    MarkDefaulted,  // Past grace period, call check_status
+    AuctionExpired, // Auction window closed with tokens unsold; supplier should restart or revoke
+}
+
+/// Invoice flagged by get_actionable
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct ActionableInvoice {
+    pub invoice_id: String,
+    pub status: InvoiceStatus,
+    pub action: KeeperAction,
+}
+
+/// Where a get_actionable scan resumes
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum ActionableCursor {
+    Start,
+    Queue(u64, u32), // (due day, index) in the default queue's day buckets
+    Funding(u32),    // Slot in the Funding status index, scanned once the queue is done
+    Done,            // Nothing left to scan
+}
+
+/// One get_actionable page and the cursor the next one starts from
+#[derive(Clone, Debug)]
+#[contracttype]
+pub struct ActionablePage {
+    pub invoices: Vec<ActionableInvoice>,
+    pub next: ActionableCursor,
+}
+
 /// Token transfer record
 #[derive(Clone, Debug)]
 #[contracttype]
@@ -97,9 +97,21 @@ export async function POST(request: NextRequest, { params }: RouteParams) {
 
     console.log('Approving invoice with contract ID:', contractInvoiceId);
 
+    // The amendment count of the terms the buyer reviewed; the contract rejects
+    // the approval if the draft was amended since
+    const body = await request.json().catch(() => ({}));
+    const expectedAmendments = Number(body.expectedAmendments ?? 0);
+    if (!Number.isInteger(expectedAmendments) || expectedAmendments < 0) {
+      return NextResponse.json(
+        { error: 'expectedAmendments must be a non-negative integer' },
+        { status: 400 }
+      );
+    }
+
     const txXdr = await buildInvoiceApprovalTx(
       contractInvoiceId,
-      user.custodialPubKey
+      user.custodialPubKey,
+      expectedAmendments
     );
 
     // 9. Sign with custodial key
@@ -37,7 +37,7 @@ export async function POST(request: NextRequest, { params }: RouteParams) {
 
     const { id } = await params;
     const body: StartAuctionRequest = await request.json();
-    const { durationHours, maxDiscountBps } = body;
+    const { durationHours, maxDiscountBps, auctionType = 'Dutch', reservedTokens = '0', minTicketTokens = '0', royaltyBps } = body;
 
     // Validation
     if (!durationHours || durationHours <= 0) {
@@ -54,6 +54,27 @@ export async function POST(request: NextRequest, { params }: RouteParams) {
       );
     }
 
+    if (auctionType !== 'Dutch' && auctionType !== 'English') {
+      return NextResponse.json(
+        { error: 'Auction type must be Dutch or English' },
+        { status: 400 }
+      );
+    }
+
+    if (BigInt(reservedTokens) < 0 || BigInt(minTicketTokens) < 0) {
+      return NextResponse.json(
+        { error: 'Reserved tokens and minimum ticket cannot be negative' },
+        { status: 400 }
+      );
+    }
+
+    if (royaltyBps !== undefined && (!Number.isInteger(royaltyBps) || royaltyBps < 0 || royaltyBps > 500)) {
+      return NextResponse.json(
+        { error: 'Royalty must be between 0 and 500 basis points (0% - 5%)' },
+        { status: 400 }
+      );
+    }
+
     const db = await getDb();
 
     // Find invoice
@@ -96,7 +117,11 @@ export async function POST(request: NextRequest, { params }: RouteParams) {
       contractInvoiceId,
       session.user.walletAddress,
       BigInt(durationHours),
-      maxDiscountBps
+      maxDiscountBps,
+      auctionType,
+      BigInt(reservedTokens),
+      BigInt(minTicketTokens),
+      royaltyBps
     );
 
     return NextResponse.json({
@@ -125,6 +125,7 @@ export async function GET(request: NextRequest) {
         id: inv._id.toString(),
         invoiceId: inv.invoiceId || inv._id.toString(),
         onChainId: inv.onChainId, // The actual contract invoice ID (e.g., INV-1001)
+        amendmentCount: inv.amendmentCount ?? 0,
         supplier: inv.supplierAddress || supplier?.walletAddress || '',
         supplierName: supplier?.name || supplier?.companyName,
         buyer: inv.buyerAddress || buyer?.walletAddress || buyer?.custodialPubKey || '',
@@ -20,7 +20,7 @@ export default function BuyerRequestsPage() {
     const [error, setError] = React.useState<string | null>(null)
     const [success, setSuccess] = React.useState<string | null>(null)
 
-    const handleApprove = async (id: string) => {
+    const handleApprove = async (id: string, expectedAmendments: number) => {
         setError(null)
         setSuccess(null)
         setApproving(id)
@@ -30,6 +30,7 @@ export default function BuyerRequestsPage() {
             const res = await fetch(`/api/invoices/${id}/approve`, {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ expectedAmendments }),
             })
 
             if (!res.ok) {
@@ -207,7 +208,7 @@ export default function BuyerRequestsPage() {
                                     )}
                                 </Button>
                                 <Button
-                                    onClick={() => handleApprove(inv.id)}
+                                    onClick={() => handleApprove(inv.id, inv.amendmentCount ?? 0)}
                                     disabled={!!approving || !!rejecting}
                                     className="bg-emerald-600 hover:bg-emerald-700 text-white"
                                 >
@@ -28,6 +28,7 @@ export interface Invoice {
   totalTokens?: string;
   tokensSold?: string;
   tokensRemaining?: string;
+  amendmentCount?: number;  // Draft amendments made to the terms on-chain
 }
 
 interface UseInvoicesOptions {
@@ -117,12 +117,14 @@ export async function mintDraftBrowser(
 
 export async function approveInvoiceBrowser(
     buyerPublicKey: string,
-    invoiceId: string
+    invoiceId: string,
+    expectedAmendments: number
 ): Promise<void> {
     const op = invoiceContract.call(
         'approve_invoice',
         nativeToScVal(invoiceId, { type: 'string' }),
-        nativeToScVal(buyerPublicKey, { type: 'address' })
+        nativeToScVal(buyerPublicKey, { type: 'address' }),
+        nativeToScVal(expectedAmendments, { type: 'u32' })
     );
 
     await submitWithFreighter(buyerPublicKey, op);
@@ -102,12 +102,14 @@ export async function mintDraft(
 
 export async function approveInvoice(
     buyerKeypair: Keypair,
-    invoiceId: string
+    invoiceId: string,
+    expectedAmendments: number
 ): Promise<void> {
     const op = invoiceContract.call(
         'approve_invoice',
         nativeToScVal(invoiceId, { type: 'string' }),
-        nativeToScVal(buyerKeypair.publicKey(), { type: 'address' })
+        nativeToScVal(buyerKeypair.publicKey(), { type: 'address' }),
+        nativeToScVal(expectedAmendments, { type: 'u32' })
     );
 
     await submitTransaction(buyerKeypair, op);
@@ -182,6 +182,10 @@ export interface CreateInvoiceRequest {
 export interface StartAuctionRequest {
   durationHours: number;
   maxDiscountBps: number;    // Basis points (1500 = 15%)
+  auctionType?: 'Dutch' | 'English'; // English: investors bid at or above the floor (default Dutch)
+  reservedTokens?: string;   // Tokens the supplier keeps out of the sale (default 0)
+  minTicketTokens?: string;  // Smallest purchase or bid, in tokens (default 0)
+  royaltyBps?: number;       // Secondary-sale royalty override, at most 500 (default: fee schedule's)
 }
 
 // Invest request
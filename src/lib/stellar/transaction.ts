@@ -78,16 +78,20 @@ async function buildContractTransaction(
 
 /**
  * Build approve_invoice transaction
+ * expectedAmendments is the amendment count of the terms the buyer reviewed;
+ * the contract rejects the approval if the draft was amended since.
  */
 export async function buildInvoiceApprovalTx(
   invoiceId: string,
-  buyerAddress: string
+  buyerAddress: string,
+  expectedAmendments: number
 ): Promise<string> {
   const contract = getInvoiceContract();
 
   const args = [
     nativeToScVal(invoiceId, { type: 'string' }),
     new Address(buyerAddress).toScVal(),
+    nativeToScVal(expectedAmendments, { type: 'u32' }),
   ];
 
   return buildContractTransaction(buyerAddress, contract, 'approve_invoice', args);
@@ -143,20 +147,45 @@ export async function buildMintDraftTx(
 
 /**
  * Build start_auction transaction
+ * The reserve, minimum ticket and royalty override go in the contract's AuctionTerms struct;
+ * royaltyBps undefined keeps the fee schedule's royalty.
  */
 export async function buildStartAuctionTx(
   invoiceId: string,
   supplierAddress: string,
   durationHours: bigint,
-  maxDiscountBps: number
+  maxDiscountBps: number,
+  auctionType: 'Dutch' | 'English' = 'Dutch',
+  reservedTokens: bigint = BigInt(0),
+  minTicketTokens: bigint = BigInt(0),
+  royaltyBps?: number
 ): Promise<string> {
   const contract = getInvoiceContract();
 
+  // Structs are encoded as a map keyed by field name, with keys in sorted order
+  const terms = xdr.ScVal.scvMap([
+    new xdr.ScMapEntry({
+      key: xdr.ScVal.scvSymbol('min_ticket'),
+      val: nativeToScVal(minTicketTokens, { type: 'i128' }),
+    }),
+    new xdr.ScMapEntry({
+      key: xdr.ScVal.scvSymbol('reserved_tokens'),
+      val: nativeToScVal(reservedTokens, { type: 'i128' }),
+    }),
+    new xdr.ScMapEntry({
+      key: xdr.ScVal.scvSymbol('royalty_bps'),
+      val: royaltyBps === undefined ? xdr.ScVal.scvVoid() : nativeToScVal(royaltyBps, { type: 'u32' }),
+    }),
+  ]);
+
   const args = [
     nativeToScVal(invoiceId, { type: 'string' }),
     new Address(supplierAddress).toScVal(),
     nativeToScVal(durationHours, { type: 'u64' }),
     nativeToScVal(maxDiscountBps, { type: 'u32' }),
+    // Unit enum variants are encoded as a one-element vec holding the variant name
+    xdr.ScVal.scvVec([xdr.ScVal.scvSymbol(auctionType)]),
+    terms,
   ];
 
   return buildContractTransaction(supplierAddress, contract, 'start_auction', args);
@@ -400,13 +429,18 @@ export async function buildCancelOrderTx(
 }
 
 /**
- * Get open orders for an invoice from contract
+ * Get open orders for an invoice from contract.
+ * The contract pages over the invoice's orders and caps `limit` at 100.
  */
-export async function getOpenOrdersFromContract(invoiceId: string): Promise<unknown[]> {
+export async function getOpenOrdersFromContract(invoiceId: string, start = 0, limit = 100): Promise<unknown[]> {
   const server = getServer();
   const contract = getInvoiceContract();
 
-  const args = [nativeToScVal(invoiceId, { type: 'string' })];
+  const args = [
+    nativeToScVal(invoiceId, { type: 'string' }),
+    nativeToScVal(start, { type: 'u32' }),
+    nativeToScVal(limit, { type: 'u32' }),
+  ];
 
   const account = await server.getAccount(INVOICE_CONTRACT);
   const transaction = new TransactionBuilder(account, {